@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Items arriving closer together than this are released together
+const BATCH_WINDOW: Duration = Duration::from_secs(2);
+
+// A steady stream of items is still released this often
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(30);
+
+// Items of one key waiting to be released
+struct PendingBatch<T> {
+    items: Vec<T>,
+    first_at: Instant,
+    last_at: Instant,
+}
+
+// When a batch is released: once it has been quiet for BATCH_WINDOW, or
+// MAX_BATCH_DELAY after its first item, whichever comes first
+fn release_at(first_at: Instant, last_at: Instant) -> Instant {
+    (last_at + BATCH_WINDOW).min(first_at + MAX_BATCH_DELAY)
+}
+
+// Groups bursts of items per key, so a burst of moves turns into one
+// notification or one webhook call instead of one per file
+pub struct Batches<K, T> {
+    pending: Mutex<HashMap<K, PendingBatch<T>>>,
+}
+
+impl<K, T> Default for Batches<K, T> {
+    fn default() -> Self {
+        Batches { pending: Mutex::new(HashMap::new()) }
+    }
+}
+
+impl<K: Eq + Hash + Clone, T> Batches<K, T> {
+    // Queue an item. True when it starts a new batch, in which case the
+    // caller waits for the batch with take_when_quiet
+    pub fn push(&self, key: K, item: T) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        let now = Instant::now();
+
+        if let Some(batch) = pending.get_mut(&key) {
+            batch.items.push(item);
+            batch.last_at = now;
+            return false;
+        }
+
+        pending.insert(key, PendingBatch {
+            items: vec![item],
+            first_at: now,
+            last_at: now,
+        });
+        true
+    }
+
+    // Wait for a key's batch to settle, then take its items
+    pub async fn take_when_quiet(&self, key: &K) -> Option<Vec<T>> {
+        loop {
+            let wait = {
+                let mut pending = self.pending.lock().unwrap();
+                let batch = pending.get(key)?;
+
+                let deadline = release_at(batch.first_at, batch.last_at);
+                let now = Instant::now();
+                if now >= deadline {
+                    return pending.remove(key).map(|batch| batch.items);
+                }
+                deadline - now
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_wait_for_quiet_but_not_forever() {
+        let start = Instant::now();
+        assert_eq!(release_at(start, start), start + BATCH_WINDOW);
+        assert_eq!(release_at(start, start + Duration::from_secs(5)), start + Duration::from_secs(7));
+        assert_eq!(release_at(start, start + MAX_BATCH_DELAY), start + MAX_BATCH_DELAY);
+    }
+
+    #[test]
+    fn only_the_first_item_of_a_key_starts_a_batch() {
+        let batches = Batches::default();
+        assert!(batches.push("organized", 1));
+        assert!(!batches.push("organized", 2));
+        assert!(batches.push("backup", 3));
+
+        let pending = batches.pending.lock().unwrap();
+        assert_eq!(pending["organized"].items, [1, 2]);
+        assert_eq!(pending["backup"].items, [3]);
+    }
+}
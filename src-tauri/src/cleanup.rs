@@ -0,0 +1,600 @@
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter};
+use crate::database;
+use crate::events;
+use crate::file_ops;
+use crate::ignore_list::IgnoreList;
+use crate::utils;
+
+// How often the background sweep checks cleanup policies
+const CLEANUP_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Setting that makes sweeps only report, leaving the user to confirm with run_cleanup_now
+pub const CONFIRMATION_MODE_SETTING: &str = "confirmation_mode";
+
+// What a cleanup policy does with stale files
+pub const ACTION_ARCHIVE: &str = "archive";
+pub const ACTION_TRASH: &str = "trash";
+pub const ACTION_REPORT: &str = "report";
+
+// Cleanup policy for a watched folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupPolicy {
+    pub id: i64,
+    pub folder_path: String,
+    pub max_age_days: i64,
+    // Comma separated filename globs; empty means every file
+    pub patterns: String,
+    // When true, the policy applies to files that do NOT match the patterns
+    pub exclude_patterns: bool,
+    pub action: String,
+    pub archive_folder: Option<String>,
+    pub is_active: bool,
+}
+
+// One file a cleanup run acted on, or would act on in a dry run
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupEntry {
+    pub path: String,
+    pub action: String,
+    pub size: u64,
+    pub modified_at: String,
+    pub destination: Option<String>,
+    pub error: Option<String>,
+}
+
+// Result of a cleanup run
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupSummary {
+    pub folder_path: String,
+    pub dry_run: bool,
+    pub processed: Vec<CleanupEntry>,
+    // Files that couldn't be handled (open, locked, ...); retried on the next sweep
+    pub skipped: Vec<CleanupEntry>,
+}
+
+// Start the background task that periodically applies cleanup policies
+pub fn start_cleanup_sweeper(app: &AppHandle) {
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = sweep(&app_handle).await {
+                tracing::error!(error = format!("{:#}", e), "Cleanup sweep failed");
+            }
+        }
+    });
+}
+
+// Apply every active cleanup policy once
+async fn sweep(app: &AppHandle) -> Result<()> {
+    // In confirmation mode the sweep only reports; the user confirms with run_cleanup_now
+    let dry_run = database::is_setting_enabled(app, CONFIRMATION_MODE_SETTING)?;
+
+    for policy in database::get_cleanup_policies(app)? {
+        if !policy.is_active {
+            continue;
+        }
+
+        let app_handle = app.clone();
+        let summary = tokio::task::spawn_blocking(move || run_policy(&app_handle, &policy, dry_run))
+            .await??;
+
+        if dry_run && !summary.processed.is_empty() {
+            let _ = app.emit(events::CLEANUP_PENDING, summary);
+        } else if !summary.processed.is_empty() {
+            let _ = app.emit(events::CLEANUP_COMPLETED, summary);
+        }
+    }
+
+    Ok(())
+}
+
+// Run the cleanup policy for a folder right away
+pub async fn run_cleanup_now(app: &AppHandle, folder_path: String, dry_run: bool) -> Result<CleanupSummary> {
+    let policy = database::get_cleanup_policy_for_folder(app, &folder_path)?
+        .ok_or_else(|| anyhow::anyhow!("No cleanup policy for {}", folder_path))?;
+
+    let app_handle = app.clone();
+    tokio::task::spawn_blocking(move || run_policy(&app_handle, &policy, dry_run)).await?
+}
+
+// Find stale files under the policy's folder and archive, trash or report them
+#[tracing::instrument(skip(app, policy), fields(folder = %policy.folder_path, policy_id = policy.id))]
+fn run_policy(app: &AppHandle, policy: &CleanupPolicy, dry_run: bool) -> Result<CleanupSummary> {
+    let folder = Path::new(&policy.folder_path);
+    if !folder.is_dir() {
+        return Err(anyhow::anyhow!("Invalid folder path"));
+    }
+
+    if policy.action == ACTION_ARCHIVE && policy.archive_folder.is_none() {
+        return Err(anyhow::anyhow!("Archive policy has no archive folder"));
+    }
+
+    let patterns = policy
+        .patterns
+        .split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| utils::rule_pattern_regex(p, false))
+        .collect::<Result<Vec<_>>>()?;
+
+    let cutoff = SystemTime::now() - Duration::from_secs(policy.max_age_days.max(0) as u64 * 24 * 60 * 60);
+
+    let mut summary = CleanupSummary {
+        folder_path: policy.folder_path.clone(),
+        dry_run,
+        processed: vec![],
+        skipped: vec![],
+    };
+
+    let ignore_list = IgnoreList::load(app)?;
+    for file_path in utils::collect_files(folder, None, true)? {
+        if ignore_list.is_ignored(&utils::normalize_path(&file_path)) {
+            continue;
+        }
+        // Never sweep up the archive itself when it lives inside the folder
+        if let Some(archive) = &policy.archive_folder {
+            if file_path.starts_with(archive) {
+                continue;
+            }
+        }
+
+        let metadata = match fs::metadata(&file_path) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+        if modified > cutoff {
+            continue;
+        }
+
+        let file_name = utils::get_file_name(&file_path).unwrap_or_default();
+        let matches = patterns.is_empty() || patterns.iter().any(|p| p.is_match(&file_name));
+        if matches == policy.exclude_patterns {
+            continue;
+        }
+
+        let mut entry = CleanupEntry {
+            path: file_path.to_string_lossy().to_string(),
+            action: policy.action.clone(),
+            size: metadata.len(),
+            modified_at: utils::format_timestamp(&modified.into()),
+            destination: None,
+            error: None,
+        };
+
+        if dry_run || policy.action == ACTION_REPORT {
+            summary.processed.push(entry);
+            continue;
+        }
+
+        match apply_action(policy, &file_path) {
+            Ok(destination) => {
+                entry.destination = destination.map(|d| d.to_string_lossy().to_string());
+                log_cleanup(app, policy, &entry);
+                update_index(app, &entry);
+                summary.processed.push(entry);
+            }
+            Err(e) => {
+                tracing::warn!(path = %entry.path, error = %e, "Cleanup skipped file");
+                entry.error = Some(e.to_string());
+                summary.skipped.push(entry);
+            }
+        }
+    }
+
+    // Reports have nothing to undo later, so they are logged as they are found
+    if !dry_run && policy.action == ACTION_REPORT {
+        for entry in &summary.processed {
+            log_cleanup(app, policy, entry);
+        }
+    }
+
+    Ok(summary)
+}
+
+// Archive or trash a single file, returning where it ended up
+fn apply_action(policy: &CleanupPolicy, file_path: &Path) -> Result<Option<PathBuf>> {
+    // A file another program has open for writing can't be opened exclusively on
+    // Windows; on other platforms the move below fails instead
+    fs::OpenOptions::new().read(true).write(true).open(file_path)?;
+
+    match policy.action.as_str() {
+        ACTION_TRASH => {
+            trash::delete(file_path)?;
+            Ok(None)
+        }
+        ACTION_ARCHIVE => {
+            let archive = PathBuf::from(policy.archive_folder.as_deref().unwrap_or_default());
+            utils::ensure_dir_exists(&archive)?;
+
+            // Read-only files never get here, the check above opens them for writing
+            let destination = utils::move_to_dir(file_path, &archive, false)?;
+            Ok(Some(destination))
+        }
+        other => Err(anyhow::anyhow!("Unknown cleanup action: {}", other)),
+    }
+}
+
+// Write an activity entry for a cleaned up file
+fn log_cleanup(app: &AppHandle, policy: &CleanupPolicy, entry: &CleanupEntry) {
+    let event_type = match entry.action.as_str() {
+        ACTION_ARCHIVE => database::EVENT_ARCHIVED,
+        ACTION_TRASH => database::EVENT_TRASHED,
+        _ => database::EVENT_CLEANUP_REPORTED,
+    };
+    let file_id = database::get_file_id_by_path(app, Path::new(&entry.path)).ok().flatten();
+    let result = database::record_file_event(
+        app,
+        file_id,
+        event_type,
+        Some(&entry.path),
+        entry.destination.as_deref(),
+        Some(&format!("cleanup: older than {} days", policy.max_age_days)),
+    );
+    if let Err(e) = result {
+        tracing::warn!(path = %entry.path, error = %e, "Failed to record cleanup");
+    }
+}
+
+// Point the index at an archived file's new path, or drop a trashed file from it
+fn update_index(app: &AppHandle, entry: &CleanupEntry) {
+    let result = database::get_file_id_by_path(app, Path::new(&entry.path)).and_then(|file_id| {
+        match (file_id, &entry.destination) {
+            (Some(file_id), Some(destination)) => database::update_file_path(app, file_id, Path::new(destination)),
+            (Some(file_id), None) => database::remove_file(app, file_id),
+            (None, _) => Ok(()),
+        }
+    });
+    if let Err(e) = result {
+        tracing::warn!(path = %entry.path, error = %e, "Failed to update the index after cleanup");
+    }
+}
+
+// Result of cleanup_empty_dirs
+#[derive(Debug, Clone, Serialize)]
+pub struct EmptyDirsSummary {
+    pub root: String,
+    // True when nothing was removed, only listed for the user to confirm
+    pub dry_run: bool,
+    // Folders removed, or that would be removed, deepest first
+    pub removed: Vec<String>,
+    // Folders that couldn't be removed, with the reason
+    pub errors: Vec<String>,
+}
+
+// Remove the folders under root that no longer contain any files, e.g. after
+// organizing. Runs as a dry run unless confirmed; root itself and watched
+// folders are always kept
+pub async fn cleanup_empty_dirs(
+    app: &AppHandle,
+    root: String,
+    use_trash: bool,
+    confirmed: bool,
+) -> Result<EmptyDirsSummary> {
+    let root_path = PathBuf::from(&root);
+    if !root_path.is_dir() {
+        return Err(anyhow::anyhow!("Invalid folder path"));
+    }
+
+    let mut protected: Vec<PathBuf> = file_ops::watched_paths(app)
+        .into_iter()
+        .chain(database::get_watched_folder_paths(app)?)
+        .map(PathBuf::from)
+        .collect();
+    protected.push(root_path.clone());
+
+    let mut summary = EmptyDirsSummary {
+        root,
+        dry_run: !confirmed,
+        removed: vec![],
+        errors: vec![],
+    };
+
+    tokio::task::spawn_blocking(move || {
+        remove_empty_dirs(&root_path, &protected, use_trash, &mut summary);
+        summary
+    })
+    .await
+    .map_err(Into::into)
+}
+
+// Walk a folder bottom-up, removing empty subfolders; returns whether the
+// folder itself ended up (or would end up) empty
+fn remove_empty_dirs(dir: &Path, protected: &[PathBuf], use_trash: bool, summary: &mut EmptyDirsSummary) -> bool {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            summary.errors.push(format!("{}: {}", dir.display(), e));
+            return false;
+        }
+    };
+
+    let mut empty = true;
+    for entry in entries {
+        let Ok(entry) = entry else {
+            empty = false;
+            continue;
+        };
+        // Symlinks count as content, even when they point at a folder
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if !is_dir || !remove_empty_dirs(&entry.path(), protected, use_trash, summary) {
+            empty = false;
+        }
+    }
+
+    if !empty || protected.iter().any(|p| p == dir) {
+        return false;
+    }
+
+    if !summary.dry_run {
+        let result = if use_trash {
+            trash::delete(dir).map_err(anyhow::Error::from)
+        } else {
+            fs::remove_dir(dir).map_err(anyhow::Error::from)
+        };
+        if let Err(e) = result {
+            tracing::warn!(path = %dir.display(), error = %e, "Failed to remove empty folder");
+            summary.errors.push(format!("{}: {}", dir.display(), e));
+            return false;
+        }
+    }
+
+    summary.removed.push(dir.to_string_lossy().to_string());
+    true
+}
+
+// Names the organizer gives a file when its destination already has one of
+// that name: a counter ("report_1.pdf") or, in older versions, a timestamp
+// ("report_20240301101500.pdf")
+static COLLISION_SUFFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(.+)_(\d{14}|\d{1,4})$").unwrap());
+
+// What clean_collision_duplicates does with a group
+pub const ACTION_REMOVE_COPIES: &str = "remove_copies";
+
+// Which identical copy clean_collision_duplicates keeps: the one with the
+// clean name (else the oldest), the oldest or the newest by modification time
+pub const KEEP_ORIGINAL: &str = "keep_original";
+pub const KEEP_OLDEST: &str = "keep_oldest";
+pub const KEEP_NEWEST: &str = "keep_newest";
+
+// One file of a collision family
+#[derive(Debug, Clone, Serialize)]
+pub struct CollisionFile {
+    pub path: String,
+    pub size: u64,
+    pub modified_at: String,
+}
+
+// Files in one folder whose names only differ by the organizer's collision
+// suffix, e.g. report.pdf, report_1.pdf and report_20240301101500.pdf
+#[derive(Debug, Clone, Serialize)]
+pub struct CollisionGroup {
+    pub folder: String,
+    // The name without the suffix
+    pub clean_name: String,
+    pub files: Vec<CollisionFile>,
+    // All the files have the same content
+    pub identical: bool,
+    // SHA-256 of that content, when identical
+    pub hash: Option<String>,
+    // ACTION_REMOVE_COPIES for identical files; ACTION_REPORT otherwise, as
+    // files with different contents are never removed
+    pub recommended_action: String,
+}
+
+// Find collision families under a folder and check whether each holds copies
+// of the same file. A family needs two or more files, and either the clean
+// name or a timestamp suffix among them, so a camera's IMG_0001.jpg,
+// IMG_0002.jpg... isn't taken for one
+pub async fn find_collision_duplicates(app: &AppHandle, folder: String) -> Result<Vec<CollisionGroup>> {
+    let folder = PathBuf::from(folder);
+    if !folder.is_dir() {
+        return Err(anyhow::anyhow!("Invalid folder path"));
+    }
+    let ignore_list = IgnoreList::load(app)?;
+    tokio::task::spawn_blocking(move || collision_groups(&folder, &ignore_list)).await?
+}
+
+fn collision_groups(folder: &Path, ignore_list: &IgnoreList) -> Result<Vec<CollisionGroup>> {
+    // Keyed by folder and clean name, so the groups come out in a stable order
+    let mut families: BTreeMap<(PathBuf, String), Vec<(PathBuf, bool)>> = BTreeMap::new();
+    for path in utils::collect_files(folder, None, false)? {
+        if ignore_list.is_ignored(&utils::normalize_path(&path)) {
+            continue;
+        }
+        let (Some(parent), Some(stem)) = (path.parent(), path.file_stem().and_then(|stem| stem.to_str())) else {
+            continue;
+        };
+        let extension = path.extension().map(|extension| format!(".{}", extension.to_string_lossy()));
+        let (clean_stem, timestamped) = match COLLISION_SUFFIX.captures(stem) {
+            Some(captures) => (captures[1].to_string(), captures[2].len() == 14),
+            None => (stem.to_string(), false),
+        };
+        let clean_name = format!("{}{}", clean_stem, extension.unwrap_or_default());
+        families
+            .entry((parent.to_path_buf(), clean_name))
+            .or_default()
+            .push((path, timestamped));
+    }
+    
+    let mut groups = vec![];
+    for ((parent, clean_name), members) in families {
+        let has_clean = members.iter().any(|(path, _)| path.file_name().is_some_and(|name| *name == *clean_name));
+        if members.len() < 2 || !(has_clean || members.iter().any(|(_, timestamped)| *timestamped)) {
+            continue;
+        }
+        
+        let mut files = vec![];
+        for (path, _) in &members {
+            let metadata = fs::metadata(path)?;
+            let modified = metadata.modified().map(chrono::DateTime::<chrono::Utc>::from)?;
+            files.push(CollisionFile {
+                path: path.to_string_lossy().to_string(),
+                size: metadata.len(),
+                modified_at: utils::format_timestamp(&modified),
+            });
+        }
+        
+        // Files of different sizes can't be identical, so only hash same-sized ones
+        let hash = if files.iter().all(|file| file.size == files[0].size) {
+            let hashes = members
+                .iter()
+                .map(|(path, _)| utils::hash_file(path))
+                .collect::<Result<Vec<_>>>()?;
+            hashes.iter().all(|hash| *hash == hashes[0]).then(|| hashes[0].clone())
+        } else {
+            None
+        };
+        
+        groups.push(CollisionGroup {
+            folder: parent.to_string_lossy().to_string(),
+            clean_name,
+            files,
+            identical: hash.is_some(),
+            recommended_action: if hash.is_some() { ACTION_REMOVE_COPIES } else { ACTION_REPORT }.to_string(),
+            hash,
+        });
+    }
+    Ok(groups)
+}
+
+// A file clean_collision_duplicates renamed back to its clean name
+#[derive(Debug, Clone, Serialize)]
+pub struct RenamedFile {
+    pub from: String,
+    pub to: String,
+}
+
+// Result of clean_collision_duplicates
+#[derive(Debug, Clone, Serialize)]
+pub struct CollisionCleanup {
+    // Copies moved to the trash
+    pub trashed: Vec<String>,
+    pub renamed: Vec<RenamedFile>,
+    // Groups left alone because their files differ
+    pub report_only: Vec<CollisionGroup>,
+    pub errors: Vec<String>,
+}
+
+// Move the redundant copies of each identical collision family under a folder
+// to the trash, keeping one chosen by strategy, then give the kept file its
+// clean name when nothing else has it. The families are found and hashed
+// again here rather than trusting an earlier find_collision_duplicates, and
+// families whose files differ are only reported
+pub async fn clean_collision_duplicates(app: &AppHandle, folder: String, strategy: String) -> Result<CollisionCleanup> {
+    if ![KEEP_ORIGINAL, KEEP_OLDEST, KEEP_NEWEST].contains(&strategy.as_str()) {
+        return Err(anyhow::anyhow!(
+            "Unknown strategy \"{}\" (expected \"{}\", \"{}\" or \"{}\")",
+            strategy,
+            KEEP_ORIGINAL,
+            KEEP_OLDEST,
+            KEEP_NEWEST
+        ));
+    }
+    let groups = find_collision_duplicates(app, folder).await?;
+    
+    let app_handle = app.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut cleanup = CollisionCleanup { trashed: vec![], renamed: vec![], report_only: vec![], errors: vec![] };
+        for group in groups {
+            if !group.identical {
+                cleanup.report_only.push(group);
+                continue;
+            }
+            clean_collision_group(&app_handle, &group, &strategy, &mut cleanup);
+        }
+        cleanup
+    })
+    .await
+    .map_err(Into::into)
+}
+
+fn clean_collision_group(app: &AppHandle, group: &CollisionGroup, strategy: &str, cleanup: &mut CollisionCleanup) {
+    let is_clean = |file: &CollisionFile| Path::new(&file.path).file_name().is_some_and(|name| *name == *group.clean_name);
+    // Timestamps are RFC 3339 in UTC, so they sort as text
+    let oldest = group.files.iter().min_by(|a, b| a.modified_at.cmp(&b.modified_at));
+    let keep = match strategy {
+        KEEP_NEWEST => group.files.iter().max_by(|a, b| a.modified_at.cmp(&b.modified_at)),
+        KEEP_OLDEST => oldest,
+        _ => group.files.iter().find(|file| is_clean(file)).or(oldest),
+    };
+    let Some(keep) = keep else { return };
+    
+    let mut trashed_all = true;
+    for file in group.files.iter().filter(|file| file.path != keep.path) {
+        let path = Path::new(&file.path);
+        match trash::delete(path) {
+            Ok(()) => {
+                let file_id = database::get_file_id_by_path(app, path).ok().flatten();
+                let recorded = database::record_file_event(
+                    app,
+                    file_id,
+                    database::EVENT_TRASHED,
+                    Some(&file.path),
+                    None,
+                    Some(&format!("duplicate of {}", keep.path)),
+                );
+                if let Err(e) = recorded {
+                    tracing::warn!(path = %file.path, error = %e, "Failed to record trashed duplicate");
+                }
+                if let Err(e) = forget_duplicate(app, file_id, Path::new(&keep.path)) {
+                    tracing::warn!(path = %file.path, error = %e, "Failed to remove trashed duplicate from the index");
+                }
+                cleanup.trashed.push(file.path.clone());
+            }
+            Err(e) => {
+                trashed_all = false;
+                cleanup.errors.push(format!("{}: {}", file.path, e));
+            }
+        }
+    }
+    
+    // Renaming is only safe once every other copy is gone, the clean name
+    // might belong to one of them
+    if !trashed_all || is_clean(keep) {
+        return;
+    }
+    match rename_to_clean_name(app, Path::new(&keep.path), &group.clean_name) {
+        Ok(new_path) => cleanup.renamed.push(RenamedFile {
+            from: keep.path.clone(),
+            to: new_path.to_string_lossy().to_string(),
+        }),
+        Err(e) => cleanup.errors.push(format!("{}: {:#}", keep.path, e)),
+    }
+}
+
+// Drop a trashed duplicate's row, handing its tags and history to the kept
+// copy when that one is indexed too
+fn forget_duplicate(app: &AppHandle, duplicate_id: Option<i64>, keep: &Path) -> Result<()> {
+    let Some(duplicate_id) = duplicate_id else { return Ok(()) };
+    match database::get_file_id_by_path(app, keep)? {
+        Some(keep_id) => database::merge_duplicate_file(app, keep_id, duplicate_id),
+        None => database::remove_file(app, duplicate_id),
+    }
+}
+
+// Rename a file in its folder, through the index when it's indexed so it
+// keeps its ID and tags. Fails rather than replace a file that has the name
+fn rename_to_clean_name(app: &AppHandle, path: &Path, clean_name: &str) -> Result<PathBuf> {
+    if let Some(file_id) = database::get_file_id_by_path(app, path)? {
+        return file_ops::rename_file(app, file_id, clean_name);
+    }
+    
+    let new_path = path.with_file_name(clean_name);
+    fs::OpenOptions::new().write(true).create_new(true).open(&new_path)?;
+    if let Err(e) = fs::rename(path, &new_path) {
+        let _ = fs::remove_file(&new_path);
+        return Err(e.into());
+    }
+    Ok(new_path)
+}
@@ -3,15 +3,488 @@ use aws_config::BehaviorVersion;
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_s3::Client;
 use aws_sdk_s3::config::{Builder, Region};
+use aws_sdk_s3::operation::put_object::builders::PutObjectFluentBuilder;
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{
+    Delete, GlacierJobParameters, ObjectIdentifier, RestoreRequest, ServerSideEncryption,
+    StorageClass, Tier,
+};
+use aws_smithy_types::body::SdkBody;
+use bytes::Bytes;
 use chrono::Utc;
 use std::fs;
-use std::path::{Path, PathBuf};
-use tauri::AppHandle;
-use tokio::sync::Semaphore;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use crate::commands::SearchFilter;
+use crate::database;
+use crate::events::{self, TransferProgress};
+use crate::file_ops;
+use crate::utils;
+use futures_util::stream::{self, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, watch, RwLock, Semaphore};
 
-// Maximum concurrent uploads
-const MAX_CONCURRENT_UPLOADS: usize = 5;
+// Bucket layouts: a full copy of every file per backup, or each distinct file
+// stored once under objects/ and referenced from per-backup manifests
+pub const LAYOUT_LEGACY: &str = "legacy";
+pub const LAYOUT_CONTENT_ADDRESSED: &str = "content_addressed";
+
+// Object at the bucket root holding its layout; no marker means legacy
+const LAYOUT_MARKER: &str = "smart_file_organizer_layout";
+
+// Where content-addressed objects live, named by their SHA-256
+const OBJECTS_PREFIX: &str = "objects/";
+
+// Manifest of a content-addressed backup
+const CONTENT_MANIFEST: &str = "content_manifest.json";
+
+// Existence checks sent at once when deduplicating
+const HEAD_BATCH_SIZE: usize = 50;
+
+// Unreferenced objects younger than this survive delete_backup
+const SWEEP_GRACE_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Held shared by content-addressed backups and exclusively by delete_backup.
+// A backup reuses objects that are already stored before its manifest
+// references them, so a sweep in between could delete them whatever their age.
+static CONTENT_OBJECTS: RwLock<()> = RwLock::const_new(());
+
+// Manifest stored alongside the files of a selection backup
+const SELECTION_MANIFEST: &str = "selection_manifest.json";
+
+// Uploads in flight at once unless configured otherwise
+const DEFAULT_UPLOAD_CONCURRENCY: usize = 5;
+
+// Upper bound for the configured upload concurrency
+const MAX_UPLOAD_CONCURRENCY: usize = 64;
+
+// Maximum concurrent downloads when restoring
+const MAX_CONCURRENT_DOWNLOADS: usize = 5;
+
+// Settings holding the transfer rate limits in KB/s; 0 or unset means unlimited
+pub const UPLOAD_LIMIT_SETTING: &str = "upload_limit_kbps";
+pub const DOWNLOAD_LIMIT_SETTING: &str = "download_limit_kbps";
+
+// Size of the chunks files are streamed in, and so the granularity of throttling
+const TRANSFER_CHUNK_SIZE: usize = 64 * 1024;
+
+// Token bucket shared by every transfer in one direction. The limit is read
+// through a watch channel so changing the setting takes effect mid-transfer.
+pub struct RateLimiter {
+    limit: watch::Receiver<u64>,
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    fn new(limit: watch::Receiver<u64>) -> Self {
+        RateLimiter {
+            limit,
+            bucket: Mutex::new((0.0, Instant::now())),
+        }
+    }
+
+    // Wait until `bytes` may be transferred
+    pub async fn acquire(&self, bytes: usize) {
+        // Bytes per second
+        let limit = *self.limit.borrow();
+        if limit == 0 {
+            return;
+        }
+
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let (tokens, last_refill) = &mut *bucket;
+            let now = Instant::now();
+
+            // Refill for the time that passed, holding at most one second of burst
+            *tokens = (*tokens + now.duration_since(*last_refill).as_secs_f64() * limit as f64)
+                .min(limit as f64);
+            *last_refill = now;
+
+            // Going into debt makes later callers wait for earlier ones too
+            *tokens -= bytes as f64;
+            if *tokens < 0.0 {
+                Duration::from_secs_f64(-*tokens / limit as f64)
+            } else {
+                Duration::ZERO
+            }
+        };
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+// Upload and download limiters, kept in app state
+pub struct BandwidthLimits {
+    upload_limit: watch::Sender<u64>,
+    download_limit: watch::Sender<u64>,
+    pub upload: Arc<RateLimiter>,
+    pub download: Arc<RateLimiter>,
+}
+
+impl Default for BandwidthLimits {
+    fn default() -> Self {
+        let (upload_limit, upload_rx) = watch::channel(0);
+        let (download_limit, download_rx) = watch::channel(0);
+        BandwidthLimits {
+            upload_limit,
+            download_limit,
+            upload: Arc::new(RateLimiter::new(upload_rx)),
+            download: Arc::new(RateLimiter::new(download_rx)),
+        }
+    }
+}
+
+// Parse a KB/s setting into bytes per second
+fn parse_limit_kbps(value: Option<&str>) -> u64 {
+    value
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(0)
+        .saturating_mul(1024)
+}
+
+// Load the saved rate limits into the limiters
+pub fn load_bandwidth_limits(app: &AppHandle) -> Result<()> {
+    let upload = database::get_setting(app, UPLOAD_LIMIT_SETTING)?;
+    let download = database::get_setting(app, DOWNLOAD_LIMIT_SETTING)?;
+
+    let limits = app.state::<BandwidthLimits>();
+    limits.upload_limit.send_replace(parse_limit_kbps(upload.as_deref()));
+    limits.download_limit.send_replace(parse_limit_kbps(download.as_deref()));
+
+    Ok(())
+}
+
+// Apply a changed setting to the limiters, if it is one of the rate limits
+pub fn apply_bandwidth_setting(app: &AppHandle, key: &str, value: &str) {
+    let limits = app.state::<BandwidthLimits>();
+    match key {
+        UPLOAD_LIMIT_SETTING => {
+            limits.upload_limit.send_replace(parse_limit_kbps(Some(value)));
+        }
+        DOWNLOAD_LIMIT_SETTING => {
+            limits.download_limit.send_replace(parse_limit_kbps(Some(value)));
+        }
+        _ => {}
+    }
+}
+
+// Settings holding the default backup options
+pub const STORAGE_CLASS_SETTING: &str = "backup_storage_class";
+pub const SSE_SETTING: &str = "backup_server_side_encryption";
+pub const KMS_KEY_ID_SETTING: &str = "backup_kms_key_id";
+pub const COMPRESSION_SETTING: &str = "backup_compression";
+pub const UPLOAD_CONCURRENCY_SETTING: &str = "upload_concurrency";
+
+// Compression modes for uploads
+const COMPRESSION_NONE: &str = "none";
+const COMPRESSION_GZIP: &str = "gzip";
+
+// Suffix added to the key of files uploaded gzipped, removed again on restore
+const COMPRESSED_SUFFIX: &str = ".compressed.gz";
+
+// Object metadata describing a compressed upload
+const META_COMPRESSION: &str = "compression";
+const META_ORIGINAL_SIZE: &str = "original-size";
+const META_ORIGINAL_SHA256: &str = "original-sha256";
+
+// Storage classes backups can be written to
+const STORAGE_CLASSES: [&str; 7] = [
+    "STANDARD",
+    "STANDARD_IA",
+    "ONEZONE_IA",
+    "INTELLIGENT_TIERING",
+    "GLACIER_IR",
+    "GLACIER",
+    "DEEP_ARCHIVE",
+];
+
+// Server-side encryption modes: SSE-S3 and SSE-KMS
+const SSE_S3: &str = "AES256";
+const SSE_KMS: &str = "aws:kms";
+
+// Storage class and encryption for uploaded objects; unset fields use the bucket defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupOptions {
+    pub storage_class: Option<String>,
+    pub server_side_encryption: Option<String>,
+    pub kms_key_id: Option<String>,
+    // "gzip" or "none"; files that are already compressed are always uploaded as is
+    pub compression: Option<String>,
+    // Number of files uploaded at once
+    pub concurrency: Option<usize>,
+}
+
+impl BackupOptions {
+    // Fill fields not given for this call from the saved defaults, then validate
+    pub fn resolve(app: &AppHandle, options: Option<BackupOptions>) -> Result<BackupOptions> {
+        let options = options.unwrap_or_default();
+        let non_empty = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+        
+        let resolved = BackupOptions {
+            storage_class: non_empty(options.storage_class)
+                .or(non_empty(database::get_setting(app, STORAGE_CLASS_SETTING)?)),
+            server_side_encryption: non_empty(options.server_side_encryption)
+                .or(non_empty(database::get_setting(app, SSE_SETTING)?)),
+            kms_key_id: non_empty(options.kms_key_id)
+                .or(non_empty(database::get_setting(app, KMS_KEY_ID_SETTING)?)),
+            compression: non_empty(options.compression)
+                .or(non_empty(database::get_setting(app, COMPRESSION_SETTING)?)),
+            concurrency: match options.concurrency {
+                Some(concurrency) => Some(concurrency),
+                None => database::get_setting(app, UPLOAD_CONCURRENCY_SETTING)?
+                    .map(|v| v.trim().parse::<usize>())
+                    .transpose()
+                    .map_err(|_| anyhow::anyhow!("Invalid {} setting", UPLOAD_CONCURRENCY_SETTING))?,
+            },
+        };
+        resolved.validate()?;
+        
+        Ok(resolved)
+    }
+    
+    // Set the storage class and encryption on an upload
+    fn apply(&self, request: PutObjectFluentBuilder) -> PutObjectFluentBuilder {
+        request
+            .set_storage_class(self.storage_class.as_deref().map(StorageClass::from))
+            .set_server_side_encryption(self.server_side_encryption.as_deref().map(ServerSideEncryption::from))
+            .set_ssekms_key_id(self.kms_key_id.clone())
+    }
+    
+    // Key to upload a file under; files that will be gzipped get a suffix
+    fn object_key(&self, path: &Path, key: String) -> String {
+        if self.compression.as_deref() == Some(COMPRESSION_GZIP) && utils::is_compressible(path) {
+            format!("{}{}", key, COMPRESSED_SUFFIX)
+        } else {
+            key
+        }
+    }
+    
+    fn upload_concurrency(&self) -> usize {
+        self.concurrency.unwrap_or(DEFAULT_UPLOAD_CONCURRENCY)
+    }
+    
+    fn validate(&self) -> Result<()> {
+        if let Some(concurrency) = self.concurrency {
+            if concurrency == 0 || concurrency > MAX_UPLOAD_CONCURRENCY {
+                return Err(anyhow::anyhow!(
+                    "Upload concurrency must be between 1 and {}",
+                    MAX_UPLOAD_CONCURRENCY
+                ));
+            }
+        }
+        
+        if let Some(compression) = &self.compression {
+            if compression != COMPRESSION_NONE && compression != COMPRESSION_GZIP {
+                return Err(anyhow::anyhow!("Unknown compression: {}", compression));
+            }
+        }
+        
+        if let Some(class) = &self.storage_class {
+            if !STORAGE_CLASSES.contains(&class.as_str()) {
+                return Err(anyhow::anyhow!("Unknown storage class: {}", class));
+            }
+        }
+        
+        match self.server_side_encryption.as_deref() {
+            None | Some(SSE_S3) if self.kms_key_id.is_some() => {
+                Err(anyhow::anyhow!("A KMS key ID needs {} encryption", SSE_KMS))
+            }
+            None | Some(SSE_S3) | Some(SSE_KMS) => Ok(()),
+            Some(other) => Err(anyhow::anyhow!("Unknown server-side encryption: {}", other)),
+        }
+    }
+}
+
+// What a selection backup contains and which filter picked it
+#[derive(Serialize, Deserialize)]
+struct SelectionManifest {
+    created_at: String,
+    filter: SearchFilter,
+    #[serde(default)]
+    options: BackupOptions,
+    files: Vec<SelectionManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SelectionManifestEntry {
+    key: String,
+    original_path: String,
+    size: u64,
+    #[serde(default)]
+    compressed: bool,
+    // SHA-256 of the original file, recorded for compressed uploads
+    #[serde(default)]
+    sha256: Option<String>,
+}
+
+// Paths of a content-addressed backup and the objects holding their content
+#[derive(Serialize, Deserialize)]
+struct ContentManifest {
+    created_at: String,
+    folder_path: String,
+    #[serde(default)]
+    options: BackupOptions,
+    files: Vec<ContentManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ContentManifestEntry {
+    // Relative to the backed up folder, "/" separated
+    path: String,
+    sha256: String,
+    key: String,
+    size: u64,
+}
+
+// Result of a selection backup
+#[derive(Debug, Serialize)]
+pub struct SelectionBackupSummary {
+    pub prefix: String,
+    pub uploaded: usize,
+    // Indexed files matching the filter that no longer exist on disk
+    pub missing: Vec<String>,
+}
+
+// Counters updated by transfer tasks and read by the progress reporter
+#[derive(Default)]
+struct TransferCounters {
+    bytes: AtomicU64,
+    files: AtomicUsize,
+}
+
+// Emit progress under each of the event names every second until the
+// returned task is aborted
+fn spawn_progress_reporter(
+    app: &AppHandle,
+    event_names: &'static [&'static str],
+    counters: Arc<TransferCounters>,
+    total_bytes: u64,
+    total_files: usize,
+) -> tokio::task::JoinHandle<()> {
+    let app = app.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        let mut last_bytes = 0;
+        let mut last_tick = Instant::now();
+        loop {
+            interval.tick().await;
+            let bytes = counters.bytes.load(Ordering::Relaxed);
+            let elapsed = last_tick.elapsed().as_secs_f64().max(0.001);
+            let progress = TransferProgress {
+                bytes_transferred: bytes,
+                total_bytes,
+                files_completed: counters.files.load(Ordering::Relaxed),
+                total_files,
+                throughput_bytes_per_sec: (bytes.saturating_sub(last_bytes) as f64 / elapsed) as u64,
+            };
+            for event in event_names {
+                let _ = app.emit(event, progress.clone());
+            }
+            last_bytes = bytes;
+            last_tick = Instant::now();
+        }
+    })
+}
+
+// Request body fed chunk by chunk from a reader task
+struct ChannelBody {
+    chunks: mpsc::Receiver<std::io::Result<Bytes>>,
+    size: u64,
+}
+
+impl http_body::Body for ChannelBody {
+    type Data = Bytes;
+    type Error = std::io::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<http_body::Frame<Bytes>, std::io::Error>>> {
+        self.chunks
+            .poll_recv(cx)
+            .map(|chunk| chunk.map(|result| result.map(http_body::Frame::data)))
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        http_body::SizeHint::with_exact(self.size)
+    }
+}
+
+// Stream a file as an upload body, paced by the upload limiter
+fn throttled_file_body(
+    path: PathBuf,
+    size: u64,
+    limiter: Arc<RateLimiter>,
+    counters: Arc<TransferCounters>,
+) -> ByteStream {
+    // Retryable: the SDK calls this again to restart the stream if a request is retried
+    ByteStream::new(SdkBody::retryable(move || {
+        let (tx, chunks) = mpsc::channel(4);
+        let path = path.clone();
+        let limiter = limiter.clone();
+        let counters = counters.clone();
+
+        tokio::spawn(async move {
+            let mut file = match tokio::fs::File::open(&path).await {
+                Ok(file) => file,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
+
+            let mut buffer = vec![0u8; TRANSFER_CHUNK_SIZE];
+            loop {
+                match file.read(&mut buffer).await {
+                    Ok(0) => break,
+                    Ok(read) => {
+                        limiter.acquire(read).await;
+                        counters.bytes.fetch_add(read as u64, Ordering::Relaxed);
+                        if tx.send(Ok(Bytes::copy_from_slice(&buffer[..read]))).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        break;
+                    }
+                }
+            }
+        });
+
+        SdkBody::from_body_1_x(ChannelBody { chunks, size })
+    }))
+}
+
+// Write a download to disk chunk by chunk as it arrives, paced by the download
+// limiter, so memory use doesn't grow with the object size
+async fn write_throttled(
+    mut body: ByteStream,
+    destination: &Path,
+    limiter: &RateLimiter,
+    counters: &TransferCounters,
+) -> Result<()> {
+    let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(destination).await?);
+    while let Some(chunk) = body.try_next().await? {
+        limiter.acquire(chunk.len()).await;
+        file.write_all(&chunk).await?;
+        counters.bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+    }
+    file.flush().await?;
+    Ok(())
+}
 
 // AWS S3 client configuration
 async fn get_s3_client() -> Result<Client> {
@@ -29,23 +502,361 @@ async fn get_s3_client() -> Result<Client> {
     Ok(Client::from_conf(s3_config))
 }
 
-// Backup a folder to S3
-pub async fn backup_folder(_app: &AppHandle, folder_path: String, bucket_name: String) -> Result<()> {
+// Backup a folder to S3, returning the number of files backed up
+#[tracing::instrument(skip(app, options))]
+pub async fn backup_folder(
+    app: &AppHandle,
+    folder_path: String,
+    bucket_name: String,
+    include_database: bool,
+    options: &BackupOptions,
+) -> Result<usize> {
+    let folder = Some(folder_path.as_str());
+    events::task_updated(app, events::TASK_BACKUP, events::TASK_STARTED, folder, None);
+    let result = backup_folder_inner(app, &folder_path, &bucket_name, include_database, options).await;
+    match &result {
+        Ok(_) => events::task_updated(app, events::TASK_BACKUP, events::TASK_FINISHED, folder, None),
+        Err(e) => events::task_updated(app, events::TASK_BACKUP, events::TASK_FAILED, folder, Some(format!("{:#}", e))),
+    }
+    result
+}
+
+async fn backup_folder_inner(
+    app: &AppHandle,
+    folder_path: &str,
+    bucket_name: &str,
+    include_database: bool,
+    options: &BackupOptions,
+) -> Result<usize> {
     // Check if folder exists
-    let folder = Path::new(&folder_path);
+    let folder = Path::new(folder_path);
     if !folder.exists() || !folder.is_dir() {
         return Err(anyhow::anyhow!("Invalid folder path"));
     }
     
     // Get S3 client
     let client = get_s3_client().await?;
+    ensure_bucket(&client, bucket_name).await?;
     
-    // Create bucket if it doesn't exist
+    // Find all files in the folder (recursively)
+    let files = utils::collect_files(folder, None, true)?;
+    let file_count = files.len();
+    
+    // Create a timestamp for the backup
+    let timestamp = Utc::now().format("%Y%m%d%H%M%S").to_string();
+    
+    if bucket_layout(&client, bucket_name).await? == LAYOUT_CONTENT_ADDRESSED {
+        backup_content_addressed(app, &client, bucket_name, folder, files, &timestamp, options).await?;
+    } else {
+        backup_legacy(app, &client, bucket_name, folder, files, &timestamp, options).await?;
+    }
+    
+    // Optionally store a snapshot of the tag/history database next to the backup
+    if include_database {
+        backup_database_snapshot(app, &client, bucket_name, &timestamp, options).await?;
+    }
+    
+    Ok(file_count)
+}
+
+// Upload a full copy of every file under backup_{timestamp}/
+async fn backup_legacy(
+    app: &AppHandle,
+    client: &Client,
+    bucket_name: &str,
+    folder: &Path,
+    files: Vec<PathBuf>,
+    timestamp: &str,
+    options: &BackupOptions,
+) -> Result<()> {
+    // Key each file by its path relative to the backed up folder
+    let uploads = files
+        .into_iter()
+        .map(|file_path| {
+            let relative_path = file_path.strip_prefix(folder).unwrap_or(&file_path);
+            let key = format!(
+                "backup_{}/{}",
+                timestamp,
+                relative_path.to_string_lossy().replace("\\", "/")
+            );
+            let key = options.object_key(&file_path, key);
+            (file_path, key)
+        })
+        .collect();
+    
+    upload_files(app, client, bucket_name, uploads, options).await?;
+    
+    Ok(())
+}
+
+// Upload only the files whose content isn't stored yet, then a manifest
+// mapping every relative path to its object
+async fn backup_content_addressed(
+    app: &AppHandle,
+    client: &Client,
+    bucket_name: &str,
+    folder: &Path,
+    files: Vec<PathBuf>,
+    timestamp: &str,
+    options: &BackupOptions,
+) -> Result<()> {
+    let _objects = CONTENT_OBJECTS.read().await;
+    
+    // The hash is the object's name
+    let hashed = file_ops::hash_files(files.clone(), file_ops::hash_concurrency(app)).await;
+    
+    let mut manifest = ContentManifest {
+        created_at: Utc::now().to_rfc3339(),
+        folder_path: folder.to_string_lossy().to_string(),
+        options: options.clone(),
+        files: vec![],
+    };
+    let mut uploads: HashMap<String, PathBuf> = HashMap::new();
+    
+    for path in files {
+        // Files that vanished or couldn't be read since they were listed are left out
+        let Some(sha256) = hashed.hashes.get(&*path.to_string_lossy()).cloned() else { continue };
+        let key = options.object_key(&path, format!("{}{}", OBJECTS_PREFIX, sha256));
+        
+        manifest.files.push(ContentManifestEntry {
+            path: path.strip_prefix(folder).unwrap_or(&path).to_string_lossy().replace('\\', "/"),
+            sha256,
+            key: key.clone(),
+            size: utils::get_file_size(&path).unwrap_or(0),
+        });
+        uploads.entry(key).or_insert(path);
+    }
+    
+    // Only upload content the bucket doesn't have yet
+    let existing = existing_objects(client, bucket_name, uploads.keys().cloned().collect()).await?;
+    let uploads = uploads
+        .into_iter()
+        .filter(|(key, _)| !existing.contains(key))
+        .map(|(key, path)| (path, key))
+        .collect();
+    upload_files(app, client, bucket_name, uploads, options).await?;
+    
+    // Written last, so objects referenced by a manifest are always there
+    let request = client.put_object()
+        .bucket(bucket_name)
+        .key(format!("backup_{}/{}", timestamp, CONTENT_MANIFEST))
+        .body(ByteStream::from(serde_json::to_vec_pretty(&manifest)?));
+    BackupOptions { storage_class: None, ..options.clone() }
+        .apply(request)
+        .send()
+        .await?;
+    
+    Ok(())
+}
+
+// Check which of the keys exist, a batch of HEAD requests at a time
+async fn existing_objects(client: &Client, bucket: &str, keys: Vec<String>) -> Result<HashSet<String>> {
+    let mut existing = HashSet::new();
+    
+    for batch in keys.chunks(HEAD_BATCH_SIZE) {
+        let mut heads = tokio::task::JoinSet::new();
+        for key in batch {
+            let client = client.clone();
+            let bucket = bucket.to_string();
+            let key = key.clone();
+            heads.spawn(async move {
+                let result = client.head_object().bucket(&bucket).key(&key).send().await;
+                (key, result)
+            });
+        }
+        
+        while let Some(joined) = heads.join_next().await {
+            match joined? {
+                (key, Ok(_)) => {
+                    existing.insert(key);
+                }
+                (_, Err(e)) if e.as_service_error().map(|e| e.is_not_found()).unwrap_or(false) => {}
+                (_, Err(e)) => return Err(e.into()),
+            }
+        }
+    }
+    
+    Ok(existing)
+}
+
+// Get the bucket's layout, without creating anything
+pub async fn get_bucket_layout(bucket: &str) -> Result<String> {
+    let client = get_s3_client().await?;
+    bucket_layout(&client, bucket).await
+}
+
+async fn bucket_layout(client: &Client, bucket: &str) -> Result<String> {
+    match client.get_object().bucket(bucket).key(LAYOUT_MARKER).send().await {
+        Ok(resp) => {
+            let bytes = resp.body.collect().await?.into_bytes();
+            Ok(String::from_utf8_lossy(&bytes).trim().to_string())
+        }
+        Err(e) if e.as_service_error().map(|e| e.is_no_such_key()).unwrap_or(false) => {
+            Ok(LAYOUT_LEGACY.to_string())
+        }
+        // A bucket that doesn't exist yet gets the default layout on first backup
+        Err(e) if e.raw_response().map(|r| r.status().as_u16() == 404).unwrap_or(false) => {
+            Ok(LAYOUT_LEGACY.to_string())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Choose the layout for new backups in a bucket; existing backups stay restorable either way
+pub async fn set_bucket_layout(bucket: &str, layout: &str) -> Result<()> {
+    if layout != LAYOUT_LEGACY && layout != LAYOUT_CONTENT_ADDRESSED {
+        return Err(anyhow::anyhow!("Unknown bucket layout: {}", layout));
+    }
+    
+    let client = get_s3_client().await?;
+    ensure_bucket(&client, bucket).await?;
+    client.put_object()
+        .bucket(bucket)
+        .key(LAYOUT_MARKER)
+        .body(ByteStream::from(layout.as_bytes().to_vec()))
+        .send()
+        .await?;
+    
+    Ok(())
+}
+
+// Read a JSON object, or None if there is no such key
+async fn read_json_object<T: serde::de::DeserializeOwned>(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+) -> Result<Option<T>> {
+    match client.get_object().bucket(bucket).key(key).send().await {
+        Ok(resp) => {
+            let bytes = resp.body.collect().await?.into_bytes();
+            Ok(Some(serde_json::from_slice(&bytes)?))
+        }
+        Err(e) if e.as_service_error().map(|e| e.is_no_such_key()).unwrap_or(false) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// List every object under a prefix, following continuation tokens
+async fn list_all_objects(client: &Client, bucket: &str, prefix: &str) -> Result<Vec<aws_sdk_s3::types::Object>> {
+    let mut objects = vec![];
+    let mut continuation = None;
+    loop {
+        let resp = client.list_objects_v2()
+            .bucket(bucket)
+            .prefix(prefix)
+            .set_continuation_token(continuation)
+            .send()
+            .await?;
+        objects.extend(resp.contents.unwrap_or_default());
+        
+        continuation = resp.next_continuation_token;
+        if continuation.is_none() {
+            return Ok(objects);
+        }
+    }
+}
+
+// List the top-level backup prefixes, following continuation tokens
+async fn list_backup_prefixes(client: &Client, bucket: &str) -> Result<Vec<String>> {
+    let mut prefixes = vec![];
+    let mut continuation = None;
+    loop {
+        let resp = client.list_objects_v2()
+            .bucket(bucket)
+            .delimiter("/")
+            .set_continuation_token(continuation)
+            .send()
+            .await?;
+        prefixes.extend(
+            resp.common_prefixes
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|p| p.prefix)
+                .filter(|p| p != OBJECTS_PREFIX),
+        );
+        
+        continuation = resp.next_continuation_token;
+        if continuation.is_none() {
+            return Ok(prefixes);
+        }
+    }
+}
+
+// Delete keys, up to 1000 per request
+async fn delete_keys(client: &Client, bucket: &str, keys: Vec<String>) -> Result<()> {
+    for batch in keys.chunks(1000) {
+        let objects = batch
+            .iter()
+            .map(|key| ObjectIdentifier::builder().key(key).build())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        client.delete_objects()
+            .bucket(bucket)
+            .delete(Delete::builder().set_objects(Some(objects)).quiet(true).build()?)
+            .send()
+            .await?;
+    }
+    
+    Ok(())
+}
+
+// Delete a backup. Content-addressed objects are shared between backups, so
+// afterwards every remaining manifest is read and only objects none of them
+// reference are removed. Returns the number of objects deleted.
+pub async fn delete_backup(bucket: &str, backup_prefix: &str) -> Result<usize> {
+    let prefix = backup_prefix_dir(backup_prefix);
+    if prefix == OBJECTS_PREFIX || prefix == "/" {
+        return Err(anyhow::anyhow!("{} is not a backup", backup_prefix));
+    }
+    
+    let client = get_s3_client().await?;
+    
+    // Wait for running backups to write their manifests
+    let _objects = CONTENT_OBJECTS.write().await;
+    
+    let keys: Vec<String> = list_all_objects(&client, bucket, &prefix)
+        .await?
+        .into_iter()
+        .filter_map(|o| o.key)
+        .collect();
+    let mut deleted = keys.len();
+    delete_keys(&client, bucket, keys).await?;
+    
+    // Mark every object still referenced...
+    let mut referenced = HashSet::new();
+    for backup in list_backup_prefixes(&client, bucket).await? {
+        if let Some(manifest) = read_content_manifest(&client, bucket, &backup).await? {
+            referenced.extend(manifest.files.into_iter().map(|f| f.key));
+        }
+    }
+    
+    // ...and sweep the rest, sparing recent objects a backup from another
+    // machine may not have written its manifest for yet
+    let cutoff = std::time::SystemTime::now() - SWEEP_GRACE_PERIOD;
+    let unreferenced: Vec<String> = list_all_objects(&client, bucket, OBJECTS_PREFIX)
+        .await?
+        .into_iter()
+        .filter(|o| {
+            o.last_modified
+                .and_then(|t| std::time::SystemTime::try_from(t).ok())
+                .map(|t| t < cutoff)
+                .unwrap_or(false)
+        })
+        .filter_map(|o| o.key)
+        .filter(|key| !referenced.contains(key))
+        .collect();
+    deleted += unreferenced.len();
+    delete_keys(&client, bucket, unreferenced).await?;
+    
+    Ok(deleted)
+}
+
+// Create the bucket if it doesn't exist yet
+async fn ensure_bucket(client: &Client, bucket_name: &str) -> Result<()> {
     let buckets = client.list_buckets().send().await?;
     let bucket_exists = if let Some(bucket_list) = buckets.buckets {
         bucket_list.iter().any(|b| {
             if let Some(name) = &b.name {
-                name == &bucket_name
+                name == bucket_name
             } else {
                 false
             }
@@ -56,82 +867,722 @@ pub async fn backup_folder(_app: &AppHandle, folder_path: String, bucket_name: S
     
     if !bucket_exists {
         client.create_bucket()
-            .bucket(&bucket_name)
+            .bucket(bucket_name)
             .send()
             .await?;
     }
     
-    // Find all files in the folder (recursively)
-    let files = collect_files(folder)?;
+    Ok(())
+}
+
+// Upload (local path, key) pairs concurrently, reporting progress as backup_progress.
+// Keys ending in COMPRESSED_SUFFIX are gzipped on the way; their original
+// SHA-256 is returned by key so it can be checked after restoring.
+async fn upload_files(
+    app: &AppHandle,
+    client: &Client,
+    bucket_name: &str,
+    uploads: Vec<(PathBuf, String)>,
+    options: &BackupOptions,
+) -> Result<HashMap<String, String>> {
+    // Report progress while uploading
+    let counters = Arc::new(TransferCounters::default());
+    let total_bytes = uploads.iter().map(|(f, _)| utils::get_file_size(f).unwrap_or(0)).sum();
+    let total_files = uploads.len();
+    let reporter = spawn_progress_reporter(app, &[events::BACKUP_PROGRESS, events::LEGACY_BACKUP_PROGRESS], counters.clone(), total_bytes, total_files);
+    
+    let context = UploadContext {
+        app,
+        client,
+        bucket: bucket_name,
+        options,
+        limiter: app.state::<BandwidthLimits>().upload.clone(),
+        counters,
+    };
+    
+    // A bounded number of uploads in flight, however many files there are
+    let mut uploaded = stream::iter(uploads)
+        .map(|(file_path, key)| upload_file(&context, file_path, key))
+        .buffer_unordered(options.upload_concurrency());
+    
+    // Wait for all uploads to complete
+    let result = async {
+        let mut checksums = HashMap::new();
+        while let Some(upload) = uploaded.next().await {
+            if let Some((key, checksum)) = upload? {
+                checksums.insert(key, checksum);
+            }
+        }
+        Ok::<_, anyhow::Error>(checksums)
+    }.await;
+    reporter.abort();
+    match &result {
+        Ok(_) => tracing::info!(bucket = bucket_name, files = total_files, "Upload finished"),
+        Err(e) => tracing::error!(bucket = bucket_name, error = format!("{:#}", e), "Upload failed"),
+    }
+    result
+}
+
+// Everything an upload needs besides the file itself
+struct UploadContext<'a> {
+    app: &'a AppHandle,
+    client: &'a Client,
+    bucket: &'a str,
+    options: &'a BackupOptions,
+    limiter: Arc<RateLimiter>,
+    counters: Arc<TransferCounters>,
+}
+
+// Upload one file, returning its key and original SHA-256 if it was compressed
+async fn upload_file(
+    context: &UploadContext<'_>,
+    file_path: PathBuf,
+    key: String,
+) -> Result<Option<(String, String)>> {
+    let counters = &context.counters;
+    
+    // Compress into a temporary file first; S3 needs the length up front
+    let original_size = utils::get_file_size(&file_path)?;
+    // The compressed copy is removed however the upload ends, also when it
+    // fails or is cancelled
+    let compressed = key.ends_with(COMPRESSED_SUFFIX).then(|| {
+        TempFile(std::env::temp_dir().join(format!("smart_file_organizer_{}.gz", temp_name_for_key(&key))))
+    });
+    let (upload_path, checksum) = match &compressed {
+        Some(TempFile(compressed_path)) => {
+            let (source, target) = (file_path.clone(), compressed_path.clone());
+            let checksum = tokio::task::spawn_blocking(move || utils::gzip_file(&source, &target)).await??;
+            (compressed_path.clone(), Some(checksum))
+        }
+        None => (file_path.clone(), None),
+    };
+    
+    let result = async {
+        // Get file content
+        let size = utils::get_file_size(&upload_path)?;
+        let body = throttled_file_body(upload_path.clone(), size, context.limiter.clone(), counters.clone());
+        
+        // Upload to S3
+        let mut request = context.client.put_object()
+            .bucket(context.bucket)
+            .key(&key)
+            .content_length(size as i64)
+            .body(body);
+        if let Some(checksum) = &checksum {
+            request = request
+                .metadata(META_COMPRESSION, COMPRESSION_GZIP)
+                .metadata(META_ORIGINAL_SIZE, original_size.to_string())
+                .metadata(META_ORIGINAL_SHA256, checksum);
+        }
+        context.options.apply(request).send().await?;
+        
+        // Progress is measured against original sizes, so count what compression saved
+        counters.bytes.fetch_add(original_size.saturating_sub(size), Ordering::Relaxed);
+        Ok::<_, anyhow::Error>(())
+    }.await;
+    
+    result?;
+    counters.files.fetch_add(1, Ordering::Relaxed);
+    
+    // Note the backup in the file's history if it's indexed
+    if let Ok(Some(file_id)) = database::get_file_id_by_path(context.app, &file_path) {
+        let result = database::record_file_event(
+            context.app,
+            Some(file_id),
+            database::EVENT_BACKED_UP,
+            None,
+            Some(&format!("s3://{}/{}", context.bucket, key)),
+            Some(context.bucket),
+        );
+        if let Err(e) = result {
+            tracing::warn!(path = %file_path.display(), error = %e, "Failed to record backup");
+        }
+    }
+    
+    Ok(checksum.map(|checksum| (key, checksum)))
+}
+
+// A temporary file, deleted when dropped
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+// Name for a temporary file that is unique per key
+fn temp_name_for_key(key: &str) -> String {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    key.hash(&mut hasher);
+    format!("{:016x}_{}", hasher.finish(), std::process::id())
+}
+
+// Local file name for an object key, without the compression suffix
+fn restored_name(key: &str) -> &str {
+    key.strip_suffix(COMPRESSED_SUFFIX).unwrap_or(key)
+}
+
+// Back up the indexed files matching a search filter. Files can come from
+// anywhere, so each is keyed by its full path and listed in a manifest that
+// restore_selection uses to put it back.
+pub async fn backup_selection(
+    app: &AppHandle,
+    bucket_name: &str,
+    filter: SearchFilter,
+    options: &BackupOptions,
+) -> Result<SelectionBackupSummary> {
+    // Resolve the filter, leaving out files that have gone since they were indexed
+    let (existing, missing): (Vec<_>, Vec<_>) = database::search_files(app, &filter)?
+        .into_iter()
+        .map(|file| PathBuf::from(file.path))
+        .partition(|path| path.is_file());
+    
+    let client = get_s3_client().await?;
+    ensure_bucket(&client, bucket_name).await?;
     
-    // Create a timestamp for the backup
     let timestamp = Utc::now().format("%Y%m%d%H%M%S").to_string();
+    let prefix = format!("backup_{}/", timestamp);
     
-    // Upload files concurrently using a semaphore inside each task
-    let mut tasks = vec![];
+    let mut manifest = SelectionManifest {
+        created_at: Utc::now().to_rfc3339(),
+        filter,
+        options: options.clone(),
+        files: vec![],
+    };
+    let mut uploads = vec![];
+    for path in existing {
+        let key = options.object_key(&path, selection_key(&prefix, &path));
+        manifest.files.push(SelectionManifestEntry {
+            key: key.clone(),
+            original_path: path.to_string_lossy().to_string(),
+            size: utils::get_file_size(&path).unwrap_or(0),
+            compressed: key.ends_with(COMPRESSED_SUFFIX),
+            sha256: None,
+        });
+        uploads.push((path, key));
+    }
+    
+    let checksums = upload_files(app, &client, bucket_name, uploads, options).await?;
+    for entry in &mut manifest.files {
+        entry.sha256 = checksums.get(&entry.key).cloned();
+    }
     
-    for file_path in files {
+    // Written last, so a backup with a manifest is always complete. Kept in
+    // the standard class so it can be read without an archive restore.
+    let request = client.put_object()
+        .bucket(bucket_name)
+        .key(format!("{}{}", prefix, SELECTION_MANIFEST))
+        .body(ByteStream::from(serde_json::to_vec_pretty(&manifest)?));
+    BackupOptions { storage_class: None, ..options.clone() }
+        .apply(request)
+        .send()
+        .await?;
+    
+    Ok(SelectionBackupSummary {
+        prefix,
+        uploaded: manifest.files.len(),
+        missing: missing.into_iter().map(|p| p.to_string_lossy().to_string()).collect(),
+    })
+}
+
+// Key for a selection backup file: its full path with the root and drive separators dropped
+fn selection_key(prefix: &str, path: &Path) -> String {
+    let parts: Vec<String> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Prefix(p) => Some(
+                p.as_os_str()
+                    .to_string_lossy()
+                    .replace([':', '\\', '?'], "")
+            ),
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .filter(|part| !part.is_empty())
+        .collect();
+    
+    format!("{}files/{}", prefix, parts.join("/"))
+}
+
+// Restore a selection backup. Without a destination each file goes back to its
+// original path; with one, all files are put side by side in that folder.
+pub async fn restore_selection(
+    app: &AppHandle,
+    bucket: &str,
+    backup_prefix: &str,
+    destination: Option<&Path>,
+) -> Result<RestoreFilesResult> {
+    let client = get_s3_client().await?;
+    
+    let prefix = backup_prefix_dir(backup_prefix);
+    
+    // Read the manifest
+    let resp = client.get_object()
+        .bucket(bucket)
+        .key(format!("{}{}", prefix, SELECTION_MANIFEST))
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("{} is not a selection backup: {}", backup_prefix, e))?;
+    let manifest: SelectionManifest = serde_json::from_slice(&resp.body.collect().await?.into_bytes())?;
+    
+    // Work out every target up front so flat restores can't collide with each other
+    let mut targets = vec![];
+    let mut taken = std::collections::HashSet::new();
+    for entry in &manifest.files {
+        let original = PathBuf::from(&entry.original_path);
+        let target = match destination {
+            Some(folder) => {
+                let mut target = folder.join(original.file_name().unwrap_or_default());
+                let mut n = 1;
+                while taken.contains(&target) || target.exists() {
+                    target = folder.join(utils::numbered_file_name(&original, n));
+                    n += 1;
+                }
+                target
+            }
+            None => original,
+        };
+        taken.insert(target.clone());
+        targets.push((entry.key.clone(), target));
+    }
+    
+    let total_bytes = manifest.files.iter().map(|f| f.size).sum();
+    let (restored, archived) = download_files(app, &client, bucket, targets, total_bytes).await?;
+    
+    Ok(RestoreFilesResult {
+        restored,
+        archived,
+        ..Default::default()
+    })
+}
+
+// Download (key, local path) pairs concurrently, reporting progress as restore_progress.
+// Returns the paths written and the keys of objects that are archived and must
+// be restored from their archive storage class before they can be downloaded.
+async fn download_files(
+    app: &AppHandle,
+    client: &Client,
+    bucket: &str,
+    downloads: Vec<(String, PathBuf)>,
+    total_bytes: u64,
+) -> Result<(Vec<String>, Vec<String>)> {
+    let limiter = app.state::<BandwidthLimits>().download.clone();
+    let counters = Arc::new(TransferCounters::default());
+    let reporter = spawn_progress_reporter(app, &[events::RESTORE_PROGRESS], counters.clone(), total_bytes, downloads.len());
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_DOWNLOADS));
+    
+    let mut tasks = vec![];
+    for (key, target) in downloads {
         let client = client.clone();
-        let bucket = bucket_name.clone();
-        let folder_base = folder.to_path_buf();
-        let timestamp = timestamp.clone(); // Clone timestamp for each task
+        let bucket = bucket.to_string();
+        let limiter = limiter.clone();
+        let counters = counters.clone();
+        let semaphore = semaphore.clone();
         
-        let task = tokio::spawn(async move {
-            // Create a local semaphore inside the task
-            let semaphore = Semaphore::new(1);
+        tasks.push(tokio::spawn(async move {
             let _permit = semaphore.acquire().await?;
             
-            // Create the S3 key with the timestamp
-            let relative_path = file_path.strip_prefix(&folder_base).unwrap_or(&file_path);
-            let key = format!(
-                "backup_{}/{}",
-                timestamp,
-                relative_path.to_string_lossy().replace("\\", "/")
-            );
+            let resp = match client.get_object().bucket(&bucket).key(&key).send().await {
+                Ok(resp) => resp,
+                Err(e) if e.as_service_error().map(|e| e.is_invalid_object_state()).unwrap_or(false) => {
+                    return Ok(Err(key));
+                }
+                Err(e) => return Err(e.into()),
+            };
             
-            // Get file content
-            let body = ByteStream::from_path(&file_path).await?;
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
             
-            // Upload to S3
-            client.put_object()
-                .bucket(&bucket)
-                .key(&key)
-                .body(body)
-                .send()
-                .await?;
+            let metadata = resp.metadata().cloned().unwrap_or_default();
+            let part_path = PathBuf::from(format!("{}.part", target.to_string_lossy()));
+            write_throttled(resp.body, &part_path, &limiter, &counters).await?;
             
-            Ok::<_, anyhow::Error>(())
-        });
-        
-        tasks.push(task);
+            if metadata.get(META_COMPRESSION).map(String::as_str) == Some(COMPRESSION_GZIP) {
+                // Unpack next to the target and check it against the original's hash
+                let unpacked_path = PathBuf::from(format!("{}.unpack", target.to_string_lossy()));
+                let checksum = metadata.get(META_ORIGINAL_SHA256).cloned();
+                let (source, unpacked) = (part_path.clone(), unpacked_path.clone());
+                let result = tokio::task::spawn_blocking(move || {
+                    utils::gunzip_file(&source, &unpacked, checksum.as_deref())
+                }).await?;
+                let _ = fs::remove_file(&part_path);
+                result?;
+                fs::rename(&unpacked_path, &target)?;
+            } else {
+                fs::rename(&part_path, &target)?;
+            }
+            counters.files.fetch_add(1, Ordering::Relaxed);
+            
+            Ok::<_, anyhow::Error>(Ok(target.to_string_lossy().to_string()))
+        }));
     }
     
-    // Wait for all uploads to complete
+    // Wait for every download, even after one fails, so none is still
+    // writing once this returns
+    let mut restored = vec![];
+    let mut archived = vec![];
+    let mut first_error = None;
     for task in tasks {
-        task.await??;
+        match task.await.map_err(anyhow::Error::from).and_then(|result| result) {
+            Ok(Ok(path)) => restored.push(path),
+            Ok(Err(key)) => archived.push(key),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    reporter.abort();
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok((restored, archived)),
+    }
+}
+
+// Tiers for restoring archived objects, fastest and most expensive first
+const ARCHIVE_RESTORE_TIERS: [&str; 3] = ["Expedited", "Standard", "Bulk"];
+
+// Ask S3 to make archived objects downloadable again for `days` days
+pub async fn request_archive_restore(
+    bucket: &str,
+    keys: &[String],
+    days: i32,
+    tier: &str,
+) -> Result<()> {
+    if !ARCHIVE_RESTORE_TIERS.contains(&tier) {
+        return Err(anyhow::anyhow!("Unknown restore tier: {}", tier));
+    }
+    if days < 1 {
+        return Err(anyhow::anyhow!("Restore days must be at least 1"));
+    }
+    
+    let client = get_s3_client().await?;
+    let request = RestoreRequest::builder()
+        .days(days)
+        .glacier_job_parameters(GlacierJobParameters::builder().tier(Tier::from(tier)).build()?)
+        .build();
+    
+    for key in keys {
+        let result = client.restore_object()
+            .bucket(bucket)
+            .key(key)
+            .restore_request(request.clone())
+            .send()
+            .await;
+        
+        // A restore that is already running is not an error
+        if let Err(e) = result {
+            let already_running = e.raw_response().map(|r| r.status().as_u16() == 409).unwrap_or(false);
+            if !already_running {
+                return Err(e.into());
+            }
+        }
     }
     
     Ok(())
 }
 
-// Helper function to recursively collect all files in a directory
-fn collect_files(dir: &Path) -> Result<Vec<PathBuf>> {
+// Page of objects under a backup; size is None for folders
+#[derive(Debug, Serialize)]
+pub struct BackupEntry {
+    pub name: String,
+    pub key: String,
+    pub size: Option<i64>,
+    pub is_folder: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupListing {
+    pub entries: Vec<BackupEntry>,
+    // Pass back to list_backup_contents to get the next page
+    pub continuation: Option<String>,
+}
+
+// Append a trailing slash so "backup_1" can't match "backup_10/..."
+fn backup_prefix_dir(backup_prefix: &str) -> String {
+    if backup_prefix.ends_with('/') {
+        backup_prefix.to_string()
+    } else {
+        format!("{}/", backup_prefix)
+    }
+}
+
+// Read a backup's content manifest, or None for backups that store each file
+// under its own key
+async fn read_content_manifest(client: &Client, bucket: &str, prefix: &str) -> Result<Option<ContentManifest>> {
+    read_json_object(client, bucket, &format!("{}{}", prefix, CONTENT_MANIFEST)).await
+}
+
+// Key standing for a file of a content-addressed backup. Objects are shared by
+// every file with the same content, so files are named by their path instead.
+fn manifest_file_key(prefix: &str, path: &str) -> String {
+    format!("{}{}", prefix, path)
+}
+
+// One level of a content manifest below `dir` ("" or ending in "/"), folders first
+fn manifest_level(manifest: &ContentManifest, prefix: &str, dir: &str) -> Vec<BackupEntry> {
+    let mut folders = std::collections::BTreeSet::new();
     let mut files = vec![];
     
-    if dir.is_dir() {
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            
-            if path.is_dir() {
-                files.extend(collect_files(&path)?);
+    for entry in &manifest.files {
+        let Some(rest) = entry.path.strip_prefix(dir) else { continue };
+        match rest.split_once('/') {
+            Some((folder, _)) => {
+                folders.insert(folder);
+            }
+            None => files.push(BackupEntry {
+                name: rest.to_string(),
+                key: manifest_file_key(prefix, &entry.path),
+                size: Some(entry.size as i64),
+                is_folder: false,
+            }),
+        }
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    
+    folders
+        .into_iter()
+        .map(|folder| BackupEntry {
+            name: folder.to_string(),
+            key: format!("{}{}{}/", prefix, dir, folder),
+            size: None,
+            is_folder: true,
+        })
+        .chain(files)
+        .collect()
+}
+
+// List one level of a backup, folders first, for browsing it as a tree.
+// Content-addressed backups are read from their manifest in a single page
+pub async fn list_backup_contents(
+    bucket: &str,
+    backup_prefix: &str,
+    subpath: Option<&str>,
+    continuation: Option<String>,
+) -> Result<BackupListing> {
+    let client = get_s3_client().await?;
+    
+    let backup_dir = backup_prefix_dir(backup_prefix);
+    let dir = subpath
+        .map(|p| p.trim_matches('/'))
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("{}/", p))
+        .unwrap_or_default();
+    
+    if let Some(manifest) = read_content_manifest(&client, bucket, &backup_dir).await? {
+        return Ok(BackupListing {
+            entries: manifest_level(&manifest, &backup_dir, &dir),
+            continuation: None,
+        });
+    }
+    
+    let prefix = format!("{}{}", backup_dir, dir);
+    
+    let resp = client.list_objects_v2()
+        .bucket(bucket)
+        .prefix(&prefix)
+        .delimiter("/")
+        .set_continuation_token(continuation)
+        .send()
+        .await?;
+    
+    let mut entries = vec![];
+    
+    for folder in resp.common_prefixes.unwrap_or_default() {
+        if let Some(key) = folder.prefix {
+            entries.push(BackupEntry {
+                name: key[prefix.len()..].trim_end_matches('/').to_string(),
+                key,
+                size: None,
+                is_folder: true,
+            });
+        }
+    }
+    
+    for object in resp.contents.unwrap_or_default() {
+        if let Some(key) = object.key {
+            if key.ends_with(SELECTION_MANIFEST) {
+                continue;
+            }
+            entries.push(BackupEntry {
+                name: restored_name(&key[prefix.len()..]).to_string(),
+                key,
+                size: object.size,
+                is_folder: false,
+            });
+        }
+    }
+    
+    Ok(BackupListing {
+        entries,
+        continuation: resp.next_continuation_token,
+    })
+}
+
+// What to do when a selected file already exists locally
+pub const CONFLICT_SKIP: &str = "skip";
+pub const CONFLICT_OVERWRITE: &str = "overwrite";
+pub const CONFLICT_RENAME: &str = "rename";
+
+// Result of a restore
+#[derive(Debug, Default, Serialize)]
+pub struct RestoreFilesResult {
+    pub restored: Vec<String>,
+    pub skipped: Vec<String>,
+    // Local paths that already exist; nothing is restored until a strategy is chosen
+    pub conflicts: Vec<String>,
+    // Keys in an archive storage class; see request_archive_restore
+    pub archived: Vec<String>,
+}
+
+// Restore selected objects of a backup, either keeping their folder layout
+// below the backup or side by side in the destination
+pub async fn restore_files(
+    app: &AppHandle,
+    bucket: &str,
+    backup_prefix: &str,
+    keys: Vec<String>,
+    destination: &Path,
+    preserve_structure: bool,
+    on_conflict: Option<&str>,
+) -> Result<RestoreFilesResult> {
+    let prefix = backup_prefix_dir(backup_prefix);
+    let client = get_s3_client().await?;
+    
+    // Files of content-addressed backups are looked up by path in the manifest
+    let manifest_files: Option<HashMap<String, ContentManifestEntry>> = read_content_manifest(&client, bucket, &prefix)
+        .await?
+        .map(|manifest| manifest.files.into_iter().map(|f| (f.path.clone(), f)).collect());
+    
+    // Work out the object and local path of every key, refusing keys outside the backup
+    let mut targets = vec![];
+    let mut sizes = HashMap::new();
+    for key in keys {
+        let relative = key
+            .strip_prefix(&prefix)
+            .filter(|rest| !rest.is_empty() && !rest.ends_with('/'))
+            .ok_or_else(|| anyhow::anyhow!("{} is not part of backup {}", key, backup_prefix))?;
+        let (object_key, relative) = match &manifest_files {
+            Some(files) => {
+                let entry = files.get(relative)
+                    .ok_or_else(|| anyhow::anyhow!("{} is not part of backup {}", key, backup_prefix))?;
+                sizes.insert(entry.key.clone(), entry.size);
+                (entry.key.clone(), Path::new(relative))
+            }
+            None => (key.clone(), Path::new(restored_name(relative))),
+        };
+        if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+            return Err(anyhow::anyhow!("Invalid backup key: {}", key));
+        }
+        
+        let target = if preserve_structure {
+            destination.join(relative)
+        } else {
+            destination.join(relative.file_name().unwrap_or_default())
+        };
+        targets.push((object_key, target));
+    }
+    
+    let mut result = RestoreFilesResult::default();
+    
+    let conflicts: Vec<String> = targets
+        .iter()
+        .filter(|(_, target)| target.exists())
+        .map(|(_, target)| target.to_string_lossy().to_string())
+        .collect();
+    if !conflicts.is_empty() && on_conflict.is_none() {
+        result.conflicts = conflicts;
+        return Ok(result);
+    }
+    
+    // Renamed copies get their name reserved with an empty placeholder,
+    // which the download replaces
+    let mut reserved = vec![];
+    let downloaded = async {
+        let mut downloads = vec![];
+        for (key, target) in targets {
+            let target = if target.exists() {
+                match on_conflict {
+                    Some(CONFLICT_OVERWRITE) => target,
+                    Some(CONFLICT_RENAME) => {
+                        let parent = target.parent().unwrap_or(destination).to_path_buf();
+                        let target = utils::reserve_path(&parent, &target)?;
+                        reserved.push(target.clone());
+                        target
+                    }
+                    Some(CONFLICT_SKIP) => {
+                        result.skipped.push(target.to_string_lossy().to_string());
+                        continue;
+                    }
+                    other => return Err(anyhow::anyhow!("Unknown conflict strategy: {:?}", other)),
+                }
             } else {
-                files.push(path);
+                target
+            };
+            
+            // Two keys flattened onto the same name must not overwrite each other
+            if downloads.iter().any(|(_, t): &(String, PathBuf)| t == &target) {
+                result.skipped.push(target.to_string_lossy().to_string());
+                continue;
             }
+            downloads.push((key, target));
+        }
+        
+        // Sizes come from the manifest or the objects themselves, for progress reporting
+        let mut total_bytes = 0;
+        for (key, _) in &downloads {
+            total_bytes += match sizes.get(key) {
+                Some(size) => *size,
+                None => {
+                    let head = client.head_object().bucket(bucket).key(key).send().await?;
+                    head.content_length.unwrap_or(0).max(0) as u64
+                }
+            };
+        }
+        
+        download_files(app, &client, bucket, downloads, total_bytes).await
+    }.await;
+    
+    // Drop the placeholders of copies that weren't restored: archived, or
+    // left behind by a failed restore, when they're still empty
+    for path in &reserved {
+        let restored = match &downloaded {
+            Ok((restored, _)) => restored.contains(&path.to_string_lossy().to_string()),
+            Err(_) => fs::metadata(path).map(|metadata| metadata.len() > 0).unwrap_or(true),
+        };
+        if !restored {
+            let _ = fs::remove_file(path);
         }
     }
     
-    Ok(files)
+    let (restored, archived) = downloaded?;
+    result.restored = restored;
+    result.archived = archived;
+    
+    Ok(result)
+}
+
+// Upload a consistent copy of the app database
+async fn backup_database_snapshot(
+    app: &AppHandle,
+    client: &Client,
+    bucket_name: &str,
+    timestamp: &str,
+    options: &BackupOptions,
+) -> Result<()> {
+    let snapshot = TempFile(std::env::temp_dir().join(format!("smart_file_organizer_{}.db", timestamp)));
+    database::backup_database(app, &snapshot.0)?;
+    
+    // Stored outside the backup_ prefix so folder restores don't pick it up
+    let key = format!("database_backup_{}.db", timestamp);
+    let body = ByteStream::from_path(&snapshot.0).await?;
+    let request = client.put_object()
+        .bucket(bucket_name)
+        .key(&key)
+        .body(body);
+    options.apply(request).send().await?;
+    Ok(())
 }
 
 // Download a file from S3
@@ -150,9 +1601,13 @@ pub async fn download_file(bucket: &str, key: &str, destination: &Path) -> Resul
         fs::create_dir_all(parent)?;
     }
     
-    // Save the file
-    let body = resp.body.collect().await?;
-    fs::write(destination, body.into_bytes())?;
+    // Stream the body to disk instead of holding the whole object in memory
+    let mut body = resp.body;
+    let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(destination).await?);
+    while let Some(chunk) = body.try_next().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
     
     Ok(())
 }
@@ -160,85 +1615,395 @@ pub async fn download_file(bucket: &str, key: &str, destination: &Path) -> Resul
 // List all backups for a bucket
 pub async fn list_backups(bucket: &str) -> Result<Vec<String>> {
     let client = get_s3_client().await?;
+    list_backup_prefixes(&client, bucket).await
+}
+
+// A file stored in a backup
+#[derive(Debug, Serialize)]
+pub struct BackupObject {
+    // Usable with restore_files
+    pub key: String,
+    // Where the file goes relative to the restore folder
+    pub path: String,
+    pub size: i64,
+    // RFC 3339; when the backup was taken for content-addressed backups
+    pub last_modified: Option<String>,
+}
+
+// List every file in a backup with its size, so it can be reviewed before
+// restoring. Content-addressed backups are read from their manifest
+pub async fn get_backup_contents(bucket: &str, backup_prefix: &str) -> Result<Vec<BackupObject>> {
+    let client = get_s3_client().await?;
+    let prefix = backup_prefix_dir(backup_prefix);
     
-    let resp = client.list_objects_v2()
-        .bucket(bucket)
-        .delimiter("/")
-        .send()
-        .await?;
+    if let Some(manifest) = read_content_manifest(&client, bucket, &prefix).await? {
+        return Ok(manifest
+            .files
+            .into_iter()
+            .map(|entry| BackupObject {
+                key: manifest_file_key(&prefix, &entry.path),
+                path: entry.path,
+                size: entry.size as i64,
+                last_modified: Some(manifest.created_at.clone()),
+            })
+            .collect());
+    }
+    
+    let objects = list_all_objects(&client, bucket, &prefix)
+        .await?
+        .into_iter()
+        .filter_map(|object| {
+            let key = object.key?;
+            if key.ends_with(SELECTION_MANIFEST) {
+                return None;
+            }
+            let last_modified = object.last_modified
+                .and_then(|t| std::time::SystemTime::try_from(t).ok())
+                .map(|t| chrono::DateTime::<Utc>::from(t).to_rfc3339());
+            Some(BackupObject {
+                path: restored_name(&key[prefix.len()..]).to_string(),
+                key,
+                size: object.size.unwrap_or(0),
+                last_modified,
+            })
+        })
+        .collect();
+    Ok(objects)
+}
+
+// How many local files to compare between compare_progress events
+const COMPARE_PROGRESS_INTERVAL: usize = 500;
+
+// A file in a local folder, its backup, or both
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncEntry {
+    pub relative_path: String,
+    // Set for files that exist locally; usable with the backup commands
+    pub local_path: Option<String>,
+    // Set for files in the backup; usable with restore_files
+    pub key: Option<String>,
+    pub local_size: Option<u64>,
+    pub remote_size: Option<i64>,
+}
+
+// Local folder compared with a backup
+#[derive(Debug, Default, Serialize)]
+pub struct SyncComparison {
+    pub local_only: Vec<SyncEntry>,
+    pub remote_only: Vec<SyncEntry>,
+    pub modified: Vec<SyncEntry>,
+    pub identical: Vec<SyncEntry>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct CompareProgress {
+    pub files_compared: usize,
+    pub remote_objects: usize,
+}
+
+struct RemoteObject {
+    // As restore_files takes it
+    key: String,
+    object_key: String,
+    // Of the original file for content-addressed backups
+    size: Option<i64>,
+    last_modified: Option<std::time::SystemTime>,
+    // SHA-256 of the original file, from a content manifest
+    sha256: Option<String>,
+}
+
+// Files of a content-addressed backup by relative path
+fn manifest_remote_objects(manifest: ContentManifest, prefix: &str) -> HashMap<String, RemoteObject> {
+    let taken_at = chrono::DateTime::parse_from_rfc3339(&manifest.created_at)
+        .ok()
+        .map(std::time::SystemTime::from);
+    manifest
+        .files
+        .into_iter()
+        .map(|entry| {
+            let object = RemoteObject {
+                key: manifest_file_key(prefix, &entry.path),
+                object_key: entry.key,
+                size: Some(entry.size as i64),
+                last_modified: taken_at,
+                sha256: Some(entry.sha256),
+            };
+            (entry.path, object)
+        })
+        .collect()
+}
+
+// Compare a local folder with a folder backup without transferring anything.
+// The backup listing is kept in memory while the local tree is walked file by
+// file, each match taken out of it; whatever is left only exists remotely.
+pub async fn compare_with_backup(
+    app: &AppHandle,
+    folder_path: &str,
+    bucket: &str,
+    backup_prefix: &str,
+) -> Result<SyncComparison> {
+    let folder = PathBuf::from(folder_path);
+    if !folder.is_dir() {
+        return Err(anyhow::anyhow!("Invalid folder path"));
+    }
     
-    let mut backups = vec![];
+    let client = get_s3_client().await?;
+    let prefix = backup_prefix_dir(backup_prefix);
     
-    if let Some(prefixes) = resp.common_prefixes {
-        for prefix in prefixes {
-            if let Some(prefix_str) = prefix.prefix {
-                backups.push(prefix_str);
+    // Read the manifest, or list the whole backup
+    let remote = match read_content_manifest(&client, bucket, &prefix).await? {
+        Some(manifest) => manifest_remote_objects(manifest, &prefix),
+        None => list_all_objects(&client, bucket, &prefix)
+            .await?
+            .into_iter()
+            .filter_map(|object| {
+                let key = object.key?;
+                if key.ends_with(SELECTION_MANIFEST) {
+                    return None;
+                }
+                let relative = restored_name(&key[prefix.len()..]).to_string();
+                let last_modified = object.last_modified
+                    .and_then(|t| std::time::SystemTime::try_from(t).ok());
+                Some((relative, RemoteObject {
+                    key: key.clone(),
+                    object_key: key,
+                    size: object.size,
+                    last_modified,
+                    sha256: None,
+                }))
+            })
+            .collect(),
+    };
+    let remote_objects = remote.len();
+    
+    // Walk the local tree on a blocking thread
+    let app_handle = app.clone();
+    let (mut comparison, remote, needs_hash) = tokio::task::spawn_blocking(move || {
+        let mut remote = remote;
+        let mut comparison = SyncComparison::default();
+        let mut needs_hash = vec![];
+        let mut compared = 0;
+        
+        utils::visit_files(&folder, &mut |path| {
+            // Files can vanish mid-walk
+            let Ok(metadata) = fs::metadata(path) else { return Ok(()) };
+            
+            let relative = path.strip_prefix(&folder).unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let mut entry = SyncEntry {
+                relative_path: relative.clone(),
+                local_path: Some(path.to_string_lossy().to_string()),
+                key: None,
+                local_size: Some(metadata.len()),
+                remote_size: None,
+            };
+            
+            match remote.remove(&relative) {
+                None => comparison.local_only.push(entry),
+                Some(object) => {
+                    // Manifest sizes are the original's
+                    let compressed = object.sha256.is_none() && object.object_key.ends_with(COMPRESSED_SUFFIX);
+                    let same_size = object.size == Some(metadata.len() as i64);
+                    let changed_since_backup = metadata.modified().ok()
+                        .zip(object.last_modified)
+                        .map(|(local, remote)| local > remote)
+                        .unwrap_or(true);
+                    entry.key = Some(object.key);
+                    entry.remote_size = object.size;
+                    
+                    // Compressed objects don't match the local size but carry the
+                    // original's hash, and manifests record it, so those are
+                    // settled by hashing below
+                    if changed_since_backup && (compressed || (same_size && object.sha256.is_some())) {
+                        needs_hash.push((entry, object.object_key, object.sha256));
+                    } else if !changed_since_backup && (compressed || same_size) {
+                        comparison.identical.push(entry);
+                    } else {
+                        comparison.modified.push(entry);
+                    }
+                }
             }
+            
+            compared += 1;
+            if compared % COMPARE_PROGRESS_INTERVAL == 0 {
+                let _ = app_handle.emit("compare_progress", CompareProgress {
+                    files_compared: compared,
+                    remote_objects,
+                });
+            }
+            Ok(())
+        })?;
+        
+        Ok::<_, anyhow::Error>((comparison, remote, needs_hash))
+    }).await??;
+    
+    for (entry, object_key, sha256) in needs_hash {
+        let remote_hash = match sha256 {
+            Some(sha256) => Some(sha256),
+            None => {
+                let head = client.head_object().bucket(bucket).key(&object_key).send().await?;
+                head.metadata().and_then(|m| m.get(META_ORIGINAL_SHA256)).cloned()
+            }
+        };
+        
+        let local_path = PathBuf::from(entry.local_path.clone().unwrap_or_default());
+        let local_hash = tokio::task::spawn_blocking(move || utils::hash_file(&local_path)).await??;
+        
+        if remote_hash.as_deref() == Some(local_hash.as_str()) {
+            comparison.identical.push(entry);
+        } else {
+            comparison.modified.push(entry);
         }
     }
     
-    Ok(backups)
+    for (relative_path, object) in remote {
+        comparison.remote_only.push(SyncEntry {
+            relative_path,
+            local_path: None,
+            key: Some(object.key),
+            local_size: None,
+            remote_size: object.size,
+        });
+    }
+    
+    Ok(comparison)
+}
+
+// Check if a previous restore already wrote this object completely
+fn is_already_restored(dest_path: &Path, expected_size: Option<i64>) -> bool {
+    match (fs::metadata(dest_path), expected_size) {
+        (Ok(metadata), Some(size)) => metadata.is_file() && metadata.len() as i64 == size,
+        _ => false,
+    }
 }
 
-// Restore a backup to local folder
-pub async fn restore_backup(bucket: &str, backup_prefix: &str, destination: &Path) -> Result<()> {
+// Restore a backup to local folder; with `resume`, files already restored are skipped
+#[tracing::instrument(skip(app))]
+pub async fn restore_backup(
+    app: &AppHandle,
+    bucket: &str,
+    backup_prefix: &str,
+    destination: &Path,
+    resume: bool,
+) -> Result<RestoreFilesResult> {
     let client = get_s3_client().await?;
     
-    // List all objects in the backup
-    let resp = client.list_objects_v2()
-        .bucket(bucket)
-        .prefix(backup_prefix)
-        .send()
-        .await?;
-    
     // Create destination directory if it doesn't exist
     fs::create_dir_all(destination)?;
     
-    let mut tasks = vec![];
+    let mut result = RestoreFilesResult::default();
+    let mut downloads = vec![];
+    let mut total_bytes = 0;
     
-    if let Some(objects) = resp.contents {
-        for obj in objects {
-            if let Some(key) = obj.key {
-                let client = client.clone();
-                let bucket = bucket.to_string();
-                let key_str = key;
-                let dest_path = destination.join(
-                    key_str.strip_prefix(backup_prefix).unwrap_or(&key_str)
-                );
-                
-                let task = tokio::spawn(async move {
-                    // Create a local semaphore inside the task
-                    let semaphore = Semaphore::new(1);
-                    let _permit = semaphore.acquire().await?;
-                    
-                    // Download the file
-                    let resp = client.get_object()
-                        .bucket(&bucket)
-                        .key(&key_str)
-                        .send()
-                        .await?;
-                    
-                    // Create parent directories if needed
-                    if let Some(parent) = dest_path.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-                    
-                    // Save the file
-                    let body = resp.body.collect().await?;
-                    fs::write(&dest_path, body.into_bytes())?;
-                    
-                    Ok::<_, anyhow::Error>(())
-                });
-                
-                tasks.push(task);
+    // Content-addressed backups are restored from their manifest
+    if let Some(manifest) = read_content_manifest(&client, bucket, &backup_prefix_dir(backup_prefix)).await? {
+        for entry in manifest.files {
+            let relative = Path::new(&entry.path);
+            if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+                return Err(anyhow::anyhow!("Invalid path in backup manifest: {}", entry.path));
+            }
+            
+            let dest_path = destination.join(relative);
+            if resume && is_already_restored(&dest_path, Some(entry.size as i64)) {
+                result.skipped.push(dest_path.to_string_lossy().to_string());
+                continue;
             }
+            
+            total_bytes += entry.size;
+            downloads.push((entry.key, dest_path));
         }
+        
+        let (restored, archived) = download_files(app, &client, bucket, downloads, total_bytes).await?;
+        result.restored = restored;
+        result.archived = archived;
+        return Ok(result);
     }
     
-    // Wait for all downloads to complete
-    for task in tasks {
-        task.await??;
+    // List every object in the backup; a single request stops at 1000
+    let prefix = backup_prefix_dir(backup_prefix);
+    for obj in list_all_objects(&client, bucket, &prefix).await? {
+        if let Some(key) = obj.key {
+            if key.ends_with(SELECTION_MANIFEST) {
+                continue;
+            }
+            
+            let dest_path = destination.join(
+                restored_name(key.strip_prefix(&prefix).unwrap_or(&key))
+            );
+            
+            // Unpacked files don't match the compressed object's size, but like
+            // every restored file they only appear once complete
+            let restored = if key.ends_with(COMPRESSED_SUFFIX) {
+                dest_path.is_file()
+            } else {
+                is_already_restored(&dest_path, obj.size)
+            };
+            if resume && restored {
+                result.skipped.push(dest_path.to_string_lossy().to_string());
+                continue;
+            }
+            
+            total_bytes += obj.size.unwrap_or(0).max(0) as u64;
+            downloads.push((key, dest_path));
+        }
     }
     
-    Ok(())
-} 
\ No newline at end of file
+    let (restored, archived) = download_files(app, &client, bucket, downloads, total_bytes).await?;
+    result.restored = restored;
+    result.archived = archived;
+    
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(paths: &[&str]) -> ContentManifest {
+        ContentManifest {
+            created_at: "2024-01-02T03:04:05+00:00".to_string(),
+            folder_path: "/home/user/Documents".to_string(),
+            options: BackupOptions::default(),
+            files: paths
+                .iter()
+                .map(|path| ContentManifestEntry {
+                    path: path.to_string(),
+                    sha256: "ab".repeat(32),
+                    key: format!("{}{}", OBJECTS_PREFIX, "ab".repeat(32)),
+                    size: 3,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn manifest_backups_are_browsed_by_path() {
+        let manifest = manifest(&["b.txt", "a.txt", "photos/2024/x.jpg", "photos/y.jpg", "docs/z.pdf"]);
+        
+        let top = manifest_level(&manifest, "backup_1/", "");
+        let names: Vec<_> = top.iter().map(|e| (e.name.as_str(), e.is_folder)).collect();
+        assert_eq!(names, [("docs", true), ("photos", true), ("a.txt", false), ("b.txt", false)]);
+        assert_eq!(top[1].key, "backup_1/photos/");
+        // Files with the same content still get keys of their own
+        assert_eq!(top[2].key, "backup_1/a.txt");
+        assert_eq!(top[3].key, "backup_1/b.txt");
+        
+        let photos = manifest_level(&manifest, "backup_1/", "photos/");
+        let names: Vec<_> = photos.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["2024", "y.jpg"]);
+        assert_eq!(photos[1].key, "backup_1/photos/y.jpg");
+    }
+
+    #[test]
+    fn manifest_backups_are_compared_by_path() {
+        let remote = manifest_remote_objects(manifest(&["a.txt", "photos/y.jpg"]), "backup_1/");
+        
+        let object = &remote["photos/y.jpg"];
+        assert_eq!(object.key, "backup_1/photos/y.jpg");
+        assert!(object.object_key.starts_with(OBJECTS_PREFIX));
+        assert_eq!(object.size, Some(3));
+        assert!(object.sha256.is_some());
+        assert!(object.last_modified.is_some());
+    }
+}
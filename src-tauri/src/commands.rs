@@ -1,7 +1,19 @@
+use crate::cleanup;
 use crate::database;
+use crate::external_rules;
 use crate::file_ops;
+use crate::health;
+use crate::hooks;
+use crate::ignore_list;
+use crate::logging;
+use crate::notifications;
+use crate::rule_config;
+use crate::schedule;
 use crate::cloud_sync;
+use crate::utils;
+use crate::webhook;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use tauri_plugin_dialog::DialogExt;
 use tokio::sync::oneshot;
@@ -37,7 +49,19 @@ pub async fn start_watching_folder(
     app: tauri::AppHandle,
     path: String,
 ) -> Result<(), String> {
-    file_ops::start_watching(&app, path)
+    file_ops::watch_folder_with_rules(&app, path, None)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Watch a folder applying only the given rules to it, instead of every rule
+#[tauri::command]
+pub async fn watch_folder_with_rules(
+    app: tauri::AppHandle,
+    path: String,
+    rule_ids: Vec<i64>,
+) -> Result<(), String> {
+    file_ops::watch_folder_with_rules(&app, path, Some(&rule_ids))
         .await
         .map_err(|e| e.to_string())
 }
@@ -49,19 +73,164 @@ pub async fn stop_watching_folder(app: tauri::AppHandle) -> Result<(), String> {
         .map_err(|e| e.to_string())
 }
 
+// Stop watching one folder or file, leaving the others watched
+#[tauri::command]
+pub fn stop_watching_path(app: tauri::AppHandle, path: String) -> Result<(), String> {
+    file_ops::stop_watching_path(&app, &path)
+        .map_err(|e| e.to_string())
+}
+
+// Pause all watchers, for duration_minutes or until resume_watching
+#[tauri::command]
+pub fn pause_watching(app: tauri::AppHandle, duration_minutes: Option<u64>) -> Result<(), String> {
+    let duration = duration_minutes.map(|minutes| std::time::Duration::from_secs(minutes * 60));
+    file_ops::pause_watching(&app, duration)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn resume_watching(app: tauri::AppHandle) -> Result<(), String> {
+    file_ops::resume_watching(&app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_watched_folders(app: tauri::AppHandle) -> Vec<file_ops::WatchedFolder> {
+    file_ops::get_watched_folders(&app)
+}
+
 #[tauri::command]
 pub async fn organize_file(
     app: tauri::AppHandle, 
     file_path: String,
     destination_folder: Option<String>,
-) -> Result<(), String> {
-    file_ops::organize_file(&app, PathBuf::from(file_path), destination_folder)
+    tag_ids: Option<Vec<i64>>,
+) -> Result<file_ops::OrganizeOutcome, String> {
+    file_ops::organize_file(&app, PathBuf::from(file_path), destination_folder, tag_ids.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Rename an indexed file in place; returns the new path
+#[tauri::command]
+pub fn rename_file(app: tauri::AppHandle, file_id: i64, new_name: String) -> Result<String, String> {
+    file_ops::rename_file(&app, file_id, &new_name)
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+// Show the first part of a text file; fails with kind "binary" for binary files
+#[tauri::command]
+pub fn preview_text_file(path: String, max_bytes: usize) -> Result<String, file_ops::PreviewError> {
+    file_ops::preview_text_file(&PathBuf::from(path), max_bytes)
+}
+
+// Apply the rules to the files already in a folder, optionally only down to
+// max_depth levels of subfolders
+#[tauri::command]
+pub async fn scan_folder(
+    app: tauri::AppHandle,
+    path: String,
+    max_depth: Option<u32>,
+) -> Result<file_ops::ScanSummary, String> {
+    file_ops::scan_folder(&app, path, max_depth)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Apply the rules to a folder one time without watching it; with dry_run,
+// only report what would be moved where
+#[tauri::command]
+pub async fn organize_folder_once(
+    app: tauri::AppHandle,
+    path: String,
+    recursive: bool,
+    dry_run: Option<bool>,
+) -> Result<file_ops::OrganizePlan, String> {
+    file_ops::organize_folder_once(&app, path, recursive, dry_run.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Never organize a file, a folder or files matching a pattern: a full path,
+// a name glob such as "*.part", or a path glob such as "*/incomplete/*"
+#[tauri::command]
+pub fn add_ignore(app: tauri::AppHandle, pattern: String) -> Result<i64, String> {
+    ignore_list::validate_pattern(&pattern).map_err(|e| e.to_string())?;
+    database::add_ignore(&app, &pattern)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn remove_ignore(app: tauri::AppHandle, ignore_id: i64) -> Result<(), String> {
+    database::remove_ignore(&app, ignore_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_ignores(app: tauri::AppHandle) -> Result<Vec<database::IgnoreEntry>, String> {
+    database::get_ignores(&app)
+        .map_err(|e| e.to_string())
+}
+
+// Organize the files queued outside the active hours now, without waiting for
+// the hours to start
+#[tauri::command]
+pub async fn flush_pending_now(app: tauri::AppHandle) -> Result<file_ops::PendingFlush, String> {
+    file_ops::flush_pending(&app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// How many files and bytes of each category a folder holds, without moving anything
+#[tauri::command]
+pub async fn analyze_folder(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<HashMap<String, file_ops::CategoryBreakdown>, String> {
+    file_ops::analyze_folder(&app, path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Sort a messy folder into a category tree in one go
+#[tauri::command]
+pub async fn organize_everything(
+    app: tauri::AppHandle,
+    source_folder: String,
+    target_root: String,
+) -> Result<file_ops::OrganizeSummary, String> {
+    file_ops::organize_everything(&app, source_folder, target_root)
         .await
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_folder_size(app: tauri::AppHandle, path: String) -> Result<u64, String> {
+    file_ops::get_folder_size(&app, path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn cancel_folder_size(app: tauri::AppHandle, path: String) {
+    file_ops::cancel_folder_size(&app, &path)
+}
+
+#[tauri::command]
+pub async fn hash_files(app: tauri::AppHandle, paths: Vec<String>) -> file_ops::HashBatchResult {
+    let concurrency = file_ops::hash_concurrency(&app);
+    file_ops::hash_files(paths.into_iter().map(PathBuf::from).collect(), concurrency).await
+}
+
+#[tauri::command]
+pub fn format_file_size(size: u64) -> String {
+    utils::format_file_size(size)
+}
+
 // Tag operations
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tag {
     pub id: i64,
     pub name: String,
@@ -80,33 +249,489 @@ pub fn add_tag(app: tauri::AppHandle, name: String, color: String) -> Result<i64
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub fn rename_tag(app: tauri::AppHandle, tag_id: i64, name: String, color: Option<String>) -> Result<(), String> {
+    database::rename_tag(&app, tag_id, name, color)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn remove_tag(app: tauri::AppHandle, tag_id: i64) -> Result<(), String> {
     database::remove_tag(&app, tag_id)
         .map_err(|e| e.to_string())
 }
 
+// Move every file from one tag to another and delete the first tag,
+// returning how many files were reassigned
+#[tauri::command]
+pub fn merge_tags(app: tauri::AppHandle, source_tag_id: i64, target_tag_id: i64) -> Result<usize, String> {
+    database::merge_tags(&app, source_tag_id, target_tag_id)
+        .map_err(|e| e.to_string())
+}
+
+// Tags likely to fit a file, best match first
+#[tauri::command]
+pub fn suggest_tags(app: tauri::AppHandle, file_id: i64) -> Result<Vec<Tag>, String> {
+    database::suggest_tags(&app, file_id)
+        .map_err(|e| e.to_string())
+}
+
+// Try an unsaved rule on sample files, given as paths or as a folder to
+// sample from; is_regex reads a file name pattern as a regex rather than a
+// glob. An invalid pattern comes back as an error message
+#[tauri::command]
+pub fn test_rule(
+    app: tauri::AppHandle,
+    rule_draft: Rule,
+    is_regex: Option<bool>,
+    sample_paths: Option<Vec<String>>,
+    sample_folder: Option<String>,
+) -> Result<Vec<file_ops::RuleTestResult>, String> {
+    file_ops::test_rule(&app, rule_draft, is_regex.unwrap_or(false), sample_paths, sample_folder)
+        .map_err(|e| e.to_string())
+}
+
+// Which rule would organize a file and why, without moving it
+#[tauri::command]
+pub fn explain_file(app: tauri::AppHandle, path: String) -> Result<Option<file_ops::RuleMatch>, String> {
+    file_ops::explain_file(&app, &PathBuf::from(path))
+        .map_err(|e| e.to_string())
+}
+
+// Whether a rule pattern matches a sample file name, for live feedback while
+// a pattern is typed. An invalid regex comes back as its error message
+#[tauri::command]
+pub fn test_rule_pattern(
+    pattern: String,
+    is_extension: bool,
+    is_regex: bool,
+    sample_filename: String,
+) -> Result<bool, String> {
+    utils::rule_matches(pattern.trim(), is_extension, is_regex, &sample_filename)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn test_rule_against_index(
+    app: tauri::AppHandle,
+    pattern: String,
+    is_extension: bool,
+    is_regex: bool,
+) -> Result<Vec<i64>, String> {
+    file_ops::find_files_matching_rule(&app, &pattern, is_extension, is_regex)
+        .map_err(|e| e.to_string())
+}
+
+// Category operations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Category {
+    pub id: i64,
+    pub name: String,
+    pub extensions: Vec<String>,
+    pub tag_id: Option<i64>,
+    pub color: String,
+}
+
+#[tauri::command]
+pub fn get_categories(app: tauri::AppHandle) -> Result<Vec<Category>, String> {
+    database::get_categories(&app)
+        .map_err(|e| e.to_string())
+}
+
+// Swap a file's category tag for another category's, in one step
+#[tauri::command]
+pub fn recategorize_file(app: tauri::AppHandle, file_id: i64, new_category: String) -> Result<(), String> {
+    database::recategorize_file(&app, file_id, &new_category)
+        .map_err(|e| e.to_string())
+}
+
+// Stop or resume auto-tagging a file. Stopping removes its category tags and
+// keeps them off when it's moved or indexed again; returns how many were removed
+#[tauri::command]
+pub fn set_auto_tag_override(app: tauri::AppHandle, file_id: i64, suppressed: bool) -> Result<usize, String> {
+    database::set_auto_tag_override(&app, file_id, suppressed)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn update_category(
+    app: tauri::AppHandle,
+    category_id: i64,
+    name: String,
+    extensions: Vec<String>,
+    color: String,
+    tag_id: Option<i64>,
+) -> Result<(), String> {
+    database::update_category(&app, category_id, name, extensions, color, tag_id)
+        .map_err(|e| e.to_string())
+}
+
+// Rule operations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: i64,
+    pub name: String,
+    pub pattern: String,
+    pub destination_folder: String,
+    pub is_extension: bool,
+    pub is_active: bool,
+    #[serde(default)]
+    pub preserve_structure: bool,
+    // Special condition such as "is_screenshot", checked instead of the pattern
+    #[serde(default)]
+    pub condition: Option<String>,
+    // Tag applied to files this rule organizes
+    #[serde(default)]
+    pub tag_id: Option<i64>,
+    // For "older_than" rules, how many days since a file was last modified
+    // before the rule moves it
+    #[serde(default)]
+    pub max_age_days: Option<i64>,
+    // Whether the pattern matches folder names, moving a matching folder
+    // with everything in it rather than single files
+    #[serde(default)]
+    pub applies_to_folders: bool,
+    // Leave out the category tag ("auto-tag") of files this rule moves; the
+    // rule's own tag is still applied
+    #[serde(default)]
+    pub exclude_auto_tags: bool,
+}
+
+#[tauri::command]
+pub fn get_rules(app: tauri::AppHandle) -> Result<Vec<Rule>, String> {
+    database::get_rules(&app)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_rule(app: tauri::AppHandle, rule: Rule) -> Result<i64, String> {
+    database::add_rule(&app, &rule)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn update_rule(app: tauri::AppHandle, rule: Rule) -> Result<(), String> {
+    database::update_rule(&app, &rule)
+        .map_err(|e| e.to_string())
+}
+
+// Add rules from a TOML or JSON file to a profile (the active one unless
+// profile_id is given), replacing its rules with the same name
+#[tauri::command]
+pub fn load_rules_from_file(
+    app: tauri::AppHandle,
+    path: String,
+    profile_id: Option<i64>,
+) -> Result<rule_config::RuleImportSummary, String> {
+    rule_config::load_rules_from_file(&app, &PathBuf::from(path), profile_id)
+        .map_err(|e| e.to_string())
+}
+
+// Import rules converted from another organizer (format "plist") into the
+// active profile. Imported rules start disabled; the report lists what was
+// left out and which were renamed
+#[tauri::command]
+pub fn import_external_rules(
+    app: tauri::AppHandle,
+    path: String,
+    format: String,
+) -> Result<external_rules::ExternalImportReport, external_rules::ExternalRulesError> {
+    external_rules::import_external_rules(&app, &PathBuf::from(path), &format)
+}
+
+// Write a profile's rules (the active one unless profile_id is given) to a
+// TOML or JSON file, returning how many were written
+#[tauri::command]
+pub fn export_rules_to_file(app: tauri::AppHandle, path: String, profile_id: Option<i64>) -> Result<usize, String> {
+    rule_config::export_rules_to_file(&app, &PathBuf::from(path), profile_id)
+        .map_err(|e| e.to_string())
+}
+
+// Write tags, rules and optionally the file index to a JSON bundle, e.g. to
+// move to another machine
+#[tauri::command]
+pub fn export_data(
+    app: tauri::AppHandle,
+    path: String,
+    include_files: Option<bool>,
+) -> Result<rule_config::DataExportSummary, String> {
+    rule_config::export_data(&app, &PathBuf::from(path), include_files.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+// Merge a bundle written by export_data, keeping tags and rules already here
+#[tauri::command]
+pub fn import_data(app: tauri::AppHandle, path: String) -> Result<rule_config::DataImportSummary, String> {
+    rule_config::import_data(&app, &PathBuf::from(path))
+        .map_err(|e| e.to_string())
+}
+
+// Profiles: separate sets of rules and watched folders, one active at a time
+#[tauri::command]
+pub fn create_profile(app: tauri::AppHandle, name: String) -> Result<database::Profile, String> {
+    database::add_profile(&app, &name)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_profiles(app: tauri::AppHandle) -> Result<Vec<database::Profile>, String> {
+    database::get_profiles(&app)
+        .map_err(|e| e.to_string())
+}
+
+// Make a profile active, swapping the rules in use and the folders watched
+#[tauri::command]
+pub async fn switch_profile(app: tauri::AppHandle, profile_id: i64) -> Result<database::Profile, String> {
+    file_ops::switch_profile(&app, profile_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Delete a profile that isn't active, with its rules and watched folders
+#[tauri::command]
+pub fn delete_profile(app: tauri::AppHandle, profile_id: i64) -> Result<(), String> {
+    database::remove_profile(&app, profile_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn remove_rule(app: tauri::AppHandle, rule_id: i64) -> Result<(), String> {
+    database::remove_rule(&app, rule_id)
+        .map_err(|e| e.to_string())
+}
+
 // File search
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileInfo {
     pub id: i64,
     pub path: String,
     pub name: String,
     pub extension: String,
     pub size: i64,
+    // RFC 3339 in UTC, e.g. "2024-03-09T14:05:00Z"
     pub created_at: String,
+    // The file system doesn't record creation times, so created_at is the
+    // earliest other timestamp instead
+    pub created_at_approximate: bool,
     pub modified_at: String,
+    pub notes: Option<String>,
+    pub rating: Option<i64>,
+    pub source_url: Option<String>,
+    pub source_domain: Option<String>,
     pub tags: Vec<Tag>,
+    // Custom key/value metadata; only loaded when asked for
+    pub metadata: Option<BTreeMap<String, String>>,
+}
+
+// File search filter; every field is optional and set fields are combined with AND
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchFilter {
+    pub query: Option<String>,
+    pub tag_ids: Option<Vec<i64>>,
+    pub extensions: Option<Vec<String>>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    // Compared against the creation and modification times: RFC 3339,
+    // "YYYY-MM-DD HH:MM:SS" (UTC), or "YYYY-MM-DD", which covers that whole
+    // UTC day
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub modified_after: Option<String>,
+    pub modified_before: Option<String>,
+    pub min_rating: Option<i64>,
+    pub has_note: Option<bool>,
+    pub source_domain: Option<String>,
+    // Load each result's custom metadata as well
+    pub include_metadata: Option<bool>,
+    // A page of the results; every match is returned when neither is set. The
+    // limit is kept within 1..=database::MAX_PAGE_SIZE and the offset at 0 or more
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[tauri::command]
+pub fn search_files(app: tauri::AppHandle, filter: SearchFilter) -> Result<Vec<FileInfo>, String> {
+    database::search_files(&app, &filter)
+        .map_err(|e| e.to_string())
+}
+
+// File history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileHistoryEvent {
+    pub id: i64,
+    pub file_id: Option<i64>,
+    pub event_type: String,
+    pub from_path: Option<String>,
+    pub to_path: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: String,
 }
 
 #[tauri::command]
-pub fn search_files(
+pub fn get_file_history(app: tauri::AppHandle, file_id: i64) -> Result<Vec<FileHistoryEvent>, String> {
+    database::get_file_history(&app, file_id)
+        .map_err(|e| e.to_string())
+}
+
+// Similar images
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarImage {
+    pub file_id: i64,
+    pub path: String,
+    pub name: String,
+    pub size: i64,
+    pub hash: String,
+}
+
+#[tauri::command]
+pub async fn find_similar_images(
     app: tauri::AppHandle,
-    query: Option<String>,
-    tag_ids: Option<Vec<i64>>,
-    extension: Option<String>,
+    threshold: Option<u32>,
+) -> Result<Vec<Vec<SimilarImage>>, String> {
+    file_ops::find_similar_images(&app, threshold.unwrap_or(file_ops::DEFAULT_SIMILARITY_THRESHOLD))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Files with exactly the same content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateFile {
+    pub file_id: i64,
+    pub path: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    // SHA-256 of the content
+    pub hash: String,
+    pub size: i64,
+    pub files: Vec<DuplicateFile>,
+}
+
+// Find indexed files with identical content. Progress is reported with
+// duplicate_scan_progress and each group with duplicate_group_found as it's
+// confirmed
+#[tauri::command]
+pub async fn find_duplicates(app: tauri::AppHandle) -> Result<file_ops::DuplicateScanResult, String> {
+    file_ops::find_duplicates(&app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Stop a running duplicate scan; it returns the groups found so far
+#[tauri::command]
+pub fn cancel_duplicate_scan() {
+    file_ops::cancel_duplicate_scan()
+}
+
+#[tauri::command]
+pub fn get_untagged_files(
+    app: tauri::AppHandle,
+    limit: Option<i64>,
+    offset: Option<i64>,
 ) -> Result<Vec<FileInfo>, String> {
-    database::search_files(&app, query, tag_ids, extension)
+    database::get_untagged_files(&app, limit, offset)
+        .map_err(|e| e.to_string())
+}
+
+// Untagged files, files no rule covers, files never moved out of a watched
+// folder and ignored files, each paged with limit and offset
+#[tauri::command]
+pub fn get_unorganized_report(
+    app: tauri::AppHandle,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<file_ops::UnorganizedReport, String> {
+    file_ops::get_unorganized_report(&app, limit, offset)
+        .map_err(|e| e.to_string())
+}
+
+// Remove indexed files that were deleted outside the app, returning how many
+// files and tag links were removed
+#[tauri::command]
+pub async fn cleanup_orphans(app: tauri::AppHandle) -> Result<database::OrphanCleanup, String> {
+    file_ops::cleanup_orphans(&app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// A file in the large files report
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LargeFile {
+    #[serde(flatten)]
+    pub file: FileInfo,
+    // Size formatted for display, e.g. "1.5 GB"
+    pub size_display: String,
+}
+
+// The biggest indexed files of at least min_size bytes, largest first
+#[tauri::command]
+pub fn get_large_files(app: tauri::AppHandle, min_size: i64, limit: u32) -> Result<Vec<LargeFile>, String> {
+    database::get_large_files(&app, min_size, limit)
+        .map_err(|e| e.to_string())
+}
+
+// Everything known about one file: tags, metadata, hash, backups and how it
+// was organized
+#[tauri::command]
+pub async fn get_file_details(
+    app: tauri::AppHandle,
+    file_id: i64,
+) -> Result<file_ops::FileDetails, file_ops::FileDetailsError> {
+    file_ops::get_file_details(&app, file_id).await
+}
+
+// Distinct extensions in the index with their file counts
+#[tauri::command]
+pub fn get_extensions(app: tauri::AppHandle) -> Result<Vec<(String, usize)>, String> {
+    database::get_extensions(&app)
+        .map_err(|e| e.to_string())
+}
+
+// Files added and organized per day, week or month, grouped by category,
+// extension or rule, for charts
+#[tauri::command]
+pub fn get_trends(
+    app: tauri::AppHandle,
+    period: String,
+    group_by: String,
+    periods: Option<u32>,
+) -> Result<Vec<file_ops::TrendBucket>, String> {
+    file_ops::get_trends(&app, &period, &group_by, periods)
+        .map_err(|e| e.to_string())
+}
+
+// Notes and ratings
+#[tauri::command]
+pub fn set_file_note(app: tauri::AppHandle, file_id: i64, note: Option<String>) -> Result<(), String> {
+    database::set_file_note(&app, file_id, note)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_file_rating(app: tauri::AppHandle, file_id: i64, rating: Option<i64>) -> Result<(), String> {
+    database::set_file_rating(&app, file_id, rating)
+        .map_err(|e| e.to_string())
+}
+
+// Custom file metadata
+#[tauri::command]
+pub fn set_file_metadata(app: tauri::AppHandle, file_id: i64, key: String, value: String) -> Result<(), String> {
+    database::set_file_metadata(&app, file_id, &key, &value)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_file_metadata(app: tauri::AppHandle, file_id: i64) -> Result<BTreeMap<String, String>, String> {
+    database::get_file_metadata(&app, file_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_file_metadata(app: tauri::AppHandle, file_id: i64, key: String) -> Result<(), String> {
+    database::delete_file_metadata(&app, file_id, &key)
         .map_err(|e| e.to_string())
 }
 
@@ -116,8 +741,406 @@ pub async fn backup_to_cloud(
     app: tauri::AppHandle,
     folder_path: String,
     bucket_name: String,
+    include_database: Option<bool>,
+    options: Option<cloud_sync::BackupOptions>,
+) -> Result<(), String> {
+    let options = cloud_sync::BackupOptions::resolve(&app, options)
+        .map_err(|e| e.to_string())?;
+    let result = cloud_sync::backup_folder(&app, folder_path, bucket_name, include_database.unwrap_or(false), &options).await;
+    notifications::notify_backup_result(&app, result.as_ref().map(|file_count| *file_count));
+    result
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn backup_selection(
+    app: tauri::AppHandle,
+    bucket_name: String,
+    filter: SearchFilter,
+    options: Option<cloud_sync::BackupOptions>,
+) -> Result<cloud_sync::SelectionBackupSummary, String> {
+    let options = cloud_sync::BackupOptions::resolve(&app, options)
+        .map_err(|e| e.to_string())?;
+    let result = cloud_sync::backup_selection(&app, &bucket_name, filter, &options).await;
+    notifications::notify_backup_result(&app, result.as_ref().map(|summary| summary.uploaded));
+    result.map_err(|e| e.to_string())
+}
+
+// Bucket layout: "legacy" or "content_addressed"
+#[tauri::command]
+pub async fn get_bucket_layout(bucket_name: String) -> Result<String, String> {
+    cloud_sync::get_bucket_layout(&bucket_name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_bucket_layout(bucket_name: String, layout: String) -> Result<(), String> {
+    cloud_sync::set_bucket_layout(&bucket_name, &layout)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_backup(bucket_name: String, backup_prefix: String) -> Result<usize, String> {
+    cloud_sync::delete_backup(&bucket_name, &backup_prefix)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_cloud_backups(bucket_name: String) -> Result<Vec<String>, String> {
+    cloud_sync::list_backups(&bucket_name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn restore_from_cloud(
+    app: tauri::AppHandle,
+    bucket_name: String,
+    backup_prefix: String,
+    destination_folder: String,
+    resume: Option<bool>,
+) -> Result<cloud_sync::RestoreFilesResult, String> {
+    cloud_sync::restore_backup(
+        &app,
+        &bucket_name,
+        &backup_prefix,
+        &PathBuf::from(destination_folder),
+        resume.unwrap_or(false),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// Restore a selection backup to where the files came from, or flat into destination_folder
+#[tauri::command]
+pub async fn restore_selection(
+    app: tauri::AppHandle,
+    bucket_name: String,
+    backup_prefix: String,
+    destination_folder: Option<String>,
+) -> Result<cloud_sync::RestoreFilesResult, String> {
+    cloud_sync::restore_selection(
+        &app,
+        &bucket_name,
+        &backup_prefix,
+        destination_folder.map(PathBuf::from).as_deref(),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// Start restoring archived (Glacier) objects so they can be downloaded later
+#[tauri::command]
+pub async fn request_archive_restore(
+    bucket_name: String,
+    keys: Vec<String>,
+    days: i32,
+    tier: Option<String>,
 ) -> Result<(), String> {
-    cloud_sync::backup_folder(&app, folder_path, bucket_name)
+    cloud_sync::request_archive_restore(&bucket_name, &keys, days, tier.as_deref().unwrap_or("Standard"))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Compare a local folder with one of its backups, without transferring anything
+#[tauri::command]
+pub async fn compare_with_backup(
+    app: tauri::AppHandle,
+    folder_path: String,
+    bucket_name: String,
+    backup_prefix: String,
+) -> Result<cloud_sync::SyncComparison, String> {
+    cloud_sync::compare_with_backup(&app, &folder_path, &bucket_name, &backup_prefix)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Browse a backup one folder level at a time
+#[tauri::command]
+pub async fn list_backup_contents(
+    bucket_name: String,
+    backup_prefix: String,
+    subpath: Option<String>,
+    continuation: Option<String>,
+) -> Result<cloud_sync::BackupListing, String> {
+    cloud_sync::list_backup_contents(&bucket_name, &backup_prefix, subpath.as_deref(), continuation)
         .await
         .map_err(|e| e.to_string())
-} 
\ No newline at end of file
+}
+
+// List every file in a backup with its size
+#[tauri::command]
+pub async fn get_backup_contents(
+    bucket_name: String,
+    backup_prefix: String,
+) -> Result<Vec<cloud_sync::BackupObject>, String> {
+    cloud_sync::get_backup_contents(&bucket_name, &backup_prefix)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Restore selected files from a backup; on_conflict is "skip", "overwrite" or "rename"
+#[tauri::command]
+pub async fn restore_files(
+    app: tauri::AppHandle,
+    bucket_name: String,
+    backup_prefix: String,
+    keys: Vec<String>,
+    destination_folder: String,
+    preserve_structure: bool,
+    on_conflict: Option<String>,
+) -> Result<cloud_sync::RestoreFilesResult, String> {
+    cloud_sync::restore_files(
+        &app,
+        &bucket_name,
+        &backup_prefix,
+        keys,
+        &PathBuf::from(destination_folder),
+        preserve_structure,
+        on_conflict.as_deref(),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// Hooks run after files or folders are organized
+#[tauri::command]
+pub fn get_hooks(app: tauri::AppHandle) -> Result<Vec<hooks::Hook>, String> {
+    database::get_hooks(&app)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_hook(app: tauri::AppHandle, hook: hooks::Hook) -> Result<i64, String> {
+    hooks::validate(&hook).map_err(|e| e.to_string())?;
+    database::add_hook(&app, &hook)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn update_hook(app: tauri::AppHandle, hook: hooks::Hook) -> Result<(), String> {
+    hooks::validate(&hook).map_err(|e| e.to_string())?;
+    database::update_hook(&app, &hook)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn remove_hook(app: tauri::AppHandle, hook_id: i64) -> Result<(), String> {
+    database::remove_hook(&app, hook_id)
+        .map_err(|e| e.to_string())
+}
+
+// Fire a hook with a made-up file, returning the error if it fails
+#[tauri::command]
+pub async fn test_hook(app: tauri::AppHandle, hook_id: i64) -> Result<(), String> {
+    hooks::test_hook(&app, hook_id)
+        .await
+        .map_err(|e| format!("{:#}", e))
+}
+
+// Cleanup policies
+#[tauri::command]
+pub fn get_cleanup_policies(app: tauri::AppHandle) -> Result<Vec<cleanup::CleanupPolicy>, String> {
+    database::get_cleanup_policies(&app)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_cleanup_policy(app: tauri::AppHandle, policy: cleanup::CleanupPolicy) -> Result<i64, String> {
+    match policy.action.as_str() {
+        cleanup::ACTION_ARCHIVE | cleanup::ACTION_TRASH | cleanup::ACTION_REPORT => {}
+        other => return Err(format!("Unknown cleanup action: {}", other)),
+    }
+
+    database::set_cleanup_policy(&app, &policy)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn remove_cleanup_policy(app: tauri::AppHandle, policy_id: i64) -> Result<(), String> {
+    database::remove_cleanup_policy(&app, policy_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn run_cleanup_now(
+    app: tauri::AppHandle,
+    path: String,
+    dry_run: Option<bool>,
+) -> Result<cleanup::CleanupSummary, String> {
+    cleanup::run_cleanup_now(&app, path, dry_run.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Remove folders left empty under root; only lists them until confirmed
+#[tauri::command]
+pub async fn cleanup_empty_dirs(
+    app: tauri::AppHandle,
+    root: String,
+    use_trash: Option<bool>,
+    confirmed: Option<bool>,
+) -> Result<cleanup::EmptyDirsSummary, String> {
+    cleanup::cleanup_empty_dirs(&app, root, use_trash.unwrap_or(true), confirmed.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Find files in a folder whose names only differ by the organizer's collision
+// suffix, and whether their contents are identical
+#[tauri::command]
+pub async fn find_collision_duplicates(
+    app: tauri::AppHandle,
+    folder: String,
+) -> Result<Vec<cleanup::CollisionGroup>, String> {
+    cleanup::find_collision_duplicates(&app, folder)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Trash the redundant identical copies found by find_collision_duplicates,
+// keeping one by strategy; families with different contents are left alone
+#[tauri::command]
+pub async fn clean_collision_duplicates(
+    app: tauri::AppHandle,
+    folder: String,
+    strategy: String,
+) -> Result<cleanup::CollisionCleanup, String> {
+    cleanup::clean_collision_duplicates(&app, folder, strategy)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Database status
+#[tauri::command]
+pub fn get_database_status(app: tauri::AppHandle) -> database::DatabaseStatus {
+    database::get_database_status(&app)
+}
+
+// Diagnostics for support requests: database, watchers, backups and disk space
+#[tauri::command]
+pub fn get_health(app: tauri::AppHandle) -> Result<health::Health, String> {
+    health::get_health(&app)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn backup_database(app: tauri::AppHandle, destination_path: String) -> Result<(), String> {
+    database::backup_database(&app, &PathBuf::from(destination_path))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn restore_database(app: tauri::AppHandle, source_path: String) -> Result<(), String> {
+    file_ops::restore_database(&app, &PathBuf::from(source_path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn compact_database(app: tauri::AppHandle) -> Result<database::CompactResult, String> {
+    database::compact_database(&app)
+        .map_err(|e| e.to_string())
+}
+
+// Logs
+#[tauri::command]
+pub fn get_recent_logs(level: Option<String>, limit: Option<usize>) -> Result<Vec<logging::LogEntry>, String> {
+    logging::get_recent_logs(level.as_deref(), limit.unwrap_or(200))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_log_level(app: tauri::AppHandle, level: String) -> Result<(), String> {
+    logging::set_log_level(&app, &level)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn open_log_folder(app: tauri::AppHandle) -> Result<(), String> {
+    let dir = logging::get_log_dir(&app).map_err(|e| e.to_string())?;
+    tauri_plugin_opener::open_path(dir, None::<&str>)
+        .map_err(|e| e.to_string())
+}
+
+// Settings
+#[tauri::command]
+pub fn get_setting(app: tauri::AppHandle, key: String) -> Result<Option<String>, String> {
+    database::get_setting(&app, &key)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_setting(app: tauri::AppHandle, key: String, value: String) -> Result<(), String> {
+    if key == webhook::WEBHOOK_URL_SETTING {
+        webhook::validate_url(&value).map_err(|e| e.to_string())?;
+    }
+    if key == file_ops::MAX_DEPTH_SETTING && !value.trim().is_empty() && value.trim().parse::<u32>().is_err() {
+        return Err(format!("{} must be a whole number of folder levels", key));
+    }
+    if key == file_ops::WATCHER_WORKERS_SETTING
+        && !value.trim().is_empty()
+        && !value.trim().parse::<usize>().is_ok_and(|workers| (1..=file_ops::MAX_WATCHER_WORKERS).contains(&workers))
+    {
+        return Err(format!("{} must be between 1 and {}", key, file_ops::MAX_WATCHER_WORKERS));
+    }
+    if key == database::SYNCHRONOUS_SETTING
+        && !value.trim().is_empty()
+        && !database::SYNCHRONOUS_LEVELS.contains(&value.trim().to_lowercase().as_str())
+    {
+        return Err(format!("{} must be one of {}", key, database::SYNCHRONOUS_LEVELS.join(", ")));
+    }
+    if key == schedule::ACTIVE_HOURS_SETTING && !value.trim().is_empty() {
+        schedule::ActiveHours::parse(&value).map_err(|e| e.to_string())?;
+    }
+    
+    if key == file_ops::BASE_DIRECTORY_SETTING && !value.trim().is_empty() {
+        file_ops::validate_base_directory(&PathBuf::from(value.trim())).map_err(|e| e.to_string())?;
+    }
+    
+    database::set_setting(&app, &key, &value)
+        .map_err(|e| e.to_string())?;
+    
+    // Rate limits apply to transfers already in progress
+    cloud_sync::apply_bandwidth_setting(&app, &key, &value);
+    database::apply_durability_setting(&app, &key)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Folder relative rule destinations are resolved against
+#[tauri::command]
+pub fn get_base_directory(app: tauri::AppHandle) -> Result<String, String> {
+    file_ops::get_base_directory(&app)
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+// Change the folder relative rule destinations are resolved against; an empty
+// path resets it to the home folder
+#[tauri::command]
+pub fn set_base_directory(app: tauri::AppHandle, path: String) -> Result<(), String> {
+    file_ops::set_base_directory(&app, &path)
+        .map_err(|e| e.to_string())
+}
+
+// Platform capabilities
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlatformCapabilities {
+    pub os: String,
+    pub os_tags: bool,
+    pub os_tag_colors: bool,
+}
+
+#[tauri::command]
+pub fn get_platform_capabilities() -> PlatformCapabilities {
+    PlatformCapabilities {
+        os: std::env::consts::OS.to_string(),
+        os_tags: utils::supports_os_tags(),
+        os_tag_colors: utils::supports_os_tag_colors(),
+    }
+}
@@ -1,19 +1,64 @@
 use anyhow::{Context, Result};
-use rusqlite::{Connection, params};
-use std::path::Path;
+use rusqlite::{Connection, DatabaseName, ErrorCode, OpenFlags, OptionalExtension, params};
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Manager};
-use crate::commands::{Tag, FileInfo};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use crate::cleanup::CleanupPolicy;
+use crate::commands::{Category, FileHistoryEvent, Rule, SearchFilter, Tag, FileInfo, LargeFile};
+use crate::hooks::Hook;
+use crate::utils;
 
 // Struct to hold the database connection
 pub struct DatabaseConnection(pub Connection);
 
+// Current schema version, stored in PRAGMA user_version
+pub const SCHEMA_VERSION: i32 = 20;
+
+// Built-in categories: name, extensions, color, and the destination of its
+// rule; categories without one get neither a tag nor a rule. Destinations
+// starting with $ are the platform's folders (see utils::resolve_known_folder)
+const DEFAULT_CATEGORIES: [(&str, &str, &str, Option<&str>); 6] = [
+    ("Documents", "pdf,doc,docx,txt,rtf,odt,xls,xlsx,ppt,pptx", "#4287f5", Some("$DOCUMENTS")),
+    ("Images", "jpg,jpeg,png,gif,bmp,webp,svg", "#42f54e", Some("$PICTURES")),
+    ("Videos", "mp4,avi,mov,wmv,mkv,webm", "#f54242", Some("$VIDEOS")),
+    ("Music", "mp3,wav,flac,ogg,aac", "#f5a742", Some("$MUSIC")),
+    ("Archives", "zip,rar,7z,tar,gz", "#8342f5", Some("Archives")),
+    ("Code", "js,ts,html,css,rs,py,java,cpp,c,h", "#607d8b", None),
+];
+
+// Screenshot rule destination
+const SCREENSHOTS_DESTINATION: &str = "$PICTURES/Screenshots/{year}/{month}";
+
+// In-memory copy of the categories table, cleared whenever a category changes
+#[derive(Default)]
+pub struct CategoryCache(Mutex<Option<Vec<Category>>>);
+
+// Forget the cached categories, after anything that writes the categories
+// table: editing a category, merging its tag, or restoring a backup
+fn clear_category_cache(app: &AppHandle) {
+    if let Some(cache) = app.try_state::<CategoryCache>() {
+        *cache.0.lock().unwrap() = None;
+    }
+}
+
 // Key for the database in the app state
 const DB_KEY: &str = "db_connection";
 
-// Initialize the database
-pub fn init_database(app: &AppHandle) -> Result<()> {
+// Health of the database as seen at startup
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DatabaseStatus {
+    pub healthy: bool,
+    pub recovered: bool,
+    pub corrupt_backup_path: Option<String>,
+    pub error: Option<String>,
+}
+
+// Get the path of the database file
+pub fn get_database_path(app: &AppHandle) -> Result<PathBuf> {
     // Get app data directory for storing the database
     let app_dir = app.path()
         .app_data_dir()
@@ -22,15 +67,84 @@ pub fn init_database(app: &AppHandle) -> Result<()> {
     // Create the directory if it doesn't exist
     fs::create_dir_all(&app_dir).context("Failed to create app data directory")?;
 
-    // Create database path
-    let db_path = app_dir.join("smart_file_organizer.db");
-    
+    Ok(app_dir.join("smart_file_organizer.db"))
+}
+
+// Open a database file, verify it, and make sure the schema exists
+pub fn open_database(db_path: &Path) -> Result<Connection> {
     // Connect to SQLite database
-    let conn = Connection::open(&db_path)
+    let conn = Connection::open(db_path)
         .context("Failed to open database connection")?;
 
+    // A garbage file only fails once SQLite actually reads it, so check it up front
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .context("Failed to run database integrity check")?;
+    if integrity != "ok" {
+        return Err(IntegrityCheckFailed(integrity).into());
+    }
+
     // Create tables
     create_tables(&conn)?;
+
+    Ok(conn)
+}
+
+// Setting that turns write-ahead logging off when "false"; on by default.
+// WAL is much faster for bulk inserts and lets reads run while a write is in
+// progress, at the cost of -wal and -shm files next to the database
+pub const WAL_SETTING: &str = "database_wal";
+
+// Setting with SQLite's sync level, one of SYNCHRONOUS_LEVELS; "normal" by
+// default. With WAL, "normal" may lose the last transactions on power loss
+// but never corrupts the database; "full" syncs every commit to disk, and
+// "off" leaves it to the operating system
+pub const SYNCHRONOUS_SETTING: &str = "database_synchronous";
+pub const SYNCHRONOUS_LEVELS: [&str; 4] = ["off", "normal", "full", "extra"];
+
+const DEFAULT_SYNCHRONOUS: &str = "normal";
+
+// Apply the journaling and sync settings to a connection
+fn apply_durability(conn: &Connection) -> Result<()> {
+    let setting = |key: &str| {
+        conn.query_row("SELECT value FROM settings WHERE key = ?", [key], |row| row.get::<_, String>(0))
+            .optional()
+    };
+    let wal = setting(WAL_SETTING)?.as_deref() != Some("false");
+    let synchronous = setting(SYNCHRONOUS_SETTING)?
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| SYNCHRONOUS_LEVELS.contains(&value.as_str()))
+        .unwrap_or_else(|| DEFAULT_SYNCHRONOUS.to_string());
+
+    let journal_mode: String = conn
+        .pragma_update_and_check(None, "journal_mode", if wal { "WAL" } else { "DELETE" }, |row| row.get(0))
+        .context("Failed to set journal mode")?;
+    // Some file systems, e.g. network shares, can't do WAL; SQLite keeps
+    // its previous mode there
+    if wal && !journal_mode.eq_ignore_ascii_case("wal") {
+        tracing::warn!(journal_mode = %journal_mode, "Write-ahead logging isn't available for the database");
+    }
+    conn.pragma_update(None, "synchronous", &synchronous)
+        .context("Failed to set synchronous mode")?;
+
+    Ok(())
+}
+
+// Apply a changed journaling or sync setting to the open database
+pub fn apply_durability_setting(app: &AppHandle, key: &str) -> Result<()> {
+    if key != WAL_SETTING && key != SYNCHRONOUS_SETTING {
+        return Ok(());
+    }
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    apply_durability(&conn_guard.0)
+}
+
+// Initialize the database
+pub fn init_database(app: &AppHandle) -> Result<()> {
+    let db_path = get_database_path(app)?;
+    let conn = open_database(&db_path)?;
+    apply_durability(&conn)?;
     
     // Store the connection in the app state
     let mutex_conn = Arc::new(Mutex::new(DatabaseConnection(conn)));
@@ -39,13 +153,146 @@ pub fn init_database(app: &AppHandle) -> Result<()> {
     Ok(())
 }
 
+// Findings of the integrity check on a database file that did open
+#[derive(Debug, thiserror::Error)]
+#[error("Database integrity check failed: {0}")]
+struct IntegrityCheckFailed(String);
+
+// Whether opening the database failed because the file is corrupt or isn't a
+// database at all, rather than e.g. locked, read-only or out of disk space
+fn is_corruption(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause.is::<IntegrityCheckFailed>()
+            || matches!(
+                cause.downcast_ref::<rusqlite::Error>().and_then(|e| e.sqlite_error_code()),
+                Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+            )
+    })
+}
+
+// Initialize the database, setting a corrupt file aside and starting fresh if
+// needed. Any other failure leaves the file alone: starting over would throw
+// away an index that's fine
+pub fn init_database_with_recovery(app: &AppHandle) -> DatabaseStatus {
+    let status = match init_database(app) {
+        Ok(()) => DatabaseStatus { healthy: true, ..Default::default() },
+        Err(e) if !is_corruption(&e) => {
+            tracing::error!(error = format!("{:#}", e), "Database initialization failed");
+            let status = DatabaseStatus {
+                healthy: false,
+                error: Some(format!("{:#}", e)),
+                ..Default::default()
+            };
+            let _ = app.emit("database_error", status.clone());
+            status
+        }
+        Err(e) => {
+            tracing::error!(error = format!("{:#}", e), "Database initialization failed");
+            match recover_database(app) {
+                Ok(corrupt_path) => {
+                    tracing::warn!(
+                        corrupt_backup = %corrupt_path.display(),
+                        "Recovered database; previous file kept"
+                    );
+                    let status = DatabaseStatus {
+                        healthy: true,
+                        recovered: true,
+                        corrupt_backup_path: Some(corrupt_path.to_string_lossy().to_string()),
+                        error: Some(format!("{:#}", e)),
+                    };
+                    let _ = app.emit("database_recovered", status.clone());
+                    status
+                }
+                Err(recovery_error) => {
+                    tracing::error!(error = format!("{:#}", recovery_error), "Database recovery failed");
+                    let status = DatabaseStatus {
+                        healthy: false,
+                        error: Some(format!("{:#}", recovery_error)),
+                        ..Default::default()
+                    };
+                    let _ = app.emit("database_error", status.clone());
+                    status
+                }
+            }
+        }
+    };
+
+    app.manage(Mutex::new(status.clone()));
+    status
+}
+
+// Move the existing database file aside and create a fresh one in its place
+fn recover_database(app: &AppHandle) -> Result<PathBuf> {
+    let db_path = get_database_path(app)?;
+    if !db_path.exists() {
+        return Err(anyhow::anyhow!("No database file to recover"));
+    }
+
+    // Keep the corrupt file next to the new one for manual salvage
+    let timestamp = Utc::now().format("%Y%m%d%H%M%S").to_string();
+    let corrupt_path = db_path.with_extension(format!("db.corrupt-{}", timestamp));
+    fs::rename(&db_path, &corrupt_path)
+        .context("Failed to move corrupt database aside")?;
+    // A leftover write-ahead log belongs to the old file, and would otherwise
+    // be replayed into the new one
+    for suffix in ["-wal", "-shm"] {
+        let sidecar = PathBuf::from(format!("{}{}", db_path.display(), suffix));
+        if sidecar.exists() {
+            fs::rename(&sidecar, format!("{}{}", corrupt_path.display(), suffix))
+                .with_context(|| format!("Failed to move {} aside", sidecar.display()))?;
+        }
+    }
+
+    init_database(app)?;
+
+    Ok(corrupt_path)
+}
+
+// Schema version of the open database
+pub fn get_schema_version(app: &AppHandle) -> Result<i32> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    Ok(conn_guard.0.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+// Run SQLite's quick check, a faster integrity_check that skips index
+// contents. "ok" when nothing is wrong, otherwise its first finding. It reads
+// the whole file, so it runs on a connection of its own rather than keeping
+// the shared one from everything else meanwhile
+pub fn quick_check(app: &AppHandle) -> Result<String> {
+    let conn = Connection::open_with_flags(get_database_path(app)?, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .context("Failed to open database for quick check")?;
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    conn.query_row("PRAGMA quick_check(1)", [], |row| row.get(0))
+        .context("Failed to run database quick check")
+}
+
+// Get the database status recorded at startup
+pub fn get_database_status(app: &AppHandle) -> DatabaseStatus {
+    app.try_state::<Mutex<DatabaseStatus>>()
+        .map(|state| state.lock().unwrap().clone())
+        .unwrap_or_default()
+}
+
 // Get the database connection from the app state
-pub fn get_connection(app: &AppHandle) -> Result<Arc<Mutex<DatabaseConnection>>> {
+pub fn get_connection<R: Runtime>(app: &AppHandle<R>) -> Result<Arc<Mutex<DatabaseConnection>>> {
     app.try_state::<Arc<Mutex<DatabaseConnection>>>()
         .map(|state| state.inner().clone())
         .ok_or_else(|| anyhow::anyhow!("Failed to get database connection from app state"))
 }
 
+// Close the database connection cleanly on shutdown
+pub fn close_database(app: &AppHandle) -> Result<()> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    
+    // Anything still running after this gets a throwaway in-memory database
+    let old = std::mem::replace(&mut conn_guard.0, Connection::open_in_memory()?);
+    old.close().map_err(|(_, e)| e).context("Failed to close database connection")?;
+    
+    Ok(())
+}
+
 // Create database tables
 fn create_tables(conn: &Connection) -> Result<()> {
     // Create files table
@@ -84,6 +331,21 @@ fn create_tables(conn: &Connection) -> Result<()> {
         [],
     ).context("Failed to create file_tags table")?;
 
+    // Separate sets of rules and watched folders, one of them active at a time.
+    // Everything from before profiles existed belongs to the default one
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS profiles (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    ).context("Failed to create profiles table")?;
+    conn.execute(
+        "INSERT OR IGNORE INTO profiles (name, created_at) VALUES (?, ?)",
+        params![DEFAULT_PROFILE_NAME, utils::format_timestamp(&Utc::now())],
+    ).context("Failed to create default profile")?;
+
     // Create rules table for auto-organization rules
     conn.execute(
         "CREATE TABLE IF NOT EXISTS rules (
@@ -107,147 +369,3024 @@ fn create_tables(conn: &Connection) -> Result<()> {
         [],
     ).context("Failed to create watched_folders table")?;
 
-    // Create default tags if they don't exist
-    let default_tags = [
-        ("Documents", "#4287f5"),
-        ("Images", "#42f54e"),
-        ("Videos", "#f54242"),
-        ("Music", "#f5a742"),
-        ("Archives", "#8342f5"),
-    ];
+    // Rules a watched folder is limited to; folders without rows here use
+    // every active rule. Rows of deleted rules are kept on purpose, so a folder
+    // whose chosen rules are all gone organizes nothing rather than everything.
+    // They're kept with the rule ID negated, as rules.id values get reused
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS watched_folder_rules (
+            folder_path TEXT NOT NULL,
+            rule_id INTEGER NOT NULL,
+            PRIMARY KEY (folder_path, rule_id)
+        )",
+        [],
+    ).context("Failed to create watched_folder_rules table")?;
+
+    // Files the watcher saw outside the active hours, moved once they start.
+    // watch_root is the watched folder the file arrived in, whose rule limit
+    // applies when it's moved
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pending_actions (
+            id INTEGER PRIMARY KEY,
+            path TEXT NOT NULL UNIQUE,
+            watch_root TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    ).context("Failed to create pending_actions table")?;
+
+    // Files and folders never to organize: full paths or glob patterns, see
+    // ignore_list
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ignored_paths (
+            id INTEGER PRIMARY KEY,
+            pattern TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    ).context("Failed to create ignored_paths table")?;
+
+    // Write-ahead record of file moves: a row is written before the file is
+    // moved and completed in the same transaction that indexes it, so a move
+    // interrupted by a crash can be finished or undone at the next launch
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS move_journal (
+            id INTEGER PRIMARY KEY,
+            source TEXT NOT NULL,
+            destination TEXT NOT NULL,
+            state TEXT NOT NULL,
+            detail TEXT,
+            started_at TEXT NOT NULL,
+            finished_at TEXT
+        )",
+        [],
+    ).context("Failed to create move_journal table")?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_move_journal_state ON move_journal (state)",
+        [],
+    ).context("Failed to create move_journal index")?;
+
+    // Create settings table for user preferences
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    ).context("Failed to create settings table")?;
+
+    // Create file_history table recording what happened to each file
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS file_history (
+            id INTEGER PRIMARY KEY,
+            file_id INTEGER,
+            event_type TEXT NOT NULL,
+            from_path TEXT,
+            to_path TEXT,
+            detail TEXT,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE SET NULL
+        )",
+        [],
+    ).context("Failed to create file_history table")?;
+
+    // Create cleanup_policies table for stale-file sweeps of watched folders
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cleanup_policies (
+            id INTEGER PRIMARY KEY,
+            folder_path TEXT NOT NULL UNIQUE,
+            max_age_days INTEGER NOT NULL,
+            patterns TEXT NOT NULL DEFAULT '',
+            exclude_patterns BOOLEAN NOT NULL DEFAULT 0,
+            action TEXT NOT NULL,
+            archive_folder TEXT,
+            is_active BOOLEAN NOT NULL DEFAULT 1
+        )",
+        [],
+    ).context("Failed to create cleanup_policies table")?;
+
+    // Create categories table mapping extensions to a category and its tag
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS categories (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            extensions TEXT NOT NULL,
+            tag_id INTEGER,
+            color TEXT NOT NULL,
+            FOREIGN KEY (tag_id) REFERENCES tags (id) ON DELETE SET NULL
+        )",
+        [],
+    ).context("Failed to create categories table")?;
+
+    // Link rules created from a category so they follow its extension list
+    add_column_if_missing(conn, "rules", "category_id", "INTEGER")?;
+
+    // Rules can recreate the file's subfolder (relative to the watched root) under the destination
+    add_column_if_missing(conn, "rules", "preserve_structure", "BOOLEAN NOT NULL DEFAULT 0")?;
+
+    // Special rule conditions (e.g. is_screenshot) and a tag applied to organized files
+    add_column_if_missing(conn, "rules", "condition", "TEXT")?;
+    add_column_if_missing(conn, "rules", "tag_id", "INTEGER")?;
+
+    // Age threshold of "older_than" rules
+    add_column_if_missing(conn, "rules", "max_age_days", "INTEGER")?;
+
+    // Rules that move whole folders rather than files
+    add_column_if_missing(conn, "rules", "applies_to_folders", "BOOLEAN NOT NULL DEFAULT 0")?;
+
+    // Rules whose files don't get their category's tag
+    add_column_if_missing(conn, "rules", "exclude_auto_tags", "BOOLEAN NOT NULL DEFAULT 0")?;
+
+    // Whether created_at is the real creation time or an estimate, for file
+    // systems that don't record one. Rows indexed before this can't be told apart
+    add_column_if_missing(conn, "files", "created_at_approximate", "BOOLEAN NOT NULL DEFAULT 0")?;
+
+    // Single files can be watched as well as folders
+    add_column_if_missing(conn, "watched_folders", "is_file", "BOOLEAN NOT NULL DEFAULT 0")?;
+
+    // Rules, watched folders and folder rule limits belong to a profile. Rows
+    // without one, including the default rules seeded below, go to the default
+    add_column_if_missing(conn, "rules", "profile_id", "INTEGER")?;
+    add_column_if_missing(conn, "watched_folder_rules", "profile_id", "INTEGER")?;
+    add_profile_to_watched_folders(conn)?;
+
+    // Where a file was downloaded from, when the browser recorded it
+    add_column_if_missing(conn, "files", "source_url", "TEXT")?;
+    add_column_if_missing(conn, "files", "source_domain", "TEXT")?;
+
+    // Per-file notes and 0-5 star ratings
+    add_column_if_missing(conn, "files", "notes", "TEXT")?;
+    add_column_if_missing(conn, "files", "rating", "INTEGER")?;
+
+    // Cached perceptual hash of image files, valid while the file's mtime matches
+    add_column_if_missing(conn, "files", "phash", "INTEGER")?;
+    add_column_if_missing(conn, "files", "phash_modified_at", "TEXT")?;
+
+    // SHA-256 of the file's content, cached for duplicate scans like the pHash
+    add_column_if_missing(conn, "files", "content_hash", "TEXT")?;
+    add_column_if_missing(conn, "files", "content_hash_modified_at", "TEXT")?;
+
+    // Tags applied automatically, as a file's category tag when it's moved,
+    // rather than by the user. An auto-tag override only removes these
+    add_column_if_missing(conn, "file_tags", "auto_applied", "BOOLEAN NOT NULL DEFAULT 0")?;
+
+    // Free-form key/value metadata attached to files
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS file_metadata (
+            file_id INTEGER NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (file_id, key),
+            FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE CASCADE
+        )",
+        [],
+    ).context("Failed to create file_metadata table")?;
+
+    // Foreign keys aren't enforced on this connection, so cascade by hand
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS delete_file_metadata AFTER DELETE ON files
+         BEGIN
+             DELETE FROM file_metadata WHERE file_id = OLD.id;
+         END",
+        [],
+    ).context("Failed to create file_metadata trigger")?;
+
+    // Files the user doesn't want auto-tagged: their category's tag isn't
+    // applied again when they're moved or indexed
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS auto_tag_overrides (
+            file_id INTEGER PRIMARY KEY,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE CASCADE
+        )",
+        [],
+    ).context("Failed to create auto_tag_overrides table")?;
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS delete_auto_tag_overrides AFTER DELETE ON files
+         BEGIN
+             DELETE FROM auto_tag_overrides WHERE file_id = OLD.id;
+         END",
+        [],
+    ).context("Failed to create auto_tag_overrides trigger")?;
+
+    // Webhooks and commands run after files or folders are organized
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS hooks (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            event TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target TEXT NOT NULL,
+            enabled BOOLEAN NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    ).context("Failed to create hooks table")?;
+
+    // Extensions of each extension rule, one row each, kept in sync with rules.pattern
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rule_extensions (
+            rule_id INTEGER NOT NULL,
+            extension TEXT NOT NULL,
+            PRIMARY KEY (rule_id, extension),
+            FOREIGN KEY (rule_id) REFERENCES rules (id) ON DELETE CASCADE
+        )",
+        [],
+    ).context("Failed to create rule_extensions table")?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_rule_extensions_extension ON rule_extensions (extension)",
+        [],
+    ).context("Failed to create rule_extensions index")?;
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS delete_rule_extensions AFTER DELETE ON rules
+         BEGIN
+             DELETE FROM rule_extensions WHERE rule_id = OLD.id;
+         END",
+        [],
+    ).context("Failed to create rule_extensions trigger")?;
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS keep_watched_folder_rules AFTER DELETE ON rules
+         BEGIN
+             UPDATE OR IGNORE watched_folder_rules SET rule_id = -OLD.id WHERE rule_id = OLD.id;
+         END",
+        [],
+    ).context("Failed to create watched_folder_rules trigger")?;
+
+    for (name, extensions, color, destination) in DEFAULT_CATEGORIES.iter() {
+        // Create default tags if they don't exist
+        if destination.is_some() {
+            conn.execute(
+                "INSERT OR IGNORE INTO tags (name, color) VALUES (?, ?)",
+                params![name, color],
+            ).context("Failed to create default tag")?;
+        }
 
-    for (name, color) in default_tags.iter() {
         conn.execute(
-            "INSERT OR IGNORE INTO tags (name, color) VALUES (?, ?)",
-            params![name, color],
-        ).context("Failed to create default tag")?;
+            "INSERT OR IGNORE INTO categories (name, extensions, tag_id, color)
+             VALUES (?, ?, (SELECT id FROM tags WHERE name = ?), ?)",
+            params![name, extensions, name, color],
+        ).context("Failed to create default category")?;
+
+        // Create default rules for common file types
+        if let Some(destination) = destination {
+            conn.execute(
+                "INSERT INTO rules (name, pattern, destination_folder, is_extension, is_active, category_id)
+                 SELECT ?1, c.extensions, ?2, 1, 1, c.id FROM categories c
+                 WHERE c.name = ?1 AND NOT EXISTS (SELECT 1 FROM rules WHERE name = ?1)",
+                params![name, destination],
+            ).context("Failed to create default rule")?;
+
+            // Rules seeded before categories existed get linked by name
+            conn.execute(
+                "UPDATE rules SET category_id = (SELECT id FROM categories WHERE name = ?1)
+                 WHERE name = ?1 AND category_id IS NULL",
+                params![name],
+            ).context("Failed to link default rule to its category")?;
+        }
     }
 
-    // Create default rules for common file types
-    let default_rules = [
-        ("Documents", "pdf,doc,docx,txt,rtf,odt", "Documents", true),
-        ("Images", "jpg,jpeg,png,gif,bmp,webp,svg", "Images", true),
-        ("Videos", "mp4,avi,mov,wmv,mkv,webm", "Videos", true),
-        ("Music", "mp3,wav,flac,ogg,aac", "Music", true),
-        ("Archives", "zip,rar,7z,tar,gz", "Archives", true),
-    ];
+    // Screenshot routing is opt-in, so the built-in rule starts out inactive
+    conn.execute(
+        "INSERT OR IGNORE INTO tags (name, color) VALUES ('Screenshots', '#00bcd4')",
+        [],
+    ).context("Failed to create screenshots tag")?;
+    conn.execute(
+        "INSERT INTO rules (name, pattern, destination_folder, is_extension, is_active, condition, tag_id)
+         SELECT 'Screenshots', '', ?1, 0, 0, 'is_screenshot',
+                (SELECT id FROM tags WHERE name = 'Screenshots')
+         WHERE NOT EXISTS (SELECT 1 FROM rules WHERE name = 'Screenshots')",
+        [SCREENSHOTS_DESTINATION],
+    ).context("Failed to create screenshots rule")?;
+
+    // Indices for searches, tag filters and rule lookups. The file_tags primary
+    // key only helps lookups by file, not by tag
+    for (name, definition) in [
+        ("idx_files_extension", "files (extension)"),
+        ("idx_files_modified_at", "files (modified_at)"),
+        ("idx_files_size", "files (size)"),
+        ("idx_files_name", "files (name COLLATE NOCASE)"),
+        ("idx_file_tags_tag_id", "file_tags (tag_id)"),
+        ("idx_rules_active", "rules (is_active, is_extension)"),
+        ("idx_watched_folders_active", "watched_folders (is_active)"),
+        ("idx_file_history_event_type", "file_history (event_type, detail, created_at)"),
+        ("idx_file_history_from_path", "file_history (from_path)"),
+        ("idx_file_history_file_id", "file_history (file_id, event_type, created_at)"),
+        ("idx_file_history_created_at", "file_history (event_type, created_at)"),
+    ] {
+        conn.execute(&format!("CREATE INDEX IF NOT EXISTS {} ON {}", name, definition), [])
+            .with_context(|| format!("Failed to create index {}", name))?;
+    }
 
-    for (name, pattern, destination, is_extension) in default_rules.iter() {
+    for table in ["rules", "watched_folders", "watched_folder_rules"] {
         conn.execute(
-            "INSERT OR IGNORE INTO rules (name, pattern, destination_folder, is_extension, is_active) 
-             VALUES (?, ?, ?, ?, 1)",
-            params![name, pattern, destination, is_extension],
-        ).context("Failed to create default rule")?;
+            &format!(
+                "UPDATE {} SET profile_id = (SELECT id FROM profiles WHERE name = ?) WHERE profile_id IS NULL",
+                table
+            ),
+            [DEFAULT_PROFILE_NAME],
+        ).with_context(|| format!("Failed to assign {} to the default profile", table))?;
     }
 
-    Ok(())
-}
+    // Version 10 started validating tag colors; fix up ones stored before that
+    let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
 
-// Tag operations
-pub fn get_all_tags(app: &AppHandle) -> Result<Vec<Tag>> {
-    let conn = get_connection(app)?;
-    let conn_guard = conn.lock().unwrap();
-    
-    let mut stmt = conn_guard.0.prepare("SELECT id, name, color FROM tags")?;
-    let tag_iter = stmt.query_map([], |row| {
-        Ok(Tag {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            color: row.get(2)?,
-        })
-    })?;
+    // Version 5 added file IDs to history; best-effort link of rows written
+    // before that. Done once only: later rows without one belong to files
+    // removed from the index, and mustn't go to a new file at the same path
+    if version < 5 {
+        conn.execute(
+            "UPDATE file_history SET file_id = (SELECT id FROM files WHERE path = file_history.to_path)
+             WHERE file_id IS NULL AND to_path IS NOT NULL",
+            [],
+        ).context("Failed to link file history to files")?;
+    }
 
-    let mut tags = vec![];
-    for tag in tag_iter {
-        tags.push(tag?);
+    if version < 10 {
+        normalize_tag_colors(conn)?;
     }
 
-    Ok(tags)
-}
+    // Version 12 moved extension lists out of rules.pattern into rule_extensions.
+    // Rules from before that, and default rules seeded above, don't have theirs yet
+    let rules = conn
+        .prepare(
+            "SELECT id, pattern FROM rules
+             WHERE is_extension = 1 AND id NOT IN (SELECT rule_id FROM rule_extensions)"
+        )?
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (rule_id, pattern) in rules {
+        sync_rule_extensions(conn, rule_id, &pattern, true)
+            .context("Failed to migrate rule extensions")?;
+    }
 
-pub fn add_tag(app: &AppHandle, name: String, color: String) -> Result<i64> {
-    let conn = get_connection(app)?;
-    let conn_guard = conn.lock().unwrap();
-    
-    conn_guard.0.execute(
-        "INSERT INTO tags (name, color) VALUES (?, ?)",
-        params![name, color],
-    )?;
+    // Version 13 started normalizing paths before storing them
+    if version < 13 {
+        normalize_file_paths(conn).context("Failed to normalize file paths")?;
+    }
 
-    Ok(conn_guard.0.last_insert_rowid())
-}
+    // Version 14 stores file times as RFC 3339 instead of "YYYY-MM-DD HH:MM:SS",
+    // both in UTC
+    if version < 14 {
+        for column in ["created_at", "modified_at", "phash_modified_at"] {
+            convert_timestamps(conn, "files", column).context("Failed to convert file timestamps")?;
+        }
+    }
 
-pub fn remove_tag(app: &AppHandle, tag_id: i64) -> Result<()> {
-    let conn = get_connection(app)?;
-    let conn_guard = conn.lock().unwrap();
-    
-    conn_guard.0.execute(
-        "DELETE FROM tags WHERE id = ?",
-        params![tag_id],
-    )?;
+    // Version 15 added the indices above; give the planner statistics for them
+    if version < 15 {
+        conn.execute_batch("ANALYZE").context("Failed to analyze database")?;
+    }
+
+    // Version 17 points default rules at the platform's folders instead of
+    // English names below the home folder. Only rules still going where they
+    // were seeded are changed, so custom destinations are kept
+    if version < 17 {
+        let seeded = DEFAULT_CATEGORIES
+            .iter()
+            .filter_map(|(name, _, _, destination)| destination.map(|destination| (*name, *name, destination)))
+            .chain([("Screenshots", "Pictures/Screenshots/{year}/{month}", SCREENSHOTS_DESTINATION)]);
+        for (name, old_destination, destination) in seeded {
+            conn.execute(
+                "UPDATE rules SET destination_folder = ?1 WHERE name = ?2 AND destination_folder = ?3",
+                params![destination, name, old_destination],
+            ).context("Failed to update default rule destination")?;
+        }
+    }
+
+    // Version 18 negates the rule IDs of deleted rules in watched_folder_rules,
+    // so a new rule given the same ID doesn't inherit the folders
+    if version < 18 {
+        conn.execute(
+            "UPDATE OR IGNORE watched_folder_rules SET rule_id = -rule_id
+             WHERE rule_id > 0 AND NOT EXISTS (
+                 SELECT 1 FROM rules r
+                 WHERE r.id = watched_folder_rules.rule_id AND r.profile_id = watched_folder_rules.profile_id
+             )",
+            [],
+        ).context("Failed to update rules of watched folders")?;
+    }
+
+    // Version 19 tells automatic tags apart. Category tags from before can't
+    // be, and were nearly all applied when the file was moved
+    if version < 19 {
+        conn.execute(
+            "UPDATE file_tags SET auto_applied = 1
+             WHERE tag_id IN (SELECT tag_id FROM categories WHERE tag_id IS NOT NULL)",
+            [],
+        ).context("Failed to mark automatic tags")?;
+    }
+
+    // Version 20 stores history times as RFC 3339 too
+    if version < 20 {
+        convert_timestamps(conn, "file_history", "created_at").context("Failed to convert history timestamps")?;
+    }
+
+    // Record the schema version so backups can be checked before restoring
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+        .context("Failed to set schema version")?;
 
     Ok(())
 }
 
-// File operations
-pub fn add_file(
-    app: &AppHandle, 
-    path: &Path, 
-    name: &str, 
-    extension: &str, 
-    size: i64, 
-    created_at: &str, 
-    modified_at: &str
-) -> Result<i64> {
-    let conn = get_connection(app)?;
-    let conn_guard = conn.lock().unwrap();
-    
-    let path_str = path.to_string_lossy().to_string();
-    
-    conn_guard.0.execute(
-        "INSERT OR REPLACE INTO files (path, name, extension, size, created_at, modified_at) 
-         VALUES (?, ?, ?, ?, ?, ?)",
-        params![path_str, name, extension, size, created_at, modified_at],
+// Rewrite "YYYY-MM-DD HH:MM:SS" times in a column, all in UTC, as RFC 3339
+fn convert_timestamps(conn: &Connection, table: &str, column: &str) -> Result<()> {
+    conn.execute(
+        &format!(
+            "UPDATE {0} SET {1} = replace({1}, ' ', 'T') || 'Z'
+             WHERE {1} GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9] [0-9][0-9]:[0-9][0-9]:[0-9][0-9]'",
+            table, column
+        ),
+        [],
     )?;
+    Ok(())
+}
 
-    Ok(conn_guard.0.last_insert_rowid())
+// Rewrite stored file paths in normalized form. Rows that turn out to be the
+// same file are merged into the oldest one, which keeps the tags, metadata,
+// history and source details of all of them
+fn normalize_file_paths(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+
+    let files = tx
+        .prepare("SELECT id, path FROM files ORDER BY id")?
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut kept: HashMap<String, i64> = HashMap::new();
+    let mut renamed = vec![];
+    for (id, path) in files {
+        let normalized = utils::normalize_path(Path::new(&path));
+        match kept.get(&utils::path_key(&normalized)) {
+            Some(&keep_id) => merge_file_rows(&tx, keep_id, id)?,
+            None => {
+                kept.insert(utils::path_key(&normalized), id);
+                let normalized = normalized.to_string_lossy().to_string();
+                if normalized != path {
+                    renamed.push((id, normalized));
+                }
+            }
+        }
+    }
+
+    // Duplicates are gone by now, so no rename can collide with another row
+    for (id, path) in renamed {
+        tx.execute("UPDATE files SET path = ? WHERE id = ?", params![path, id])?;
+    }
+
+    tx.commit()?;
+    Ok(())
 }
 
-pub fn add_tag_to_file(app: &AppHandle, file_id: i64, tag_id: i64) -> Result<()> {
+// Fold the row of a duplicate the app trashed into the copy that was kept, so
+// its tags, metadata and history carry over
+pub fn merge_duplicate_file(app: &AppHandle, keep_id: i64, duplicate_id: i64) -> Result<()> {
     let conn = get_connection(app)?;
-    let conn_guard = conn.lock().unwrap();
-    
-    conn_guard.0.execute(
-        "INSERT OR IGNORE INTO file_tags (file_id, tag_id) VALUES (?, ?)",
-        params![file_id, tag_id],
-    )?;
+    let mut conn_guard = conn.lock().unwrap();
+    merge_duplicate_file_in(&mut conn_guard.0, keep_id, duplicate_id)
+}
 
+fn merge_duplicate_file_in(conn: &mut Connection, keep_id: i64, duplicate_id: i64) -> Result<()> {
+    let tx = conn.transaction()?;
+    merge_file_rows(&tx, keep_id, duplicate_id)?;
+    tx.commit()?;
     Ok(())
 }
 
-pub fn search_files(
-    app: &AppHandle,
-    query: Option<String>,
-    tag_ids: Option<Vec<i64>>,
-    extension: Option<String>,
-) -> Result<Vec<FileInfo>> {
+// Fold a duplicate file row into the one being kept, then delete it
+fn merge_file_rows(conn: &Connection, keep_id: i64, duplicate_id: i64) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO file_tags (file_id, tag_id, auto_applied)
+         SELECT ?1, tag_id, auto_applied FROM file_tags WHERE file_id = ?2",
+        params![keep_id, duplicate_id],
+    )?;
+    conn.execute("DELETE FROM file_tags WHERE file_id = ?", [duplicate_id])?;
+    conn.execute(
+        "INSERT OR IGNORE INTO file_metadata (file_id, key, value)
+         SELECT ?1, key, value FROM file_metadata WHERE file_id = ?2",
+        params![keep_id, duplicate_id],
+    )?;
+    conn.execute(
+        "UPDATE file_history SET file_id = ?1 WHERE file_id = ?2",
+        params![keep_id, duplicate_id],
+    )?;
+    conn.execute(
+        "UPDATE files SET
+            source_url = COALESCE(source_url, (SELECT source_url FROM files WHERE id = ?2)),
+            source_domain = COALESCE(source_domain, (SELECT source_domain FROM files WHERE id = ?2)),
+            notes = COALESCE(notes, (SELECT notes FROM files WHERE id = ?2)),
+            rating = COALESCE(rating, (SELECT rating FROM files WHERE id = ?2))
+         WHERE id = ?1",
+        params![keep_id, duplicate_id],
+    )?;
+    conn.execute("DELETE FROM files WHERE id = ?", [duplicate_id])?;
+
+    Ok(())
+}
+
+// Normalize stored tag colors, replacing invalid ones with the default color
+fn normalize_tag_colors(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT id, color FROM tags")?;
+    let tags = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (id, color) in tags {
+        let normalized = utils::normalize_hex_color(&color)
+            .unwrap_or_else(|_| utils::DEFAULT_TAG_COLOR.to_string());
+        if normalized != color {
+            conn.execute("UPDATE tags SET color = ? WHERE id = ?", params![normalized, id])
+                .context("Failed to normalize tag color")?;
+        }
+    }
+
+    Ok(())
+}
+
+// Names of a table's columns
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(columns)
+}
+
+// Add a column to an existing table unless it is already there
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    if !table_columns(conn, table)?.iter().any(|c| c == column) {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        ).with_context(|| format!("Failed to add {}.{} column", table, column))?;
+    }
+
+    Ok(())
+}
+
+// Rebuild watched_folders with a profile_id, unique per profile rather than
+// overall, so each profile can watch the same folder with its own options.
+// SQLite can't change a table's constraints in place
+fn add_profile_to_watched_folders(conn: &Connection) -> Result<()> {
+    if table_columns(conn, "watched_folders")?.iter().any(|c| c == "profile_id") {
+        return Ok(());
+    }
+
+    conn.execute_batch(
+        "BEGIN;
+         CREATE TABLE watched_folders_new (
+             id INTEGER PRIMARY KEY,
+             path TEXT NOT NULL,
+             is_active BOOLEAN NOT NULL DEFAULT 1,
+             is_file BOOLEAN NOT NULL DEFAULT 0,
+             profile_id INTEGER,
+             UNIQUE (profile_id, path)
+         );
+         INSERT INTO watched_folders_new (id, path, is_active, is_file)
+             SELECT id, path, is_active, is_file FROM watched_folders;
+         DROP TABLE watched_folders;
+         ALTER TABLE watched_folders_new RENAME TO watched_folders;
+         COMMIT;",
+    ).context("Failed to add profiles to watched folders")?;
+
+    Ok(())
+}
+
+// Backup, restore and maintenance
+#[derive(Debug, Clone, Serialize)]
+pub struct CompactResult {
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+// Copy the live database to a file using SQLite's online backup API
+pub fn backup_database(app: &AppHandle, destination: &Path) -> Result<()> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.backup(DatabaseName::Main, destination, None)
+        .context("Failed to back up database")?;
+
+    Ok(())
+}
+
+// Check that a file is a healthy database this version of the app can use
+pub fn validate_database_file(path: &Path) -> Result<()> {
+    if !path.is_file() {
+        return Err(anyhow::anyhow!("Database file not found: {}", path.display()));
+    }
+
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .context("Failed to open database file")?;
+
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .context("File is not a valid database")?;
+    if integrity != "ok" {
+        return Err(anyhow::anyhow!("Database integrity check failed: {}", integrity));
+    }
+
+    let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(anyhow::anyhow!(
+            "Database was created by a newer version of the app (schema {}, supported {})",
+            version,
+            SCHEMA_VERSION
+        ));
+    }
+
+    let has_files_table: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'files')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_files_table {
+        return Err(anyhow::anyhow!("File is not a Smart File Organizer database"));
+    }
+
+    Ok(())
+}
+
+// Replace the live database contents with a validated backup file
+pub fn restore_database(app: &AppHandle, source: &Path) -> Result<()> {
+    validate_database_file(source)?;
+
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+
+    // Copy into the managed connection so every holder of the state sees the restored data
+    conn_guard.0.restore(DatabaseName::Main, source, None::<fn(rusqlite::backup::Progress)>)
+        .context("Failed to restore database")?;
+
+    // Bring older backups up to the current schema
+    let upgraded = create_tables(&conn_guard.0);
+    // The categories are the backup's now, even if upgrading it failed
+    clear_category_cache(app);
+    upgraded
+}
+
+// Reclaim unused space and refresh query planner statistics
+pub fn compact_database(app: &AppHandle) -> Result<CompactResult> {
+    let db_path = get_database_path(app)?;
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    // Recent writes may only be in the write-ahead log; fold them into the
+    // database file first, so both sizes measure the same thing
+    conn_guard.0.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")
+        .context("Failed to checkpoint database")?;
+    let size_before = fs::metadata(&db_path)?.len();
+
+    // VACUUM goes through the log too
+    conn_guard.0.execute_batch("VACUUM; ANALYZE; PRAGMA wal_checkpoint(TRUNCATE);")
+        .context("Failed to compact database")?;
+
+    let size_after = fs::metadata(&db_path)?.len();
+
+    Ok(CompactResult { size_before, size_after })
+}
+
+// Tag operations
+pub fn get_all_tags(app: &AppHandle) -> Result<Vec<Tag>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    
+    let mut stmt = conn_guard.0.prepare("SELECT id, name, color FROM tags")?;
+    let tag_iter = stmt.query_map([], |row| {
+        Ok(Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            color: row.get(2)?,
+        })
+    })?;
+
+    let mut tags = vec![];
+    for tag in tag_iter {
+        tags.push(tag?);
+    }
+
+    Ok(tags)
+}
+
+pub fn add_tag(app: &AppHandle, name: String, color: String) -> Result<i64> {
+    let color = utils::normalize_hex_color(&color)?;
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    
+    conn_guard.0.execute(
+        "INSERT INTO tags (name, color) VALUES (?, ?)",
+        params![name, color],
+    )?;
+
+    Ok(conn_guard.0.last_insert_rowid())
+}
+
+// Rename a tag, optionally changing its color too
+pub fn rename_tag(app: &AppHandle, tag_id: i64, name: String, color: Option<String>) -> Result<()> {
+    let color = color.map(|c| utils::normalize_hex_color(&c)).transpose()?;
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    
+    let updated = conn_guard.0.execute(
+        "UPDATE tags SET name = ?, color = COALESCE(?, color) WHERE id = ?",
+        params![name, color, tag_id],
+    )?;
+    if updated == 0 {
+        return Err(anyhow::anyhow!("Tag {} not found", tag_id));
+    }
+
+    Ok(())
+}
+
+// Merge one tag into another: every file tagged with the source gets the
+// target instead, rules and categories follow, and the source tag is removed.
+// Returns how many files had the source tag
+pub fn merge_tags(app: &AppHandle, source_tag_id: i64, target_tag_id: i64) -> Result<usize> {
+    if source_tag_id == target_tag_id {
+        return Err(anyhow::anyhow!("Can't merge a tag into itself"));
+    }
+
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    let tx = conn_guard.0.transaction()?;
+
+    for tag_id in [source_tag_id, target_tag_id] {
+        let exists = tx
+            .query_row("SELECT 1 FROM tags WHERE id = ?", [tag_id], |_| Ok(()))
+            .optional()?
+            .is_some();
+        if !exists {
+            return Err(anyhow::anyhow!("Tag {} not found", tag_id));
+        }
+    }
+
+    // Files that already have both keep a single link to the target
+    tx.execute(
+        "INSERT OR IGNORE INTO file_tags (file_id, tag_id, auto_applied)
+         SELECT file_id, ?1, auto_applied FROM file_tags WHERE tag_id = ?2",
+        params![target_tag_id, source_tag_id],
+    )?;
+    let reassigned = tx.execute("DELETE FROM file_tags WHERE tag_id = ?", [source_tag_id])?;
+    tx.execute(
+        "UPDATE rules SET tag_id = ?1 WHERE tag_id = ?2",
+        params![target_tag_id, source_tag_id],
+    )?;
+    tx.execute(
+        "UPDATE categories SET tag_id = ?1 WHERE tag_id = ?2",
+        params![target_tag_id, source_tag_id],
+    )?;
+    tx.execute("DELETE FROM tags WHERE id = ?", [source_tag_id])?;
+
+    tx.commit()?;
+    clear_category_cache(app);
+    Ok(reassigned)
+}
+
+// Delete a tag, and with it its file links and its place on rules and
+// categories. Tag IDs can be reused, so nothing may keep pointing at it
+pub fn remove_tag(app: &AppHandle, tag_id: i64) -> Result<()> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    remove_tag_in(&mut conn_guard.0, tag_id)?;
+    drop(conn_guard);
+
+    clear_category_cache(app);
+    Ok(())
+}
+
+fn remove_tag_in(conn: &mut Connection, tag_id: i64) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    tx.execute("DELETE FROM file_tags WHERE tag_id = ?", [tag_id])?;
+    tx.execute("UPDATE rules SET tag_id = NULL WHERE tag_id = ?", [tag_id])?;
+    tx.execute("UPDATE categories SET tag_id = NULL WHERE tag_id = ?", [tag_id])?;
+    tx.execute("DELETE FROM tags WHERE id = ?", [tag_id])?;
+
+    tx.commit()?;
+    Ok(())
+}
+
+// Get a tag's ID by name, creating it with the given color if it doesn't exist
+pub fn get_or_create_tag<R: Runtime>(app: &AppHandle<R>, name: &str, color: &str) -> Result<i64> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "INSERT OR IGNORE INTO tags (name, color) VALUES (?, ?)",
+        params![name, color],
+    )?;
+
+    let tag_id = conn_guard.0.query_row(
+        "SELECT id FROM tags WHERE name = ?",
+        [name],
+        |row| row.get::<_, i64>(0),
+    )?;
+
+    Ok(tag_id)
+}
+
+pub fn get_file_tags<R: Runtime>(app: &AppHandle<R>, file_id: i64) -> Result<Vec<Tag>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT t.id, t.name, t.color 
+         FROM tags t
+         JOIN file_tags ft ON t.id = ft.tag_id
+         WHERE ft.file_id = ?"
+    )?;
+    let tag_iter = stmt.query_map([file_id], |row| {
+        Ok(Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            color: row.get(2)?,
+        })
+    })?;
+
+    let mut tags = vec![];
+    for tag in tag_iter {
+        tags.push(tag?);
+    }
+
+    Ok(tags)
+}
+
+// Files that must share a keyword and a tag before the tag is suggested
+const MIN_HISTORY_FILES: u32 = 2;
+
+// Suggest tags for a file, best match first. A tag is suggested when its name
+// appears in the filename, or when most other files sharing a keyword with
+// this one carry it (files named "invoice..." usually tagged "Finance")
+pub fn suggest_tags(app: &AppHandle, file_id: i64) -> Result<Vec<Tag>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let name: String = conn_guard.0.query_row(
+        "SELECT name FROM files WHERE id = ?",
+        [file_id],
+        |row| row.get(0),
+    ).optional()?
+        .ok_or_else(|| anyhow::anyhow!("File {} not found", file_id))?;
+    let stem = utils::get_file_stem(Path::new(&name))
+        .unwrap_or_else(|| name.clone())
+        .to_lowercase();
+    let keywords = filename_keywords(&stem);
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT id, name, color FROM tags
+         WHERE id NOT IN (SELECT tag_id FROM file_tags WHERE file_id = ?)"
+    )?;
+    let candidates = stmt.query_map([file_id], |row| {
+        Ok(Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            color: row.get(2)?,
+        })
+    })?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut scores: HashMap<i64, u32> = HashMap::new();
+
+    // Tag names found in the filename; whole words beat substrings, and longer
+    // names beat shorter ones
+    for tag in &candidates {
+        let tag_name = tag.name.trim().to_lowercase();
+        if tag_name.is_empty() || !stem.contains(&tag_name) {
+            continue;
+        }
+        let mut score = 100 + tag_name.chars().count() as u32;
+        if keywords.contains(&tag_name) {
+            score += 50;
+        }
+        *scores.entry(tag.id).or_insert(0) += score;
+    }
+
+    // Tags most other files with the same keyword carry
+    let mut history_stmt = conn_guard.0.prepare(
+        "SELECT ft.tag_id, COUNT(*),
+                (SELECT COUNT(*) FROM files WHERE id != ?1 AND lower(name) LIKE ?2)
+         FROM files f
+         JOIN file_tags ft ON ft.file_id = f.id
+         WHERE f.id != ?1 AND lower(f.name) LIKE ?2
+         GROUP BY ft.tag_id"
+    )?;
+    for keyword in &keywords {
+        let pattern = format!("%{}%", keyword);
+        let rows = history_stmt.query_map(params![file_id, pattern], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, u32>(1)?, row.get::<_, u32>(2)?))
+        })?;
+        for row in rows {
+            let (tag_id, tagged, total) = row?;
+            if tagged >= MIN_HISTORY_FILES && tagged * 2 > total {
+                *scores.entry(tag_id).or_insert(0) += tagged * 50 / total;
+            }
+        }
+    }
+
+    let mut suggestions: Vec<(u32, Tag)> = candidates
+        .into_iter()
+        .filter_map(|tag| scores.get(&tag.id).map(|score| (*score, tag)))
+        .collect();
+    suggestions.sort_by(|(a_score, a), (b_score, b)| {
+        b_score.cmp(a_score).then_with(|| a.name.cmp(&b.name))
+    });
+
+    Ok(suggestions.into_iter().map(|(_, tag)| tag).collect())
+}
+
+// Lowercased words of a filename worth learning from; numbers and very short
+// fragments like "a" or "v2" say nothing about the file
+fn filename_keywords(stem: &str) -> Vec<String> {
+    let mut keywords: Vec<String> = stem
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3 && !word.chars().all(|c| c.is_ascii_digit()))
+        .map(|word| word.to_string())
+        .collect();
+    keywords.sort();
+    keywords.dedup();
+    keywords
+}
+
+// Category operations
+pub fn get_categories<R: Runtime>(app: &AppHandle<R>) -> Result<Vec<Category>> {
+    let cache = app.state::<CategoryCache>();
+    if let Some(categories) = cache.0.lock().unwrap().as_ref() {
+        return Ok(categories.clone());
+    }
+
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT id, name, extensions, tag_id, color FROM categories ORDER BY name ASC"
+    )?;
+    let category_iter = stmt.query_map([], |row| {
+        Ok(Category {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            extensions: split_extensions(&row.get::<_, String>(2)?),
+            tag_id: row.get(3)?,
+            color: row.get(4)?,
+        })
+    })?;
+
+    let mut categories = vec![];
+    for category in category_iter {
+        categories.push(category?);
+    }
+
+    *cache.0.lock().unwrap() = Some(categories.clone());
+    Ok(categories)
+}
+
+// Find the category an extension belongs to
+pub fn get_category_for_extension<R: Runtime>(app: &AppHandle<R>, extension: &str) -> Result<Option<Category>> {
+    let extension = extension.to_lowercase();
+    Ok(get_categories(app)?
+        .into_iter()
+        .find(|c| c.extensions.contains(&extension)))
+}
+
+pub fn update_category(
+    app: &AppHandle,
+    category_id: i64,
+    name: String,
+    extensions: Vec<String>,
+    color: String,
+    tag_id: Option<i64>,
+) -> Result<()> {
+    let color = utils::normalize_hex_color(&color)?;
+    let extensions = join_extensions(&extensions);
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    let tx = conn_guard.0.transaction()?;
+
+    let updated = tx.execute(
+        "UPDATE categories SET name = ?, extensions = ?, color = ?, tag_id = ? WHERE id = ?",
+        params![name, extensions, color, tag_id, category_id],
+    )?;
+    if updated == 0 {
+        return Err(anyhow::anyhow!("Category not found"));
+    }
+
+    // Rules created from this category organize the same extensions
+    tx.execute(
+        "UPDATE rules SET pattern = ? WHERE category_id = ?",
+        params![extensions, category_id],
+    )?;
+    let rules = tx
+        .prepare("SELECT id, is_extension FROM rules WHERE category_id = ?")?
+        .query_map([category_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (rule_id, is_extension) in rules {
+        sync_rule_extensions(&tx, rule_id, &extensions, is_extension)?;
+    }
+
+    tx.commit()?;
+    clear_category_cache(app);
+    Ok(())
+}
+
+// Parse a comma separated extension list into normalized extensions
+pub fn split_extensions(extensions: &str) -> Vec<String> {
+    extensions
+        .split(',')
+        .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+fn join_extensions(extensions: &[String]) -> String {
+    let mut normalized: Vec<String> = vec![];
+    for extension in split_extensions(&extensions.join(",")) {
+        if !normalized.contains(&extension) {
+            normalized.push(extension);
+        }
+    }
+    normalized.join(",")
+}
+
+// Store the extensions an extension rule matches; other rules get none
+fn sync_rule_extensions(conn: &Connection, rule_id: i64, pattern: &str, is_extension: bool) -> Result<()> {
+    conn.execute("DELETE FROM rule_extensions WHERE rule_id = ?", [rule_id])?;
+
+    if is_extension {
+        for extension in split_extensions(pattern) {
+            conn.execute(
+                "INSERT OR IGNORE INTO rule_extensions (rule_id, extension) VALUES (?, ?)",
+                params![rule_id, extension],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+// Rule operations
+// The active profile's rules
+pub fn get_rules<R: Runtime>(app: &AppHandle<R>) -> Result<Vec<Rule>> {
+    get_profile_rules(app, get_active_profile_id(app)?)
+}
+
+pub fn get_profile_rules<R: Runtime>(app: &AppHandle<R>, profile_id: i64) -> Result<Vec<Rule>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT id, name, pattern, destination_folder, is_extension, is_active, preserve_structure,
+                condition, tag_id, max_age_days, applies_to_folders, exclude_auto_tags
+         FROM rules WHERE profile_id = ? ORDER BY id ASC"
+    )?;
+    let rule_iter = stmt.query_map([profile_id], |row| {
+        Ok(Rule {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            pattern: row.get(2)?,
+            destination_folder: row.get(3)?,
+            is_extension: row.get(4)?,
+            is_active: row.get(5)?,
+            preserve_structure: row.get(6)?,
+            condition: row.get(7)?,
+            tag_id: row.get(8)?,
+            max_age_days: row.get(9)?,
+            applies_to_folders: row.get(10)?,
+            exclude_auto_tags: row.get(11)?,
+        })
+    })?;
+
+    let mut rules = vec![];
+    for rule in rule_iter {
+        rules.push(rule?);
+    }
+
+    Ok(rules)
+}
+
+// Add a rule to the active profile
+pub fn add_rule(app: &AppHandle, rule: &Rule) -> Result<i64> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    let profile_id = active_profile_id_in(&conn_guard.0)?;
+
+    insert_rule(&conn_guard.0, rule, profile_id)
+}
+
+fn insert_rule(conn: &Connection, rule: &Rule, profile_id: i64) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO rules (name, pattern, destination_folder, is_extension, is_active, preserve_structure,
+                            condition, tag_id, max_age_days, applies_to_folders, exclude_auto_tags,
+                            profile_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            rule.name,
+            rule.pattern,
+            rule.destination_folder,
+            rule.is_extension,
+            rule.is_active,
+            rule.preserve_structure,
+            rule.condition,
+            rule.tag_id,
+            rule.max_age_days,
+            rule.applies_to_folders,
+            rule.exclude_auto_tags,
+            profile_id
+        ],
+    )?;
+    let rule_id = conn.last_insert_rowid();
+    sync_rule_extensions(conn, rule_id, &rule.pattern, rule.is_extension)?;
+
+    Ok(rule_id)
+}
+
+pub fn update_rule(app: &AppHandle, rule: &Rule) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let updated = conn_guard.0.execute(
+        "UPDATE rules SET name = ?, pattern = ?, destination_folder = ?, is_extension = ?,
+                is_active = ?, preserve_structure = ?, condition = ?, tag_id = ?, max_age_days = ?,
+                applies_to_folders = ?, exclude_auto_tags = ?
+         WHERE id = ?",
+        params![
+            rule.name,
+            rule.pattern,
+            rule.destination_folder,
+            rule.is_extension,
+            rule.is_active,
+            rule.preserve_structure,
+            rule.condition,
+            rule.tag_id,
+            rule.max_age_days,
+            rule.applies_to_folders,
+            rule.exclude_auto_tags,
+            rule.id
+        ],
+    )?;
+    if updated == 0 {
+        return Err(anyhow::anyhow!("Rule not found"));
+    }
+    sync_rule_extensions(&conn_guard.0, rule.id, &rule.pattern, rule.is_extension)?;
+
+    Ok(())
+}
+
+// Save rules to a profile in one transaction, updating the first of its rules
+// with the same name or adding a new one. Returns how many were added and updated
+pub fn upsert_rules_by_name(app: &AppHandle, profile_id: i64, rules: &[Rule]) -> Result<(usize, usize)> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    let tx = conn_guard.0.transaction()?;
+
+    let (mut added, mut updated) = (0, 0);
+    for rule in rules {
+        let existing = tx.query_row(
+            "SELECT id FROM rules WHERE name = ? AND profile_id = ? ORDER BY id ASC LIMIT 1",
+            params![rule.name, profile_id],
+            |row| row.get::<_, i64>(0),
+        ).optional()?;
+
+        let rule_id = match existing {
+            Some(rule_id) => {
+                tx.execute(
+                    "UPDATE rules SET pattern = ?, destination_folder = ?, is_extension = ?, is_active = ?,
+                            preserve_structure = ?, condition = ?, tag_id = ?, max_age_days = ?,
+                            applies_to_folders = ?, exclude_auto_tags = ?
+                     WHERE id = ?",
+                    params![
+                        rule.pattern,
+                        rule.destination_folder,
+                        rule.is_extension,
+                        rule.is_active,
+                        rule.preserve_structure,
+                        rule.condition,
+                        rule.tag_id,
+                        rule.max_age_days,
+                        rule.applies_to_folders,
+                        rule.exclude_auto_tags,
+                        rule_id
+                    ],
+                )?;
+                updated += 1;
+                rule_id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO rules (name, pattern, destination_folder, is_extension, is_active, preserve_structure,
+                                        condition, tag_id, max_age_days, applies_to_folders, exclude_auto_tags,
+                                        profile_id)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    params![
+                        rule.name,
+                        rule.pattern,
+                        rule.destination_folder,
+                        rule.is_extension,
+                        rule.is_active,
+                        rule.preserve_structure,
+                        rule.condition,
+                        rule.tag_id,
+                        rule.max_age_days,
+                        rule.applies_to_folders,
+                        rule.exclude_auto_tags,
+                        profile_id
+                    ],
+                )?;
+                added += 1;
+                tx.last_insert_rowid()
+            }
+        };
+        sync_rule_extensions(&tx, rule_id, &rule.pattern, rule.is_extension)?;
+    }
+
+    tx.commit()?;
+    Ok((added, updated))
+}
+
+// Add rules to a profile in one transaction without touching its existing
+// rules: a rule whose name is taken is added as "<name> (imported)", or
+// "<name> (imported 2)" and so on. Returns the name each rule was saved under
+pub fn add_rules_keeping_existing(app: &AppHandle, profile_id: i64, rules: &[Rule]) -> Result<Vec<String>> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    add_rules_keeping_existing_in(&mut conn_guard.0, profile_id, rules)
+}
+
+fn add_rules_keeping_existing_in(conn: &mut Connection, profile_id: i64, rules: &[Rule]) -> Result<Vec<String>> {
+    let tx = conn.transaction()?;
+
+    let mut names = vec![];
+    for rule in rules {
+        let mut name = rule.name.clone();
+        let mut n = 1;
+        while tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM rules WHERE name = ? AND profile_id = ?)",
+            params![name, profile_id],
+            |row| row.get::<_, bool>(0),
+        )? {
+            name = match n {
+                1 => format!("{} (imported)", rule.name),
+                n => format!("{} (imported {})", rule.name, n),
+            };
+            n += 1;
+        }
+        insert_rule(&tx, &Rule { name: name.clone(), ..rule.clone() }, profile_id)?;
+        names.push(name);
+    }
+
+    tx.commit()?;
+    Ok(names)
+}
+
+pub fn remove_rule(app: &AppHandle, rule_id: i64) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "DELETE FROM rules WHERE id = ?",
+        params![rule_id],
+    )?;
+
+    Ok(())
+}
+
+// Profile operations
+// Profile every existing rule and watched folder was put in when profiles
+// were added, and the one used when no other is active
+pub const DEFAULT_PROFILE_NAME: &str = "Default";
+
+// Setting with the ID of the active profile
+pub const ACTIVE_PROFILE_SETTING: &str = "active_profile";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Profile {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+    pub is_active: bool,
+}
+
+// The profile in ACTIVE_PROFILE_SETTING, or the default one when that's unset
+// or names a profile that is gone
+fn active_profile_id_in(conn: &Connection) -> Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(
+             (SELECT p.id FROM profiles p JOIN settings s ON s.key = ?1 AND p.id = CAST(s.value AS INTEGER)),
+             (SELECT id FROM profiles WHERE name = ?2),
+             (SELECT MIN(id) FROM profiles))",
+        params![ACTIVE_PROFILE_SETTING, DEFAULT_PROFILE_NAME],
+        |row| row.get::<_, Option<i64>>(0),
+    )?
+    .ok_or_else(|| anyhow::anyhow!("No profile found"))
+}
+
+pub fn get_active_profile_id<R: Runtime>(app: &AppHandle<R>) -> Result<i64> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    active_profile_id_in(&conn_guard.0)
+}
+
+pub fn get_profiles(app: &AppHandle) -> Result<Vec<Profile>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    let active_id = active_profile_id_in(&conn_guard.0)?;
+
+    let mut stmt = conn_guard.0.prepare("SELECT id, name, created_at FROM profiles ORDER BY id")?;
+    let profiles = stmt
+        .query_map([], |row| {
+            let id = row.get(0)?;
+            Ok(Profile {
+                id,
+                name: row.get(1)?,
+                created_at: row.get(2)?,
+                is_active: id == active_id,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(profiles)
+}
+
+// Add an empty profile; it gets rules and watched folders once it's active
+pub fn add_profile(app: &AppHandle, name: &str) -> Result<Profile> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("Profile name is empty"));
+    }
+
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    let created_at = utils::format_timestamp(&Utc::now());
+    let inserted = conn_guard.0.execute(
+        "INSERT OR IGNORE INTO profiles (name, created_at) VALUES (?, ?)",
+        params![name, created_at],
+    )?;
+    if inserted == 0 {
+        return Err(anyhow::anyhow!("A profile named \"{}\" already exists", name));
+    }
+
+    Ok(Profile {
+        id: conn_guard.0.last_insert_rowid(),
+        name: name.to_string(),
+        created_at,
+        is_active: false,
+    })
+}
+
+// Make a profile the active one and note the switch in the activity log.
+// Returns the profile
+pub fn set_active_profile(app: &AppHandle, profile_id: i64) -> Result<Profile> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    let tx = conn_guard.0.transaction()?;
+
+    let (name, created_at) = tx
+        .query_row(
+            "SELECT name, created_at FROM profiles WHERE id = ?",
+            [profile_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?
+        .ok_or_else(|| anyhow::anyhow!("Profile {} not found", profile_id))?;
+    tx.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)",
+        params![ACTIVE_PROFILE_SETTING, profile_id.to_string()],
+    )?;
+    insert_file_event(&tx, None, EVENT_PROFILE_SWITCHED, None, None, Some(&name))?;
+    tx.commit()?;
+
+    Ok(Profile { id: profile_id, name, created_at, is_active: true })
+}
+
+// Delete a profile with its rules and watched folders. The active profile
+// can't be deleted
+pub fn remove_profile(app: &AppHandle, profile_id: i64) -> Result<()> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    let tx = conn_guard.0.transaction()?;
+
+    if active_profile_id_in(&tx)? == profile_id {
+        return Err(anyhow::anyhow!("Switch to another profile before deleting this one"));
+    }
+    let is_default: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM profiles WHERE id = ? AND name = ?)",
+        params![profile_id, DEFAULT_PROFILE_NAME],
+        |row| row.get(0),
+    )?;
+    if is_default {
+        return Err(anyhow::anyhow!("The default profile can't be deleted"));
+    }
+    for table in ["rules", "watched_folders", "watched_folder_rules"] {
+        tx.execute(&format!("DELETE FROM {} WHERE profile_id = ?", table), [profile_id])?;
+    }
+    let deleted = tx.execute("DELETE FROM profiles WHERE id = ?", [profile_id])?;
+    if deleted == 0 {
+        return Err(anyhow::anyhow!("Profile {} not found", profile_id));
+    }
+    tx.commit()?;
+
+    Ok(())
+}
+
+// Cleanup policy operations
+const CLEANUP_POLICY_COLUMNS: &str =
+    "id, folder_path, max_age_days, patterns, exclude_patterns, action, archive_folder, is_active";
+
+fn map_cleanup_policy(row: &rusqlite::Row) -> rusqlite::Result<CleanupPolicy> {
+    Ok(CleanupPolicy {
+        id: row.get(0)?,
+        folder_path: row.get(1)?,
+        max_age_days: row.get(2)?,
+        patterns: row.get(3)?,
+        exclude_patterns: row.get(4)?,
+        action: row.get(5)?,
+        archive_folder: row.get(6)?,
+        is_active: row.get(7)?,
+    })
+}
+
+pub fn get_cleanup_policies(app: &AppHandle) -> Result<Vec<CleanupPolicy>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(
+        &format!("SELECT {} FROM cleanup_policies ORDER BY folder_path ASC", CLEANUP_POLICY_COLUMNS)
+    )?;
+    let policy_iter = stmt.query_map([], map_cleanup_policy)?;
+
+    let mut policies = vec![];
+    for policy in policy_iter {
+        policies.push(policy?);
+    }
+
+    Ok(policies)
+}
+
+pub fn get_cleanup_policy_for_folder(app: &AppHandle, folder_path: &str) -> Result<Option<CleanupPolicy>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let policy = conn_guard.0.query_row(
+        &format!("SELECT {} FROM cleanup_policies WHERE folder_path = ?", CLEANUP_POLICY_COLUMNS),
+        [folder_path],
+        map_cleanup_policy,
+    ).optional()?;
+
+    Ok(policy)
+}
+
+// Create or replace the cleanup policy for a folder
+pub fn set_cleanup_policy(app: &AppHandle, policy: &CleanupPolicy) -> Result<i64> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "INSERT INTO cleanup_policies
+            (folder_path, max_age_days, patterns, exclude_patterns, action, archive_folder, is_active)
+         VALUES (?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(folder_path) DO UPDATE SET
+            max_age_days = excluded.max_age_days,
+            patterns = excluded.patterns,
+            exclude_patterns = excluded.exclude_patterns,
+            action = excluded.action,
+            archive_folder = excluded.archive_folder,
+            is_active = excluded.is_active",
+        params![
+            policy.folder_path,
+            policy.max_age_days,
+            policy.patterns,
+            policy.exclude_patterns,
+            policy.action,
+            policy.archive_folder,
+            policy.is_active
+        ],
+    )?;
+
+    let policy_id = conn_guard.0.query_row(
+        "SELECT id FROM cleanup_policies WHERE folder_path = ?",
+        [&policy.folder_path],
+        |row| row.get::<_, i64>(0),
+    )?;
+
+    Ok(policy_id)
+}
+
+pub fn remove_cleanup_policy(app: &AppHandle, policy_id: i64) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "DELETE FROM cleanup_policies WHERE id = ?",
+        params![policy_id],
+    )?;
+
+    Ok(())
+}
+
+// Hook operations
+const HOOK_COLUMNS: &str = "id, name, event, action, target, enabled";
+
+fn map_hook(row: &rusqlite::Row) -> rusqlite::Result<Hook> {
+    Ok(Hook {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        event: row.get(2)?,
+        action: row.get(3)?,
+        target: row.get(4)?,
+        enabled: row.get(5)?,
+    })
+}
+
+pub fn get_hooks(app: &AppHandle) -> Result<Vec<Hook>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(&format!("SELECT {} FROM hooks ORDER BY id ASC", HOOK_COLUMNS))?;
+    let hooks = stmt.query_map([], map_hook)?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(hooks)
+}
+
+// The enabled hooks of an event, oldest first. The statement is cached, as
+// this runs for every organized file
+pub fn get_enabled_hooks<R: Runtime>(app: &AppHandle<R>, event: &str) -> Result<Vec<Hook>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare_cached(
+        &format!("SELECT {} FROM hooks WHERE event = ? AND enabled = 1 ORDER BY id ASC", HOOK_COLUMNS)
+    )?;
+    let hooks = stmt.query_map([event], map_hook)?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(hooks)
+}
+
+pub fn get_hook(app: &AppHandle, hook_id: i64) -> Result<Option<Hook>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let hook = conn_guard.0.query_row(
+        &format!("SELECT {} FROM hooks WHERE id = ?", HOOK_COLUMNS),
+        [hook_id],
+        map_hook,
+    ).optional()?;
+
+    Ok(hook)
+}
+
+pub fn add_hook(app: &AppHandle, hook: &Hook) -> Result<i64> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "INSERT INTO hooks (name, event, action, target, enabled, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        params![
+            hook.name.trim(),
+            hook.event,
+            hook.action,
+            hook.target.trim(),
+            hook.enabled,
+            utils::format_timestamp(&Utc::now())
+        ],
+    )?;
+
+    Ok(conn_guard.0.last_insert_rowid())
+}
+
+pub fn update_hook(app: &AppHandle, hook: &Hook) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let updated = conn_guard.0.execute(
+        "UPDATE hooks SET name = ?, event = ?, action = ?, target = ?, enabled = ? WHERE id = ?",
+        params![hook.name.trim(), hook.event, hook.action, hook.target.trim(), hook.enabled, hook.id],
+    )?;
+    if updated == 0 {
+        return Err(anyhow::anyhow!("Hook {} not found", hook.id));
+    }
+
+    Ok(())
+}
+
+pub fn remove_hook(app: &AppHandle, hook_id: i64) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute("DELETE FROM hooks WHERE id = ?", [hook_id])?;
+
+    Ok(())
+}
+
+// Watched folder operations
+// Get every folder that has ever been watched, active or not
+pub fn get_watched_folder_paths(app: &AppHandle) -> Result<Vec<String>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare("SELECT DISTINCT path FROM watched_folders")?;
+    let path_iter = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+    let mut paths = vec![];
+    for path in path_iter {
+        paths.push(path?);
+    }
+
+    Ok(paths)
+}
+
+// Get the folders and files the active profile marks as watched, which
+// should be watched now
+pub fn get_active_watched_paths(app: &AppHandle) -> Result<Vec<String>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    let profile_id = active_profile_id_in(&conn_guard.0)?;
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT path FROM watched_folders WHERE is_active = 1 AND profile_id = ? ORDER BY path"
+    )?;
+    let paths = stmt
+        .query_map([profile_id], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(paths)
+}
+
+// Mark a folder or file as watched by the active profile
+pub fn save_watched_path(app: &AppHandle, path: &str, is_file: bool) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    let profile_id = active_profile_id_in(&conn_guard.0)?;
+
+    conn_guard.0.execute(
+        "INSERT INTO watched_folders (path, is_active, is_file, profile_id) VALUES (?1, 1, ?2, ?3)
+         ON CONFLICT (profile_id, path) DO UPDATE SET is_active = 1, is_file = ?2",
+        params![path, is_file, profile_id],
+    )?;
+
+    Ok(())
+}
+
+// Mark one path, or everything, as no longer watched by the active profile
+pub fn set_watched_paths_inactive(app: &AppHandle, path: Option<&str>) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    let profile_id = active_profile_id_in(&conn_guard.0)?;
+
+    conn_guard.0.execute(
+        "UPDATE watched_folders SET is_active = 0 WHERE profile_id = ?1 AND (?2 IS NULL OR path = ?2)",
+        params![profile_id, path],
+    )?;
+
+    Ok(())
+}
+
+// Limit a watched folder to some rules of the active profile; an empty list
+// lifts the limit. Fails without changing anything if a rule doesn't exist
+pub fn set_watched_folder_rules(app: &AppHandle, path: &str, rule_ids: &[i64]) -> Result<()> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    let tx = conn_guard.0.transaction()?;
+    let profile_id = active_profile_id_in(&tx)?;
+
+    for rule_id in rule_ids {
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM rules WHERE id = ? AND profile_id = ?)",
+            params![rule_id, profile_id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(anyhow::anyhow!("Rule {} not found", rule_id));
+        }
+    }
+
+    tx.execute(
+        "DELETE FROM watched_folder_rules WHERE folder_path = ? AND profile_id = ?",
+        params![path, profile_id],
+    )?;
+    for rule_id in rule_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO watched_folder_rules (folder_path, rule_id, profile_id) VALUES (?, ?, ?)",
+            params![path, rule_id, profile_id],
+        )?;
+    }
+    tx.commit()?;
+
+    Ok(())
+}
+
+// Rules each limited watched folder of the active profile uses, by folder path
+pub fn get_watched_folder_rules<R: Runtime>(app: &AppHandle<R>) -> Result<HashMap<String, Vec<i64>>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    watched_folder_rules_in(&conn_guard.0)
+}
+
+fn watched_folder_rules_in(conn: &Connection) -> Result<HashMap<String, Vec<i64>>> {
+    let profile_id = active_profile_id_in(conn)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT folder_path, rule_id FROM watched_folder_rules WHERE profile_id = ? ORDER BY folder_path, rule_id"
+    )?;
+    let rows = stmt.query_map([profile_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+
+    // Deleted rules (negative IDs) still limit their folder, to the rules left
+    let mut folders: HashMap<String, Vec<i64>> = HashMap::new();
+    for row in rows {
+        let (path, rule_id) = row?;
+        let rule_ids = folders.entry(path).or_default();
+        if rule_id > 0 {
+            rule_ids.push(rule_id);
+        }
+    }
+
+    Ok(folders)
+}
+
+// Rules a watched folder is limited to, or None when it uses every rule
+pub fn get_folder_rule_limit<R: Runtime>(app: &AppHandle<R>, path: &str) -> Result<Option<Vec<i64>>> {
+    Ok(get_watched_folder_rules(app)?.remove(path))
+}
+
+// Ignore list operations
+// An entry of the ignore list
+#[derive(Debug, Clone, Serialize)]
+pub struct IgnoreEntry {
+    pub id: i64,
+    pub pattern: String,
+    pub created_at: String,
+}
+
+// Add a path or pattern to the ignore list, returning its ID. Adding one that
+// is already there returns the existing entry
+pub fn add_ignore(app: &AppHandle, pattern: &str) -> Result<i64> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    let pattern = pattern.trim();
+
+    conn_guard.0.execute(
+        "INSERT OR IGNORE INTO ignored_paths (pattern, created_at) VALUES (?, ?)",
+        params![pattern, utils::format_timestamp(&Utc::now())],
+    )?;
+    Ok(conn_guard.0.query_row(
+        "SELECT id FROM ignored_paths WHERE pattern = ?",
+        [pattern],
+        |row| row.get(0),
+    )?)
+}
+
+pub fn remove_ignore(app: &AppHandle, ignore_id: i64) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let removed = conn_guard.0.execute("DELETE FROM ignored_paths WHERE id = ?", [ignore_id])?;
+    if removed == 0 {
+        return Err(anyhow::anyhow!("Ignore entry {} not found", ignore_id));
+    }
+    Ok(())
+}
+
+pub fn get_ignores<R: Runtime>(app: &AppHandle<R>) -> Result<Vec<IgnoreEntry>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT id, pattern, created_at FROM ignored_paths ORDER BY pattern ASC"
+    )?;
+    let entries = stmt
+        .query_map([], |row| {
+            Ok(IgnoreEntry {
+                id: row.get(0)?,
+                pattern: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(entries)
+}
+
+// Pending action operations
+// A file waiting for the active hours to be organized
+pub struct PendingAction {
+    pub id: i64,
+    pub path: String,
+    pub watch_root: String,
+}
+
+// Queue a file to be organized once the active hours start. A file already
+// queued keeps its place
+pub fn add_pending_action(app: &AppHandle, path: &str, watch_root: &str) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "INSERT OR IGNORE INTO pending_actions (path, watch_root, created_at) VALUES (?, ?, ?)",
+        params![path, watch_root, utils::format_timestamp(&Utc::now())],
+    )?;
+
+    Ok(())
+}
+
+// Queued files, oldest first
+pub fn get_pending_actions(app: &AppHandle) -> Result<Vec<PendingAction>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT id, path, watch_root FROM pending_actions ORDER BY id ASC"
+    )?;
+    let actions = stmt
+        .query_map([], |row| {
+            Ok(PendingAction {
+                id: row.get(0)?,
+                path: row.get(1)?,
+                watch_root: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(actions)
+}
+
+pub fn count_pending_actions(app: &AppHandle) -> Result<usize> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let count: i64 = conn_guard.0.query_row("SELECT COUNT(*) FROM pending_actions", [], |row| row.get(0))?;
+    Ok(count as usize)
+}
+
+// Forget the files queued by one watched folder or file
+pub fn remove_pending_actions_from(app: &AppHandle, watch_root: &str) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute("DELETE FROM pending_actions WHERE watch_root = ?", [watch_root])?;
+    Ok(())
+}
+
+pub fn remove_pending_actions(app: &AppHandle, ids: &[i64]) -> Result<()> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    let tx = conn_guard.0.transaction()?;
+
+    for id in ids {
+        tx.execute("DELETE FROM pending_actions WHERE id = ?", [id])?;
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+// Settings operations
+pub fn get_setting<R: Runtime>(app: &AppHandle<R>, key: &str) -> Result<Option<String>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let value = conn_guard.0.query_row(
+        "SELECT value FROM settings WHERE key = ?",
+        [key],
+        |row| row.get::<_, String>(0),
+    ).optional()?;
+
+    Ok(value)
+}
+
+pub fn set_setting(app: &AppHandle, key: &str, value: &str) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)",
+        params![key, value],
+    )?;
+
+    Ok(())
+}
+
+// Check if a boolean setting is turned on
+pub fn is_setting_enabled<R: Runtime>(app: &AppHandle<R>, key: &str) -> Result<bool> {
+    Ok(get_setting(app, key)?.as_deref() == Some("true"))
+}
+
+// File operations
+pub fn add_file(app: &AppHandle, file: &NewFile) -> Result<i64> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    
+    insert_file(&conn_guard.0, file)
+}
+
+// Index several files in one transaction, returning each file's ID or why it
+// couldn't be indexed, in order. Much faster than add_file per file for a
+// burst of files: the statement is prepared once and there's a single
+// commit. Each file gets its own savepoint, so one bad file doesn't lose the
+// rest
+pub fn add_files_batch(app: &AppHandle, files: &[NewFile]) -> Result<Vec<Result<i64>>> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+
+    add_files_batch_in(&mut conn_guard.0, files)
+}
+
+fn add_files_batch_in(conn: &mut Connection, files: &[NewFile]) -> Result<Vec<Result<i64>>> {
+    let mut tx = conn.transaction()?;
+
+    let mut results = Vec::with_capacity(files.len());
+    for file in files {
+        let savepoint = tx.savepoint()?;
+        let result = insert_file(&savepoint, file)
+            .with_context(|| format!("Failed to index {}", file.path.display()));
+        if result.is_ok() {
+            savepoint.commit()?;
+        }
+        results.push(result);
+    }
+
+    tx.commit()?;
+    Ok(results)
+}
+
+// Paths are stored as text, so one that isn't valid UTF-8 can't be indexed
+// or journaled: a lossy copy would name a file that doesn't exist
+fn stored_path(path: &Path) -> Result<String> {
+    utils::normalize_path(path)
+        .into_os_string()
+        .into_string()
+        .map_err(|_| anyhow::anyhow!("{} has a name that isn't valid UTF-8", path.display()))
+}
+
+fn insert_file(conn: &Connection, file: &NewFile) -> Result<i64> {
+    let path_str = stored_path(&file.path)?;
+    
+    // Update a file that is already indexed in place rather than replacing
+    // the row, so it keeps its ID and everything attached to it (tags,
+    // metadata, history)
+    let file_id = conn.prepare_cached(
+        "INSERT INTO files (path, name, extension, size, created_at, created_at_approximate, modified_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(path) DO UPDATE SET
+            name = excluded.name,
+            extension = excluded.extension,
+            size = excluded.size,
+            created_at = excluded.created_at,
+            created_at_approximate = excluded.created_at_approximate,
+            modified_at = excluded.modified_at
+         RETURNING id",
+    )?.query_row(
+        params![
+            path_str,
+            file.name,
+            file.extension,
+            file.size,
+            file.created_at,
+            file.created_at_approximate,
+            file.modified_at
+        ],
+        |row| row.get::<_, i64>(0),
+    )?;
+
+    insert_file_event(conn, Some(file_id), EVENT_INDEXED, None, Some(&path_str), None)?;
+
+    Ok(file_id)
+}
+
+// A file to index, with its times as stored by utils::format_timestamp
+pub struct NewFile {
+    pub path: PathBuf,
+    pub name: String,
+    pub extension: String,
+    pub size: i64,
+    pub created_at: String,
+    // created_at is an estimate; see utils::file_created
+    pub created_at_approximate: bool,
+    pub modified_at: String,
+}
+
+// Batches at least this large are followed by PRAGMA optimize
+const OPTIMIZE_BATCH_SIZE: usize = 100;
+
+// A file the organizer just moved, to be indexed by index_moved_files
+pub struct MovedFile {
+    // The file at its new location
+    pub file: NewFile,
+    pub from: PathBuf,
+    // Why it was moved, recorded in its history (e.g. "rule: Documents")
+    pub detail: String,
+    pub tag_ids: Vec<i64>,
+    // Category tags, left off files with an auto-tag override
+    pub auto_tag_ids: Vec<i64>,
+    // The move's journal entry, completed along with the index
+    pub journal_id: Option<i64>,
+}
+
+// A file indexed by index_moved_files
+pub struct IndexedMove {
+    pub file_id: i64,
+    // Its category tags were left off for its auto-tag override
+    pub auto_tags_suppressed: bool,
+}
+
+// Index moved files, record the moves and apply their tags in a single
+// transaction, returning each indexed file or why it couldn't be indexed, in
+// order. Each file gets its own savepoint, so one that fails (e.g. a name
+// that isn't valid UTF-8) is left out without losing the rest of the batch.
+// Its journal entry is completed all the same: the file did move
+pub fn index_moved_files<R: Runtime>(app: &AppHandle<R>, files: &[MovedFile]) -> Result<Vec<Result<IndexedMove>>> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    let results = index_moved_files_in(&mut conn_guard.0, files)?;
+
+    // Keep planner statistics current after large batches; optimize only
+    // re-analyzes tables that changed enough to need it
+    if files.len() >= OPTIMIZE_BATCH_SIZE {
+        if let Err(e) = conn_guard.0.execute_batch("PRAGMA optimize") {
+            tracing::warn!(error = %e, "Failed to optimize database");
+        }
+    }
+
+    Ok(results)
+}
+
+fn index_moved_files_in(conn: &mut Connection, files: &[MovedFile]) -> Result<Vec<Result<IndexedMove>>> {
+    let mut tx = conn.transaction()?;
+
+    let mut results = Vec::with_capacity(files.len());
+    for moved in files {
+        let savepoint = tx.savepoint()?;
+        let result = index_moved_file(&savepoint, moved)
+            .with_context(|| format!("Failed to index {}", moved.file.path.display()));
+        match result {
+            Ok(_) => savepoint.commit()?,
+            Err(_) => {
+                drop(savepoint);
+                if let Some(journal_id) = moved.journal_id {
+                    finish_journaled_move_in(&tx, journal_id, MOVE_COMPLETED)?;
+                }
+            }
+        }
+        results.push(result);
+    }
+
+    tx.commit()?;
+    Ok(results)
+}
+
+fn index_moved_file(conn: &Connection, moved: &MovedFile) -> Result<IndexedMove> {
+    let file = &moved.file;
+    // A file indexed where it was (e.g. one whose move waited for the active
+    // hours) keeps its row and everything attached to it
+    conn.prepare_cached("UPDATE OR IGNORE files SET path = ? WHERE path = ?")?.execute(params![
+        stored_path(&file.path)?,
+        utils::normalize_path(&moved.from).to_string_lossy(),
+    ])?;
+    let file_id = insert_file(conn, file)?;
+    insert_file_event(
+        conn,
+        Some(file_id),
+        EVENT_MOVED,
+        Some(&moved.from.to_string_lossy()),
+        Some(&utils::normalize_path(&file.path).to_string_lossy()),
+        Some(&moved.detail),
+    )?;
+    for tag_id in &moved.tag_ids {
+        insert_file_tag(conn, file_id, *tag_id)?;
+    }
+    let auto_tags_suppressed = !moved.auto_tag_ids.is_empty() && has_auto_tag_override_in(conn, file_id)?;
+    if !auto_tags_suppressed {
+        for tag_id in &moved.auto_tag_ids {
+            insert_auto_tag(conn, file_id, *tag_id)?;
+        }
+    }
+    if let Some(journal_id) = moved.journal_id {
+        finish_journaled_move_in(conn, journal_id, MOVE_COMPLETED)?;
+    }
+
+    Ok(IndexedMove { file_id, auto_tags_suppressed })
+}
+
+// States of a move_journal entry. A move is started once its destination is
+// claimed, and copying once it's being copied to another drive instead of
+// renamed, until it's completed or failed
+pub const MOVE_STARTED: &str = "started";
+pub const MOVE_COPYING: &str = "copying";
+pub const MOVE_COMPLETED: &str = "completed";
+pub const MOVE_FAILED: &str = "failed";
+
+// A move that was started but never completed or failed
+pub struct JournaledMove {
+    pub id: i64,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub detail: Option<String>,
+    // Whether it had got to copying
+    pub copying: bool,
+}
+
+// Record that a file is about to be moved to destination, returning the
+// journal entry. With an existing entry (a retried move), only its
+// destination is updated
+pub fn journal_move<R: Runtime>(
+    app: &AppHandle<R>,
+    journal_id: Option<i64>,
+    source: &Path,
+    destination: &Path,
+    detail: &str,
+) -> Result<i64> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    let destination = stored_path(destination)?;
+
+    if let Some(journal_id) = journal_id {
+        conn_guard.0.execute(
+            "UPDATE move_journal SET destination = ?, state = ? WHERE id = ?",
+            params![destination, MOVE_STARTED, journal_id],
+        )?;
+        return Ok(journal_id);
+    }
+
+    conn_guard.0.execute(
+        "INSERT INTO move_journal (source, destination, state, detail, started_at) VALUES (?, ?, ?, ?, ?)",
+        params![
+            stored_path(source)?,
+            destination,
+            MOVE_STARTED,
+            detail,
+            utils::format_timestamp(&Utc::now())
+        ],
+    )?;
+    Ok(conn_guard.0.last_insert_rowid())
+}
+
+// Record that a journaled move is copying to another drive rather than renaming
+pub fn journal_move_copying<R: Runtime>(app: &AppHandle<R>, journal_id: i64) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    conn_guard.0.execute(
+        "UPDATE move_journal SET state = ? WHERE id = ?",
+        params![MOVE_COPYING, journal_id],
+    )?;
+    Ok(())
+}
+
+// Mark a journaled move as completed or failed
+pub fn finish_journaled_move<R: Runtime>(app: &AppHandle<R>, journal_id: i64, state: &str) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    finish_journaled_move_in(&conn_guard.0, journal_id, state)
+}
+
+fn finish_journaled_move_in(conn: &Connection, journal_id: i64, state: &str) -> Result<()> {
+    conn.prepare_cached("UPDATE move_journal SET state = ?, finished_at = ? WHERE id = ?")?
+        .execute(params![state, utils::format_timestamp(&Utc::now()), journal_id])?;
+    Ok(())
+}
+
+// Moves left started by a crash or by a failure to index them, oldest first
+pub fn get_unfinished_moves(app: &AppHandle) -> Result<Vec<JournaledMove>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT id, source, destination, detail, state FROM move_journal WHERE state IN (?, ?) ORDER BY id ASC"
+    )?;
+    let moves = stmt
+        .query_map([MOVE_STARTED, MOVE_COPYING], |row| {
+            Ok(JournaledMove {
+                id: row.get(0)?,
+                source: PathBuf::from(row.get::<_, String>(1)?),
+                destination: PathBuf::from(row.get::<_, String>(2)?),
+                detail: row.get(3)?,
+                copying: row.get::<_, String>(4)? == MOVE_COPYING,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(moves)
+}
+
+// Delete finished journal entries older than before; the history keeps what
+// was moved. Returns how many were deleted
+pub fn prune_move_journal(app: &AppHandle, before: &str) -> Result<usize> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    Ok(conn_guard.0.execute(
+        "DELETE FROM move_journal WHERE state IN (?, ?) AND finished_at < ?",
+        params![MOVE_COMPLETED, MOVE_FAILED, before],
+    )?)
+}
+
+// An indexed file as sorted into the unorganized files report
+pub struct ReportFile {
+    // Without its tags
+    pub file: FileInfo,
+    pub tagged: bool,
+    // Moved at least once
+    pub moved: bool,
+}
+
+// Get every indexed file for the unorganized files report, ordered by name
+pub fn get_report_files(app: &AppHandle) -> Result<Vec<ReportFile>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(&format!(
+        "SELECT {},
+            EXISTS (SELECT 1 FROM file_tags ft WHERE ft.file_id = f.id),
+            EXISTS (SELECT 1 FROM file_history h WHERE h.file_id = f.id AND h.event_type = ?)
+         FROM files f ORDER BY f.name ASC, f.id ASC",
+        FILE_INFO_COLUMNS
+    ))?;
+    let files = stmt
+        .query_map([EVENT_MOVED], |row| {
+            Ok(ReportFile {
+                file: map_file_info(row)?,
+                tagged: row.get(12)?,
+                moved: row.get(13)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(files)
+}
+
+// Get a page of the given files with their tags, ordered by name
+pub fn get_files_by_ids(app: &AppHandle, ids: &[i64], limit: Option<i64>, offset: Option<i64>) -> Result<Vec<FileInfo>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    get_files_by_ids_in(&conn_guard.0, ids, limit, offset)
+}
+
+fn get_files_by_ids_in(conn: &Connection, ids: &[i64], limit: Option<i64>, offset: Option<i64>) -> Result<Vec<FileInfo>> {
+    // Passed as one JSON array rather than a parameter per ID, which SQLite limits
+    let ids = format!("[{}]", ids.iter().map(i64::to_string).collect::<Vec<_>>().join(","));
+    let (limit, offset) = page_bounds(limit, offset);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM files f
+         WHERE f.id IN (SELECT value FROM json_each(?))
+         ORDER BY f.name ASC, f.id ASC
+         LIMIT ? OFFSET ?",
+        FILE_INFO_COLUMNS
+    ))?;
+    let mut files = stmt
+        .query_map(
+            params![ids, limit, offset],
+            map_file_info,
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    fill_file_tags(conn, &mut files)?;
+
+    Ok(files)
+}
+
+// Get the ID and path of every indexed file
+pub fn get_all_file_paths(app: &AppHandle) -> Result<Vec<(i64, String)>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare("SELECT id, path FROM files")?;
+    let files = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(files)
+}
+
+// Result of remove_orphans
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanCleanup {
+    pub files_removed: usize,
+    pub tag_links_removed: usize,
+}
+
+// Remove the rows of files that no longer exist, then any tag links left
+// pointing at a missing file or tag. A row is only removed while it still has
+// the path found missing, so a file re-indexed or moved in the meantime stays.
+// Foreign keys aren't enforced, so the cascades are done here: tags and
+// metadata go with the file, while its history is kept with the file ID cleared
+pub fn remove_orphans(app: &AppHandle, missing: &[(i64, String)]) -> Result<OrphanCleanup> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    remove_orphans_in(&mut conn_guard.0, missing)
+}
+
+fn remove_orphans_in(conn: &mut Connection, missing: &[(i64, String)]) -> Result<OrphanCleanup> {
+    let tx = conn.transaction()?;
+
+    let mut cleanup = OrphanCleanup { files_removed: 0, tag_links_removed: 0 };
+    for (file_id, path) in missing {
+        let unchanged: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM files WHERE id = ? AND path = ?)",
+            params![file_id, path],
+            |row| row.get(0),
+        )?;
+        if !unchanged {
+            continue;
+        }
+        cleanup.tag_links_removed += delete_file_row(&tx, *file_id)?;
+        cleanup.files_removed += 1;
+    }
+    cleanup.tag_links_removed += tx.execute(
+        "DELETE FROM file_tags
+         WHERE file_id NOT IN (SELECT id FROM files) OR tag_id NOT IN (SELECT id FROM tags)",
+        [],
+    )?;
+
+    tx.commit()?;
+    Ok(cleanup)
+}
+
+// Remove a file the app itself deleted or trashed from the index, with its
+// tags and metadata; its history is kept with the file ID cleared
+pub fn remove_file(app: &AppHandle, file_id: i64) -> Result<()> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    remove_file_in(&mut conn_guard.0, file_id)
+}
+
+fn remove_file_in(conn: &mut Connection, file_id: i64) -> Result<()> {
+    let tx = conn.transaction()?;
+    delete_file_row(&tx, file_id)?;
+    tx.commit()?;
+    Ok(())
+}
+
+// Delete a file's row and cascade by hand, as foreign keys aren't enforced.
+// Returns the number of tag links removed
+fn delete_file_row(conn: &Connection, file_id: i64) -> Result<usize> {
+    let tag_links = conn.execute("DELETE FROM file_tags WHERE file_id = ?", [file_id])?;
+    conn.execute("DELETE FROM file_metadata WHERE file_id = ?", [file_id])?;
+    conn.execute("UPDATE file_history SET file_id = NULL WHERE file_id = ?", [file_id])?;
+    conn.execute("DELETE FROM files WHERE id = ?", [file_id])?;
+    Ok(tag_links)
+}
+
+// Get the ID and name of every indexed file
+pub fn get_all_file_names(app: &AppHandle) -> Result<Vec<(i64, String)>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare("SELECT id, name FROM files")?;
+    let file_iter = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let mut files = vec![];
+    for file in file_iter {
+        files.push(file?);
+    }
+
+    Ok(files)
+}
+
+pub fn set_file_source<R: Runtime>(
+    app: &AppHandle<R>,
+    file_id: i64,
+    source_url: Option<&str>,
+    source_domain: Option<&str>,
+) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "UPDATE files SET source_url = ?, source_domain = ? WHERE id = ?",
+        params![source_url, source_domain, file_id],
+    )?;
+
+    Ok(())
+}
+
+pub fn get_file_id_by_path<R: Runtime>(app: &AppHandle<R>, path: &Path) -> Result<Option<i64>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let file_id = conn_guard.0.query_row(
+        "SELECT id FROM files WHERE path = ?",
+        [utils::normalize_path(path).to_string_lossy().to_string()],
+        |row| row.get::<_, i64>(0),
+    ).optional()?;
+
+    Ok(file_id)
+}
+
+pub fn get_file_path(app: &AppHandle, file_id: i64) -> Result<Option<PathBuf>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let path = conn_guard.0.query_row(
+        "SELECT path FROM files WHERE id = ?",
+        [file_id],
+        |row| row.get::<_, String>(0),
+    ).optional()?;
+
+    Ok(path.map(PathBuf::from))
+}
+
+// Get one indexed file with its tags and metadata
+pub fn get_file_info(app: &AppHandle, file_id: i64) -> Result<Option<FileInfo>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let file = conn_guard.0.query_row(
+        &format!("SELECT {} FROM files f WHERE f.id = ?", FILE_INFO_COLUMNS),
+        [file_id],
+        map_file_info,
+    ).optional()?;
+    let Some(file) = file else { return Ok(None) };
+
+    let mut files = [file];
+    fill_file_tags(&conn_guard.0, &mut files)?;
+    fill_file_metadata(&conn_guard.0, &mut files)?;
+    let [file] = files;
+
+    Ok(Some(file))
+}
+
+// Get the most recent history event of one type for a file
+pub fn get_last_file_event(app: &AppHandle, file_id: i64, event_type: &str) -> Result<Option<FileHistoryEvent>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let event = conn_guard.0.query_row(
+        "SELECT id, file_id, event_type, from_path, to_path, detail, created_at
+         FROM file_history WHERE file_id = ? AND event_type = ?
+         ORDER BY created_at DESC, id DESC LIMIT 1",
+        params![file_id, event_type],
+        |row| {
+            Ok(FileHistoryEvent {
+                id: row.get(0)?,
+                file_id: row.get(1)?,
+                event_type: row.get(2)?,
+                from_path: row.get(3)?,
+                to_path: row.get(4)?,
+                detail: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        },
+    ).optional()?;
+
+    Ok(event)
+}
+
+// Where the file last moved away from a path went, if one ever did
+pub fn get_moved_destination<R: Runtime>(app: &AppHandle<R>, from_path: &Path) -> Result<Option<PathBuf>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let to_path = conn_guard.0.query_row(
+        "SELECT to_path FROM file_history
+         WHERE from_path = ? AND event_type = ? AND to_path IS NOT NULL
+         ORDER BY id DESC LIMIT 1",
+        params![from_path.to_string_lossy(), EVENT_MOVED],
+        |row| row.get::<_, String>(0),
+    ).optional()?;
+
+    Ok(to_path.map(PathBuf::from))
+}
+
+// Point an indexed file at its new path after it was renamed on disk
+pub fn update_file_path(app: &AppHandle, file_id: i64, new_path: &Path) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    update_file_path_in(&conn_guard.0, file_id, new_path)
+}
+
+fn update_file_path_in(conn: &Connection, file_id: i64, new_path: &Path) -> Result<()> {
+    conn.execute(
+        "UPDATE files SET path = ?, name = ?, extension = ? WHERE id = ?",
+        params![
+            stored_path(new_path)?,
+            utils::get_file_name(new_path).unwrap_or_default(),
+            utils::get_file_extension(new_path).unwrap_or_default(),
+            file_id
+        ],
+    )?;
+
+    Ok(())
+}
+
+pub fn add_tag_to_file<R: Runtime>(app: &AppHandle<R>, file_id: i64, tag_id: i64) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    
+    insert_file_tag(&conn_guard.0, file_id, tag_id)
+}
+
+// Tag a file with several tags in one transaction
+pub fn add_tags_to_file(app: &AppHandle, file_id: i64, tag_ids: &[i64]) -> Result<()> {
+    if tag_ids.is_empty() {
+        return Ok(());
+    }
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    let tx = conn_guard.0.transaction()?;
+
+    for tag_id in tag_ids {
+        insert_file_tag(&tx, file_id, *tag_id)?;
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+// Stop or resume auto-tagging a file. Stopping it also removes the category
+// tags that were applied automatically, returning how many; ones the user
+// added stay. They aren't applied again when the file is moved or indexed
+// until the override is lifted
+pub fn set_auto_tag_override(app: &AppHandle, file_id: i64, suppressed: bool) -> Result<usize> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    set_auto_tag_override_in(&mut conn_guard.0, file_id, suppressed)
+}
+
+fn set_auto_tag_override_in(conn: &mut Connection, file_id: i64, suppressed: bool) -> Result<usize> {
+    let tx = conn.transaction()?;
+
+    let file_exists: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM files WHERE id = ?)",
+        [file_id],
+        |row| row.get(0),
+    )?;
+    if !file_exists {
+        return Err(anyhow::anyhow!("File {} not found", file_id));
+    }
+
+    let removed = if suppressed {
+        tx.execute(
+            "INSERT OR IGNORE INTO auto_tag_overrides (file_id, created_at) VALUES (?, ?)",
+            params![file_id, utils::format_timestamp(&Utc::now())],
+        )?;
+        tx.execute(
+            "DELETE FROM file_tags WHERE file_id = ? AND auto_applied = 1
+                 AND tag_id IN (SELECT tag_id FROM categories WHERE tag_id IS NOT NULL)",
+            [file_id],
+        )?
+    } else {
+        tx.execute("DELETE FROM auto_tag_overrides WHERE file_id = ?", [file_id])?;
+        0
+    };
+    tx.commit()?;
+
+    Ok(removed)
+}
+
+// Whether the user turned off auto-tagging for a file
+fn has_auto_tag_override_in(conn: &Connection, file_id: i64) -> Result<bool> {
+    Ok(conn
+        .prepare_cached("SELECT EXISTS (SELECT 1 FROM auto_tag_overrides WHERE file_id = ?)")?
+        .query_row([file_id], |row| row.get(0))?)
+}
+
+// Move a file to another category: in one transaction, remove every category
+// tag it has and add the new category's, so category tags stay exclusive.
+// Other tags are left alone
+pub fn recategorize_file(app: &AppHandle, file_id: i64, category_name: &str) -> Result<()> {
+    let conn = get_connection(app)?;
+    let mut conn_guard = conn.lock().unwrap();
+    let tx = conn_guard.0.transaction()?;
+
+    let file_exists: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM files WHERE id = ?)",
+        [file_id],
+        |row| row.get(0),
+    )?;
+    if !file_exists {
+        return Err(anyhow::anyhow!("File {} not found", file_id));
+    }
+
+    let (name, tag_id) = tx
+        .query_row(
+            "SELECT name, tag_id FROM categories WHERE name = ? COLLATE NOCASE",
+            [category_name.trim()],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?)),
+        )
+        .optional()?
+        .ok_or_else(|| anyhow::anyhow!("Unknown category: {}", category_name))?;
+    let tag_id = tag_id.ok_or_else(|| anyhow::anyhow!("Category {} has no tag", name))?;
+
+    tx.execute(
+        "DELETE FROM file_tags WHERE file_id = ?1 AND tag_id != ?2
+             AND tag_id IN (SELECT tag_id FROM categories WHERE tag_id IS NOT NULL)",
+        params![file_id, tag_id],
+    )?;
+    insert_file_tag(&tx, file_id, tag_id)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+// Tag a file on the user's behalf. A tag the file already had automatically
+// becomes the user's own, so an auto-tag override leaves it
+fn insert_file_tag(conn: &Connection, file_id: i64, tag_id: i64) -> Result<()> {
+    if !link_tag(conn, file_id, tag_id, false)? {
+        conn.prepare_cached("UPDATE file_tags SET auto_applied = 0 WHERE file_id = ? AND tag_id = ?")?
+            .execute(params![file_id, tag_id])?;
+    }
+    Ok(())
+}
+
+// Apply an automatic tag, e.g. a moved file's category tag
+fn insert_auto_tag(conn: &Connection, file_id: i64, tag_id: i64) -> Result<()> {
+    link_tag(conn, file_id, tag_id, true)?;
+    Ok(())
+}
+
+// Link a tag to a file and record it in the file's history, unless the file
+// already had it. True when the link is new
+fn link_tag(conn: &Connection, file_id: i64, tag_id: i64, auto_applied: bool) -> Result<bool> {
+    let inserted = conn
+        .prepare_cached("INSERT OR IGNORE INTO file_tags (file_id, tag_id, auto_applied) VALUES (?, ?, ?)")?
+        .execute(params![file_id, tag_id, auto_applied])?;
+
+    if inserted > 0 {
+        let tag_name = conn
+            .prepare_cached("SELECT name FROM tags WHERE id = ?")?
+            .query_row([tag_id], |row| row.get::<_, String>(0))
+            .optional()?;
+        insert_file_event(conn, Some(file_id), EVENT_TAGGED, None, None, tag_name.as_deref())?;
+    }
+
+    Ok(inserted > 0)
+}
+
+// File history event types
+pub const EVENT_INDEXED: &str = "indexed";
+pub const EVENT_MOVED: &str = "moved";
+pub const EVENT_TAGGED: &str = "tagged";
+pub const EVENT_RENAMED: &str = "renamed";
+pub const EVENT_BACKED_UP: &str = "backed_up";
+pub const EVENT_ARCHIVED: &str = "archived";
+pub const EVENT_TRASHED: &str = "trashed";
+pub const EVENT_CLEANUP_REPORTED: &str = "cleanup_reported";
+// A file the organizer left in place, e.g. because another program had it open
+pub const EVENT_SKIPPED: &str = "skipped";
+// The active profile changed; not tied to a file, the detail is the profile name
+pub const EVENT_PROFILE_SWITCHED: &str = "profile_switched";
+// A hook run after a file or folder was organized failed; the detail says which and why
+pub const EVENT_HOOK_FAILED: &str = "hook_failed";
+
+fn insert_file_event(
+    conn: &Connection,
+    file_id: Option<i64>,
+    event_type: &str,
+    from_path: Option<&str>,
+    to_path: Option<&str>,
+    detail: Option<&str>,
+) -> Result<()> {
+    // Cached, as bulk indexing runs this once or twice per file
+    conn.prepare_cached(
+        "INSERT INTO file_history (file_id, event_type, from_path, to_path, detail, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )?.execute(
+        params![
+            file_id,
+            event_type,
+            from_path,
+            to_path,
+            detail,
+            utils::format_timestamp(&Utc::now())
+        ],
+    )?;
+
+    Ok(())
+}
+
+// Record something that happened to a file
+pub fn record_file_event<R: Runtime>(
+    app: &AppHandle<R>,
+    file_id: Option<i64>,
+    event_type: &str,
+    from_path: Option<&str>,
+    to_path: Option<&str>,
+    detail: Option<&str>,
+) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    insert_file_event(&conn_guard.0, file_id, event_type, from_path, to_path, detail)
+}
+
+// Count the history events recorded since a time, as utils::format_timestamp formats it
+pub fn count_file_events_since<R: Runtime>(app: &AppHandle<R>, since: &str) -> Result<usize> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let count = conn_guard.0.query_row(
+        "SELECT COUNT(*) FROM file_history WHERE created_at >= ?",
+        [since],
+        |row| row.get::<_, i64>(0),
+    )?;
+
+    Ok(count as usize)
+}
+
+// When each bucket last received a backed up file, most recent first
+pub fn get_last_backups(app: &AppHandle) -> Result<Vec<(String, String)>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT detail, MAX(created_at) AS last FROM file_history
+         WHERE event_type = ? AND detail IS NOT NULL
+         GROUP BY detail ORDER BY last DESC"
+    )?;
+    let backups = stmt
+        .query_map([EVENT_BACKED_UP], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(backups)
+}
+
+// Get the chronological history of a file, following it back across moves and renames
+pub fn get_file_history(app: &AppHandle, file_id: i64) -> Result<Vec<FileHistoryEvent>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT id, file_id, event_type, from_path, to_path, detail, created_at
+         FROM file_history WHERE file_id = ?"
+    )?;
+    let mut previous_stmt = conn_guard.0.prepare("SELECT id FROM files WHERE path = ?")?;
+
+    let mut events = vec![];
+    let mut visited = vec![];
+    let mut pending = vec![file_id];
+
+    while let Some(id) = pending.pop() {
+        if visited.contains(&id) {
+            continue;
+        }
+        visited.push(id);
+
+        let event_iter = stmt.query_map([id], |row| {
+            Ok(FileHistoryEvent {
+                id: row.get(0)?,
+                file_id: row.get(1)?,
+                event_type: row.get(2)?,
+                from_path: row.get(3)?,
+                to_path: row.get(4)?,
+                detail: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })?;
+
+        for event in event_iter {
+            let event = event?;
+
+            // If the file was indexed at its old location, pull in that history too
+            if event.event_type == EVENT_MOVED || event.event_type == EVENT_RENAMED {
+                if let Some(from_path) = &event.from_path {
+                    if let Some(previous_id) = previous_stmt
+                        .query_row([from_path], |row| row.get::<_, i64>(0))
+                        .optional()?
+                    {
+                        pending.push(previous_id);
+                    }
+                }
+            }
+
+            events.push(event);
+        }
+    }
+
+    events.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+    Ok(events)
+}
+
+// Highest star rating a file can have
+pub const MAX_RATING: i64 = 5;
+
+// Set one metadata value on a file, replacing any previous value for the key
+pub fn set_file_metadata(app: &AppHandle, file_id: i64, key: &str, value: &str) -> Result<()> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(anyhow::anyhow!("Metadata key cannot be empty"));
+    }
+
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let exists = conn_guard.0.query_row(
+        "SELECT 1 FROM files WHERE id = ?",
+        [file_id],
+        |_| Ok(()),
+    ).optional()?;
+    if exists.is_none() {
+        return Err(anyhow::anyhow!("File not found"));
+    }
+
+    conn_guard.0.execute(
+        "INSERT OR REPLACE INTO file_metadata (file_id, key, value) VALUES (?, ?, ?)",
+        params![file_id, key, value],
+    )?;
+
+    Ok(())
+}
+
+// Get all metadata of a file, ordered by key
+pub fn get_file_metadata(app: &AppHandle, file_id: i64) -> Result<BTreeMap<String, String>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare("SELECT key, value FROM file_metadata WHERE file_id = ?")?;
+    let metadata = stmt
+        .query_map([file_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<BTreeMap<_, _>>>()?;
+
+    Ok(metadata)
+}
+
+// Remove one metadata key from a file
+pub fn delete_file_metadata(app: &AppHandle, file_id: i64, key: &str) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "DELETE FROM file_metadata WHERE file_id = ? AND key = ?",
+        params![file_id, key.trim()],
+    )?;
+
+    Ok(())
+}
+
+pub fn set_file_note(app: &AppHandle, file_id: i64, note: Option<String>) -> Result<()> {
+    // Treat a blank note as clearing it
+    let note = note.filter(|n| !n.trim().is_empty());
+
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let updated = conn_guard.0.execute(
+        "UPDATE files SET notes = ? WHERE id = ?",
+        params![note, file_id],
+    )?;
+    if updated == 0 {
+        return Err(anyhow::anyhow!("File not found"));
+    }
+
+    Ok(())
+}
+
+pub fn set_file_rating(app: &AppHandle, file_id: i64, rating: Option<i64>) -> Result<()> {
+    if let Some(r) = rating {
+        if !(0..=MAX_RATING).contains(&r) {
+            return Err(anyhow::anyhow!("Rating must be between 0 and {}", MAX_RATING));
+        }
+    }
+
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let updated = conn_guard.0.execute(
+        "UPDATE files SET rating = ? WHERE id = ?",
+        params![rating, file_id],
+    )?;
+    if updated == 0 {
+        return Err(anyhow::anyhow!("File not found"));
+    }
+
+    Ok(())
+}
+
+// An indexed image together with its cached perceptual hash
+pub struct ImageHashEntry {
+    pub file_id: i64,
+    pub path: String,
+    pub name: String,
+    pub size: i64,
+    pub phash: Option<u64>,
+    pub phash_modified_at: Option<String>,
+}
+
+pub fn get_image_hash_entries(app: &AppHandle, extensions: &[&str]) -> Result<Vec<ImageHashEntry>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let placeholders: Vec<String> = (0..extensions.len()).map(|_| "?".to_string()).collect();
+    let mut stmt = conn_guard.0.prepare(&format!(
+        "SELECT id, path, name, size, phash, phash_modified_at FROM files WHERE extension IN ({})",
+        placeholders.join(",")
+    ))?;
+    let entry_iter = stmt.query_map(rusqlite::params_from_iter(extensions.iter()), |row| {
+        Ok(ImageHashEntry {
+            file_id: row.get(0)?,
+            path: row.get(1)?,
+            name: row.get(2)?,
+            size: row.get(3)?,
+            // SQLite integers are signed, the hash is stored bit-for-bit
+            phash: row.get::<_, Option<i64>>(4)?.map(|h| h as u64),
+            phash_modified_at: row.get(5)?,
+        })
+    })?;
+
+    let mut entries = vec![];
+    for entry in entry_iter {
+        entries.push(entry?);
+    }
+
+    Ok(entries)
+}
+
+pub fn set_file_phash(app: &AppHandle, file_id: i64, phash: u64, modified_at: &str) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "UPDATE files SET phash = ?, phash_modified_at = ? WHERE id = ?",
+        params![phash as i64, modified_at, file_id],
+    )?;
+
+    Ok(())
+}
+
+// An indexed file that may have duplicates, with its cached content hash
+pub struct ContentHashEntry {
+    pub file_id: i64,
+    pub path: String,
+    pub name: String,
+    pub size: i64,
+    pub content_hash: Option<String>,
+    pub content_hash_modified_at: Option<String>,
+}
+
+// Indexed files sharing their size with another one, the only files that can
+// have an exact duplicate. Files of the same size are next to each other
+pub fn get_content_hash_entries(app: &AppHandle) -> Result<Vec<ContentHashEntry>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT id, path, name, size, content_hash, content_hash_modified_at FROM files
+         WHERE size > 0 AND size IN (SELECT size FROM files GROUP BY size HAVING COUNT(*) > 1)
+         ORDER BY size DESC, id"
+    )?;
+    let entries = stmt
+        .query_map([], |row| {
+            Ok(ContentHashEntry {
+                file_id: row.get(0)?,
+                path: row.get(1)?,
+                name: row.get(2)?,
+                size: row.get(3)?,
+                content_hash: row.get(4)?,
+                content_hash_modified_at: row.get(5)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(entries)
+}
+
+pub fn set_file_content_hash(app: &AppHandle, file_id: i64, content_hash: &str, modified_at: &str) -> Result<()> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+
+    conn_guard.0.execute(
+        "UPDATE files SET content_hash = ?, content_hash_modified_at = ? WHERE id = ?",
+        params![content_hash, modified_at, file_id],
+    )?;
+
+    Ok(())
+}
+
+pub fn search_files<R: Runtime>(app: &AppHandle<R>, filter: &SearchFilter) -> Result<Vec<FileInfo>> {
     let conn = get_connection(app)?;
     let conn_guard = conn.lock().unwrap();
+    search_files_in(&conn_guard.0, filter)
+}
+
+fn search_files_in(conn: &Connection, filter: &SearchFilter) -> Result<Vec<FileInfo>> {
+    let (sql, params) = search_query(filter)?;
+    
+    // Prepare and execute the query
+    let mut stmt = conn.prepare(&sql)?;
+    let file_iter = stmt.query_map(rusqlite::params_from_iter(params.iter()), map_file_info)?;
     
+    // Collect files
+    let mut files = vec![];
+    for file_result in file_iter {
+        files.push(file_result?);
+    }
+    
+    fill_file_tags(conn, &mut files)?;
+    if filter.include_metadata.unwrap_or(false) {
+        fill_file_metadata(conn, &mut files)?;
+    }
+    
+    Ok(files)
+}
+
+// Build the SQL of a search and its parameters
+fn search_query(filter: &SearchFilter) -> Result<(String, Vec<String>)> {
     // Build the query
-    let mut sql = String::from(
-        "SELECT DISTINCT f.id, f.path, f.name, f.extension, f.size, f.created_at, f.modified_at 
-         FROM files f"
-    );
+    let mut sql = format!("SELECT DISTINCT {} FROM files f", FILE_INFO_COLUMNS);
     
     let mut where_clauses = vec![];
     let mut params = vec![];
     
     // Join with file_tags if filtering by tags
-    if tag_ids.is_some() {
+    if filter.tag_ids.is_some() {
         sql.push_str(" JOIN file_tags ft ON f.id = ft.file_id");
         
-        if let Some(ids) = &tag_ids {
+        if let Some(ids) = &filter.tag_ids {
             let placeholders: Vec<String> = (0..ids.len()).map(|_| "?".to_string()).collect();
             let placeholders_str = placeholders.join(",");
             where_clauses.push(format!("ft.tag_id IN ({})", placeholders_str));
@@ -259,17 +3398,70 @@ pub fn search_files(
     }
     
     // Add search query
-    if let Some(q) = &query {
+    if let Some(q) = &filter.query {
         where_clauses.push("(f.name LIKE ? OR f.path LIKE ?)".to_string());
-        let like_pattern = format!("%{}%", q);
+        let like_pattern = format!("%{}%", utils::normalize_path_query(q));
         params.push(like_pattern.clone());
         params.push(like_pattern);
     }
     
     // Add extension filter
-    if let Some(ext) = &extension {
-        where_clauses.push("f.extension = ?".to_string());
-        params.push(ext.to_string());
+    if let Some(extensions) = &filter.extensions {
+        let placeholders: Vec<String> = (0..extensions.len()).map(|_| "?".to_string()).collect();
+        where_clauses.push(format!("f.extension IN ({})", placeholders.join(",")));
+        
+        for ext in extensions {
+            params.push(ext.trim_start_matches('.').to_lowercase());
+        }
+    }
+    
+    // Add size range
+    if let Some(min_size) = filter.min_size {
+        where_clauses.push("f.size >= ?".to_string());
+        params.push(min_size.to_string());
+    }
+    if let Some(max_size) = filter.max_size {
+        where_clauses.push("f.size <= ?".to_string());
+        params.push(max_size.to_string());
+    }
+    
+    // Add creation and modification date ranges; stored timestamps sort as text
+    if let Some(after) = &filter.created_after {
+        where_clauses.push("f.created_at >= ?".to_string());
+        params.push(utils::parse_timestamp_bound(after, false)?);
+    }
+    if let Some(before) = &filter.created_before {
+        where_clauses.push("f.created_at <= ?".to_string());
+        params.push(utils::parse_timestamp_bound(before, true)?);
+    }
+    if let Some(after) = &filter.modified_after {
+        where_clauses.push("f.modified_at >= ?".to_string());
+        params.push(utils::parse_timestamp_bound(after, false)?);
+    }
+    if let Some(before) = &filter.modified_before {
+        where_clauses.push("f.modified_at <= ?".to_string());
+        params.push(utils::parse_timestamp_bound(before, true)?);
+    }
+    
+    // Add rating filter
+    if let Some(rating) = filter.min_rating {
+        where_clauses.push("f.rating >= ?".to_string());
+        params.push(rating.to_string());
+    }
+    
+    // Add notes filter
+    match filter.has_note {
+        Some(true) => where_clauses.push("f.notes IS NOT NULL".to_string()),
+        Some(false) => where_clauses.push("f.notes IS NULL".to_string()),
+        None => {}
+    }
+    
+    // Add download source filter, subdomains included
+    if let Some(domain) = &filter.source_domain {
+        where_clauses.push("(f.source_domain = ? OR f.source_domain LIKE ?)".to_string());
+        let domain = domain.to_lowercase();
+        params.push(domain.clone());
+        params.push(format!("%.{}", domain));
     }
     
     // Add WHERE clause if needed
@@ -281,34 +3473,63 @@ pub fn search_files(
     // Add ORDER BY
     sql.push_str(" ORDER BY f.name ASC");
     
-    // Prepare and execute the query
-    let mut stmt = conn_guard.0.prepare(&sql)?;
-    let file_iter = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
-        Ok(FileInfo {
-            id: row.get(0)?,
-            path: row.get(1)?,
-            name: row.get(2)?,
-            extension: row.get(3)?,
-            size: row.get(4)?,
-            created_at: row.get(5)?,
-            modified_at: row.get(6)?,
-            tags: vec![], // Will fill separately
-        })
-    })?;
+    // Page through the results only when asked to; the bounds are clamped integers
+    if filter.limit.is_some() || filter.offset.is_some() {
+        let (limit, offset) = page_bounds(filter.limit, filter.offset);
+        sql.push_str(&format!(" LIMIT {} OFFSET {}", limit, offset));
+    }
     
-    // Collect files
-    let mut files = vec![];
-    for file_result in file_iter {
-        let mut file = file_result?;
-        
-        // Get tags for this file
-        let mut tag_stmt = conn_guard.0.prepare(
-            "SELECT t.id, t.name, t.color 
-             FROM tags t
-             JOIN file_tags ft ON t.id = ft.tag_id
-             WHERE ft.file_id = ?"
-        )?;
-        
+    Ok((sql, params))
+}
+
+// Load the metadata of each file
+fn fill_file_metadata(conn: &Connection, files: &mut [FileInfo]) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT key, value FROM file_metadata WHERE file_id = ?")?;
+
+    for file in files.iter_mut() {
+        let metadata = stmt
+            .query_map([file.id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<BTreeMap<_, _>>>()?;
+        file.metadata = Some(metadata);
+    }
+
+    Ok(())
+}
+
+// Columns read by map_file_info, for queries aliasing files as f
+const FILE_INFO_COLUMNS: &str =
+    "f.id, f.path, f.name, f.extension, f.size, f.created_at, f.modified_at,
+     f.notes, f.rating, f.source_url, f.source_domain, f.created_at_approximate";
+
+fn map_file_info(row: &rusqlite::Row) -> rusqlite::Result<FileInfo> {
+    Ok(FileInfo {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        name: row.get(2)?,
+        extension: row.get(3)?,
+        size: row.get(4)?,
+        created_at: row.get(5)?,
+        modified_at: row.get(6)?,
+        notes: row.get(7)?,
+        rating: row.get(8)?,
+        source_url: row.get(9)?,
+        source_domain: row.get(10)?,
+        created_at_approximate: row.get(11)?,
+        tags: vec![], // Will fill separately
+        metadata: None,
+    })
+}
+
+// Load the tags of each file
+fn fill_file_tags(conn: &Connection, files: &mut [FileInfo]) -> Result<()> {
+    let mut tag_stmt = conn.prepare(
+        "SELECT t.id, t.name, t.color 
+         FROM tags t
+         JOIN file_tags ft ON t.id = ft.tag_id
+         WHERE ft.file_id = ?"
+    )?;
+    
+    for file in files.iter_mut() {
         let tag_iter = tag_stmt.query_map([file.id], |row| {
             Ok(Tag {
                 id: row.get(0)?,
@@ -320,9 +3541,698 @@ pub fn search_files(
         for tag_result in tag_iter {
             file.tags.push(tag_result?);
         }
-        
-        files.push(file);
+    }
+    
+    Ok(())
+}
+
+// Default page size for listing files
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+
+// Largest page of files a caller can ask for
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+// The LIMIT and OFFSET to bind for a requested page: the limit kept within
+// 1..=MAX_PAGE_SIZE, DEFAULT_PAGE_SIZE when not given, and the offset never negative
+fn page_bounds(limit: Option<i64>, offset: Option<i64>) -> (i64, i64) {
+    (limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE), offset.unwrap_or(0).max(0))
+}
+
+// Get each distinct file extension in the index with its file count, most common first
+pub fn get_extensions(app: &AppHandle) -> Result<Vec<(String, usize)>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    
+    let mut stmt = conn_guard.0.prepare(
+        "SELECT extension, COUNT(*) AS file_count FROM files
+         GROUP BY extension
+         ORDER BY file_count DESC, extension ASC"
+    )?;
+    let extensions = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    
+    Ok(extensions)
+}
+
+// Files added to or organized by the index in one period, for one extension
+// and move detail
+pub struct TrendRow {
+    // First day of the period, YYYY-MM-DD
+    pub bucket: String,
+    // Moved by the organizer; otherwise indexed for the first time
+    pub organized: bool,
+    pub extension: String,
+    // The move's history detail, e.g. "rule: Documents"
+    pub detail: Option<String>,
+    pub count: u64,
+    pub bytes: u64,
+}
+
+// Count files indexed for the first time and files moved since a date,
+// grouped by period. bucket_sql turns a timestamp column into the first day
+// of its period with SQLite date functions
+pub fn get_trend_rows(app: &AppHandle, bucket_sql: &str, since: &str) -> Result<Vec<TrendRow>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    get_trend_rows_in(&conn_guard.0, bucket_sql, since)
+}
+
+fn get_trend_rows_in(conn: &Connection, bucket_sql: &str, since: &str) -> Result<Vec<TrendRow>> {
+    let bucket = |column: &str| bucket_sql.replace("{}", column);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT bucket, organized, extension, detail, COUNT(*), COALESCE(SUM(size), 0) FROM (
+            SELECT {} AS bucket, 0 AS organized, f.extension, NULL AS detail, f.size
+            FROM file_history h JOIN files f ON f.id = h.file_id
+            WHERE h.event_type = ?1
+            GROUP BY h.file_id
+            HAVING MIN(h.created_at) >= ?3
+            UNION ALL
+            SELECT {} AS bucket, 1 AS organized, f.extension, h.detail, f.size
+            FROM file_history h JOIN files f ON f.id = h.file_id
+            WHERE h.event_type = ?2 AND h.created_at >= ?3
+         )
+         GROUP BY bucket, organized, extension, detail",
+        bucket("MIN(h.created_at)"),
+        bucket("h.created_at"),
+    ))?;
+    let rows = stmt
+        .query_map(params![EVENT_INDEXED, EVENT_MOVED, since], |row| {
+            Ok(TrendRow {
+                bucket: row.get(0)?,
+                organized: row.get(1)?,
+                extension: row.get(2)?,
+                detail: row.get(3)?,
+                count: row.get::<_, i64>(4)? as u64,
+                bytes: row.get::<_, i64>(5)? as u64,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(rows)
+}
+
+// Get the biggest files of at least min_size bytes, largest first
+pub fn get_large_files(app: &AppHandle, min_size: i64, limit: u32) -> Result<Vec<LargeFile>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    
+    let mut stmt = conn_guard.0.prepare(&format!(
+        "SELECT {} FROM files f
+         WHERE f.size >= ?
+         ORDER BY f.size DESC, f.name ASC
+         LIMIT ?",
+        FILE_INFO_COLUMNS
+    ))?;
+    let mut files = stmt
+        .query_map(params![min_size, limit], map_file_info)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    fill_file_tags(&conn_guard.0, &mut files)?;
+    
+    Ok(files
+        .into_iter()
+        .map(|file| LargeFile {
+            size_display: utils::format_file_size(file.size.max(0) as u64),
+            file,
+        })
+        .collect())
+}
+
+// Get files that have no tags at all
+pub fn get_untagged_files(app: &AppHandle, limit: Option<i64>, offset: Option<i64>) -> Result<Vec<FileInfo>> {
+    let conn = get_connection(app)?;
+    let conn_guard = conn.lock().unwrap();
+    
+    let (limit, offset) = page_bounds(limit, offset);
+    let mut stmt = conn_guard.0.prepare(&format!(
+        "SELECT {} FROM files f
+         LEFT JOIN file_tags ft ON f.id = ft.file_id
+         WHERE ft.file_id IS NULL
+         ORDER BY f.name ASC
+         LIMIT ? OFFSET ?",
+        FILE_INFO_COLUMNS
+    ))?;
+    let file_iter = stmt.query_map(params![limit, offset], map_file_info)?;
+    
+    let mut files = vec![];
+    for file in file_iter {
+        files.push(file?);
     }
     
     Ok(files)
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // An empty in-memory index with the full schema
+    fn test_connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn
+    }
+
+    fn new_file(path: &str) -> NewFile {
+        let path = PathBuf::from(path);
+        NewFile {
+            name: utils::get_file_name(&path).unwrap_or_default(),
+            extension: utils::get_file_extension(&path).unwrap_or_default(),
+            path,
+            size: 10,
+            created_at: "2024-03-09T14:05:00Z".to_string(),
+            created_at_approximate: false,
+            modified_at: "2024-03-09T14:05:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn orphans_are_removed_only_while_their_path_is_unchanged() {
+        let mut conn = test_connection();
+        let gone = insert_file(&conn, &new_file("/organizer-test/missing/gone.pdf")).unwrap();
+        let moved = insert_file(&conn, &new_file("/organizer-test/missing/moved.pdf")).unwrap();
+        conn.execute("INSERT INTO tags (name, color) VALUES ('Orphan test', '#9e9e9e')", []).unwrap();
+        let tag = conn.last_insert_rowid();
+        conn.execute("INSERT INTO file_tags (file_id, tag_id) VALUES (?1, ?3), (?2, ?3)", [gone, moved, tag]).unwrap();
+        // Re-indexed at a new path after it was found missing
+        conn.execute("UPDATE files SET path = '/organizer-test/found/moved.pdf' WHERE id = ?", [moved]).unwrap();
+
+        let cleanup = remove_orphans_in(&mut conn, &[
+            (gone, "/organizer-test/missing/gone.pdf".to_string()),
+            (moved, "/organizer-test/missing/moved.pdf".to_string()),
+        ])
+        .unwrap();
+        assert_eq!(cleanup.files_removed, 1);
+        assert_eq!(cleanup.tag_links_removed, 1);
+        let left: Vec<i64> = conn
+            .prepare("SELECT id FROM files")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(left, [moved]);
+    }
+
+    #[test]
+    fn unicode_names_are_indexed_found_and_renamed() {
+        let conn = test_connection();
+        for name in utils::tests::UNICODE_NAMES {
+            let path = Path::new("/organizer-test/unicode").join(name);
+            let id = insert_file(&conn, &new_file(&path.to_string_lossy())).unwrap();
+
+            let stem = utils::get_file_stem(&path).unwrap();
+            let found = search_files_in(&conn, &SearchFilter { query: Some(stem), ..Default::default() }).unwrap();
+            assert_eq!(found.iter().map(|file| file.id).collect::<Vec<_>>(), [id]);
+            assert_eq!(found[0].name, name);
+            assert_eq!(Path::new(&found[0].path), utils::normalize_path(&path));
+
+            let renamed = path.with_file_name(format!("済み {}", name));
+            update_file_path_in(&conn, id, &renamed).unwrap();
+            let found = search_files_in(&conn, &SearchFilter { query: Some("済み".to_string()), ..Default::default() }).unwrap();
+            assert_eq!(found.iter().map(|file| file.id).collect::<Vec<_>>(), [id]);
+            assert_eq!(found[0].name, format!("済み {}", name));
+            conn.execute("DELETE FROM files WHERE id = ?", [id]).unwrap();
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn undecodable_paths_are_not_indexed() {
+        use std::os::unix::ffi::OsStrExt;
+        let conn = test_connection();
+        let mut file = new_file("/organizer-test/unicode/placeholder.pdf");
+        file.path = PathBuf::from(std::ffi::OsStr::from_bytes(b"/organizer-test/unicode/caf\xe9.pdf"));
+        assert!(insert_file(&conn, &file).is_err());
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM files", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn imported_rules_never_replace_existing_ones() {
+        let mut conn = test_connection();
+        let profile_id = active_profile_id_in(&conn).unwrap();
+        let rule = Rule {
+            id: 0,
+            name: "Invoices".to_string(),
+            pattern: "pdf".to_string(),
+            destination_folder: "/organizer-test/Invoices".to_string(),
+            is_extension: true,
+            is_active: false,
+            preserve_structure: false,
+            condition: None,
+            tag_id: None,
+            max_age_days: None,
+            applies_to_folders: false,
+            exclude_auto_tags: false,
+        };
+        let existing = insert_rule(&conn, &Rule { is_active: true, ..rule.clone() }, profile_id).unwrap();
+
+        let names = add_rules_keeping_existing_in(&mut conn, profile_id, &[rule.clone(), rule.clone()]).unwrap();
+        assert_eq!(names, ["Invoices (imported)", "Invoices (imported 2)"]);
+        let active: bool = conn.query_row("SELECT is_active FROM rules WHERE id = ?", [existing], |row| row.get(0)).unwrap();
+        assert!(active);
+    }
+
+    #[test]
+    fn deleted_rules_keep_limiting_their_folders() {
+        let conn = test_connection();
+        let profile_id = active_profile_id_in(&conn).unwrap();
+        let rule = Rule {
+            id: 0,
+            name: "Invoices".to_string(),
+            pattern: "pdf".to_string(),
+            destination_folder: "/organizer-test/Invoices".to_string(),
+            is_extension: true,
+            is_active: true,
+            preserve_structure: false,
+            condition: None,
+            tag_id: None,
+            max_age_days: None,
+            applies_to_folders: false,
+            exclude_auto_tags: false,
+        };
+        let rule_id = insert_rule(&conn, &rule, profile_id).unwrap();
+        conn.execute(
+            "INSERT INTO watched_folder_rules (folder_path, rule_id, profile_id) VALUES ('/organizer-test/Downloads', ?, ?)",
+            [rule_id, profile_id],
+        ).unwrap();
+
+        conn.execute("DELETE FROM rules WHERE id = ?", [rule_id]).unwrap();
+        // The next rule takes the deleted rule's ID, but not its folder
+        assert_eq!(insert_rule(&conn, &rule, profile_id).unwrap(), rule_id);
+        let folders = watched_folder_rules_in(&conn).unwrap();
+        assert_eq!(folders.get("/organizer-test/Downloads"), Some(&vec![]));
+    }
+
+    #[test]
+    fn auto_tag_overrides_keep_the_users_own_category_tags() {
+        let mut conn = test_connection();
+        let category_tags: Vec<i64> = conn
+            .prepare("SELECT tag_id FROM categories WHERE tag_id IS NOT NULL ORDER BY id LIMIT 2").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        let (auto_tag, own_tag) = (category_tags[0], category_tags[1]);
+        let moved = |path: &str| MovedFile {
+            file: new_file(path),
+            from: PathBuf::from("/organizer-test/Downloads/report.pdf"),
+            detail: "rule: Documents".to_string(),
+            tag_ids: vec![],
+            auto_tag_ids: vec![auto_tag],
+            journal_id: None,
+        };
+        let tags_of = |conn: &Connection, file_id: i64| -> Vec<i64> {
+            conn.prepare("SELECT tag_id FROM file_tags WHERE file_id = ? ORDER BY tag_id").unwrap()
+                .query_map([file_id], |row| row.get(0)).unwrap()
+                .collect::<rusqlite::Result<_>>().unwrap()
+        };
+
+        let indexed = index_moved_files_in(&mut conn, &[moved("/organizer-test/Documents/report.pdf")]).unwrap().remove(0).unwrap();
+        assert!(!indexed.auto_tags_suppressed);
+        let file_id = indexed.file_id;
+        insert_file_tag(&conn, file_id, own_tag).unwrap();
+
+        // Only the tag applied on the move goes
+        assert_eq!(set_auto_tag_override_in(&mut conn, file_id, true).unwrap(), 1);
+        assert_eq!(tags_of(&conn, file_id), [own_tag]);
+
+        // And it isn't applied again on the next move
+        let indexed = index_moved_files_in(&mut conn, &[MovedFile {
+            from: PathBuf::from("/organizer-test/Documents/report.pdf"),
+            ..moved("/organizer-test/Archive/report.pdf")
+        }]).unwrap().remove(0).unwrap();
+        assert_eq!(indexed.file_id, file_id);
+        assert!(indexed.auto_tags_suppressed);
+        assert_eq!(tags_of(&conn, file_id), [own_tag]);
+
+        // A category tag the user adds again themselves is theirs from then on
+        set_auto_tag_override_in(&mut conn, file_id, false).unwrap();
+        index_moved_files_in(&mut conn, &[MovedFile {
+            from: PathBuf::from("/organizer-test/Archive/report.pdf"),
+            ..moved("/organizer-test/Documents/report.pdf")
+        }]).unwrap().remove(0).unwrap();
+        insert_file_tag(&conn, file_id, auto_tag).unwrap();
+        assert_eq!(set_auto_tag_override_in(&mut conn, file_id, true).unwrap(), 0);
+        assert_eq!(tags_of(&conn, file_id).len(), 2);
+    }
+
+    #[test]
+    fn report_pages_are_cut_in_sql_by_name() {
+        let conn = test_connection();
+        let ids: Vec<i64> = ["c.pdf", "a.pdf", "d.pdf", "b.pdf"]
+            .iter()
+            .map(|name| insert_file(&conn, &new_file(&format!("/organizer-test/Downloads/{}", name))).unwrap())
+            .collect();
+
+        let page = get_files_by_ids_in(&conn, &ids[..3], Some(2), Some(1)).unwrap();
+        let names: Vec<_> = page.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(names, ["c.pdf", "d.pdf"]);
+        assert!(get_files_by_ids_in(&conn, &[], None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_corrupt_databases_are_set_aside() {
+        let dir = utils::tests::scratch_dir("corrupt-db");
+        let garbage = dir.join("garbage.db");
+        fs::write(&garbage, vec![0x5a; 8192]).unwrap();
+        assert!(is_corruption(&open_database(&garbage).unwrap_err()));
+
+        let missing = dir.join("missing").join("index.db");
+        assert!(!is_corruption(&open_database(&missing).unwrap_err()));
+        assert!(is_corruption(&IntegrityCheckFailed("row 1 missing from index".to_string()).into()));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_file_that_cant_be_indexed_is_left_out_of_its_batch() {
+        use std::os::unix::ffi::OsStrExt;
+        let mut conn = test_connection();
+        conn.execute(
+            "INSERT INTO move_journal (source, destination, state, detail, started_at) VALUES ('a', 'b', ?, 'rule', '')",
+            [MOVE_STARTED],
+        ).unwrap();
+        let journal_id = conn.last_insert_rowid();
+        let moved = |file: NewFile| MovedFile {
+            from: PathBuf::from("/organizer-test/Downloads").join(&file.name),
+            file,
+            detail: "rule: Documents".to_string(),
+            tag_ids: vec![],
+            auto_tag_ids: vec![],
+            journal_id: Some(journal_id),
+        };
+        let mut bad = new_file("/organizer-test/Documents/placeholder.pdf");
+        bad.path = PathBuf::from(std::ffi::OsStr::from_bytes(b"/organizer-test/Documents/caf\xe9.pdf"));
+
+        let results = index_moved_files_in(&mut conn, &[
+            moved(new_file("/organizer-test/Documents/a.pdf")),
+            moved(bad),
+            moved(new_file("/organizer-test/Documents/b.pdf")),
+        ]).unwrap();
+        assert!(results[0].is_ok() && results[1].is_err() && results[2].is_ok());
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM files", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+        let state: String = conn.query_row("SELECT state FROM move_journal WHERE id = ?", [journal_id], |row| row.get(0)).unwrap();
+        assert_eq!(state, MOVE_COMPLETED);
+    }
+
+    // Count the transactions committed on a connection
+    fn count_commits(conn: &Connection) -> Arc<AtomicUsize> {
+        let commits = Arc::new(AtomicUsize::new(0));
+        let counter = commits.clone();
+        conn.commit_hook(Some(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            false
+        }));
+        commits
+    }
+
+    #[test]
+    fn batches_are_indexed_in_one_commit() {
+        let mut conn = test_connection();
+        let commits = count_commits(&conn);
+        let files: Vec<_> = (0..1_000).map(|i| new_file(&format!("/organizer-test/Downloads/{}.pdf", i))).collect();
+
+        let results = add_files_batch_in(&mut conn, &files).unwrap();
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(commits.load(Ordering::SeqCst), 1);
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM files", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1_000);
+
+        let moves: Vec<_> = files
+            .iter()
+            .map(|file| MovedFile {
+                file: NewFile { path: Path::new("/organizer-test/Documents").join(&file.name), ..new_file(&file.path.to_string_lossy()) },
+                from: file.path.clone(),
+                detail: "rule: Documents".to_string(),
+                tag_ids: vec![],
+                auto_tag_ids: vec![],
+                journal_id: None,
+            })
+            .collect();
+        let results = index_moved_files_in(&mut conn, &moves).unwrap();
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(commits.load(Ordering::SeqCst), 2);
+        let moved: i64 = conn
+            .query_row("SELECT COUNT(*) FROM files WHERE path LIKE '/organizer-test/Documents/%'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(moved, 1_000);
+    }
+
+    fn add_tag(conn: &Connection, name: &str) -> i64 {
+        conn.execute("INSERT INTO tags (name, color) VALUES (?, '#9e9e9e')", [name]).unwrap();
+        conn.last_insert_rowid()
+    }
+
+    fn tag_ids_of(conn: &Connection, file_id: i64) -> Vec<i64> {
+        conn.prepare("SELECT tag_id FROM file_tags WHERE file_id = ? ORDER BY tag_id").unwrap()
+            .query_map([file_id], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn reindexed_files_keep_their_id_and_tags() {
+        let conn = test_connection();
+        let tag = add_tag(&conn, "Reindex test");
+        let file_id = insert_file(&conn, &new_file("/organizer-test/Downloads/report.pdf")).unwrap();
+        insert_file_tag(&conn, file_id, tag).unwrap();
+
+        let changed = NewFile { size: 20, ..new_file("/organizer-test/Downloads/report.pdf") };
+        assert_eq!(insert_file(&conn, &changed).unwrap(), file_id);
+        assert_eq!(tag_ids_of(&conn, file_id), [tag]);
+        let size: i64 = conn.query_row("SELECT size FROM files WHERE id = ?", [file_id], |row| row.get(0)).unwrap();
+        assert_eq!(size, 20);
+    }
+
+    #[test]
+    fn paths_written_differently_are_merged_into_one_row() {
+        let conn = test_connection();
+        let mut ids = vec![];
+        for path in [
+            "/organizer-test/Downloads/a.pdf",
+            "/organizer-test/Downloads//a.pdf",
+            "/organizer-test/./Downloads/a.pdf",
+        ] {
+            // Stored as received, the way rows were before paths were normalized
+            conn.execute(
+                "INSERT INTO files (path, name, extension, size, created_at, modified_at)
+                 VALUES (?, 'a.pdf', 'pdf', 10, '2024-03-09T14:05:00Z', '2024-03-09T14:05:00Z')",
+                [path],
+            ).unwrap();
+            let file_id = conn.last_insert_rowid();
+            insert_file_tag(&conn, file_id, add_tag(&conn, path)).unwrap();
+            ids.push(file_id);
+        }
+
+        normalize_file_paths(&conn).unwrap();
+        let rows: Vec<(i64, String)> = conn.prepare("SELECT id, path FROM files").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        let expected = utils::normalize_path(Path::new("/organizer-test/Downloads/a.pdf"));
+        assert_eq!(rows, [(ids[0], expected.to_string_lossy().to_string())]);
+        assert_eq!(tag_ids_of(&conn, ids[0]).len(), 3);
+
+        // Found again whichever way the path is written
+        assert_eq!(insert_file(&conn, &new_file("/organizer-test/Downloads/./a.pdf")).unwrap(), ids[0]);
+    }
+
+    #[test]
+    fn files_from_either_side_of_midnight_land_on_their_own_day() {
+        let conn = test_connection();
+        for (path, created_at) in [
+            ("/organizer-test/Downloads/before.pdf", "2024-03-09T23:59:59Z"),
+            ("/organizer-test/Downloads/after.pdf", "2024-03-10T00:00:00Z"),
+        ] {
+            let file = NewFile { created_at: created_at.to_string(), ..new_file(path) };
+            insert_file(&conn, &file).unwrap();
+        }
+        let created_on = |after: &str, before: &str| -> Vec<String> {
+            let filter = SearchFilter {
+                created_after: Some(after.to_string()),
+                created_before: Some(before.to_string()),
+                ..Default::default()
+            };
+            search_files_in(&conn, &filter).unwrap().into_iter().map(|file| file.name).collect()
+        };
+
+        assert_eq!(created_on("2024-03-09", "2024-03-09"), ["before.pdf"]);
+        assert_eq!(created_on("2024-03-10", "2024-03-10"), ["after.pdf"]);
+        // Bounds in another time zone are compared in UTC: midnight in Paris
+        // is still the 9th there
+        assert_eq!(created_on("2024-03-10T00:00:00+01:00", "2024-03-10T00:59:59+01:00"), ["before.pdf"]);
+        assert_eq!(created_on("2024-03-09 23:59:59", "2024-03-10 00:00:00"), ["after.pdf", "before.pdf"]);
+    }
+
+    #[test]
+    fn searches_by_extension_and_tag_use_their_indices() {
+        let conn = test_connection();
+        let query_plan = |filter: &SearchFilter| -> String {
+            let (sql, params) = search_query(filter).unwrap();
+            conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql)).unwrap()
+                .query_map(rusqlite::params_from_iter(params.iter()), |row| row.get::<_, String>(3)).unwrap()
+                .collect::<rusqlite::Result<Vec<_>>>().unwrap()
+                .join("\n")
+        };
+
+        let by_extension = query_plan(&SearchFilter { extensions: Some(vec!["pdf".to_string()]), ..Default::default() });
+        assert!(by_extension.contains("USING INDEX idx_files_extension"), "{}", by_extension);
+        let by_tag = query_plan(&SearchFilter { tag_ids: Some(vec![1, 2]), ..Default::default() });
+        assert!(by_tag.contains("INDEX idx_file_tags_tag_id"), "{}", by_tag);
+        let by_date = query_plan(&SearchFilter { modified_after: Some("2024-03-09".to_string()), ..Default::default() });
+        assert!(by_date.contains("USING INDEX idx_files_modified_at"), "{}", by_date);
+    }
+
+    #[test]
+    fn history_times_are_stored_like_file_times() {
+        let conn = test_connection();
+        conn.execute(
+            "INSERT INTO file_history (event_type, created_at) VALUES (?, '2024-03-09 23:59:59')",
+            [EVENT_INDEXED],
+        ).unwrap();
+        insert_file_event(&conn, None, EVENT_INDEXED, None, None, None).unwrap();
+
+        // Opening a database from before version 20 converts its older times
+        conn.pragma_update(None, "user_version", 19).unwrap();
+        create_tables(&conn).unwrap();
+        let times: Vec<String> = conn.prepare("SELECT created_at FROM file_history ORDER BY id").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(times[0], "2024-03-09T23:59:59Z");
+        assert!(chrono::DateTime::parse_from_rfc3339(&times[1]).is_ok(), "{}", times[1]);
+        assert!(times[0] < times[1]);
+    }
+
+    #[test]
+    fn trends_count_events_from_the_first_moments_of_their_period() {
+        let conn = test_connection();
+        let file_id = insert_file(&conn, &new_file("/organizer-test/Documents/report.pdf")).unwrap();
+        conn.execute("UPDATE file_history SET created_at = '2024-03-09T00:00:05Z' WHERE file_id = ?", [file_id]).unwrap();
+        conn.execute(
+            "INSERT INTO file_history (file_id, event_type, detail, created_at)
+             VALUES (?1, ?2, 'rule: Documents', '2024-03-09T00:00:07Z'), (?1, ?2, 'rule: Documents', '2024-03-08T23:59:59Z')",
+            params![file_id, EVENT_MOVED],
+        ).unwrap();
+
+        let rows = get_trend_rows_in(&conn, "date({})", "2024-03-09T00:00:00Z").unwrap();
+        let counts: Vec<(&str, bool, u64)> = rows.iter().map(|row| (row.bucket.as_str(), row.organized, row.count)).collect();
+        assert_eq!(counts.len(), 2);
+        assert!(counts.contains(&("2024-03-09", false, 1)));
+        assert!(counts.contains(&("2024-03-09", true, 1)));
+    }
+
+    #[test]
+    fn a_new_tag_inherits_nothing_from_a_removed_one() {
+        let mut conn = test_connection();
+        let tag = add_tag(&conn, "Short-lived");
+        let file_id = insert_file(&conn, &new_file("/organizer-test/Documents/report.pdf")).unwrap();
+        insert_file_tag(&conn, file_id, tag).unwrap();
+        assert_eq!(conn.execute("UPDATE categories SET tag_id = ? WHERE name = 'Documents'", [tag]).unwrap(), 1);
+        assert_eq!(conn.execute("UPDATE rules SET tag_id = ? WHERE name = 'Documents'", [tag]).unwrap(), 1);
+
+        remove_tag_in(&mut conn, tag).unwrap();
+        // The next tag is given the same ID
+        assert_eq!(add_tag(&conn, "Unrelated"), tag);
+        assert!(tag_ids_of(&conn, file_id).is_empty());
+        let pointing: i64 = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM categories WHERE tag_id = ?1) + (SELECT COUNT(*) FROM rules WHERE tag_id = ?1)",
+                [tag],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(pointing, 0);
+    }
+
+    #[test]
+    fn archived_files_move_in_the_index_and_trashed_ones_leave_it() {
+        let mut conn = test_connection();
+        let tag = add_tag(&conn, "Cleanup test");
+        let archived = insert_file(&conn, &new_file("/organizer-test/Downloads/old.zip")).unwrap();
+        let trashed = insert_file(&conn, &new_file("/organizer-test/Downloads/stale.tmp")).unwrap();
+        insert_file_tag(&conn, archived, tag).unwrap();
+        insert_file_tag(&conn, trashed, tag).unwrap();
+        conn.execute("INSERT INTO file_metadata (file_id, key, value) VALUES (?, 'origin', 'mail')", [trashed]).unwrap();
+        insert_file_event(&conn, Some(trashed), EVENT_TRASHED, Some("/organizer-test/Downloads/stale.tmp"), None, None).unwrap();
+
+        update_file_path_in(&conn, archived, Path::new("/organizer-test/Archive/old.zip")).unwrap();
+        remove_file_in(&mut conn, trashed).unwrap();
+
+        let path: String = conn.query_row("SELECT path FROM files WHERE id = ?", [archived], |row| row.get(0)).unwrap();
+        assert_eq!(Path::new(&path), utils::normalize_path(Path::new("/organizer-test/Archive/old.zip")));
+        assert_eq!(tag_ids_of(&conn, archived), [tag]);
+
+        let left: i64 = conn.query_row("SELECT COUNT(*) FROM files WHERE id = ?", [trashed], |row| row.get(0)).unwrap();
+        assert_eq!(left, 0);
+        assert!(tag_ids_of(&conn, trashed).is_empty());
+        let metadata: i64 = conn.query_row("SELECT COUNT(*) FROM file_metadata", [], |row| row.get(0)).unwrap();
+        assert_eq!(metadata, 0);
+        let history: Option<i64> = conn
+            .query_row("SELECT file_id FROM file_history WHERE event_type = ?", [EVENT_TRASHED], |row| row.get(0))
+            .unwrap();
+        assert_eq!(history, None);
+    }
+
+    #[test]
+    fn trashed_duplicates_hand_their_tags_to_the_kept_copy() {
+        let mut conn = test_connection();
+        let tag = add_tag(&conn, "Collision test");
+        let keep = insert_file(&conn, &new_file("/organizer-test/Downloads/invoice.pdf")).unwrap();
+        let duplicate = insert_file(&conn, &new_file("/organizer-test/Downloads/invoice (1).pdf")).unwrap();
+        insert_file_tag(&conn, duplicate, tag).unwrap();
+        insert_file_event(&conn, Some(duplicate), EVENT_TRASHED, Some("/organizer-test/Downloads/invoice (1).pdf"), None, None).unwrap();
+
+        merge_duplicate_file_in(&mut conn, keep, duplicate).unwrap();
+
+        let ids: Vec<i64> = conn
+            .prepare("SELECT id FROM files").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(ids, [keep]);
+        assert_eq!(tag_ids_of(&conn, keep), [tag]);
+        let history: Option<i64> = conn
+            .query_row("SELECT file_id FROM file_history WHERE event_type = ?", [EVENT_TRASHED], |row| row.get(0))
+            .unwrap();
+        assert_eq!(history, Some(keep));
+    }
+
+    #[test]
+    fn pages_stay_within_their_bounds() {
+        assert_eq!(page_bounds(None, None), (DEFAULT_PAGE_SIZE, 0));
+        assert_eq!(page_bounds(Some(0), Some(-5)), (1, 0));
+        assert_eq!(page_bounds(Some(-1), Some(20)), (1, 20));
+        assert_eq!(page_bounds(Some(i64::MAX), None), (MAX_PAGE_SIZE, 0));
+
+        let conn = test_connection();
+        for name in ["a.pdf", "b.pdf", "c.pdf", "d.pdf"] {
+            insert_file(&conn, &new_file(&format!("/organizer-test/paged/{}", name))).unwrap();
+        }
+        let names = |limit, offset| -> Vec<String> {
+            search_files_in(&conn, &SearchFilter { query: Some("paged".to_string()), limit, offset, ..Default::default() })
+                .unwrap()
+                .into_iter()
+                .map(|file| file.name)
+                .collect()
+        };
+        assert_eq!(names(None, None), ["a.pdf", "b.pdf", "c.pdf", "d.pdf"]);
+        assert_eq!(names(Some(2), Some(1)), ["b.pdf", "c.pdf"]);
+        assert_eq!(names(Some(0), Some(-3)), ["a.pdf"]);
+        assert_eq!(names(None, Some(3)), ["d.pdf"]);
+    }
+
+    #[test]
+    fn history_of_removed_files_stays_unlinked_across_launches() {
+        let mut conn = test_connection();
+        let path = "/organizer-test/Downloads/setup.exe";
+        let removed = insert_file(&conn, &new_file(path)).unwrap();
+        remove_file_in(&mut conn, removed).unwrap();
+        let reindexed = insert_file(&conn, &new_file(path)).unwrap();
+
+        create_tables(&conn).unwrap();
+
+        let linked: Vec<Option<i64>> = conn
+            .prepare("SELECT file_id FROM file_history WHERE event_type = ? ORDER BY id").unwrap()
+            .query_map([EVENT_INDEXED], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(linked, [None, Some(reindexed)]);
+    }
+}
@@ -0,0 +1,284 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Runtime};
+use crate::file_ops::WatchedFolder;
+use crate::hooks;
+
+// Events sent to the frontend, one per concern, so each view only listens to
+// what it shows. The payload of each is the struct of the same name below
+
+// A file was moved: by a rule, by organize_everything or by hand
+pub const FILE_ORGANIZED: &str = "file-organized";
+
+// A file was added to the index, or its entry was updated
+pub const FILE_INDEXED: &str = "file-indexed";
+
+// A file couldn't be organized, whether by the watcher, a scan, the
+// scheduled scan or by hand
+pub const FILE_ERROR: &str = "file-error";
+
+// Watching started, stopped, paused or resumed for any folder or file
+pub const WATCH_STATUS_CHANGED: &str = "watch-status-changed";
+
+// A watcher failed, or the folder it watched is gone, and watching it stopped;
+// its payload is WatcherError
+pub const WATCHER_ERROR: &str = "watcher_error";
+
+// A watcher lost events, e.g. because too many changed at once; the folder is
+// rescanned to pick up what was missed. Its payload is WatchOverflow
+pub const WATCH_OVERFLOW: &str = "watch_overflow";
+
+// Progress of a running folder backup, about once a second
+pub const BACKUP_PROGRESS: &str = "backup-progress";
+
+// A background task started, finished or failed
+pub const TASK_UPDATED: &str = "task-updated";
+
+// Deprecated: the watcher's catch-all event, replaced by file-organized,
+// file-indexed and file-error. Still sent for one more release
+pub const LEGACY_FILE_EVENT: &str = "file_event";
+
+// Deprecated: replaced by backup-progress. Still sent for one more release
+pub const LEGACY_BACKUP_PROGRESS: &str = "backup_progress";
+
+// Progress of a restore; its payload is TransferProgress
+pub const RESTORE_PROGRESS: &str = "restore_progress";
+
+// Something looked wrong in the checks run shortly after launch; its payload
+// is health::HealthWarning
+pub const HEALTH_WARNING: &str = "health_warning";
+
+// Progress of find_duplicates; its payload is DuplicateScanProgress
+pub const DUPLICATE_SCAN_PROGRESS: &str = "duplicate_scan_progress";
+
+// find_duplicates confirmed a group of identical files; its payload is a
+// commands::DuplicateGroup
+pub const DUPLICATE_GROUP_FOUND: &str = "duplicate_group_found";
+
+// Files the watcher queued outside the active hours were organized; its
+// payload is file_ops::PendingFlush with the counts
+pub const DEFERRED_BATCH_EXECUTED: &str = "deferred-batch-executed";
+
+// A folder rule moved a folder with everything in it; its payload is
+// file_ops::FolderMove. Each file in it is also reported by file-organized
+pub const FOLDER_ORGANIZED: &str = "folder-organized";
+
+// A sweep in confirmation mode found stale files; its payload is the
+// cleanup::CleanupSummary of the dry run, for the user to confirm
+pub const CLEANUP_PENDING: &str = "cleanup_pending";
+
+// A sweep archived or trashed stale files; its payload is the cleanup::CleanupSummary
+pub const CLEANUP_COMPLETED: &str = "cleanup_completed";
+
+// Tasks reported by task-updated
+pub const TASK_SCAN: &str = "scan";
+pub const TASK_ORPHAN_CLEANUP: &str = "orphan_cleanup";
+pub const TASK_BACKUP: &str = "backup";
+
+// Task states reported by task-updated
+pub const TASK_STARTED: &str = "started";
+pub const TASK_FINISHED: &str = "finished";
+pub const TASK_FAILED: &str = "failed";
+
+// Payload of file-organized
+#[derive(Debug, Clone, Serialize)]
+pub struct FileOrganized {
+    pub source: String,
+    pub destination: String,
+    // Name of the rule that moved the file; None when no rule was involved
+    pub rule: Option<String>,
+    pub category: Option<String>,
+}
+
+// Payload of file-indexed
+#[derive(Debug, Clone, Serialize)]
+pub struct FileIndexed {
+    pub file_id: i64,
+    pub path: String,
+    pub size: u64,
+}
+
+// Payload of file-error
+#[derive(Debug, Clone, Serialize)]
+pub struct FileError {
+    pub path: String,
+    pub message: String,
+}
+
+// Payload of watcher_error
+#[derive(Debug, Clone, Serialize)]
+pub struct WatcherError {
+    // The watched folder or file
+    pub path: String,
+    pub error: String,
+}
+
+// Payload of watch_overflow
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchOverflow {
+    // The watched folder or file
+    pub path: String,
+    pub error: String,
+    // Whether a rescan of the folder was queued
+    pub rescan_queued: bool,
+}
+
+// Payload of watch-status-changed: everything watched after the change
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchStatusChanged {
+    pub watched: Vec<WatchedFolder>,
+    pub paused: bool,
+}
+
+// Payload of backup-progress and restore_progress
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferProgress {
+    pub bytes_transferred: u64,
+    pub total_bytes: u64,
+    pub files_completed: usize,
+    pub total_files: usize,
+    // Measured over the last reporting interval, so a rate limit shows up here
+    pub throughput_bytes_per_sec: u64,
+}
+
+// Payload of duplicate_scan_progress
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateScanProgress {
+    // Files hashed or found in the hash cache so far
+    pub hashed: usize,
+    // Files that need comparing: those sharing their size with another
+    pub total: usize,
+}
+
+// Payload of task-updated
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskUpdated {
+    // One of the TASK_* names, e.g. "scan"
+    pub task: String,
+    // "started", "finished" or "failed"
+    pub status: String,
+    // The folder the task works on, if it has one, whatever its status
+    pub path: Option<String>,
+    // Why it failed
+    pub detail: Option<String>,
+}
+
+// Also runs the file-organized hooks
+pub fn file_organized<R: Runtime>(app: &AppHandle<R>, payload: FileOrganized) {
+    hooks::dispatch(app, FILE_ORGANIZED, hooks::HookContext {
+        name: std::path::Path::new(&payload.destination)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: payload.destination.clone(),
+        from: payload.source.clone(),
+        rule: payload.rule.clone(),
+        category: payload.category.clone(),
+        tags: vec![],
+    });
+    let _ = app.emit(FILE_ORGANIZED, payload);
+}
+
+// file-organized for a file moved along with its folder. File hooks don't run:
+// the folder's hooks run once for the whole move, rather than a command per file
+pub fn folder_file_organized<R: Runtime>(app: &AppHandle<R>, payload: FileOrganized) {
+    let _ = app.emit(FILE_ORGANIZED, payload);
+}
+
+pub fn file_indexed<R: Runtime>(app: &AppHandle<R>, payload: FileIndexed) {
+    let _ = app.emit(FILE_INDEXED, payload);
+}
+
+pub fn file_error<R: Runtime>(app: &AppHandle<R>, payload: FileError) {
+    let _ = app.emit(FILE_ERROR, payload);
+}
+
+pub fn watcher_error<R: Runtime>(app: &AppHandle<R>, payload: WatcherError) {
+    let _ = app.emit(WATCHER_ERROR, payload);
+}
+
+pub fn watch_overflow<R: Runtime>(app: &AppHandle<R>, payload: WatchOverflow) {
+    let _ = app.emit(WATCH_OVERFLOW, payload);
+}
+
+pub fn watch_status_changed<R: Runtime>(app: &AppHandle<R>, payload: WatchStatusChanged) {
+    let _ = app.emit(WATCH_STATUS_CHANGED, payload);
+}
+
+pub fn task_updated<R: Runtime>(app: &AppHandle<R>, task: &str, status: &str, path: Option<&str>, detail: Option<String>) {
+    let _ = app.emit(TASK_UPDATED, TaskUpdated {
+        task: task.to_string(),
+        status: status.to_string(),
+        path: path.map(str::to_string),
+        detail,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use tauri::test::{mock_app, MockRuntime};
+    use tauri::Listener;
+
+    // Everything sent under the given names, in order, with the payload parsed
+    fn record(app: &AppHandle<MockRuntime>, names: &[&str]) -> Arc<Mutex<Vec<(String, Value)>>> {
+        let sent = Arc::new(Mutex::new(vec![]));
+        for name in names {
+            let sent = sent.clone();
+            let name = name.to_string();
+            app.listen_any(name.clone(), move |event| {
+                let payload = serde_json::from_str(event.payload()).unwrap();
+                sent.lock().unwrap().push((name.clone(), payload));
+            });
+        }
+        sent
+    }
+
+    #[test]
+    fn events_are_sent_under_their_names_with_their_payloads() {
+        let app = mock_app();
+        let handle = app.handle();
+        let sent = record(handle, &[FILE_ORGANIZED, TASK_UPDATED, WATCH_OVERFLOW]);
+
+        file_organized(handle, FileOrganized {
+            source: "/organizer-test/Downloads/report.pdf".to_string(),
+            destination: "/organizer-test/Documents/report.pdf".to_string(),
+            rule: Some("PDFs".to_string()),
+            category: None,
+        });
+        task_updated(handle, TASK_SCAN, TASK_FAILED, Some("/organizer-test/Downloads"), Some("disk full".to_string()));
+        task_updated(handle, TASK_BACKUP, TASK_STARTED, None, None);
+        watch_overflow(handle, WatchOverflow {
+            path: "/organizer-test/Downloads".to_string(),
+            error: "event queue overflow".to_string(),
+            rescan_queued: true,
+        });
+
+        assert_eq!(*sent.lock().unwrap(), [
+            ("file-organized".to_string(), json!({
+                "source": "/organizer-test/Downloads/report.pdf",
+                "destination": "/organizer-test/Documents/report.pdf",
+                "rule": "PDFs",
+                "category": null,
+            })),
+            ("task-updated".to_string(), json!({
+                "task": "scan",
+                "status": "failed",
+                "path": "/organizer-test/Downloads",
+                "detail": "disk full",
+            })),
+            ("task-updated".to_string(), json!({
+                "task": "backup",
+                "status": "started",
+                "path": null,
+                "detail": null,
+            })),
+            ("watch_overflow".to_string(), json!({
+                "path": "/organizer-test/Downloads",
+                "error": "event queue overflow",
+                "rescan_queued": true,
+            })),
+        ]);
+    }
+}
@@ -0,0 +1,539 @@
+use plist::{Dictionary, Value};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tauri::AppHandle;
+use crate::commands::Rule;
+use crate::database;
+use crate::file_ops;
+use crate::rule_engine::RuleEngine;
+use crate::utils;
+
+// Rules described in a plist, XML or binary, for converting another
+// organizer's rules:
+//
+//   rules = [{
+//     name = "Invoices", enabled = true, match = "all",
+//     conditions = [
+//       { attribute = "extension", operator = "is", value = "pdf" },
+//       { attribute = "date last modified", operator = "is not in the last", value = 2, unit = "weeks" },
+//     ],
+//     actions = [
+//       { action = "move", destination = "~/Documents/Invoices" },
+//       { action = "add tags", tags = ["Finance"] },
+//     ],
+//   }]
+//
+// A top-level array of rules works too. Attributes, operators and actions are
+// compared ignoring case, spaces and dashes, so "Date Last Modified" is
+// "date_last_modified". Name conditions ("name" without the extension, "full
+// name" with it) become a name_matches rule with a glob, such as "Invoice*.pdf"
+// for the name starting with "Invoice" and the extension pdf. Keyed archives (such as Hazel's .hazelrules files)
+// are a different format, rejected with ExternalRulesError::KeyedArchive
+pub const FORMAT_PLIST: &str = "plist";
+
+// Why import_external_rules couldn't read a file at all; serialized as
+// {"kind": ..., "message": ...}. A rule that can't be translated doesn't fail
+// the import, it's listed in the report instead
+#[derive(Debug, thiserror::Error, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum ExternalRulesError {
+    #[error("Unsupported rules format \"{0}\" (expected \"plist\")")]
+    UnsupportedFormat(String),
+    #[error("Failed to read the rules file: {0}")]
+    Read(String),
+    #[error("Invalid plist: {0}")]
+    InvalidPlist(String),
+    #[error("The file is a keyed archive (such as Hazel's .hazelrules), not a rules plist")]
+    KeyedArchive,
+    #[error("No rules list found in the file")]
+    NoRules,
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<anyhow::Error> for ExternalRulesError {
+    fn from(e: anyhow::Error) -> Self {
+        ExternalRulesError::Other(format!("{:#}", e))
+    }
+}
+
+// A rule imported without some of its conditions or actions
+#[derive(Debug, Clone, Serialize)]
+pub struct PartialRule {
+    pub name: String,
+    // What was left out, e.g. "size is greater than 10 MB"
+    pub dropped: Vec<String>,
+}
+
+// A rule that wasn't imported
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedRule {
+    pub name: String,
+    pub reason: String,
+}
+
+// Result of import_external_rules
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalImportReport {
+    pub format: String,
+    // Rules translated with all their conditions and actions
+    pub imported: Vec<String>,
+    pub partial: Vec<PartialRule>,
+    pub skipped: Vec<SkippedRule>,
+    // Imported rules saved under another name, as theirs was taken
+    pub renamed: Vec<RenamedRule>,
+}
+
+// An imported rule whose name the active profile already had
+#[derive(Debug, Clone, Serialize)]
+pub struct RenamedRule {
+    pub name: String,
+    // e.g. "Invoices (imported)"
+    pub saved_as: String,
+}
+
+// A translated rule, its tag still referred to by name
+struct Translated {
+    rule: Rule,
+    tag: Option<String>,
+    dropped: Vec<String>,
+}
+
+// Import rules from a rules plist into the active profile, leaving its own
+// rules as they are. Extension, name and modification age conditions and move
+// and tag actions are translated; the rest is left out and reported. Imported
+// rules are saved disabled, to be reviewed before they move anything
+pub fn import_external_rules(app: &AppHandle, path: &Path, format: &str) -> Result<ExternalImportReport, ExternalRulesError> {
+    if !format.trim().eq_ignore_ascii_case(FORMAT_PLIST) {
+        return Err(ExternalRulesError::UnsupportedFormat(format.to_string()));
+    }
+    let data = fs::read(path).map_err(|e| ExternalRulesError::Read(format!("{}: {}", path.display(), e)))?;
+    let value = Value::from_reader(std::io::Cursor::new(data))
+        .map_err(|e| ExternalRulesError::InvalidPlist(e.to_string()))?;
+    let entries = rule_entries(&value)?;
+
+    let mut report = ExternalImportReport {
+        format: FORMAT_PLIST.to_string(),
+        imported: vec![],
+        partial: vec![],
+        skipped: vec![],
+        renamed: vec![],
+    };
+    let mut rules = vec![];
+    for (i, entry) in entries.iter().enumerate() {
+        let name = entry
+            .as_dictionary()
+            .and_then(|rule| string(rule, "name"))
+            .unwrap_or_else(|| format!("Rule {}", i + 1));
+        match entry.as_dictionary().ok_or_else(|| "not a dictionary".to_string()).and_then(|rule| translate_rule(&name, rule)) {
+            Ok(mut translated) => {
+                if let Some(tag) = &translated.tag {
+                    translated.rule.tag_id = Some(database::get_or_create_tag(app, tag, utils::DEFAULT_TAG_COLOR)?);
+                }
+                if translated.dropped.is_empty() {
+                    report.imported.push(name);
+                } else {
+                    report.partial.push(PartialRule { name, dropped: translated.dropped });
+                }
+                rules.push(translated.rule);
+            }
+            Err(reason) => report.skipped.push(SkippedRule { name, reason }),
+        }
+    }
+
+    let profile_id = database::get_profiles(app)?
+        .into_iter()
+        .find(|profile| profile.is_active)
+        .ok_or_else(|| ExternalRulesError::Other("No active profile".to_string()))?
+        .id;
+    let saved_as = database::add_rules_keeping_existing(app, profile_id, &rules)?;
+    for (rule, saved_as) in rules.into_iter().zip(saved_as) {
+        if saved_as != rule.name {
+            report.renamed.push(RenamedRule { name: rule.name, saved_as });
+        }
+    }
+    tracing::info!(
+        file = %path.display(),
+        imported = report.imported.len(),
+        partial = report.partial.len(),
+        skipped = report.skipped.len(),
+        "Imported rules plist"
+    );
+    Ok(report)
+}
+
+// The rule entries of a rules plist
+fn rule_entries(value: &Value) -> Result<&Vec<Value>, ExternalRulesError> {
+    match value {
+        Value::Array(rules) => Ok(rules),
+        Value::Dictionary(root) if root.contains_key("$archiver") => Err(ExternalRulesError::KeyedArchive),
+        Value::Dictionary(root) => root
+            .get("rules")
+            .and_then(Value::as_array)
+            .ok_or(ExternalRulesError::NoRules),
+        _ => Err(ExternalRulesError::NoRules),
+    }
+}
+
+// Translate one rule entry, or say why it can't be
+fn translate_rule(name: &str, rule: &Dictionary) -> Result<Translated, String> {
+    if name.trim().is_empty() {
+        return Err("the rule has no name".to_string());
+    }
+    let match_any = match string(rule, "match").map(|mode| key(&mode)).as_deref() {
+        None | Some("all") => false,
+        Some("any") => true,
+        Some(other) => return Err(format!("unknown match mode \"{}\"", other)),
+    };
+    let mut dropped = vec![];
+
+    let mut extensions: Vec<String> = vec![];
+    let mut names: Vec<(NameGlob, String)> = vec![];
+    let mut max_age_days = None;
+    for condition in list(rule, "conditions") {
+        let Some(condition) = condition.as_dictionary() else {
+            dropped.push("a condition that isn't a dictionary".to_string());
+            continue;
+        };
+        let attribute = string(condition, "attribute").map(|attribute| key(&attribute)).unwrap_or_default();
+        let operator = string(condition, "operator").map(|operator| key(&operator)).unwrap_or_default();
+        let text = condition.get("value").and_then(value_text).unwrap_or_default();
+        let translated = match attribute.as_str() {
+            "extension" if operator == "is" && !text.trim().trim_start_matches('.').is_empty() => {
+                extensions.push(text.trim().trim_start_matches('.').to_lowercase());
+                true
+            }
+            "name" | "full_name" | "filename" | "file_name" => match name_glob(&attribute, &operator, &text) {
+                Some(glob) => {
+                    names.push((glob, describe(condition)));
+                    true
+                }
+                None => false,
+            },
+            "date_last_modified" if operator == "is_not_in_the_last" || operator == "not_in_the_last" => {
+                match age_in_days(condition) {
+                    Some(days) => {
+                        max_age_days = Some(max_age_days.map_or(days, |max: i64| max.max(days)));
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        };
+        if !translated {
+            dropped.push(describe(condition));
+        }
+    }
+
+    // A rule matches on extensions, with an optional age, or on a name pattern.
+    // Where the entry needs a file to have several extensions at once, the
+    // first one wins
+    if !match_any && extensions.len() > 1 {
+        for extension in extensions.drain(1..) {
+            dropped.push(format!("extension is {}", extension));
+        }
+    }
+    // "Any" can't put a name and extensions in one rule, so the extensions,
+    // which cover more files, are kept
+    if match_any && !extensions.is_empty() {
+        dropped.extend(names.drain(..).map(|(_, description)| description));
+    }
+    let mut names = names.into_iter();
+    let name_pattern = names.next().map(|(glob, _)| match (glob, extensions.pop()) {
+        // With "all", the name and the one extension go into a single pattern
+        (NameGlob::Stem(stem), Some(extension)) => format!("{}.{}", stem, extension),
+        (NameGlob::Stem(stem), None) => format!("{}.*", stem),
+        (NameGlob::Whole(glob), extension) => {
+            dropped.extend(extension.map(|extension| format!("extension is {}", extension)));
+            glob
+        }
+    });
+    dropped.extend(names.map(|(_, description)| description));
+    if (match_any && max_age_days.is_some() && !extensions.is_empty()) || (name_pattern.is_some() && max_age_days.is_some()) {
+        // "Any" would match old files of every type, and name rules have no
+        // age; rules can't say either
+        dropped.push(format!("date last modified is not in the last {} days", max_age_days.unwrap_or_default()));
+        max_age_days = None;
+    }
+    if extensions.is_empty() && max_age_days.is_none() && name_pattern.is_none() {
+        return Err("none of its conditions can be translated".to_string());
+    }
+
+    let mut destination = None;
+    let mut copy_destination = None;
+    let mut tag = None;
+    for action in list(rule, "actions") {
+        let Some(action) = action.as_dictionary() else {
+            dropped.push("an action that isn't a dictionary".to_string());
+            continue;
+        };
+        let kind = string(action, "action").map(|kind| key(&kind)).unwrap_or_default();
+        match kind.as_str() {
+            "move" | "move_to_folder" if destination.is_none() => {
+                match string(action, "destination").or_else(|| string(action, "folder")) {
+                    Some(folder) if !folder.trim().is_empty() => destination = Some(expand_home(folder.trim())),
+                    _ => dropped.push("move without a destination".to_string()),
+                }
+            }
+            // Rules only move: a copy would be made again on every scan, as the
+            // original stays behind. A copy becomes the rule's move, and the
+            // report says so; imported rules start off, so it's looked over
+            // before it runs
+            "copy" | "copy_to_folder" if copy_destination.is_none() => {
+                match string(action, "destination").or_else(|| string(action, "folder")) {
+                    Some(folder) if !folder.trim().is_empty() => copy_destination = Some(expand_home(folder.trim())),
+                    _ => dropped.push("copy without a destination".to_string()),
+                }
+            }
+            "add_tags" | "tag" => {
+                let mut tags = list(action, "tags")
+                    .iter()
+                    .filter_map(value_text)
+                    .chain(string(action, "tag"))
+                    .map(|tag| tag.trim().to_string())
+                    .filter(|tag| !tag.is_empty());
+                if tag.is_none() {
+                    tag = tags.next();
+                }
+                dropped.extend(tags.map(|extra| format!("tag {}", extra)));
+            }
+            _ => dropped.push(describe(action)),
+        }
+    }
+    let destination = match (destination, copy_destination) {
+        (Some(destination), Some(copy)) => {
+            dropped.push(format!("copy to {}", copy));
+            Some(destination)
+        }
+        (None, Some(copy)) => {
+            dropped.push(format!("copy to {} (moves there instead)", copy));
+            Some(copy)
+        }
+        (destination, None) => destination,
+    };
+    let Some(destination) = destination else {
+        return Err("it has no move action, and rules always move files".to_string());
+    };
+
+    let condition = match (&name_pattern, max_age_days) {
+        (Some(_), _) => Some(file_ops::CONDITION_NAME_MATCHES.to_string()),
+        (None, Some(_)) => Some(file_ops::CONDITION_OLDER_THAN.to_string()),
+        (None, None) => None,
+    };
+    let is_extension = name_pattern.is_none() && !extensions.is_empty();
+    let rule = Rule {
+        id: 0,
+        name: name.trim().to_string(),
+        pattern: name_pattern.unwrap_or_else(|| extensions.join(",")),
+        destination_folder: destination,
+        is_extension,
+        is_active: false,
+        preserve_structure: false,
+        condition,
+        tag_id: None,
+        max_age_days,
+        applies_to_folders: false,
+        exclude_auto_tags: false,
+    };
+    RuleEngine::validate(&rule).map_err(|e| format!("{:#}", e))?;
+    Ok(Translated { rule, tag, dropped })
+}
+
+// A name condition as a glob, over the name without its extension (Hazel's
+// "name") or over the whole file name
+enum NameGlob {
+    Stem(String),
+    Whole(String),
+}
+
+// The glob for a name condition; None for an operator globs can't express, or
+// a value with "*" or "?" in it, which globs can't match literally
+fn name_glob(attribute: &str, operator: &str, value: &str) -> Option<NameGlob> {
+    if value.is_empty() || value.contains(['*', '?']) {
+        return None;
+    }
+    let glob = match operator {
+        "is" => value.to_string(),
+        "contains" => format!("*{}*", value),
+        "starts_with" => format!("{}*", value),
+        "ends_with" => format!("*{}", value),
+        _ => return None,
+    };
+    Some(if attribute == "name" { NameGlob::Stem(glob) } else { NameGlob::Whole(glob) })
+}
+
+// The days in a "not in the last N units" condition
+fn age_in_days(condition: &Dictionary) -> Option<i64> {
+    let amount = match condition.get("value")? {
+        Value::Integer(amount) => amount.as_signed()?,
+        Value::Real(amount) => amount.round() as i64,
+        Value::String(amount) => amount.trim().parse().ok()?,
+        _ => return None,
+    };
+    let days_per_unit = match string(condition, "unit").map(|unit| key(&unit)).as_deref() {
+        None | Some("day" | "days") => 1,
+        Some("week" | "weeks") => 7,
+        Some("month" | "months") => 30,
+        Some("year" | "years") => 365,
+        Some(_) => return None,
+    };
+    Some(amount * days_per_unit).filter(|days| *days > 0)
+}
+
+// A condition or action as text for the report, e.g. "size is greater than 10 MB"
+fn describe(entry: &Dictionary) -> String {
+    ["attribute", "action", "operator", "value", "unit", "destination"]
+        .iter()
+        .filter_map(|field| entry.get(field).and_then(value_text))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Attributes and operators may be written as words ("Date Last Modified");
+// compare them as lowercase keys
+fn key(text: &str) -> String {
+    text.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+fn string(dict: &Dictionary, field: &str) -> Option<String> {
+    dict.get(field).and_then(Value::as_string).map(str::to_string)
+}
+
+fn list<'a>(dict: &'a Dictionary, field: &str) -> &'a [Value] {
+    dict.get(field).and_then(Value::as_array).map_or(&[], Vec::as_slice)
+}
+
+fn value_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Integer(number) => Some(number.to_string()),
+        Value::Real(number) => Some(number.to_string()),
+        Value::Boolean(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+// Folders in the home folder may be written as "~/..."
+fn expand_home(folder: &str) -> String {
+    match (folder.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+        _ => folder.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(xml: &str) -> Dictionary {
+        let plist = format!(r#"<?xml version="1.0" encoding="UTF-8"?><plist version="1.0">{}</plist>"#, xml);
+        Value::from_reader(std::io::Cursor::new(plist.into_bytes()))
+            .unwrap()
+            .into_dictionary()
+            .unwrap()
+    }
+
+    #[test]
+    fn supported_conditions_are_translated_and_the_rest_reported() {
+        let rule = entry(r#"<dict>
+            <key>conditions</key><array>
+                <dict><key>attribute</key><string>Extension</string><key>operator</key><string>is</string><key>value</key><string>.PDF</string></dict>
+                <dict><key>attribute</key><string>Date Last Modified</string><key>operator</key><string>is not in the last</string><key>value</key><integer>2</integer><key>unit</key><string>weeks</string></dict>
+                <dict><key>attribute</key><string>size</string><key>operator</key><string>is greater than</string><key>value</key><string>10 MB</string></dict>
+            </array>
+            <key>actions</key><array>
+                <dict><key>action</key><string>move</string><key>destination</key><string>/organizer-test/Invoices</string></dict>
+                <dict><key>action</key><string>add tags</string><key>tags</key><array><string>Finance</string><string>2024</string></array></dict>
+            </array>
+        </dict>"#);
+
+        let translated = translate_rule("Invoices", &rule).unwrap();
+        assert_eq!(translated.rule.pattern, "pdf");
+        assert!(translated.rule.is_extension);
+        assert!(!translated.rule.is_active);
+        assert_eq!(translated.rule.condition.as_deref(), Some(file_ops::CONDITION_OLDER_THAN));
+        assert_eq!(translated.rule.max_age_days, Some(14));
+        assert_eq!(translated.tag.as_deref(), Some("Finance"));
+        assert_eq!(translated.dropped, ["size is greater than 10 MB", "tag 2024"]);
+    }
+
+    #[test]
+    fn rules_without_a_move_are_skipped() {
+        let rule = entry(r#"<dict>
+            <key>conditions</key><array>
+                <dict><key>attribute</key><string>extension</string><key>operator</key><string>is</string><key>value</key><string>tiff</string></dict>
+            </array>
+            <key>actions</key><array><dict><key>action</key><string>add tags</string><key>tag</key><string>Scans</string></dict></array>
+        </dict>"#);
+        assert!(translate_rule("Scans", &rule).is_err());
+    }
+
+    #[test]
+    fn keyed_archives_are_rejected() {
+        let archive = entry("<dict><key>$archiver</key><string>NSKeyedArchiver</string></dict>");
+        assert!(matches!(rule_entries(&Value::Dictionary(archive)), Err(ExternalRulesError::KeyedArchive)));
+    }
+
+    fn conditions(match_kind: &str, conditions: &[(&str, &str, &str)]) -> Dictionary {
+        let conditions: String = conditions
+            .iter()
+            .map(|(attribute, operator, value)| {
+                format!(
+                    "<dict><key>attribute</key><string>{}</string><key>operator</key><string>{}</string><key>value</key><string>{}</string></dict>",
+                    attribute, operator, value
+                )
+            })
+            .collect();
+        entry(&format!(
+            r#"<dict><key>match</key><string>{}</string><key>conditions</key><array>{}</array>
+            <key>actions</key><array><dict><key>action</key><string>move</string><key>destination</key><string>/organizer-test/Out</string></dict></array></dict>"#,
+            match_kind, conditions
+        ))
+    }
+
+    // Match kind, conditions, the pattern they make and what's dropped
+    type NameCase<'a> = (&'a str, &'a [(&'a str, &'a str, &'a str)], &'a str, &'a [&'a str]);
+
+    #[test]
+    fn name_conditions_become_name_globs() {
+        let cases: [NameCase; 6] = [
+            ("all", &[("Name", "starts with", "Invoice"), ("Extension", "is", "pdf")], "Invoice*.pdf", &[]),
+            ("all", &[("Name", "contains", "draft")], "*draft*.*", &[]),
+            ("all", &[("Full Name", "is", "notes.txt")], "notes.txt", &[]),
+            ("all", &[("Full Name", "ends with", ".tar.gz"), ("Extension", "is", "gz")], "*.tar.gz", &["extension is gz"]),
+            ("all", &[("Name", "is", "a"), ("Name", "ends with", "b")], "a.*", &["Name ends with b"]),
+            ("any", &[("Name", "is", "a"), ("Extension", "is", "pdf")], "pdf", &["Name is a"]),
+        ];
+        for (match_kind, entries, pattern, dropped) in cases {
+            let translated = translate_rule("Names", &conditions(match_kind, entries)).unwrap();
+            assert_eq!(translated.rule.pattern, pattern);
+            assert_eq!(translated.rule.is_extension, match_kind == "any");
+            if match_kind == "all" {
+                assert_eq!(translated.rule.condition.as_deref(), Some(file_ops::CONDITION_NAME_MATCHES));
+                assert!(RuleEngine::validate(&translated.rule).is_ok());
+            }
+            assert_eq!(translated.dropped, dropped);
+        }
+
+        // Globs can't match a literal "*", nor say "doesn't contain"
+        for (operator, value) in [("is", "a*b"), ("does not contain", "x")] {
+            assert!(translate_rule("Names", &conditions("all", &[("Name", operator, value)])).is_err());
+        }
+    }
+
+    #[test]
+    fn a_copy_becomes_the_move_and_is_reported() {
+        let rule = entry(r#"<dict>
+            <key>conditions</key><array>
+                <dict><key>attribute</key><string>extension</string><key>operator</key><string>is</string><key>value</key><string>jpg</string></dict>
+            </array>
+            <key>actions</key><array><dict><key>action</key><string>copy</string><key>destination</key><string>/organizer-test/Photos</string></dict></array>
+        </dict>"#);
+        let translated = translate_rule("Photos", &rule).unwrap();
+        assert_eq!(translated.rule.destination_folder, "/organizer-test/Photos");
+        assert!(!translated.rule.is_active);
+        assert_eq!(translated.dropped, ["copy to /organizer-test/Photos (moves there instead)"]);
+    }
+}
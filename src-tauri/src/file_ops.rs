@@ -1,354 +1,4532 @@
 use anyhow::Result;
-use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Duration;
 use notify_debouncer_mini::{new_debouncer, DebouncedEvent};
 use tokio::sync::mpsc;
-use tauri::{AppHandle, Manager, Emitter};
+use tauri::{AppHandle, Manager, Emitter, Runtime};
+use crate::commands::{DuplicateFile, DuplicateGroup, FileInfo, Rule, SimilarImage};
 use crate::database;
+use crate::events;
+use crate::health;
+use crate::hooks;
+use crate::ignore_list::{self, IgnoreList};
+use crate::notifications;
+use crate::rule_engine::{FileFacts, MatchedRule, RuleEngine};
+use crate::schedule;
+use crate::tray;
+use crate::utils;
+use crate::webhook;
+
+// Number of file events buffered between the watcher and the organizer task
+const WATCHER_CHANNEL_CAPACITY: usize = 1000;
+
+// Most queued file events organized and indexed together
+const WATCHER_BATCH_SIZE: usize = 100;
+
+// Setting with the number of workers organizing each watched folder's events;
+// unset or empty uses the number of CPU cores, up to DEFAULT_MAX_WATCHER_WORKERS
+pub const WATCHER_WORKERS_SETTING: &str = "watcher_workers";
+
+const DEFAULT_MAX_WATCHER_WORKERS: usize = 4;
+pub const MAX_WATCHER_WORKERS: usize = 16;
+
+// How long a worker waits before retrying paths another worker is busy with
+const IN_FLIGHT_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+// Event type of a watcher event for a folder directly in a watched folder,
+// which folder rules may move as a whole
+const FOLDER_EVENT: &str = "folder";
+
+// How long nothing in a folder must change before a folder rule moves it, so
+// a camera import or an archive being extracted is moved once it's complete
+const FOLDER_SETTLE_TIME: Duration = Duration::from_secs(10);
+
+// Paths being organized right now, so two events for the same file are never
+// handled at the same time, even from overlapping watched folders
+static IN_FLIGHT_PATHS: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
 
 // State used to hold file watchers
 #[derive(Default)]
 pub struct WatcherState {
     watchers: HashMap<String, notify_debouncer_mini::Debouncer<notify::RecommendedWatcher>>,
+    // Watched paths that are single files rather than folders
+    files: HashSet<String>,
+    // Event processing tasks, awaited on shutdown so queued moves can finish
+    processors: Vec<tokio::task::JoinHandle<()>>,
+    // Folders whose watchers were stopped by pause_watching, to restart on resume
+    paused: Vec<String>,
+    // When a timed pause ends by itself
+    paused_until: Option<DateTime<Utc>>,
+    // Bumped on every pause/resume so a stale auto-resume timer does nothing
+    pause_generation: u64,
+    // Paths whose watcher is being set up, so a second call for the same path
+    // doesn't start a second watcher while the first is still starting, and
+    // whether each is a single file
+    starting: HashMap<String, bool>,
+    // Watchers stopped by a failure, with the error. Only kept in memory: the
+    // folder stays watched in the database and is tried again next launch
+    failed: HashMap<String, String>,
+}
+
+// A watched folder and whether it's currently active or paused
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct WatchedFolder {
+    pub path: String,
+    pub is_file: bool,
+    pub paused: bool,
+    pub paused_until: Option<String>,
+    // Rules the folder is limited to; None when every rule applies
+    pub rule_ids: Option<Vec<i64>>,
+    // Why the watcher stopped, for folders that aren't watched after a failure
+    pub error: Option<String>,
+}
+
+// State used to cancel in-flight folder size scans, keyed by path
+#[derive(Default)]
+pub struct FolderSizeState {
+    scans: HashMap<String, Arc<AtomicBool>>,
+}
+
+// Event struct for frontend
+#[derive(Clone, serde::Serialize)]
+pub struct FileEvent {
+    pub path: String,
+    pub file_name: String,
+    pub event_type: String,
+    pub extension: String,
+    pub size: u64,
+    // What the organizer did with the file, once it has run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<OrganizeOutcome>,
+}
+
+// What organizing a file did; serialized as {"status": "moved", ...}
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum OrganizeOutcome {
+    Moved {
+        from: String,
+        to: String,
+        // None for a move to a folder picked by hand
+        rule: Option<String>,
+        // The name was numbered because the destination already had the file
+        renamed: bool,
+        // The file's category tag was left off, by the rule's
+        // exclude_auto_tags or the file's auto-tag override
+        auto_tag_suppressed: bool,
+    },
+    Skipped { reason: SkipReason },
+    // Already in the folder its rule puts it in
+    AlreadyOrganized { rule: String },
+    Failed { error: String },
+}
+
+impl OrganizeOutcome {
+    fn moved(from: &Path, to: &Path, rule: Option<String>, auto_tag_suppressed: bool) -> OrganizeOutcome {
+        OrganizeOutcome::Moved {
+            from: from.to_string_lossy().to_string(),
+            to: to.to_string_lossy().to_string(),
+            rule,
+            renamed: from.file_name() != to.file_name(),
+            auto_tag_suppressed,
+        }
+    }
+    
+    fn failed(e: &anyhow::Error) -> OrganizeOutcome {
+        OrganizeOutcome::Failed { error: format!("{:#}", e) }
+    }
+}
+
+// Why a file was left where it was
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    NotFound,
+    NoExtension,
+    NoMatchingRule,
+    InUse,
+    ReadOnly,
+    // Indexed and queued until the active hours start
+    OutsideActiveHours,
+    // On the ignore list
+    Ignored,
+    // A hard link or note left where a moved file used to be
+    SourceMirror,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            SkipReason::NotFound => "the file doesn't exist",
+            SkipReason::NoExtension => "the file has no extension",
+            SkipReason::NoMatchingRule => "no rule applies to the file",
+            SkipReason::InUse => "the file is in use by another program",
+            SkipReason::ReadOnly => "the file is read-only",
+            SkipReason::OutsideActiveHours => "files are only moved during the active hours",
+            SkipReason::Ignored => "the file is on the ignore list",
+            SkipReason::SourceMirror => "the file was left behind for a file that was moved",
+        })
+    }
+}
+
+// Setting limiting how many folder levels below a watched or scanned folder
+// are organized; 0 means only the folder itself, unset or empty means no limit
+pub const MAX_DEPTH_SETTING: &str = "max_depth";
+
+fn max_depth_setting<R: Runtime>(app: &AppHandle<R>) -> Result<Option<u32>> {
+    match database::get_setting(app, MAX_DEPTH_SETTING)? {
+        Some(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse::<u32>()
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("Invalid {} setting: {}", MAX_DEPTH_SETTING, value)),
+        _ => Ok(None),
+    }
+}
+
+// Setting that lets the organizer clear the read-only flag of a file it moves
+// to another drive, so the original can be removed. When off, such files are
+// left where they are
+pub const CLEAR_READ_ONLY_SETTING: &str = "clear_read_only";
+
+// Setting that organizes hidden files too: dotfiles, or on Windows files with
+// the hidden attribute. Off by default
+pub const INCLUDE_HIDDEN_SETTING: &str = "include_hidden";
+
+fn watcher_workers_setting(app: &AppHandle) -> Result<usize> {
+    match database::get_setting(app, WATCHER_WORKERS_SETTING)? {
+        Some(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|workers| (1..=MAX_WATCHER_WORKERS).contains(workers))
+            .ok_or_else(|| anyhow::anyhow!("Invalid {} setting: {}", WATCHER_WORKERS_SETTING, value)),
+        _ => Ok(std::thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1)
+            .min(DEFAULT_MAX_WATCHER_WORKERS)),
+    }
+}
+
+// Paths claimed in IN_FLIGHT_PATHS, released when dropped
+struct InFlightPaths(Vec<PathBuf>);
+
+impl Drop for InFlightPaths {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT_PATHS.lock().unwrap();
+        for path in &self.0 {
+            in_flight.remove(path);
+        }
+    }
+}
+
+// Path claimed in WatcherState::starting, released when dropped
+struct StartingWatch {
+    state: Arc<Mutex<WatcherState>>,
+    path: String,
+}
+
+impl Drop for StartingWatch {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().starting.remove(&self.path);
+    }
+}
+
+// Claim a path before setting up its watcher. None when it's already watched,
+// paused or being started, in which case there's nothing to do. Folders where
+// one is watched deeply enough to reach the other are refused, whichever comes
+// first, since both watchers would organize the same files
+fn claim_watch(app: &AppHandle, path: &str, is_file: bool) -> Result<Option<StartingWatch>> {
+    let max_depth = max_depth_setting(app)?;
+    let state = app.state::<Arc<Mutex<WatcherState>>>().inner().clone();
+    claim_watch_in(&state, path, is_file, max_depth)
+}
+
+fn claim_watch_in(
+    state: &Arc<Mutex<WatcherState>>,
+    path: &str,
+    is_file: bool,
+    max_depth: Option<u32>,
+) -> Result<Option<StartingWatch>> {
+    let normalized = utils::normalize_path(Path::new(path));
+    let mut state_guard = state.lock().unwrap();
+    
+    let watched = state_guard
+        .watchers
+        .keys()
+        .chain(&state_guard.paused)
+        .map(|other| (other, state_guard.files.contains(other)))
+        .chain(state_guard.starting.iter().map(|(other, is_file)| (other, *is_file)));
+    for (other, other_is_file) in watched {
+        let other_normalized = utils::normalize_path(Path::new(other));
+        if other == path || other_normalized == normalized {
+            tracing::debug!(path = %path, "Already watching");
+            return Ok(None);
+        }
+        
+        // A watched file only sees events for itself, and a watched file
+        // inside a watched folder is kept up to date rather than organized
+        if is_file || other_is_file {
+            continue;
+        }
+        let reaches = |root: &Path, folder: &Path| {
+            utils::folder_depth(root, &folder.join("_"))
+                .is_some_and(|depth| max_depth.is_none_or(|max_depth| depth <= max_depth as usize))
+        };
+        if reaches(&other_normalized, &normalized) {
+            return Err(anyhow::anyhow!("{} is already watched as part of {}", path, other));
+        }
+        if reaches(&normalized, &other_normalized) {
+            return Err(anyhow::anyhow!("{} contains {}, which is already watched", path, other));
+        }
+    }
+    
+    state_guard.starting.insert(path.to_string(), is_file);
+    drop(state_guard);
+    Ok(Some(StartingWatch { state: state.clone(), path: path.to_string() }))
+}
+
+// Wait for the next file event, and take a share of whatever else is already
+// queued with it, e.g. a burst of files from unzipping, so their moves are
+// indexed together. None once the watcher is gone
+async fn next_watcher_batch(rx: &tokio::sync::Mutex<mpsc::Receiver<FileEvent>>, workers: usize) -> Option<Vec<FileEvent>> {
+    let mut rx = rx.lock().await;
+    let event = rx.recv().await?;
+    let share = (rx.len() + 1).div_ceil(workers).min(WATCHER_BATCH_SIZE);
+    let mut events = vec![event];
+    while events.len() < share {
+        match rx.try_recv() {
+            Ok(event) => events.push(event),
+            Err(_) => break,
+        }
+    }
+    Some(events)
+}
+
+// Organize a batch of watcher events off the async runtime, then report them
+// to the frontend. Paths another worker is still busy with wait their turn
+async fn process_watcher_events(app: &AppHandle, root: &str, mut events: Vec<FileEvent>) {
+    // Folders a folder rule moves take the files in them along, so those files
+    // aren't organized one by one
+    let folders = take_rule_folders(app, root, &mut events);
+    if !folders.is_empty() {
+        if schedule::is_active_now(app) {
+            for folder in folders {
+                spawn_folder_move(app, root, folder);
+            }
+        } else {
+            for folder in folders {
+                log_if_err(
+                    database::add_pending_action(app, &folder.to_string_lossy(), root),
+                    "Failed to queue folder",
+                );
+            }
+        }
+    }
+    
+    // Ignored files are never queued or organized; they're only indexed when
+    // that's turned on. An unreadable list leaves it to the organizer, which
+    // reads it again and reports the failure
+    let (ignored, events): (Vec<FileEvent>, Vec<FileEvent>) = match IgnoreList::load(app) {
+        Ok(ignore_list) => events.into_iter().partition(|event| ignore_list.is_ignored(Path::new(&event.path))),
+        Err(_) => (vec![], events),
+    };
+    if !ignored.is_empty() {
+        skip_ignored_events(app, ignored).await;
+    }
+    if events.is_empty() {
+        return;
+    }
+    
+    if !schedule::is_active_now(app) {
+        defer_watcher_events(app, root, events).await;
+        return;
+    }
+    
+    // Read for every batch, so limiting a folder to some rules applies at once
+    let rule_limit = match database::get_folder_rule_limit(app, root) {
+        Ok(rule_limit) => rule_limit,
+        Err(e) => {
+            tracing::error!(folder = %root, error = format!("{:#}", e), "Failed to read the folder's rules");
+            return;
+        }
+    };
+    
+    let app_handle = app.clone();
+    let organize = move |paths: &[PathBuf]| organize_files_by_rules(&app_handle, paths, rule_limit.as_deref());
+    organize_when_free(events, organize, |mut event, outcome| {
+        if let OrganizeOutcome::Failed { error } = &outcome {
+            tracing::warn!(path = %event.path, error = %error, "Failed to organize file");
+            events::file_error(app, events::FileError {
+                path: event.path.clone(),
+                message: error.clone(),
+            });
+        }
+        
+        // Deprecated, superseded by the events sent as files are organized
+        event.outcome = Some(outcome);
+        let _ = app.emit(events::LEGACY_FILE_EVENT, event);
+    })
+    .await;
+}
+
+// Organize events off the async runtime, a group at a time as their paths
+// become free, so no path is organized by two workers at once. organize
+// returns the outcome of each path, in order, and report is given each event
+// with its outcome
+async fn organize_when_free<F>(mut pending: Vec<FileEvent>, organize: F, mut report: impl FnMut(FileEvent, OrganizeOutcome))
+where
+    F: Fn(&[PathBuf]) -> Vec<OrganizeOutcome> + Clone + Send + 'static,
+{
+    while !pending.is_empty() {
+        let (claimed, waiting) = claim_in_flight(pending);
+        pending = waiting;
+        if claimed.is_empty() {
+            tokio::time::sleep(IN_FLIGHT_RETRY_DELAY).await;
+            continue;
+        }
+        
+        let paths = InFlightPaths(claimed.iter().map(|event| PathBuf::from(&event.path)).collect());
+        let organize = organize.clone();
+        let outcomes = tokio::task::spawn_blocking(move || organize(&paths.0)).await;
+        let outcomes = match outcomes {
+            Ok(outcomes) => outcomes,
+            Err(e) => {
+                tracing::error!(error = %e, "Organizing watcher events panicked");
+                claimed.iter().map(|_| OrganizeOutcome::Failed { error: e.to_string() }).collect()
+            }
+        };
+        
+        for (event, outcome) in claimed.into_iter().zip(outcomes) {
+            report(event, outcome);
+        }
+    }
+}
+
+// Split events into those whose paths were free and are now claimed in
+// IN_FLIGHT_PATHS, and those another worker is still busy with
+fn claim_in_flight(events: Vec<FileEvent>) -> (Vec<FileEvent>, Vec<FileEvent>) {
+    let mut in_flight = IN_FLIGHT_PATHS.lock().unwrap();
+    events
+        .into_iter()
+        .partition(|event| in_flight.insert(PathBuf::from(&event.path)))
+}
+
+// Take folder events, and events for files inside folders a folder rule moves,
+// out of a batch, returning those folders. Ignored folders are left where
+// they are, with everything in them
+fn take_rule_folders(app: &AppHandle, root: &str, events: &mut Vec<FileEvent>) -> Vec<PathBuf> {
+    let engine = database::get_folder_rule_limit(app, root)
+        .and_then(|rule_limit| RuleEngine::load_limited(app, rule_limit.as_deref()));
+    let engine = match engine {
+        Ok(engine) if engine.has_folder_rules() => engine,
+        result => {
+            if let Err(e) = result {
+                tracing::error!(folder = %root, error = format!("{:#}", e), "Failed to read folder rules");
+            }
+            events.retain(|event| event.event_type != FOLDER_EVENT);
+            return vec![];
+        }
+    };
+    
+    // The folder directly in the watched folder each event is in or for
+    let root = utils::normalize_path(Path::new(root));
+    let top_folder = |event: &FileEvent| -> Option<PathBuf> {
+        let path = Path::new(&event.path);
+        let relative = path.strip_prefix(&root).ok()?;
+        let mut components = relative.components();
+        let first = components.next()?;
+        (event.event_type == FOLDER_EVENT || components.next().is_some()).then(|| root.join(first))
+    };
+    
+    let mut folders = BTreeSet::new();
+    events.retain(|event| {
+        let folder = top_folder(event)
+            .filter(|folder| utils::get_file_name(folder).is_some_and(|name| engine.matching_folder(&name).is_some()));
+        match folder {
+            Some(folder) => {
+                folders.insert(folder);
+                false
+            }
+            None => event.event_type != FOLDER_EVENT,
+        }
+    });
+    
+    match IgnoreList::load(app) {
+        Ok(ignore_list) => folders.into_iter().filter(|folder| !ignore_list.is_ignored(folder)).collect(),
+        Err(e) => {
+            tracing::error!(error = format!("{:#}", e), "Failed to read the ignore list");
+            vec![]
+        }
+    }
+}
+
+// Move a folder with its folder rule once nothing in it has changed for
+// FOLDER_SETTLE_TIME. A folder already waiting or being moved is left to that
+// move
+fn spawn_folder_move(app: &AppHandle, root: &str, folder: PathBuf) {
+    if !IN_FLIGHT_PATHS.lock().unwrap().insert(folder.clone()) {
+        return;
+    }
+    let claim = InFlightPaths(vec![folder.clone()]);
+    let app_handle = app.clone();
+    let root = root.to_string();
+    tauri::async_runtime::spawn(async move {
+        let _claim = claim;
+        let mut last_seen = None;
+        loop {
+            tokio::time::sleep(FOLDER_SETTLE_TIME).await;
+            let snapshot_folder = folder.clone();
+            let snapshot = match tokio::task::spawn_blocking(move || FolderSnapshot::of(&snapshot_folder)).await {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    tracing::error!(error = %e, "Checking a folder panicked");
+                    return;
+                }
+            };
+            match snapshot {
+                Ok(snapshot) if snapshot.is_settled(last_seen.as_ref()) => break,
+                Ok(snapshot) => last_seen = Some(snapshot),
+                // Gone: there's nothing left to wait for
+                Err(_) if !folder.exists() => return,
+                Err(e) => {
+                    tracing::warn!(folder = %folder.display(), error = %e, "Can't read folder, leaving it where it is");
+                    return;
+                }
+            }
+        }
+        if !folder.is_dir() {
+            return;
+        }
+        
+        let moving_app = app_handle.clone();
+        let moving_folder = folder.clone();
+        let result = tokio::task::spawn_blocking(move || move_folder_by_rules(&moving_app, &root, &moving_folder)).await;
+        match result {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                tracing::warn!(folder = %folder.display(), error = format!("{:#}", e), "Failed to move folder");
+                events::file_error(&app_handle, events::FileError {
+                    path: folder.to_string_lossy().to_string(),
+                    message: format!("{:#}", e),
+                });
+            }
+            Err(e) => tracing::error!(error = %e, "Moving a folder panicked"),
+        }
+    });
+}
+
+// What a folder looks like, for telling whether anything in it still changes
+#[derive(Debug, PartialEq, Eq)]
+struct FolderSnapshot {
+    newest: std::time::SystemTime,
+    files: usize,
+    bytes: u64,
+}
+
+impl FolderSnapshot {
+    fn of(folder: &Path) -> std::io::Result<Self> {
+        fn add(dir: &Path, snapshot: &mut FolderSnapshot) -> std::io::Result<()> {
+            snapshot.newest = snapshot.newest.max(fs::metadata(dir)?.modified()?);
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    add(&entry.path(), snapshot)?;
+                } else if !file_type.is_symlink() {
+                    let metadata = entry.metadata()?;
+                    snapshot.newest = snapshot.newest.max(metadata.modified()?);
+                    snapshot.files += 1;
+                    snapshot.bytes += metadata.len();
+                }
+            }
+            Ok(())
+        }
+        
+        let mut snapshot = FolderSnapshot { newest: std::time::SystemTime::UNIX_EPOCH, files: 0, bytes: 0 };
+        add(folder, &mut snapshot)?;
+        Ok(snapshot)
+    }
+    
+    // Settled once nothing has changed for FOLDER_SETTLE_TIME: the newest
+    // change is that old, or nothing changed since the last look. The second
+    // lets a folder holding a file dated in the future (e.g. from a camera
+    // with its clock set wrong) settle too
+    fn is_settled(&self, last_seen: Option<&FolderSnapshot>) -> bool {
+        last_seen == Some(self) || self.newest.elapsed().is_ok_and(|elapsed| elapsed >= FOLDER_SETTLE_TIME)
+    }
+}
+
+// Report watcher events for ignored files, indexing the files first when
+// INDEX_IGNORED_SETTING is on
+async fn skip_ignored_events(app: &AppHandle, events: Vec<FileEvent>) {
+    let index = database::is_setting_enabled(app, ignore_list::INDEX_IGNORED_SETTING).unwrap_or(false);
+    let app_handle = app.clone();
+    let results = tokio::task::spawn_blocking(move || {
+        let indexed: Vec<Result<()>> = match index {
+            true => {
+                let paths: Vec<_> = events.iter().map(|event| Path::new(&event.path)).collect();
+                refresh_watched_files(&app_handle, &paths).into_iter().map(|result| result.map(|_| ())).collect()
+            }
+            false => events.iter().map(|_| Ok(())).collect(),
+        };
+        events.into_iter().zip(indexed).collect::<Vec<_>>()
+    }).await;
+    let results = match results {
+        Ok(results) => results,
+        Err(e) => {
+            tracing::error!(error = %e, "Indexing ignored files panicked");
+            return;
+        }
+    };
+    
+    for (mut event, result) in results {
+        if let Err(e) = result {
+            tracing::warn!(path = %event.path, error = format!("{:#}", e), "Failed to index ignored file");
+        }
+        
+        // Deprecated, superseded by file-indexed
+        event.outcome = Some(OrganizeOutcome::Skipped { reason: SkipReason::Ignored });
+        let _ = app.emit(events::LEGACY_FILE_EVENT, event);
+    }
+}
+
+// Outside the active hours, index the files and queue them to be organized
+// once the hours start
+async fn defer_watcher_events(app: &AppHandle, root: &str, events: Vec<FileEvent>) {
+    let app_handle = app.clone();
+    let watch_root = root.to_string();
+    let results = tokio::task::spawn_blocking(move || {
+        let paths: Vec<_> = events.iter().map(|event| Path::new(&event.path)).collect();
+        let indexed = refresh_watched_files(&app_handle, &paths);
+        events
+            .into_iter()
+            .zip(indexed)
+            .map(|(event, indexed)| {
+                let result = indexed.and_then(|indexed| {
+                    // Gone before it could be indexed, there's nothing to move later
+                    if indexed.event_type == "removed" {
+                        return Ok(OrganizeOutcome::Skipped { reason: SkipReason::NotFound });
+                    }
+                    database::add_pending_action(&app_handle, &event.path, &watch_root)?;
+                    Ok(OrganizeOutcome::Skipped { reason: SkipReason::OutsideActiveHours })
+                });
+                (event, result)
+            })
+            .collect::<Vec<_>>()
+    }).await;
+    let results = match results {
+        Ok(results) => results,
+        Err(e) => {
+            tracing::error!(error = %e, "Queueing watcher events panicked");
+            return;
+        }
+    };
+    
+    for (mut event, result) in results {
+        let outcome = result.unwrap_or_else(|e| {
+            tracing::warn!(path = %event.path, error = format!("{:#}", e), "Failed to queue file");
+            events::file_error(app, events::FileError {
+                path: event.path.clone(),
+                message: format!("{:#}", e),
+            });
+            OrganizeOutcome::failed(&e)
+        });
+        
+        // Deprecated, superseded by file-indexed
+        event.outcome = Some(outcome);
+        let _ = app.emit(events::LEGACY_FILE_EVENT, event);
+    }
+}
+
+// Start watching a folder, or a single file. Watching a path that's already
+// watched, or still being set up by another call, does nothing
+pub async fn start_watching(app: &AppHandle, path: String) -> Result<()> {
+    let is_file = Path::new(&path).is_file();
+    let Some(_claim) = claim_watch(app, &path, is_file)? else {
+        return Ok(());
+    };
+    if is_file {
+        return start_watching_file(app, path).await;
+    }
+    
+    // Create state if it doesn't exist
+    let state = app.state::<Arc<Mutex<WatcherState>>>();
+    
+    // notify watches the whole tree, so events from deeper than the limit are
+    // dropped here; the limit is read once, when watching starts
+    let max_depth = max_depth_setting(app)?;
+    let include_hidden = database::is_setting_enabled(app, INCLUDE_HIDDEN_SETTING)?;
+    let workers = watcher_workers_setting(app)?;
+    let depth_root = utils::normalize_path(Path::new(&path));
+    
+    // Create channel for events
+    let (tx, rx) = mpsc::channel::<FileEvent>(WATCHER_CHANNEL_CAPACITY);
+    
+    // Create debouncer - properly implemented for notify-debouncer-mini 0.4
+    let event_handler = watcher_event_handler(app.clone(), path.clone(), depth_root, max_depth, include_hidden, tx);
+    let mut debouncer = new_debouncer(Duration::from_secs(2), event_handler)?;
+    
+    // Start watcher
+    let recursive_mode = if max_depth == Some(0) {
+        notify::RecursiveMode::NonRecursive
+    } else {
+        notify::RecursiveMode::Recursive
+    };
+    match debouncer.watcher().watch(Path::new(&path), recursive_mode) {
+        Ok(_) => {
+            let mut state_guard = state.lock().unwrap();
+            state_guard.failed.remove(&path);
+            state_guard.watchers.insert(path.clone(), debouncer);
+        }
+        Err(e) => {
+            return Err(anyhow::anyhow!("Failed to watch path: {}", e));
+        }
+    }
+    
+    // Create the workers that process file events. They share the queue, so
+    // a burst of files is split between them and moved in parallel
+    let rx = Arc::new(tokio::sync::Mutex::new(rx));
+    let mut processors = vec![];
+    for _ in 0..workers {
+        let app_handle = app.clone();
+        let rx = rx.clone();
+        let root = path.clone();
+        processors.push(tokio::spawn(async move {
+            while let Some(events) = next_watcher_batch(&rx, workers).await {
+                process_watcher_events(&app_handle, &root, events).await;
+            }
+        }));
+    }
+    {
+        let mut state_guard = state.lock().unwrap();
+        state_guard.processors.retain(|p| !p.is_finished());
+        state_guard.processors.extend(processors);
+    }
+    
+    save_watched_path(app, path.clone(), false);
+    
+    tracing::info!(folder = %path, "Started watching folder");
+    watch_status_changed(app);
+    Ok(())
+}
+
+// Handle a batch of debounced events from a folder's watcher: queue the
+// files in it for the workers, or report lost events or a failed watcher
+fn watcher_event_handler<R: Runtime>(
+    app: AppHandle<R>,
+    watch_root: String,
+    depth_root: PathBuf,
+    max_depth: Option<u32>,
+    include_hidden: bool,
+    tx: mpsc::Sender<FileEvent>,
+) -> impl FnMut(notify::Result<Vec<DebouncedEvent>>) + Send + 'static {
+    move |res: notify::Result<Vec<DebouncedEvent>>| {
+        let events = match res {
+            Ok(events) => events,
+            Err(e) if is_event_overflow(&e) => {
+                let rescan_queued = queue_overflow_rescan(&app, &watch_root);
+                report_watch_overflow(&app, &watch_root, &e, rescan_queued);
+                return;
+            }
+            Err(e) => {
+                tracing::error!(folder = %watch_root, error = %e, "File watcher error");
+                report_watcher_failure(&app, &watch_root, e.to_string());
+                return;
+            }
+        };
+        if !depth_root.is_dir() {
+            report_watcher_failure(&app, &watch_root, "The watched folder no longer exists".to_string());
+            return;
+        }
+        
+        for e in events {
+            let path = utils::normalize_path(&e.path);
+            
+            // Skip hidden files or files in hidden folders, and folders other
+            // than those directly in the watched folder, the only ones folder
+            // rules move
+            let is_dir = path.is_dir();
+            if is_dir && path.parent() != Some(depth_root.as_path()) {
+                continue;
+            }
+            if !include_hidden && utils::is_hidden_under(&depth_root, &path) {
+                continue;
+            }
+            
+            if utils::is_beyond_depth(&depth_root, &path, max_depth) {
+                continue;
+            }
+            
+            // Get file extension and name
+            let extension = match is_dir {
+                true => String::new(),
+                false => utils::get_file_extension(&path).unwrap_or_default(),
+            };
+            let file_name = utils::get_file_name(&path).unwrap_or_default();
+            
+            // Get file size
+            let size = match is_dir {
+                true => 0,
+                false => fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            };
+            
+            // Create event
+            let file_event = FileEvent {
+                path: path.to_string_lossy().to_string(),
+                file_name,
+                extension,
+                size,
+                event_type: if is_dir { FOLDER_EVENT } else { "created" }.into(),
+                outcome: None,
+            };
+            
+            // Send to channel; the debouncer runs on its own thread, so
+            // blocking here applies backpressure instead of dropping events
+            // during bursts (e.g. unzipping hundreds of files)
+            if tx.blocking_send(file_event).is_err() {
+                // Receiver is gone, the watcher is being torn down
+                return;
+            }
+        }
+    }
+}
+
+// Watch a folder applying only some rules to it, or every rule when rule_ids
+// is None. Fails if a rule doesn't exist. A folder already being watched
+// keeps its watcher and switches rules from its next batch of files
+pub async fn watch_folder_with_rules(app: &AppHandle, path: String, rule_ids: Option<&[i64]>) -> Result<()> {
+    if let Some(rule_ids) = rule_ids {
+        if rule_ids.is_empty() {
+            return Err(anyhow::anyhow!("Choose at least one rule"));
+        }
+        if !Path::new(&path).is_dir() {
+            return Err(anyhow::anyhow!("{} is not a folder", path));
+        }
+    }
+    database::set_watched_folder_rules(app, &path, rule_ids.unwrap_or_default())?;
+    
+    let already_watched = {
+        let state = app.state::<Arc<Mutex<WatcherState>>>();
+        let state_guard = state.lock().unwrap();
+        state_guard.watchers.contains_key(&path) || state_guard.paused.contains(&path)
+    };
+    if already_watched {
+        watch_status_changed(app);
+        return Ok(());
+    }
+    start_watching(app, path).await
+}
+
+// Watch a single file. notify can only watch folders reliably (editors often
+// save by replacing the file), so the file's folder is watched on its own and
+// only events for the file are kept. Each file gets its own watcher, so
+// several files in one folder, or the folder itself, can be watched and
+// stopped independently. Watched files are kept up to date in the index
+// rather than organized
+async fn start_watching_file(app: &AppHandle, path: String) -> Result<()> {
+    let state = app.state::<Arc<Mutex<WatcherState>>>();
+    let file_path = utils::normalize_path(Path::new(&path));
+    let parent = file_path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Invalid file path"))?
+        .to_path_buf();
+    
+    let (tx, mut rx) = mpsc::channel::<PathBuf>(WATCHER_CHANNEL_CAPACITY);
+    let watch_file = path.clone();
+    let watch_parent = parent.clone();
+    let app_handle = app.clone();
+    let event_handler = move |res: notify::Result<Vec<DebouncedEvent>>| {
+        let events = match res {
+            Ok(events) => events,
+            // The file may have changed among the lost events, so check it again
+            Err(e) if is_event_overflow(&e) => {
+                let rescan_queued = tx.blocking_send(file_path.clone()).is_ok();
+                report_watch_overflow(&app_handle, &watch_file, &e, rescan_queued);
+                return;
+            }
+            Err(e) => {
+                tracing::error!(file = %watch_file, error = %e, "File watcher error");
+                report_watcher_failure(&app_handle, &watch_file, e.to_string());
+                return;
+            }
+        };
+        // The file itself may come and go (editors save by replacing it), but
+        // without its folder there's nothing left to watch
+        if !watch_parent.is_dir() {
+            report_watcher_failure(&app_handle, &watch_file, "The watched file's folder no longer exists".to_string());
+            return;
+        }
+        
+        if events.iter().any(|e| utils::normalize_path(&e.path) == file_path) {
+            // Receiver is gone when the watcher is being torn down
+            let _ = tx.blocking_send(file_path.clone());
+        }
+    };
+    
+    let mut debouncer = new_debouncer(Duration::from_secs(2), event_handler)?;
+    debouncer
+        .watcher()
+        .watch(&parent, notify::RecursiveMode::NonRecursive)
+        .map_err(|e| anyhow::anyhow!("Failed to watch path: {}", e))?;
+    
+    // Index the file now, so there's a row to keep up to date
+    if let Err(e) = refresh_watched_file(app, Path::new(&path)) {
+        tracing::warn!(file = %path, error = format!("{:#}", e), "Failed to index watched file");
+    }
+    
+    let app_handle = app.clone();
+    let processor = tokio::spawn(async move {
+        while let Some(file_path) = rx.recv().await {
+            let event = match refresh_watched_file(&app_handle, &file_path) {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::warn!(file = %file_path.display(), error = format!("{:#}", e), "Failed to update watched file");
+                    continue;
+                }
+            };
+            let _ = app_handle.emit(events::LEGACY_FILE_EVENT, event);
+        }
+    });
+    
+    {
+        let mut state_guard = state.lock().unwrap();
+        state_guard.failed.remove(&path);
+        state_guard.watchers.insert(path.clone(), debouncer);
+        state_guard.files.insert(path.clone());
+        state_guard.processors.retain(|p| !p.is_finished());
+        state_guard.processors.push(processor);
+    }
+    
+    save_watched_path(app, path.clone(), true);
+    
+    tracing::info!(file = %path, "Started watching file");
+    watch_status_changed(app);
+    Ok(())
+}
+
+// Update a watched file's size and modification time in the index. A changed
+// modification time also makes its cached perceptual hash stale, so it is
+// hashed again next time it's needed. A file that is gone is left in the
+// index for orphan cleanup
+fn refresh_watched_file(app: &AppHandle, path: &Path) -> Result<FileEvent> {
+    refresh_watched_files(app, &[path]).remove(0)
+}
+
+// refresh_watched_file for a burst of watcher events, indexing them all in
+// one transaction. Returns the result for each path, in order
+fn refresh_watched_files(app: &AppHandle, paths: &[&Path]) -> Vec<Result<FileEvent>> {
+    let mut results = Vec::with_capacity(paths.len());
+    let mut new_files = vec![];
+    let mut new_file_indices = vec![];
+    for path in paths {
+        let event = FileEvent {
+            path: path.to_string_lossy().to_string(),
+            file_name: utils::get_file_name(path).unwrap_or_default(),
+            extension: utils::get_file_extension(path).unwrap_or_default(),
+            size: 0,
+            event_type: "removed".into(),
+            outcome: None,
+        };
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                results.push(Ok(event));
+                continue;
+            }
+            Err(e) => {
+                results.push(Err(e.into()));
+                continue;
+            }
+        };
+        
+        let (created, created_approximate) = utils::file_created(&metadata);
+        let modified: DateTime<Utc> = metadata.modified().unwrap_or_else(|_| std::time::SystemTime::now()).into();
+        new_files.push(database::NewFile {
+            path: path.to_path_buf(),
+            name: event.file_name.clone(),
+            extension: event.extension.clone(),
+            size: metadata.len() as i64,
+            created_at: utils::format_timestamp(&created),
+            created_at_approximate: created_approximate,
+            modified_at: utils::format_timestamp(&modified),
+        });
+        new_file_indices.push(results.len());
+        results.push(Ok(FileEvent {
+            size: metadata.len(),
+            event_type: "modified".into(),
+            ..event
+        }));
+    }
+    if new_files.is_empty() {
+        return results;
+    }
+    
+    match database::add_files_batch(app, &new_files) {
+        Ok(indexed) => {
+            for ((i, file), result) in new_file_indices.into_iter().zip(&new_files).zip(indexed) {
+                match result {
+                    Ok(file_id) => events::file_indexed(app, events::FileIndexed {
+                        file_id,
+                        path: file.path.to_string_lossy().to_string(),
+                        size: file.size as u64,
+                    }),
+                    Err(e) => results[i] = Err(e),
+                }
+            }
+        }
+        Err(e) => {
+            for i in new_file_indices {
+                results[i] = Err(anyhow::anyhow!("{:#}", e));
+            }
+        }
+    }
+    results
+}
+
+// Store a watched path in the database. This is async, so we don't await it
+// to avoid blocking
+fn save_watched_path(app: &AppHandle, path: String, is_file: bool) {
+    let app_handle = app.clone();
+    tokio::spawn(async move {
+        if let Err(e) = database::save_watched_path(&app_handle, &path, is_file) {
+            tracing::error!(folder = %path, error = %e, "Failed to save watched folder");
+        }
+    });
+}
+
+// Drop every watcher, and forget paused ones so they aren't resumed
+fn clear_watchers(state_guard: &mut WatcherState) {
+    state_guard.watchers.clear();
+    state_guard.failed.clear();
+    state_guard.files.clear();
+    state_guard.paused.clear();
+    state_guard.paused_until = None;
+    state_guard.pause_generation += 1;
+}
+
+// Stop watching a folder
+pub async fn stop_watching(app: &AppHandle) -> Result<()> {
+    // Get the watcher state if it exists
+    if let Some(state) = app.try_state::<Arc<Mutex<WatcherState>>>() {
+        clear_watchers(&mut state.lock().unwrap());
+        
+        // Update database
+        let app_handle = app.clone();
+        tokio::spawn(async move {
+            if let Err(e) = database::set_watched_paths_inactive(&app_handle, None) {
+                tracing::error!(error = %e, "Failed to mark watched folders inactive");
+            }
+        });
+    }
+    
+    watch_status_changed(app);
+    Ok(())
+}
+
+// Make another profile active. Its rules apply from the next batch of files
+// organized, and the watchers are swapped for the folders it watches; a pause
+// doesn't carry over. Folders that fail to start are logged and left for the
+// health check to report
+pub async fn switch_profile(app: &AppHandle, profile_id: i64) -> Result<database::Profile> {
+    if !database::get_profiles(app)?.iter().any(|profile| profile.id == profile_id) {
+        return Err(anyhow::anyhow!("Profile {} not found", profile_id));
+    }
+    
+    // Stop the old profile's watchers first, so nothing new is picked up while
+    // the rules change underneath
+    clear_watchers(&mut app.state::<Arc<Mutex<WatcherState>>>().lock().unwrap());
+    let result = database::set_active_profile(app, profile_id);
+    
+    // Whichever profile is active now, the old one if switching failed
+    for path in database::get_active_watched_paths(app)? {
+        if let Err(e) = start_watching(app, path.clone()).await {
+            tracing::warn!(folder = %path, error = format!("{:#}", e), "Failed to watch folder after switching profile");
+        }
+    }
+    watch_status_changed(app);
+    
+    let profile = result?;
+    tracing::info!(profile = %profile.name, "Switched profile");
+    Ok(profile)
+}
+
+// Replace the database with a backup. The watchers are swapped for the folders
+// the restored database watches; a pause doesn't carry over. Folders that
+// fail to start are reported as failed watchers
+pub async fn restore_database(app: &AppHandle, source: &Path) -> Result<()> {
+    // Stop watching first, so nothing writes to the database while it's
+    // replaced. Unlike stop_watching, this leaves the folders marked active
+    let state = app.state::<Arc<Mutex<WatcherState>>>();
+    clear_watchers(&mut state.lock().unwrap());
+    let result = database::restore_database(app, source);
+    
+    // Whichever database is in place now, the old one if restoring failed
+    for path in database::get_active_watched_paths(app)? {
+        if let Err(e) = start_watching(app, path.clone()).await {
+            let error = format!("{:#}", e);
+            tracing::warn!(folder = %path, error = %error, "Failed to watch folder after restoring the database");
+            state.lock().unwrap().failed.insert(path.clone(), error.clone());
+            events::watcher_error(app, events::WatcherError { path, error });
+        }
+    }
+    watch_status_changed(app);
+    
+    result
+}
+
+// Stop watching one folder or file, leaving every other watcher running
+pub fn stop_watching_path(app: &AppHandle, path: &str) -> Result<()> {
+    let state = app.state::<Arc<Mutex<WatcherState>>>();
+    {
+        let mut state_guard = state.lock().unwrap();
+        let was_active = state_guard.watchers.remove(path).is_some();
+        let was_failed = state_guard.failed.remove(path).is_some();
+        let paused_count = state_guard.paused.len();
+        state_guard.paused.retain(|paused| paused != path);
+        if !was_active && !was_failed && state_guard.paused.len() == paused_count {
+            return Err(anyhow::anyhow!("Not watching {}", path));
+        }
+        state_guard.files.remove(path);
+    }
+    
+    database::set_watched_folder_rules(app, path, &[])?;
+    database::set_watched_paths_inactive(app, Some(path))?;
+    // Files queued while it was outside the active hours stay where they are
+    database::remove_pending_actions_from(app, path)?;
+    
+    tracing::info!(path = %path, "Stopped watching");
+    watch_status_changed(app);
+    Ok(())
+}
+
+// Stop all watchers for a while without forgetting them; with a duration,
+// watching resumes by itself afterwards
+pub fn pause_watching(app: &AppHandle, duration: Option<Duration>) -> Result<()> {
+    let state = app.state::<Arc<Mutex<WatcherState>>>();
+    let generation = {
+        let mut state_guard = state.lock().unwrap();
+        let paths: Vec<String> = state_guard.watchers.keys().cloned().collect();
+        state_guard.watchers.clear();
+        state_guard.paused.extend(paths);
+        state_guard.paused_until = duration
+            .map(chrono::Duration::from_std)
+            .transpose()?
+            .map(|duration| Utc::now() + duration);
+        state_guard.pause_generation += 1;
+        state_guard.pause_generation
+    };
+    tracing::info!(?duration, "Paused watching");
+    
+    if let Some(duration) = duration {
+        let app_handle = app.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(duration).await;
+            
+            let current = app_handle.state::<Arc<Mutex<WatcherState>>>().lock().unwrap().pause_generation;
+            if current == generation {
+                if let Err(e) = resume_watching(&app_handle).await {
+                    tracing::error!(error = format!("{:#}", e), "Failed to resume watching");
+                }
+            }
+        });
+    }
+    
+    watch_status_changed(app);
+    Ok(())
+}
+
+// Restart the watchers stopped by pause_watching
+pub async fn resume_watching(app: &AppHandle) -> Result<()> {
+    let paths = {
+        let state = app.state::<Arc<Mutex<WatcherState>>>();
+        let mut state_guard = state.lock().unwrap();
+        state_guard.paused_until = None;
+        state_guard.pause_generation += 1;
+        std::mem::take(&mut state_guard.paused)
+    };
+    
+    let mut errors = vec![];
+    for path in paths {
+        if let Err(e) = start_watching(app, path.clone()).await {
+            errors.push(format!("{}: {}", path, e));
+        }
+    }
+    tracing::info!("Resumed watching");
+    
+    watch_status_changed(app);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Failed to resume watching {}", errors.join(", ")))
+    }
+}
+
+// Whether watching is paused, and until when for a timed pause
+pub fn pause_status<R: Runtime>(app: &AppHandle<R>) -> (bool, Option<DateTime<Utc>>) {
+    app.try_state::<Arc<Mutex<WatcherState>>>()
+        .map(|state| {
+            let state_guard = state.lock().unwrap();
+            (!state_guard.paused.is_empty(), state_guard.paused_until)
+        })
+        .unwrap_or((false, None))
+}
+
+// Stop a watcher that failed or whose folder is gone, mark it failed and send
+// watcher_error, so the user can watch it again or remove it rather than it
+// silently doing nothing. The failure may be passing, such as a drive that's
+// unplugged, so the folder stays watched in the database. Called from the
+// watcher's own thread, so the watcher is dropped on the async runtime instead
+fn report_watcher_failure<R: Runtime>(app: &AppHandle<R>, path: &str, error: String) {
+    let app_handle = app.clone();
+    let path = path.to_string();
+    tauri::async_runtime::spawn(async move {
+        let removed = {
+            let state = app_handle.state::<Arc<Mutex<WatcherState>>>();
+            let mut state_guard = state.lock().unwrap();
+            let removed = state_guard.watchers.remove(&path);
+            if removed.is_some() {
+                state_guard.failed.insert(path.clone(), error.clone());
+            }
+            removed
+        };
+        // Already stopped, by an earlier error of the same watcher or by the user
+        if removed.is_none() {
+            return;
+        }
+        
+        tracing::error!(path = %path, error = %error, "Stopped watching after a watcher failure");
+        events::watcher_error(&app_handle, events::WatcherError { path, error });
+        watch_status_changed(&app_handle);
+    });
+}
+
+// Watcher errors after which the watcher keeps running but events were lost:
+// reading the event queue failed, or a new subfolder couldn't be watched
+// because the system's watch limit was reached. The queue overflow notices
+// notify sends as rescan events have no path, and notify-debouncer-mini drops
+// them before they get here
+fn is_event_overflow(error: &notify::Error) -> bool {
+    matches!(error.kind, notify::ErrorKind::Io(_) | notify::ErrorKind::MaxFilesWatch)
+}
+
+// Log lost watcher events, count them for get_health and send watch_overflow
+fn report_watch_overflow<R: Runtime>(app: &AppHandle<R>, path: &str, error: &notify::Error, rescan_queued: bool) {
+    WATCH_OVERFLOWS.fetch_add(1, Ordering::Relaxed);
+    tracing::warn!(path = %path, error = %error, rescan_queued, "Watcher lost events");
+    events::watch_overflow(app, events::WatchOverflow {
+        path: path.to_string(),
+        error: error.to_string(),
+        rescan_queued,
+    });
+}
+
+// Times a watcher lost events since launch
+pub fn watch_overflow_count() -> u64 {
+    WATCH_OVERFLOWS.load(Ordering::Relaxed)
+}
+
+// Scan a watched folder once the burst that overflowed its watcher has had
+// OVERFLOW_RESCAN_DELAY to settle, waiting for any other scan to finish, for
+// watching to be resumed and for the active hours. Dropped when the folder
+// stops being watched. Returns false when a rescan of the folder is already queued
+fn queue_overflow_rescan<R: Runtime>(app: &AppHandle<R>, folder: &str) -> bool {
+    if !OVERFLOW_RESCANS.lock().unwrap().insert(folder.to_string()) {
+        return false;
+    }
+    let app_handle = app.clone();
+    let folder = folder.to_string();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(OVERFLOW_RESCAN_DELAY).await;
+            let (watched, paused) = watch_status_of(&app_handle, &folder);
+            let step = overflow_rescan_step(
+                watched,
+                paused,
+                schedule::is_active_now(&app_handle),
+                SCAN_RUNNING.load(Ordering::SeqCst),
+            );
+            match step {
+                RescanStep::Wait => continue,
+                RescanStep::Drop => {
+                    OVERFLOW_RESCANS.lock().unwrap().remove(&folder);
+                    tracing::info!(folder = %folder, "Dropped rescan of a folder no longer watched");
+                    return;
+                }
+                RescanStep::Run => {}
+            }
+            
+            // Events arriving from here on are seen by the scan, or handled by
+            // the watcher, so a new overflow queues a new rescan
+            OVERFLOW_RESCANS.lock().unwrap().remove(&folder);
+            match scan_folder(&app_handle, folder.clone(), None).await {
+                Ok(summary) => tracing::info!(
+                    folder = %summary.folder,
+                    scanned = summary.scanned,
+                    organized = summary.organized,
+                    errors = summary.errors.len(),
+                    "Rescan after lost watcher events finished"
+                ),
+                // Another scan started in the meantime; wait for it, unless a
+                // new overflow queued a rescan of its own
+                Err(e) if e.is::<ScanAlreadyRunning>() => {
+                    if OVERFLOW_RESCANS.lock().unwrap().insert(folder.clone()) {
+                        continue;
+                    }
+                }
+                Err(e) => tracing::warn!(folder = %folder, error = format!("{:#}", e), "Rescan after lost watcher events failed"),
+            }
+            return;
+        }
+    });
+    true
+}
+
+// What a queued overflow rescan does after each wait
+#[derive(Debug, PartialEq)]
+enum RescanStep {
+    Run,
+    Wait,
+    Drop,
+}
+
+fn overflow_rescan_step(watched: bool, paused: bool, active_hours: bool, scan_running: bool) -> RescanStep {
+    if !watched {
+        RescanStep::Drop
+    } else if paused || !active_hours || scan_running {
+        RescanStep::Wait
+    } else {
+        RescanStep::Run
+    }
+}
+
+// Whether a path is watched, paused watches included, and whether it's paused
+fn watch_status_of<R: Runtime>(app: &AppHandle<R>, path: &str) -> (bool, bool) {
+    let Some(state) = app.try_state::<Arc<Mutex<WatcherState>>>() else { return (false, false) };
+    let state_guard = state.lock().unwrap();
+    let paused = state_guard.paused.iter().any(|paused| paused == path);
+    (paused || state_guard.watchers.contains_key(path), paused)
+}
+
+// Update the tray and tell the frontend after watching changed
+fn watch_status_changed<R: Runtime>(app: &AppHandle<R>) {
+    tray::refresh(app);
+    events::watch_status_changed(app, events::WatchStatusChanged {
+        watched: get_watched_folders(app),
+        paused: pause_status(app).0,
+    });
+}
+
+// Get the folders being watched, including paused ones and ones whose watcher failed
+pub fn get_watched_folders<R: Runtime>(app: &AppHandle<R>) -> Vec<WatchedFolder> {
+    let Some(state) = app.try_state::<Arc<Mutex<WatcherState>>>() else { return vec![] };
+    let rule_limits = database::get_watched_folder_rules(app).unwrap_or_default();
+    let state_guard = state.lock().unwrap();
+    let paused_until = state_guard
+        .paused_until
+        .map(|until| utils::format_timestamp(&until));
+    
+    let mut folders: Vec<WatchedFolder> = state_guard
+        .watchers
+        .keys()
+        .map(|path| WatchedFolder {
+            path: path.clone(),
+            is_file: state_guard.files.contains(path),
+            paused: false,
+            paused_until: None,
+            rule_ids: rule_limits.get(path).cloned(),
+            error: None,
+        })
+        .chain(state_guard.paused.iter().map(|path| WatchedFolder {
+            path: path.clone(),
+            is_file: state_guard.files.contains(path),
+            paused: true,
+            paused_until: paused_until.clone(),
+            rule_ids: rule_limits.get(path).cloned(),
+            error: None,
+        }))
+        .chain(state_guard.failed.iter().map(|(path, error)| WatchedFolder {
+            path: path.clone(),
+            is_file: state_guard.files.contains(path),
+            paused: false,
+            paused_until: None,
+            rule_ids: rule_limits.get(path).cloned(),
+            error: Some(error.clone()),
+        }))
+        .collect();
+    folders.sort_by(|a, b| a.path.cmp(&b.path));
+    folders
+}
+
+// Log a failed secondary step that shouldn't fail the operation it belongs to
+fn log_if_err<T>(result: Result<T>, message: &str) {
+    if let Err(e) = result {
+        tracing::warn!(error = format!("{:#}", e), "{}", message);
+    }
+}
+
+// Stop all watchers and wait for the events already queued to be processed
+pub async fn shutdown_watchers(app: &AppHandle) {
+    let processors = match app.try_state::<Arc<Mutex<WatcherState>>>() {
+        Some(state) => {
+            let mut state_guard = state.lock().unwrap();
+            // Dropping the debouncers closes their channels, so each processing
+            // task drains what is left and then exits
+            state_guard.watchers.clear();
+            std::mem::take(&mut state_guard.processors)
+        }
+        None => return,
+    };
+    
+    for processor in processors {
+        let _ = processor.await;
+    }
+}
+
+// Get the paths of all folders and files currently being watched
+pub fn watched_paths<R: Runtime>(app: &AppHandle<R>) -> Vec<String> {
+    app.try_state::<Arc<Mutex<WatcherState>>>()
+        .map(|state| state.lock().unwrap().watchers.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+// Setting with extra comma separated screenshot filename prefixes, for other locales
+pub const SCREENSHOT_PATTERNS_SETTING: &str = "screenshot_patterns";
+
+// Rule condition matching files detected as screenshots
+pub const CONDITION_IS_SCREENSHOT: &str = "is_screenshot";
+
+// Rule condition matching the domain a file was downloaded from; the rule's
+// pattern holds a comma separated list of domains, subdomains included
+pub const CONDITION_SOURCE_DOMAIN: &str = "source_domain";
+
+// Rule condition matching files not modified for the rule's max_age_days.
+// When the rule is an extension rule, only those extensions are matched
+pub const CONDITION_OLDER_THAN: &str = "older_than";
+
+// Rule condition matching files by the name of a folder they're in: the
+// rule's pattern is a glob checked against each folder between the file and
+// its watched root, e.g. "Invoices*"
+pub const CONDITION_FOLDER_NAME: &str = "folder_name";
+
+// Rule condition matching files by name: the rule's pattern is a glob checked
+// against the whole file name, e.g. "Invoice*.pdf". Rules with a pattern but
+// no condition are still never applied
+pub const CONDITION_NAME_MATCHES: &str = "name_matches";
+
+// Find the watched folder a path lives under, preferring the most specific one
+fn watched_root_for<R: Runtime>(app: &AppHandle<R>, path: &Path) -> Option<PathBuf> {
+    watched_paths(app)
+        .into_iter()
+        .map(|root| utils::normalize_path(Path::new(&root)))
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+}
+
+// Get the folder of a file relative to its watched root, e.g. "project" for
+// Downloads/project/report.pdf when Downloads is watched
+fn relative_subfolder<R: Runtime>(app: &AppHandle<R>, file_path: &Path) -> Option<PathBuf> {
+    let root = watched_root_for(app, file_path)?;
+    let relative = file_path.parent()?.strip_prefix(&root).ok()?;
+    if relative.as_os_str().is_empty() {
+        None
+    } else {
+        Some(relative.to_path_buf())
+    }
+}
+
+// Get the IDs of indexed files whose name matches a rule pattern
+pub fn find_files_matching_rule(
+    app: &AppHandle,
+    pattern: &str,
+    is_extension: bool,
+    is_regex: bool,
+) -> Result<Vec<i64>> {
+    // Compile once up front so a bad pattern fails fast
+    let regex = if is_extension {
+        None
+    } else {
+        Some(utils::rule_pattern_regex(pattern, is_regex)?)
+    };
+    
+    let mut matching = vec![];
+    for (file_id, name) in database::get_all_file_names(app)? {
+        let matched = match &regex {
+            Some(regex) => regex.is_match(&name),
+            None => utils::rule_matches(pattern, true, false, &name)?,
+        };
+        if matched {
+            matching.push(file_id);
+        }
+    }
+    
+    Ok(matching)
+}
+
+// Setting with the folder relative rule destinations are resolved against,
+// e.g. a secondary drive; unset or empty means the home folder
+pub const BASE_DIRECTORY_SETTING: &str = "base_directory";
+
+// The folder relative rule destinations are resolved against
+pub fn get_base_directory<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf> {
+    match database::get_setting(app, BASE_DIRECTORY_SETTING)? {
+        Some(value) if !value.trim().is_empty() => Ok(PathBuf::from(value.trim())),
+        _ => dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Failed to get home directory")),
+    }
+}
+
+// Resolve relative rule destinations against a new folder; an empty path goes
+// back to the home folder
+pub fn set_base_directory(app: &AppHandle, path: &str) -> Result<()> {
+    let path = path.trim();
+    if !path.is_empty() {
+        validate_base_directory(Path::new(path))?;
+    }
+    database::set_setting(app, BASE_DIRECTORY_SETTING, path)
+}
+
+// Check a base directory is an existing folder files can be created in
+pub fn validate_base_directory(path: &Path) -> Result<()> {
+    if !path.is_absolute() {
+        return Err(anyhow::anyhow!("{} is not an absolute path", path.display()));
+    }
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("{} is not an existing folder", path.display()));
+    }
+    
+    // Permissions don't tell the whole story (read-only mounts, ACLs), so try it
+    let probe = path.join(format!(".smart_file_organizer_write_test_{}", std::process::id()));
+    fs::File::create(&probe)
+        .map_err(|e| anyhow::anyhow!("{} is not writable: {}", path.display(), e))?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
+
+// Folder a rule puts a file in. The final path is built in a fixed order:
+// destination folder with {year}/{month}/{day} expanded from the given date
+// (see FileFacts::organize_date) and {parent} from the folder a folder name
+// rule matched, or else the file's own folder, and resolved against the base
+// directory, or against the platform's folder when it starts with one such as
+// $DOCUMENTS, or the home folder for "~", then the subfolder relative to the
+// watched root (preserve_structure), then the file name; collision renaming
+// only ever changes that last component.
+fn rule_destination<R: Runtime>(app: &AppHandle<R>, rule: &MatchedRule, file_path: &Path, date: &DateTime<Utc>) -> Result<PathBuf> {
+    let parent = rule
+        .matched_folder
+        .clone()
+        .or_else(|| file_path.parent().and_then(utils::get_file_name))
+        .unwrap_or_default();
+    let dest_folder = utils::expand_destination_template(&rule.destination_folder, date, &parent);
+    let mut dest_path = match utils::resolve_known_folder(&dest_folder) {
+        Some(known_folder) => known_folder?,
+        None => get_base_directory(app)?.join(&dest_folder),
+    };
+    if rule.preserve_structure {
+        if let Some(relative_dir) = relative_subfolder(app, file_path) {
+            dest_path = dest_path.join(relative_dir);
+        }
+    }
+    
+    Ok(dest_path)
+}
+
+// Whether a file already sits in the folder it would be moved to
+fn is_in_destination(file_path: &Path, dest_path: &Path) -> bool {
+    let dest_key = utils::path_key(&utils::normalize_path(dest_path));
+    file_path.parent().map(utils::path_key).as_deref() == Some(dest_key.as_str())
+}
+
+// Organize a file based on rules, returning what was done with it
+#[tracing::instrument(skip(app), fields(path = %file_path.display()))]
+pub async fn organize_file_by_rules<R: Runtime>(app: &AppHandle<R>, file_path: &Path) -> Result<OrganizeOutcome> {
+    let engine = RuleEngine::load(app)?;
+    let ignore_list = IgnoreList::load(app)?;
+    let screenshot_patterns = extra_screenshot_patterns(app)?;
+    // Moving may wait on a locked file or copy across drives
+    let app_handle = app.clone();
+    let file_path = file_path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let app = &app_handle;
+        match move_file_by_rules(app, &engine, &ignore_list, &screenshot_patterns, &file_path) {
+            Ok(RuleMoveResult::Moved(rule_move)) => Ok(record_rule_moves(app, vec![*rule_move])?.remove(0)),
+            Ok(RuleMoveResult::Unmoved(outcome)) => Ok(outcome),
+            Err(e) => skipped_move(app, &file_path, &e).ok_or(e),
+        }
+    })
+    .await?
+}
+
+// Organize several files by rules, indexing all the moves in one transaction.
+// With rule_limit, only those rules are applied. Returns the outcome for each
+// path, in order
+fn organize_files_by_rules<R: Runtime>(app: &AppHandle<R>, paths: &[PathBuf], rule_limit: Option<&[i64]>) -> Vec<OrganizeOutcome> {
+    let loaded = RuleEngine::load_limited(app, rule_limit)
+        .and_then(|engine| Ok((engine, IgnoreList::load(app)?, extra_screenshot_patterns(app)?)));
+    let (engine, ignore_list, screenshot_patterns) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            let e = e.context("Failed to load rules");
+            return paths.iter().map(|_| OrganizeOutcome::failed(&e)).collect();
+        }
+    };
+    
+    let mut outcomes = Vec::with_capacity(paths.len());
+    let mut rule_moves = vec![];
+    let mut moved_indices = vec![];
+    for (i, path) in paths.iter().enumerate() {
+        match move_file_by_rules(app, &engine, &ignore_list, &screenshot_patterns, path) {
+            Ok(RuleMoveResult::Moved(rule_move)) => {
+                rule_moves.push(*rule_move);
+                moved_indices.push(i);
+                // Replaced below once the move is indexed
+                outcomes.push(OrganizeOutcome::Failed { error: "Moved, but not indexed".to_string() });
+            }
+            Ok(RuleMoveResult::Unmoved(outcome)) => outcomes.push(outcome),
+            Err(e) => outcomes.push(skipped_move(app, path, &e).unwrap_or_else(|| OrganizeOutcome::failed(&e))),
+        }
+    }
+    if rule_moves.is_empty() {
+        return outcomes;
+    }
+    
+    match record_rule_moves(app, rule_moves) {
+        Ok(moved) => {
+            for (i, outcome) in moved_indices.into_iter().zip(moved) {
+                outcomes[i] = outcome;
+            }
+        }
+        Err(e) => {
+            // The files were moved; only their index entries are missing
+            let e = e.context("Moved, but not indexed");
+            for i in moved_indices {
+                outcomes[i] = OrganizeOutcome::failed(&e);
+            }
+        }
+    }
+    outcomes
+}
+
+// A file that couldn't be moved right now, e.g. one open in another program,
+// is skipped rather than failed. It gets an activity entry so it's clear why
+// it's still where it was
+fn skipped_move<R: Runtime>(app: &AppHandle<R>, file_path: &Path, e: &anyhow::Error) -> Option<OrganizeOutcome> {
+    let move_error = e.downcast_ref::<utils::MoveError>()?;
+    let file_id = database::get_file_id_by_path(app, file_path).ok().flatten();
+    log_if_err(
+        database::record_file_event(
+            app,
+            file_id,
+            database::EVENT_SKIPPED,
+            Some(&file_path.to_string_lossy()),
+            None,
+            Some(&move_error.to_string()),
+        ),
+        "Failed to record skipped file",
+    );
+    
+    let reason = match move_error {
+        utils::MoveError::Locked(_) => SkipReason::InUse,
+        utils::MoveError::ReadOnly(_) => SkipReason::ReadOnly,
+    };
+    Some(OrganizeOutcome::Skipped { reason })
+}
+
+// What move_file_by_rules did with a file
+enum RuleMoveResult {
+    // Moved, but still to be indexed by record_rule_moves
+    Moved(Box<RuleMove>),
+    Unmoved(OrganizeOutcome),
+}
+
+// A file moved by a rule that still has to be indexed by record_rule_moves
+struct RuleMove {
+    moved: database::MovedFile,
+    details: RuleMoveDetails,
+}
+
+// What's recorded and reported about a rule move once it's indexed
+struct RuleMoveDetails {
+    rule_name: String,
+    dest_folder_name: String,
+    category: Option<String>,
+    // The rule left off the category's tag
+    auto_tag_excluded: bool,
+    source_url: Option<String>,
+    source_domain: Option<String>,
+    os_tags: Vec<utils::OsTag>,
+}
+
+// What the rules do with a file: leave it, with the outcome to report, or
+// move it into a folder
+enum RuleDecision {
+    Unmoved(OrganizeOutcome),
+    Move(Box<RuleTarget>),
+}
+
+struct RuleTarget {
+    // Normalized the way the index stores paths
+    file_path: PathBuf,
+    facts: FileFacts,
+    rule: MatchedRule,
+    dest_path: PathBuf,
+}
+
+// Decide what the rules do with a file, checking what's skipped in the same
+// order for moving it and for planning a move
+fn decide_by_rules<R: Runtime>(
+    app: &AppHandle<R>,
+    engine: &RuleEngine,
+    ignore_list: &IgnoreList,
+    screenshot_patterns: &[String],
+    file_path: &Path,
+) -> Result<RuleDecision> {
+    let skipped = |reason| Ok(RuleDecision::Unmoved(OrganizeOutcome::Skipped { reason }));
+    
+    // Check if file exists and is a file
+    if !file_path.is_file() {
+        return skipped(SkipReason::NotFound);
+    }
+    
+    // Compare and record the path the same way the index stores it
+    let file_path = utils::normalize_path(file_path);
+    if ignore_list.is_ignored(&file_path) {
+        return skipped(SkipReason::Ignored);
+    }
+    if is_source_mirror(app, &file_path)? {
+        return skipped(SkipReason::SourceMirror);
+    }
+    if utils::get_file_extension(&file_path).unwrap_or_default().is_empty() {
+        return skipped(SkipReason::NoExtension);
+    }
+    
+    let watched_root = watched_root_for(app, &file_path);
+    let facts = FileFacts::read(&file_path, screenshot_patterns, watched_root.as_deref());
+    let Some(rule) = engine.matching(&facts).into_iter().next() else {
+        return skipped(SkipReason::NoMatchingRule);
+    };
+    let dest_path = rule_destination(app, &rule, &file_path, &facts.organize_date())?;
+    
+    // Already where the rule puts it, e.g. found again by a scan
+    if is_in_destination(&file_path, &dest_path) {
+        return Ok(RuleDecision::Unmoved(OrganizeOutcome::AlreadyOrganized { rule: rule.name }));
+    }
+    
+    Ok(RuleDecision::Move(Box::new(RuleTarget { file_path, facts, rule, dest_path })))
+}
+
+// Move a file to where its rule puts it, without indexing it yet, so moves
+// can be indexed in batches
+fn move_file_by_rules<R: Runtime>(
+    app: &AppHandle<R>,
+    engine: &RuleEngine,
+    ignore_list: &IgnoreList,
+    screenshot_patterns: &[String],
+    file_path: &Path,
+) -> Result<RuleMoveResult> {
+    let RuleTarget { file_path, facts, rule, dest_path } = match decide_by_rules(app, engine, ignore_list, screenshot_patterns, file_path)? {
+        RuleDecision::Unmoved(outcome) => return Ok(RuleMoveResult::Unmoved(outcome)),
+        RuleDecision::Move(target) => *target,
+    };
+    let file_path = &file_path;
+    
+    let metadata = fs::metadata(file_path)?;
+    let size = metadata.len() as i64;
+    let (created_dt, created_approximate) = utils::file_created(&metadata);
+    let FileFacts { extension, source_domain, modified: modified_dt, .. } = facts;
+    
+    // Copying the file drops extended attributes, so read OS metadata while it's still in place
+    let source_url = utils::read_source_url(file_path).ok().flatten();
+    let os_tags = utils::read_os_tags(file_path).unwrap_or_default();
+    
+    if !dest_path.exists() {
+        fs::create_dir_all(&dest_path)?;
+    }
+    
+    // Move the file, numbering the name if the destination is taken
+    let clear_read_only = database::is_setting_enabled(app, CLEAR_READ_ONLY_SETTING)?;
+    let detail = format!("rule: {}", rule.name);
+    let (new_path, journal_id) = journaled_move(app, file_path, &dest_path, clear_read_only, &detail)?;
+    
+    // Tag with the rule's own tag and, unless the rule opts out, the category's tag
+    let category = database::get_category_for_extension(app, &extension)?;
+    let category_tag_id = category.as_ref().and_then(|category| category.tag_id);
+    let auto_tag_excluded = rule.exclude_auto_tags && category_tag_id.is_some();
+    
+    Ok(RuleMoveResult::Moved(Box::new(RuleMove {
+        moved: database::MovedFile {
+            file: database::NewFile {
+                // The name it was moved under, numbered if the original was taken
+                name: utils::get_file_name(&new_path).unwrap_or_default(),
+                path: new_path,
+                extension,
+                size,
+                created_at: utils::format_timestamp(&created_dt),
+                created_at_approximate: created_approximate,
+                modified_at: utils::format_timestamp(&modified_dt),
+            },
+            from: file_path.clone(),
+            detail,
+            tag_ids: rule.tag_id.into_iter().collect(),
+            auto_tag_ids: category_tag_id.filter(|_| !rule.exclude_auto_tags).into_iter().collect(),
+            journal_id,
+        },
+        details: RuleMoveDetails {
+            rule_name: rule.name,
+            dest_folder_name: utils::get_file_name(&dest_path).unwrap_or_default(),
+            category: category.map(|category| category.name),
+            auto_tag_excluded,
+            source_url,
+            source_domain,
+            os_tags,
+        },
+    })))
+}
+
+// Index files moved by move_file_by_rules in one transaction, then record
+// their sources and OS tags and report the moves. Returns a Moved outcome for
+// each, in order, or a Failed one for a file that couldn't be indexed
+fn record_rule_moves<R: Runtime>(app: &AppHandle<R>, rule_moves: Vec<RuleMove>) -> Result<Vec<OrganizeOutcome>> {
+    let (moved, details): (Vec<_>, Vec<_>) = rule_moves
+        .into_iter()
+        .map(|rule_move| (rule_move.moved, rule_move.details))
+        .unzip();
+    let results = database::index_moved_files(app, &moved)?;
+    
+    let mut outcomes = vec![];
+    for ((moved, details), result) in moved.into_iter().zip(details).zip(results) {
+        let new_path = moved.file.path;
+        let indexed = match result {
+            Ok(indexed) => indexed,
+            Err(e) => {
+                // The file was moved; only its index entry is missing
+                let e = e.context("Moved, but not indexed");
+                tracing::error!(destination = %new_path.display(), error = format!("{:#}", e), "Failed to index moved file");
+                outcomes.push(OrganizeOutcome::failed(&e));
+                continue;
+            }
+        };
+        tracing::info!(rule = %details.rule_name, destination = %new_path.display(), "Moved file by rule");
+        let file_id = indexed.file_id;
+        let auto_tag_suppressed = details.auto_tag_excluded || indexed.auto_tags_suppressed;
+        
+        // Keep the download source and Finder/xdg tags; a failure here shouldn't undo the move
+        log_if_err(
+            database::set_file_source(app, file_id, details.source_url.as_deref(), details.source_domain.as_deref()),
+            "Failed to record download source",
+        );
+        log_if_err(sync_os_tags(app, file_id, details.os_tags, &new_path), "Failed to sync OS tags");
+        mirror_source(app, &moved.from, &new_path);
+        
+        notifications::notify(
+            app,
+            notifications::KIND_ORGANIZED,
+            format!("Moved {} → {}", moved.file.name, details.dest_folder_name),
+        );
+        events::file_organized(app, events::FileOrganized {
+            source: moved.from.to_string_lossy().to_string(),
+            destination: new_path.to_string_lossy().to_string(),
+            rule: Some(details.rule_name.clone()),
+            category: details.category.clone(),
+        });
+        events::file_indexed(app, events::FileIndexed {
+            file_id,
+            path: new_path.to_string_lossy().to_string(),
+            size: moved.file.size as u64,
+        });
+        webhook::file_organized(app, webhook::OrganizedFile {
+            name: moved.file.name,
+            from: moved.from.to_string_lossy().to_string(),
+            to: new_path.to_string_lossy().to_string(),
+            category: details.category,
+        });
+        
+        outcomes.push(OrganizeOutcome::moved(&moved.from, &new_path, Some(details.rule_name), auto_tag_suppressed));
+    }
+    
+    Ok(outcomes)
+}
+
+// Extra screenshot filename prefixes from SCREENSHOT_PATTERNS_SETTING
+fn extra_screenshot_patterns<R: Runtime>(app: &AppHandle<R>) -> Result<Vec<String>> {
+    Ok(database::get_setting(app, SCREENSHOT_PATTERNS_SETTING)?
+        .map(|patterns| patterns.split(',').map(|p| p.trim().to_string()).collect())
+        .unwrap_or_default())
+}
+
+// A rule matching a file, as reported by explain_file
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RuleCandidate {
+    pub rule_id: i64,
+    pub rule_name: String,
+    pub destination: String,
+    pub reason: String,
+    // The folder a folder name rule matched
+    pub matched_folder: Option<String>,
+}
+
+// The rule organize_file_by_rules would apply to a file
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RuleMatch {
+    pub rule: RuleCandidate,
+    // The file is already in the rule's folder, so it would be left alone
+    pub already_in_place: bool,
+    // Other matching rules that lost on precedence
+    pub also_matched: Vec<RuleCandidate>,
+}
+
+// Work out which rule would organize a file and where it would go, without
+// moving anything. None means no rule applies and the file stays put
+pub fn explain_file(app: &AppHandle, file_path: &Path) -> Result<Option<RuleMatch>> {
+    if !file_path.is_file() {
+        return Err(anyhow::anyhow!("{} is not a file", file_path.display()));
+    }
+    let file_path = &utils::normalize_path(file_path);
+    
+    // The organizer skips files without an extension
+    let extension = utils::get_file_extension(file_path).unwrap_or_default();
+    if extension.is_empty() {
+        return Ok(None);
+    }
+    
+    let watched_root = watched_root_for(app, file_path);
+    let facts = FileFacts::read(file_path, &extra_screenshot_patterns(app)?, watched_root.as_deref());
+    
+    let mut candidates = vec![];
+    for rule in RuleEngine::load(app)?.matching(&facts) {
+        let destination = rule_destination(app, &rule, file_path, &facts.organize_date())?;
+        let candidate = RuleCandidate {
+            rule_id: rule.id,
+            rule_name: rule.name,
+            destination: destination.to_string_lossy().to_string(),
+            reason: rule.reason,
+            matched_folder: rule.matched_folder,
+        };
+        candidates.push((candidate, destination));
+    }
+    
+    let mut candidates = candidates.into_iter();
+    let Some((rule, destination)) = candidates.next() else { return Ok(None) };
+    
+    Ok(Some(RuleMatch {
+        rule,
+        already_in_place: is_in_destination(file_path, &destination),
+        also_matched: candidates.map(|(candidate, _)| candidate).collect(),
+    }))
+}
+
+// Most files test_rule samples from a folder
+const RULE_TEST_SAMPLE_LIMIT: usize = 50;
+
+// How a draft rule treats one sample file, as reported by test_rule
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RuleTestResult {
+    pub path: String,
+    // Paths that don't exist are judged by their name alone
+    pub exists: bool,
+    pub matches: bool,
+    // Why the draft matched
+    pub reason: Option<String>,
+    // The folder the draft matched, for a folder name rule
+    pub matched_folder: Option<String>,
+    // Where the draft would put the file, templates expanded
+    pub destination: Option<String>,
+    // The saved rule that would organize the file instead of the draft
+    pub winning_rule: Option<RuleCandidate>,
+}
+
+// Try an unsaved rule on sample files: whether it matches, where it would put
+// each file and which saved rule would win instead. A draft with the ID of a
+// saved rule stands in for it; a new one ranks after saved rules of its kind.
+// The organizer never applies plain file name patterns, so for a draft of one
+// this only says which samples the pattern matches, as a glob or, with
+// is_regex, a regex
+pub fn test_rule(
+    app: &AppHandle,
+    draft: Rule,
+    is_regex: bool,
+    sample_paths: Option<Vec<String>>,
+    sample_folder: Option<String>,
+) -> Result<Vec<RuleTestResult>> {
+    let name_pattern = RuleEngine::is_name_pattern_rule(&draft);
+    if name_pattern {
+        utils::rule_pattern_regex(draft.pattern.trim(), is_regex)?;
+    } else {
+        RuleEngine::validate(&draft)?;
+    }
+    
+    let samples: Vec<PathBuf> = match (sample_paths, sample_folder) {
+        (Some(paths), _) => paths.into_iter().map(PathBuf::from).collect(),
+        (None, Some(folder)) => {
+            let folder = PathBuf::from(folder);
+            if !folder.is_dir() {
+                return Err(anyhow::anyhow!("{} is not a folder", folder.display()));
+            }
+            let mut files = utils::collect_files(&folder, Some(0), false)?;
+            files.sort();
+            files.truncate(RULE_TEST_SAMPLE_LIMIT);
+            files
+        }
+        (None, None) => return Err(anyhow::anyhow!("Give sample paths or a folder to sample from")),
+    };
+    
+    let draft_id = if draft.id > 0 { draft.id } else { i64::MAX };
+    let mut rules: Vec<Rule> = database::get_rules(app)?
+        .into_iter()
+        .filter(|rule| rule.is_active && rule.id != draft_id)
+        .collect();
+    let draft = Rule { id: draft_id, is_active: true, ..draft };
+    rules.push(draft.clone());
+    let engine = RuleEngine::new(rules);
+    let screenshot_patterns = extra_screenshot_patterns(app)?;
+    
+    let mut results = vec![];
+    for path in samples {
+        let exists = path.exists();
+        let path = if exists { utils::normalize_path(&path) } else { path };
+        let watched_root = watched_root_for(app, &path);
+        let facts = FileFacts::read(&path, &screenshot_patterns, watched_root.as_deref());
+        
+        // The organizer skips files without an extension
+        let matches = if facts.extension.is_empty() { vec![] } else { engine.matching(&facts) };
+        let name_match = match name_pattern && !facts.extension.is_empty() {
+            true => RuleEngine::name_pattern_match(&draft, is_regex, &facts)?,
+            false => None,
+        };
+        let draft_match = name_match.as_ref().or_else(|| matches.iter().find(|rule| rule.id == draft_id));
+        let winning_rule = match matches.first() {
+            Some(winner) if winner.id != draft_id => Some(RuleCandidate {
+                rule_id: winner.id,
+                rule_name: winner.name.clone(),
+                destination: rule_destination(app, winner, &path, &facts.organize_date())?.to_string_lossy().to_string(),
+                reason: winner.reason.clone(),
+                matched_folder: winner.matched_folder.clone(),
+            }),
+            _ => None,
+        };
+        let destination = match draft_match {
+            Some(rule) => Some(rule_destination(app, rule, &path, &facts.organize_date())?.to_string_lossy().to_string()),
+            None => None,
+        };
+        
+        results.push(RuleTestResult {
+            path: path.to_string_lossy().to_string(),
+            exists,
+            matches: draft_match.is_some(),
+            reason: draft_match.map(|rule| rule.reason.clone()),
+            matched_folder: draft_match.and_then(|rule| rule.matched_folder.clone()),
+            destination,
+            winning_rule,
+        });
+    }
+    
+    Ok(results)
+}
+
+// One list in the unorganized files report
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ReportPage {
+    // Number of files in the whole list, not just this page
+    pub total: usize,
+    pub files: Vec<FileInfo>,
+}
+
+// Files that slipped through the rules. Entries carry the file ID and path, so
+// they can be passed straight to tagging or organize_file
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UnorganizedReport {
+    // Files without any tag
+    pub untagged: ReportPage,
+    // Files no active rule would organize
+    pub unmatched: ReportPage,
+    // Files in a watched folder that were indexed but never moved
+    pub never_moved: ReportPage,
+    // Files on the ignore list, left alone on purpose; they aren't in the
+    // other lists
+    pub ignored: ReportPage,
+}
+
+// Build the unorganized files report; limit and offset page each list separately
+pub fn get_unorganized_report(app: &AppHandle, limit: Option<i64>, offset: Option<i64>) -> Result<UnorganizedReport> {
+    let files = database::get_report_files(app)?;
+    let engine = RuleEngine::load(app)?;
+    let ignore_list = IgnoreList::load(app)?;
+    let screenshot_patterns = extra_screenshot_patterns(app)?;
+    let watched_roots: Vec<PathBuf> = get_watched_folders(app)
+        .into_iter()
+        .filter(|folder| !folder.is_file)
+        .map(|folder| utils::normalize_path(Path::new(&folder.path)))
+        .collect();
+    
+    // Sort the IDs into the lists; only each list's page is loaded in full
+    let (mut untagged, mut unmatched, mut never_moved, mut ignored) = (vec![], vec![], vec![], vec![]);
+    for database::ReportFile { file, tagged, moved } in &files {
+        let path = Path::new(&file.path);
+        if ignore_list.is_ignored(path) {
+            ignored.push(file.id);
+            continue;
+        }
+        if !tagged {
+            untagged.push(file.id);
+        }
+        if !has_matching_rule(&engine, file, &screenshot_patterns, &watched_roots) {
+            unmatched.push(file.id);
+        }
+        if !moved && watched_roots.iter().any(|root| path.starts_with(root)) {
+            never_moved.push(file.id);
+        }
+    }
+    
+    let page = |ids: Vec<i64>| -> Result<ReportPage> {
+        Ok(ReportPage {
+            total: ids.len(),
+            files: database::get_files_by_ids(app, &ids, limit, offset)?,
+        })
+    };
+    
+    Ok(UnorganizedReport {
+        untagged: page(untagged)?,
+        unmatched: page(unmatched)?,
+        never_moved: page(never_moved)?,
+        ignored: page(ignored)?,
+    })
+}
+
+// Whether any active rule would organize an indexed file, checked with the
+// same matching the organizer uses but from what the index recorded
+fn has_matching_rule(
+    engine: &RuleEngine,
+    file: &FileInfo,
+    screenshot_patterns: &[String],
+    watched_roots: &[PathBuf],
+) -> bool {
+    // The organizer skips files without an extension
+    if file.extension.is_empty() {
+        return false;
+    }
+    
+    let path = Path::new(&file.path);
+    let watched_root = watched_roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count());
+    !engine.matching(&FileFacts::from_index(file, screenshot_patterns, watched_root.map(PathBuf::as_path))).is_empty()
+}
+
+// Trends never look back further than this
+const MAX_TREND_MONTHS: u32 = 24;
+
+// Trend group of files no rule moved, when grouping by rule
+const NO_RULE_GROUP: &str = "(no rule)";
+
+// Files added and organized in one trend period
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct TrendCounts {
+    // Indexed for the first time
+    pub files_added: u64,
+    pub bytes_added: u64,
+    pub files_organized: u64,
+    pub bytes_organized: u64,
+}
+
+impl TrendCounts {
+    fn add(&mut self, row: &database::TrendRow) {
+        if row.organized {
+            self.files_organized += row.count;
+            self.bytes_organized += row.bytes;
+        } else {
+            self.files_added += row.count;
+            self.bytes_added += row.bytes;
+        }
+    }
+}
+
+// One period of get_trends
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TrendBucket {
+    // First day of the period (UTC), YYYY-MM-DD; weeks start on Monday
+    pub start: String,
+    pub total: TrendCounts,
+    // By category, extension or rule; empty for a period without activity
+    pub groups: BTreeMap<String, TrendCounts>,
+}
+
+// Files added to the index and organized per day, week or month, grouped by
+// "category", "extension" or "rule", oldest period first. Every period up to
+// the current one is included, active or not. periods defaults to a year's
+// worth and is capped at MAX_TREND_MONTHS. Files added have no rule yet, so
+// by rule they are all in NO_RULE_GROUP
+pub fn get_trends(app: &AppHandle, period: &str, group_by: &str, periods: Option<u32>) -> Result<Vec<TrendBucket>> {
+    let (bucket_sql, default_periods, max_periods) = match period {
+        "day" => ("date({})", 30, MAX_TREND_MONTHS * 31),
+        "week" => ("date({}, 'weekday 0', '-6 days')", 13, MAX_TREND_MONTHS * 31 / 7),
+        "month" => ("strftime('%Y-%m-01', {})", 12, MAX_TREND_MONTHS),
+        other => return Err(anyhow::anyhow!("Unknown trend period {:?}; use day, week or month", other)),
+    };
+    if !["category", "extension", "rule"].contains(&group_by) {
+        return Err(anyhow::anyhow!("Unknown trend grouping {:?}; use category, extension or rule", group_by));
+    }
+    let periods = periods.unwrap_or(default_periods).clamp(1, max_periods);
+    
+    // Period starts from the oldest to the current one
+    let today = Utc::now().date_naive();
+    let current = match period {
+        "day" => today,
+        "week" => today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64),
+        _ => today.with_day(1).unwrap_or(today),
+    };
+    let step_back = |date: NaiveDate, n: u32| match period {
+        "day" => date - chrono::Duration::days(n as i64),
+        "week" => date - chrono::Duration::weeks(n as i64),
+        _ => date - chrono::Months::new(n),
+    };
+    let mut buckets: Vec<TrendBucket> = (0..periods)
+        .rev()
+        .map(|n| TrendBucket {
+            start: step_back(current, n).format("%Y-%m-%d").to_string(),
+            total: TrendCounts::default(),
+            groups: BTreeMap::new(),
+        })
+        .collect();
+    
+    let categories = database::get_categories(app)?;
+    // Compared as text against stored times, so formatted the same way
+    let first_start = step_back(current, periods - 1).and_time(chrono::NaiveTime::MIN).and_utc();
+    let since = utils::format_timestamp(&first_start);
+    for row in database::get_trend_rows(app, bucket_sql, &since)? {
+        let Some(bucket) = buckets.iter_mut().find(|bucket| bucket.start == row.bucket) else { continue };
+        let extension = row.extension.to_lowercase();
+        let group = match group_by {
+            "category" => categories
+                .iter()
+                .find(|category| category.extensions.contains(&extension))
+                .map_or(OTHER_CATEGORY.to_string(), |category| category.name.clone()),
+            "extension" => extension,
+            _ => row
+                .detail
+                .as_deref()
+                .and_then(|detail| detail.strip_prefix("rule: "))
+                .unwrap_or(NO_RULE_GROUP)
+                .to_string(),
+        };
+        bucket.total.add(&row);
+        bucket.groups.entry(group).or_default().add(&row);
+    }
+    
+    Ok(buckets)
+}
+
+// Setting that mirrors organizer tags back onto the file's OS tags
+pub const WRITE_OS_TAGS_SETTING: &str = "write_os_tags";
+
+// Import the tags the OS had stored on a file, optionally writing ours back
+fn sync_os_tags<R: Runtime>(app: &AppHandle<R>, file_id: i64, os_tags: Vec<utils::OsTag>, path: &Path) -> Result<()> {
+    if !utils::supports_os_tags() {
+        return Ok(());
+    }
+    
+    for os_tag in os_tags {
+        let color = os_tag
+            .color
+            .unwrap_or_else(|| utils::DEFAULT_TAG_COLOR.to_string());
+        let tag_id = database::get_or_create_tag(app, &os_tag.name, &color)?;
+        database::add_tag_to_file(app, file_id, tag_id)?;
+    }
+    
+    if database::is_setting_enabled(app, WRITE_OS_TAGS_SETTING)? {
+        let names: Vec<String> = database::get_file_tags(app, file_id)?
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+        utils::write_os_tags(path, &names)?;
+    }
+    
+    Ok(())
+}
+
+// Manually organize a file, returning what was done with it
+pub async fn organize_file(
+    app: &AppHandle, 
+    file_path: PathBuf,
+    destination_folder: Option<String>,
+    tag_ids: Vec<i64>,
+) -> Result<OrganizeOutcome> {
+    let result = organize_file_inner(app, file_path.clone(), destination_folder, tag_ids).await;
+    let error = match &result {
+        Ok(OrganizeOutcome::Failed { error }) => Some(error.clone()),
+        Ok(_) => None,
+        Err(e) => Some(format!("{:#}", e)),
+    };
+    if let Some(message) = error {
+        events::file_error(app, events::FileError {
+            path: file_path.to_string_lossy().to_string(),
+            message,
+        });
+    }
+    result
+}
+
+async fn organize_file_inner(
+    app: &AppHandle, 
+    file_path: PathBuf,
+    destination_folder: Option<String>,
+    tag_ids: Vec<i64>,
+) -> Result<OrganizeOutcome> {
+    // Check the tags before anything moves, so a bad ID can't leave the file half done
+    if !tag_ids.is_empty() {
+        let known: Vec<i64> = database::get_all_tags(app)?.into_iter().map(|tag| tag.id).collect();
+        if let Some(tag_id) = tag_ids.iter().find(|tag_id| !known.contains(tag_id)) {
+            return Err(anyhow::anyhow!("Tag {} not found", tag_id));
+        }
+    }
+    
+    if let Some(dest) = destination_folder {
+        if !file_path.is_file() {
+            return Ok(OrganizeOutcome::Skipped { reason: SkipReason::NotFound });
+        }
+        
+        // User specified a destination folder
+        let dest_path = PathBuf::from(dest);
+        
+        // Ensure destination folder exists
+        if !dest_path.exists() {
+            fs::create_dir_all(&dest_path)?;
+        }
+        
+        // Read OS metadata before a copy drops it
+        let source_url = utils::read_source_url(&file_path).ok().flatten();
+        let source_domain = source_url.as_deref().and_then(utils::url_domain);
+        let os_tags = utils::read_os_tags(&file_path).unwrap_or_default();
+        
+        // Move the file, numbering the name if the destination is taken
+        let clear_read_only = database::is_setting_enabled(app, CLEAR_READ_ONLY_SETTING)?;
+        let (app_handle, source, destination) = (app.clone(), file_path.clone(), dest_path.clone());
+        let moved = tokio::task::spawn_blocking(move || {
+            journaled_move(&app_handle, &source, &destination, clear_read_only, "manual")
+        })
+        .await?;
+        let (new_path, journal_id) = match moved {
+            Ok(moved) => moved,
+            Err(e) => return skipped_move(app, &file_path, &e).ok_or(e),
+        };
+        
+        // Add to database
+        let extension = utils::get_file_extension(&file_path).unwrap_or_default();
+        
+        let metadata = fs::metadata(&new_path)?;
+        let size = metadata.len() as i64;
+        
+        let (created_dt, created_approximate) = utils::file_created(&metadata);
+        let modified = metadata.modified().unwrap_or_else(|_| std::time::SystemTime::now());
+        let modified_dt: DateTime<Utc> = modified.into();
+        
+        let file_id = database::add_file(app, &database::NewFile {
+            path: new_path.clone(),
+            name: utils::get_file_name(&new_path).unwrap_or_default(),
+            extension,
+            size,
+            created_at: utils::format_timestamp(&created_dt),
+            created_at_approximate: created_approximate,
+            modified_at: utils::format_timestamp(&modified_dt),
+        })?;
+        
+        database::record_file_event(
+            app,
+            Some(file_id),
+            database::EVENT_MOVED,
+            Some(&file_path.to_string_lossy()),
+            Some(&new_path.to_string_lossy()),
+            Some("manual"),
+        )?;
+        
+        // Before syncing OS tags, so they're written back along with the rest
+        database::add_tags_to_file(app, file_id, &tag_ids)?;
+        if let Some(journal_id) = journal_id {
+            database::finish_journaled_move(app, journal_id, database::MOVE_COMPLETED)?;
+        }
+        
+        log_if_err(
+            database::set_file_source(app, file_id, source_url.as_deref(), source_domain.as_deref()),
+            "Failed to record download source",
+        );
+        log_if_err(sync_os_tags(app, file_id, os_tags, &new_path), "Failed to sync OS tags");
+        mirror_source(app, &file_path, &new_path);
+        
+        events::file_organized(app, events::FileOrganized {
+            source: file_path.to_string_lossy().to_string(),
+            destination: new_path.to_string_lossy().to_string(),
+            rule: None,
+            category: None,
+        });
+        events::file_indexed(app, events::FileIndexed {
+            file_id,
+            path: new_path.to_string_lossy().to_string(),
+            size: size as u64,
+        });
+        
+        Ok(OrganizeOutcome::moved(&file_path, &new_path, None, false))
+    } else {
+        // Use rule-based organization; the tags go on the file once a rule has moved it
+        let outcome = organize_file_by_rules(app, &file_path).await?;
+        if let OrganizeOutcome::Moved { to, .. } = &outcome {
+            if let Some(file_id) = database::get_file_id_by_path(app, Path::new(to))? {
+                database::add_tags_to_file(app, file_id, &tag_ids)?;
+            }
+        }
+        Ok(outcome)
+    }
+}
+
+// Rename an indexed file within its directory, keeping its id and tags
+pub fn rename_file(app: &AppHandle, file_id: i64, new_name: &str) -> Result<PathBuf> {
+    let new_name = new_name.trim();
+    if new_name.is_empty() || new_name == "." || new_name == ".." {
+        return Err(anyhow::anyhow!("Invalid file name"));
+    }
+    // Check both separators so a name valid on one platform can't escape the folder on another
+    if new_name.contains('/') || new_name.contains('\\') {
+        return Err(anyhow::anyhow!("File name can't contain path separators"));
+    }
+    
+    let old_path = database::get_file_path(app, file_id)?
+        .ok_or_else(|| anyhow::anyhow!("File {} not found", file_id))?;
+    let dir = old_path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Invalid file path"))?;
+    let new_path = dir.join(new_name);
+    
+    if new_path == old_path {
+        return Ok(new_path);
+    }
+    
+    // fs::rename silently replaces an existing file on most platforms, so the
+    // new name is claimed with create_new first, the same way moves reserve
+    // their destination. On case-insensitive file systems a change in case
+    // only finds the file itself, which is renamed directly.
+    let case_change = new_path.exists() && utils::is_same_file(&old_path, &new_path);
+    if !case_change {
+        match fs::OpenOptions::new().write(true).create_new(true).open(&new_path) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(anyhow::anyhow!("A file named {} already exists in {}", new_name, dir.display()));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    
+    if let Err(e) = fs::rename(&old_path, &new_path) {
+        if !case_change {
+            let _ = fs::remove_file(&new_path);
+        }
+        return Err(e.into());
+    }
+    database::update_file_path(app, file_id, &new_path)?;
+    database::record_file_event(
+        app,
+        Some(file_id),
+        database::EVENT_RENAMED,
+        Some(&old_path.to_string_lossy()),
+        Some(&new_path.to_string_lossy()),
+        None,
+    )?;
+    
+    Ok(new_path)
+}
+
+// Folder for files that match no category
+const OTHER_CATEGORY: &str = "Other";
+
+// Result of organize_everything
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OrganizeSummary {
+    // Number of files moved into each category folder
+    pub counts: HashMap<String, usize>,
+    // Files that couldn't be moved, with the reason
+    pub errors: Vec<String>,
+    // Space moved onto each drive from other drives
+    pub drives: Vec<DriveDelta>,
+}
+
+// Space moved onto one drive from other drives by a scan or organize
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DriveDelta {
+    // Where the drive is mounted, e.g. "D:\" or "/mnt/data"
+    pub drive: String,
+    pub files: usize,
+    pub bytes: u64,
+    // e.g. "Moved 4.2 GB to D:\"
+    pub summary: String,
+    // Space left on the drive once the files are moved; for a dry run that's
+    // an estimate. None where the platform can't tell
+    pub free_bytes: Option<u64>,
+    // Set when the drive is left with less than health::LOW_DISK_SPACE_BYTES
+    pub warning: Option<String>,
+}
+
+// Adds up the files moved between drives while organizing. Moves within one
+// drive free nothing up, so they aren't counted
+#[derive(Default)]
+struct DriveTally {
+    // Files and bytes per destination drive
+    drives: BTreeMap<PathBuf, (usize, u64)>,
+}
+
+impl DriveTally {
+    fn add(&mut self, from: &Path, to: &Path, size: u64) {
+        let Some(to_drive) = utils::drive_root(to) else { return };
+        if utils::drive_root(from).as_ref() == Some(&to_drive) {
+            return;
+        }
+        let (files, bytes) = self.drives.entry(to_drive).or_default();
+        *files += 1;
+        *bytes += size;
+    }
+    
+    // Count a move from a scan or organize_folder_once. A file a dry run
+    // would move is still where it was
+    fn add_outcome(&mut self, outcome: &OrganizeOutcome) {
+        if let OrganizeOutcome::Moved { from, to, .. } = outcome {
+            let size = fs::metadata(to).or_else(|_| fs::metadata(from)).map(|metadata| metadata.len());
+            self.add(Path::new(from), Path::new(to), size.unwrap_or(0));
+        }
+    }
+    
+    // The totals, checked against the space left on each drive. Without
+    // dry_run the files are already there, so the space left is read as is
+    fn into_deltas(self, dry_run: bool) -> Vec<DriveDelta> {
+        self.drives
+            .into_iter()
+            .map(|(drive, (files, bytes))| {
+                let drive_name = drive.to_string_lossy().to_string();
+                let free_bytes = utils::free_disk_space(&drive)
+                    .map(|free| if dry_run { free.saturating_sub(bytes) } else { free });
+                let warning = free_bytes
+                    .filter(|free| *free < health::LOW_DISK_SPACE_BYTES)
+                    .map(|free| format!("Low disk space on {}: {} left", drive_name, utils::format_file_size(free)));
+                if let Some(warning) = &warning {
+                    tracing::warn!(drive = %drive_name, bytes, "{}", warning);
+                }
+                DriveDelta {
+                    summary: format!(
+                        "{} {} to {}",
+                        if dry_run { "Would move" } else { "Moved" },
+                        utils::format_file_size(bytes),
+                        drive_name
+                    ),
+                    drive: drive_name,
+                    files,
+                    bytes,
+                    free_bytes,
+                    warning,
+                }
+            })
+            .collect()
+    }
+}
+
+// Files and bytes of one category in analyze_folder
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct CategoryBreakdown {
+    pub count: usize,
+    pub total_size: u64,
+}
+
+// How many files and bytes of each category a folder holds, e.g. to preview an
+// organize before running it. Nothing is moved or indexed. Subfolders are
+// walked as deep as the max_depth setting allows, and hidden and ignored
+// files are left out the way the organizer leaves them out
+pub async fn analyze_folder(app: &AppHandle, path: String) -> Result<HashMap<String, CategoryBreakdown>> {
+    let folder = PathBuf::from(&path);
+    if !folder.is_dir() {
+        return Err(anyhow::anyhow!("Invalid folder path"));
+    }
+    let max_depth = max_depth_setting(app)?;
+    let include_hidden = database::is_setting_enabled(app, INCLUDE_HIDDEN_SETTING)?;
+    let ignore_list = IgnoreList::load(app)?;
+    let categories = database::get_categories(app)?;
+    
+    // Walking a large tree blocks, so keep it off the async runtime
+    tokio::task::spawn_blocking(move || {
+        let files: Vec<PathBuf> = utils::collect_files(&folder, max_depth, include_hidden)?
+            .into_iter()
+            .filter(|file| !ignore_list.is_ignored(&utils::normalize_path(file)))
+            .collect();
+        
+        let mut breakdown = HashMap::new();
+        for (category_name, paths) in utils::categorize_files(&files, &categories) {
+            let total_size = paths
+                .iter()
+                .filter_map(|path| fs::metadata(path).ok())
+                .map(|metadata| metadata.len())
+                .sum();
+            breakdown.insert(category_name, CategoryBreakdown { count: paths.len(), total_size });
+        }
+        Ok(breakdown)
+    })
+    .await?
+}
+
+// Sort every file directly inside source_folder into one folder per category
+// under target_root, no rules needed
+pub async fn organize_everything(
+    app: &AppHandle,
+    source_folder: String,
+    target_root: String,
+) -> Result<OrganizeSummary> {
+    let source = Path::new(&source_folder);
+    if !source.is_dir() {
+        return Err(anyhow::anyhow!("Invalid folder path"));
+    }
+    let target_root = PathBuf::from(target_root);
+    let include_hidden = database::is_setting_enabled(app, INCLUDE_HIDDEN_SETTING)?;
+    let clear_read_only = database::is_setting_enabled(app, CLEAR_READ_ONLY_SETTING)?;
+    let ignore_list = IgnoreList::load(app)?;
+    
+    // Only the top level: subfolders are usually already organized (projects,
+    // albums) and flattening them would lose that
+    let mut files = vec![];
+    for entry in fs::read_dir(source)? {
+        let path = entry?.path();
+        if path.is_file() && (include_hidden || !utils::is_hidden(&path)) && !ignore_list.is_ignored(&utils::normalize_path(&path)) {
+            files.push(path);
+        }
+    }
+    
+    let categories = database::get_categories(app)?;
+    
+    // Create the whole tree up front so the layout is there even for empty categories
+    for name in categories.iter().map(|c| c.name.as_str()).chain([OTHER_CATEGORY]) {
+        utils::ensure_dir_exists(&target_root.join(name))?;
+    }
+    
+    let mut summary = OrganizeSummary {
+        counts: HashMap::new(),
+        errors: vec![],
+        drives: vec![],
+    };
+    let mut drives = DriveTally::default();
+    
+    for (category_name, paths) in utils::categorize_files(&files, &categories) {
+        let dest_dir = target_root.join(&category_name);
+        let tag_id = categories.iter().find(|c| c.name == category_name).and_then(|c| c.tag_id);
+        
+        let app_handle = app.clone();
+        let (category, destination) = (category_name.clone(), dest_dir.clone());
+        let results = tokio::task::spawn_blocking(move || {
+            paths
+                .into_iter()
+                .map(|file_path| {
+                    let result = move_into_category(&app_handle, &file_path, &destination, &category, tag_id, clear_read_only);
+                    (file_path, result)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await?;
+        let mut moved = vec![];
+        for (file_path, result) in results {
+            match result {
+                Ok(file) => moved.push(file),
+                Err(e) => {
+                    events::file_error(app, events::FileError {
+                        path: file_path.to_string_lossy().to_string(),
+                        message: format!("{:#}", e),
+                    });
+                    summary.errors.push(format!("{}: {}", file_path.display(), e));
+                }
+            }
+        }
+        if moved.is_empty() {
+            continue;
+        }
+        
+        // Index the whole category at once; the files were moved either way
+        let results = match database::index_moved_files(app, &moved) {
+            Ok(results) => results,
+            Err(e) => {
+                for file in &moved {
+                    events::file_error(app, events::FileError {
+                        path: file.file.path.to_string_lossy().to_string(),
+                        message: format!("Moved, but not indexed: {:#}", e),
+                    });
+                }
+                summary.errors.push(format!("{}: moved, but not indexed: {:#}", category_name, e));
+                continue;
+            }
+        };
+        
+        for (file, result) in moved.into_iter().zip(results) {
+            let file_id = match result {
+                Ok(indexed) => indexed.file_id,
+                Err(e) => {
+                    events::file_error(app, events::FileError {
+                        path: file.file.path.to_string_lossy().to_string(),
+                        message: format!("Moved, but not indexed: {:#}", e),
+                    });
+                    summary.errors.push(format!("{}: moved, but not indexed: {:#}", file.file.path.display(), e));
+                    continue;
+                }
+            };
+            *summary.counts.entry(category_name.clone()).or_default() += 1;
+            drives.add(&file.from, &file.file.path, file.file.size as u64);
+            mirror_source(app, &file.from, &file.file.path);
+            events::file_organized(app, events::FileOrganized {
+                source: file.from.to_string_lossy().to_string(),
+                destination: file.file.path.to_string_lossy().to_string(),
+                rule: None,
+                category: Some(category_name.clone()),
+            });
+            events::file_indexed(app, events::FileIndexed {
+                file_id,
+                path: file.file.path.to_string_lossy().to_string(),
+                size: file.file.size as u64,
+            });
+            notifications::notify(
+                app,
+                notifications::KIND_ORGANIZED,
+                format!("Moved {} → {}", file.file.name, category_name),
+            );
+            webhook::file_organized(app, webhook::OrganizedFile {
+                name: file.file.name,
+                from: file.from.to_string_lossy().to_string(),
+                to: file.file.path.to_string_lossy().to_string(),
+                category: Some(category_name.clone()),
+            });
+        }
+    }
+    
+    summary.drives = drives.into_deltas(false);
+    Ok(summary)
+}
+
+// Move one file into its category folder, returning it ready to be indexed
+fn move_into_category(
+    app: &AppHandle,
+    file_path: &Path,
+    dest_dir: &Path,
+    category_name: &str,
+    tag_id: Option<i64>,
+    clear_read_only: bool,
+) -> Result<database::MovedFile> {
+    let detail = format!("organize everything: {}", category_name);
+    let (new_path, journal_id) = journaled_move(app, file_path, dest_dir, clear_read_only, &detail)?;
+    
+    let metadata = fs::metadata(&new_path)?;
+    let (created, created_approximate) = utils::file_created(&metadata);
+    let modified: DateTime<Utc> = metadata.modified().unwrap_or_else(|_| std::time::SystemTime::now()).into();
+    
+    Ok(database::MovedFile {
+        file: database::NewFile {
+            extension: utils::get_file_extension(&new_path).unwrap_or_default(),
+            name: utils::get_file_name(&new_path).unwrap_or_default(),
+            path: new_path,
+            size: metadata.len() as i64,
+            created_at: utils::format_timestamp(&created),
+            created_at_approximate: created_approximate,
+            modified_at: utils::format_timestamp(&modified),
+        },
+        from: file_path.to_path_buf(),
+        detail,
+        tag_ids: vec![],
+        auto_tag_ids: tag_id.into_iter().collect(),
+        journal_id,
+    })
+}
+
+// Move a file like utils::move_to_dir, journaling the move first so one cut
+// short by a crash is finished or undone at the next launch. The returned
+// journal entry is to be completed once the move is indexed
+fn journaled_move<R: Runtime>(
+    app: &AppHandle<R>,
+    file_path: &Path,
+    dest_dir: &Path,
+    clear_read_only: bool,
+    detail: &str,
+) -> Result<(PathBuf, Option<i64>)> {
+    check_space_before_move(app, file_path, dest_dir)?;
+    
+    let mut journal_id = None;
+    let result = utils::move_to_dir_with(file_path, dest_dir, clear_read_only, |destination, step| {
+        match (step, journal_id) {
+            (utils::MoveStep::Copying, Some(journal_id)) => database::journal_move_copying(app, journal_id),
+            _ => {
+                journal_id = Some(database::journal_move(app, journal_id, file_path, destination, detail)?);
+                Ok(())
+            }
+        }
+    });
+    
+    match result {
+        Ok(new_path) => Ok((new_path, journal_id)),
+        Err(e) => {
+            if let Some(journal_id) = journal_id {
+                log_if_err(
+                    database::finish_journaled_move(app, journal_id, database::MOVE_FAILED),
+                    "Failed to record failed move",
+                );
+            }
+            Err(e)
+        }
+    }
+}
+
+// How long after warning that a drive runs low the next move onto it warns again
+const LOW_SPACE_WARNING_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// When each drive was last warned about, so a run of moves warns once
+static LOW_SPACE_WARNED: Mutex<BTreeMap<PathBuf, std::time::Instant>> = Mutex::new(BTreeMap::new());
+
+// What moving a file of `size` bytes does to a drive with `free` bytes left
+#[derive(Debug, PartialEq)]
+enum SpaceAfterMove {
+    Enough,
+    // Bytes left afterwards, below health::LOW_DISK_SPACE_BYTES
+    Low(u64),
+    NoRoom,
+}
+
+fn space_after_move(free: u64, size: u64) -> SpaceAfterMove {
+    match free.checked_sub(size) {
+        None => SpaceAfterMove::NoRoom,
+        Some(left) if left < health::LOW_DISK_SPACE_BYTES => SpaceAfterMove::Low(left),
+        Some(_) => SpaceAfterMove::Enough,
+    }
+}
+
+// Check the destination drive before copying a file onto it from another
+// drive: a file that doesn't fit isn't moved, and one that leaves the drive
+// running low sends a health_warning first. Moves within a drive take no space
+fn check_space_before_move<R: Runtime>(app: &AppHandle<R>, file_path: &Path, dest_dir: &Path) -> Result<()> {
+    let Some(drive) = utils::drive_root(dest_dir) else { return Ok(()) };
+    if utils::drive_root(file_path).as_ref() == Some(&drive) {
+        return Ok(());
+    }
+    let Some(free) = utils::free_disk_space(&drive) else { return Ok(()) };
+    let size = utils::get_file_size(file_path)?;
+    
+    match space_after_move(free, size) {
+        SpaceAfterMove::Enough => Ok(()),
+        SpaceAfterMove::NoRoom => Err(anyhow::anyhow!(
+            "Not enough space on {}: {} needed, {} left",
+            drive.display(),
+            utils::format_file_size(size),
+            utils::format_file_size(free)
+        )),
+        SpaceAfterMove::Low(left) => {
+            let mut warned = LOW_SPACE_WARNED.lock().unwrap();
+            let recently = warned
+                .get(&drive)
+                .is_some_and(|at| at.elapsed() < LOW_SPACE_WARNING_INTERVAL);
+            if !recently {
+                warned.insert(drive.clone(), std::time::Instant::now());
+                let message = format!(
+                    "Low disk space on {}: moving files there leaves {}",
+                    drive.display(),
+                    utils::format_file_size(left)
+                );
+                tracing::warn!(drive = %drive.display(), "{}", message);
+                let _ = app.emit(events::HEALTH_WARNING, health::HealthWarning { message });
+            }
+            Ok(())
+        }
+    }
+}
+
+// A folder moved whole by a folder rule; the payload of folder-organized
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FolderMove {
+    pub source: String,
+    pub destination: String,
+    pub rule: String,
+    // Files moved along with it, all indexed under their new paths
+    pub files: usize,
+}
+
+// Move a folder directly in a watched folder with the folder rule matching its
+// name, indexing every file in it under its new path in one transaction. None
+// when no folder rule applies to it (any more) or it's already in place
+fn move_folder_by_rules(app: &AppHandle, root: &str, folder: &Path) -> Result<Option<FolderMove>> {
+    let rule_limit = database::get_folder_rule_limit(app, root)?;
+    let engine = RuleEngine::load_limited(app, rule_limit.as_deref())?;
+    let name = utils::get_file_name(folder).ok_or_else(|| anyhow::anyhow!("Invalid folder path"))?;
+    let Some(rule) = engine.matching_folder(&name) else { return Ok(None) };
+    if IgnoreList::load(app)?.is_ignored(folder) {
+        return Ok(None);
+    }
+    if is_leftover_of_move(app, folder)? {
+        // Its files are duplicates of ones already moved, so removing it
+        // again is all that's left to do
+        if let Err(e) = fs::remove_dir_all(folder) {
+            tracing::warn!(folder = %folder.display(), error = %e, "Failed to remove what's left of a moved folder");
+        }
+        return Ok(None);
+    }
+    
+    let metadata = fs::metadata(folder)?;
+    let (created, created_approximate) = utils::file_created(&metadata);
+    let date = match created_approximate {
+        false => created,
+        true => metadata.modified().map(DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now()),
+    };
+    let dest_dir = rule_destination(app, &rule, folder, &date)?;
+    if is_in_destination(folder, &dest_dir) {
+        return Ok(None);
+    }
+    if utils::normalize_path(&dest_dir).starts_with(folder) {
+        return Err(anyhow::anyhow!("{} can't be moved into itself", folder.display()));
+    }
+    utils::ensure_dir_exists(&dest_dir)?;
+    
+    // One journal entry covers the whole folder, so a move cut short is
+    // finished or undone as a unit at the next launch
+    let detail = format!("folder rule: {}", rule.name);
+    let mut journal_id = None;
+    let result = utils::move_dir_with(folder, &dest_dir, |destination, step| {
+        match (step, journal_id) {
+            (utils::MoveStep::Copying, Some(journal_id)) => database::journal_move_copying(app, journal_id),
+            _ => {
+                journal_id = Some(database::journal_move(app, None, folder, destination, &detail)?);
+                Ok(())
+            }
+        }
+    });
+    let new_folder = match result {
+        Ok(new_folder) => new_folder,
+        Err(e) => {
+            if let Some(journal_id) = journal_id {
+                log_if_err(
+                    database::finish_journaled_move(app, journal_id, database::MOVE_FAILED),
+                    "Failed to record failed move",
+                );
+            }
+            return Err(e);
+        }
+    };
+    
+    let moved = index_moved_folder(app, folder, &new_folder, &detail, rule.tag_id, journal_id)?;
+    let folder_move = FolderMove {
+        source: folder.to_string_lossy().to_string(),
+        destination: new_folder.to_string_lossy().to_string(),
+        rule: rule.name.clone(),
+        files: moved.len(),
+    };
+    for (file, file_id) in moved {
+        events::folder_file_organized(app, events::FileOrganized {
+            source: file.from.to_string_lossy().to_string(),
+            destination: file.file.path.to_string_lossy().to_string(),
+            rule: Some(rule.name.clone()),
+            category: None,
+        });
+        events::file_indexed(app, events::FileIndexed {
+            file_id,
+            path: file.file.path.to_string_lossy().to_string(),
+            size: file.file.size as u64,
+        });
+    }
+    
+    tracing::info!(
+        folder = %folder.display(),
+        destination = %new_folder.display(),
+        rule = %rule.name,
+        files = folder_move.files,
+        "Moved folder"
+    );
+    let _ = app.emit(events::FOLDER_ORGANIZED, folder_move.clone());
+    hooks::dispatch(app, events::FOLDER_ORGANIZED, hooks::HookContext {
+        path: folder_move.destination.clone(),
+        name: utils::get_file_name(&new_folder).unwrap_or_default(),
+        from: folder_move.source.clone(),
+        rule: Some(folder_move.rule.clone()),
+        category: None,
+        tags: vec![],
+    });
+    Ok(Some(folder_move))
+}
+
+// Whether a folder is what's left of one copied to another drive whose
+// original couldn't be removed in full, e.g. because a file in it was open:
+// every file still in it was moved away from that path, and the moved file
+// is an identical copy. Such a folder is never moved a second time
+fn is_leftover_of_move(app: &AppHandle, folder: &Path) -> Result<bool> {
+    let files = utils::collect_files(folder, None, true)?;
+    if files.is_empty() {
+        return Ok(false);
+    }
+    for file in files {
+        let Some(destination) = database::get_moved_destination(app, &file)? else { return Ok(false) };
+        let identical = fs::metadata(&destination)?.len() == fs::metadata(&file)?.len()
+            && utils::is_prefix_of(&file, &destination)?;
+        if !identical {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// Index every file of a moved folder under its new path in one transaction,
+// completing the move's journal entry along with it. Returns the files that
+// were indexed; one that couldn't be is logged and left out
+fn index_moved_folder(
+    app: &AppHandle,
+    from: &Path,
+    folder: &Path,
+    detail: &str,
+    tag_id: Option<i64>,
+    journal_id: Option<i64>,
+) -> Result<Vec<(database::MovedFile, i64)>> {
+    let mut moved = vec![];
+    for path in utils::collect_files(folder, None, true)? {
+        let Ok(relative) = path.strip_prefix(folder) else { continue };
+        let metadata = fs::metadata(&path)?;
+        let (created, created_approximate) = utils::file_created(&metadata);
+        let modified: DateTime<Utc> = metadata.modified().unwrap_or_else(|_| std::time::SystemTime::now()).into();
+        moved.push(database::MovedFile {
+            file: database::NewFile {
+                name: utils::get_file_name(&path).unwrap_or_default(),
+                extension: utils::get_file_extension(&path).unwrap_or_default(),
+                size: metadata.len() as i64,
+                created_at: utils::format_timestamp(&created),
+                created_at_approximate: created_approximate,
+                modified_at: utils::format_timestamp(&modified),
+                path: utils::normalize_path(&path),
+            },
+            from: from.join(relative),
+            detail: detail.to_string(),
+            tag_ids: tag_id.into_iter().collect(),
+            auto_tag_ids: vec![],
+            journal_id,
+        });
+    }
+    
+    // An empty folder has no file to complete the journal entry with
+    if moved.is_empty() {
+        if let Some(journal_id) = journal_id {
+            database::finish_journaled_move(app, journal_id, database::MOVE_COMPLETED)?;
+        }
+        return Ok(vec![]);
+    }
+    let results = database::index_moved_files(app, &moved)?;
+    let mut indexed = Vec::with_capacity(moved.len());
+    for (file, result) in moved.into_iter().zip(results) {
+        match result {
+            Ok(file_indexed) => indexed.push((file, file_indexed.file_id)),
+            Err(e) => tracing::warn!(file = %file.file.path.display(), error = format!("{:#}", e), "Moved, but not indexed"),
+        }
+    }
+    Ok(indexed)
+}
+
+// Setting that leaves something at a moved file's old path, for programs that
+// still expect it there: a hard link to the moved file, or when that isn't
+// possible (e.g. across drives) a BREADCRUMB_SUFFIX note saying where it went
+pub const SOURCE_MIRROR_SETTING: &str = "source_mirror";
+
+const BREADCRUMB_SUFFIX: &str = ".moved.txt";
+
+// Leave a SOURCE_MIRROR_SETTING link or note at the old path of a file, when
+// the setting is on. Only called once the move is indexed: its history entry
+// is what tells is_source_mirror the mirror apart from a new file
+fn mirror_source<R: Runtime>(app: &AppHandle<R>, source: &Path, destination: &Path) {
+    match database::is_setting_enabled(app, SOURCE_MIRROR_SETTING) {
+        Ok(true) => log_if_err(leave_source_mirror(source, destination), "Failed to leave a link at the old path"),
+        Ok(false) => {}
+        Err(e) => tracing::warn!(error = format!("{:#}", e), "Failed to read the source mirror setting"),
+    }
+}
+
+fn leave_source_mirror(source: &Path, destination: &Path) -> Result<()> {
+    // Claimed while it's made, so a watcher never handles a half-written note
+    let breadcrumb = PathBuf::from(format!("{}{}", source.display(), BREADCRUMB_SUFFIX));
+    let _claim = {
+        let mut in_flight = IN_FLIGHT_PATHS.lock().unwrap();
+        InFlightPaths(
+            [source.to_path_buf(), breadcrumb.clone()]
+                .into_iter()
+                .filter(|path| in_flight.insert(path.clone()))
+                .collect(),
+        )
+    };
+    
+    if fs::hard_link(destination, source).is_ok() {
+        return Ok(());
+    }
+    
+    fs::write(
+        &breadcrumb,
+        format!(
+            "{} was moved to:\n{}\n",
+            utils::get_file_name(source).unwrap_or_default(),
+            destination.display()
+        ),
+    )?;
+    Ok(())
+}
+
+// Whether a file is a hard link or note left by SOURCE_MIRROR_SETTING, which
+// must not be organized again. Checked whether or not the setting is still on
+fn is_source_mirror<R: Runtime>(app: &AppHandle<R>, path: &Path) -> Result<bool> {
+    let path_str = path.to_string_lossy();
+    if let Some(original) = path_str.strip_suffix(BREADCRUMB_SUFFIX) {
+        return Ok(database::get_moved_destination(app, Path::new(original))?.is_some());
+    }
+    Ok(database::get_moved_destination(app, path)?
+        .is_some_and(|destination| utils::is_hard_link_of(path, &destination)))
+}
+
+// Finished journal entries are kept this long
+const MOVE_JOURNAL_RETENTION_DAYS: i64 = 30;
+
+// Settle moves the journal shows were started but never completed, e.g.
+// because the app crashed part way through, as recovery_of decides
+pub fn reconcile_move_journal(app: &AppHandle) -> Result<()> {
+    for journaled in database::get_unfinished_moves(app)? {
+        let (source, destination) = (&journaled.source, &journaled.destination);
+        let result = match recovery_of(&journaled) {
+            Recovery::Undo => {
+                let removed = if destination.is_dir() {
+                    fs::remove_dir_all(destination)
+                } else {
+                    fs::remove_file(destination)
+                };
+                removed
+                    .map_err(anyhow::Error::from)
+                    .and_then(|_| database::finish_journaled_move(app, journaled.id, database::MOVE_FAILED))
+            }
+            Recovery::Finish if destination.is_dir() => index_moved_folder(
+                app,
+                source,
+                destination,
+                &format!("{} (recovered)", journaled.detail.as_deref().unwrap_or("move")),
+                None,
+                Some(journaled.id),
+            )
+            .map(|_| ()),
+            Recovery::Finish => index_recovered_move(app, &journaled),
+            Recovery::Fail => database::finish_journaled_move(app, journaled.id, database::MOVE_FAILED),
+        };
+        match result {
+            Ok(()) => tracing::info!(
+                source = %journaled.source.display(),
+                destination = %journaled.destination.display(),
+                "Reconciled interrupted move"
+            ),
+            Err(e) => tracing::error!(
+                source = %journaled.source.display(),
+                error = format!("{:#}", e),
+                "Failed to reconcile interrupted move"
+            ),
+        }
+    }
+    
+    let before = Utc::now() - chrono::Duration::days(MOVE_JOURNAL_RETENTION_DAYS);
+    database::prune_move_journal(app, &utils::format_timestamp(&before))?;
+    Ok(())
+}
+
+// Index a file whose move finished before it could be indexed, completing its
+// journal entry
+fn index_recovered_move(app: &AppHandle, journaled: &database::JournaledMove) -> Result<()> {
+    let path = &journaled.destination;
+    let metadata = fs::metadata(path)?;
+    let (created, created_approximate) = utils::file_created(&metadata);
+    let modified: DateTime<Utc> = metadata.modified().unwrap_or_else(|_| std::time::SystemTime::now()).into();
+    
+    database::index_moved_files(app, &[database::MovedFile {
+        file: database::NewFile {
+            path: path.clone(),
+            name: utils::get_file_name(path).unwrap_or_default(),
+            extension: utils::get_file_extension(path).unwrap_or_default(),
+            size: metadata.len() as i64,
+            created_at: utils::format_timestamp(&created),
+            created_at_approximate: created_approximate,
+            modified_at: utils::format_timestamp(&modified),
+        },
+        from: journaled.source.clone(),
+        detail: format!("{} (recovered)", journaled.detail.as_deref().unwrap_or("move")),
+        tag_ids: vec![],
+        auto_tag_ids: vec![],
+        journal_id: Some(journaled.id),
+    }])?
+    .remove(0)?;
+    Ok(())
+}
+
+// What to do about an interrupted move
+#[derive(Debug, PartialEq, Eq)]
+enum Recovery {
+    // The original is still complete: delete what was claimed or copied at
+    // the destination, leaving the original where it was
+    Undo,
+    // The file or folder got to its destination: index it there
+    Finish,
+    // Nothing reached the destination
+    Fail,
+}
+
+// Decide an interrupted move from what is on disk and how far the journal
+// says it got:
+// - only the destination exists: it was moved but not indexed
+// - both exist as links to one file: the move finished and left a
+//   SOURCE_MIRROR_SETTING link
+// - both exist before any copying: a rename is atomic, so the destination is
+//   either still the empty claim, or the move happened and something new has
+//   turned up at the source since, which is left alone. An empty file can't
+//   be told from its claim, but deleting one loses nothing
+// - both exist while copying: the copy is undone only when every file in it
+//   matches the start of its original, so a completed copy is never deleted
+//   for a different file that turned up at the source after the move
+// - only the source, or neither, exists: there's nothing to undo
+fn recovery_of(journaled: &database::JournaledMove) -> Recovery {
+    let (source, destination) = (&journaled.source, &journaled.destination);
+    match (source.exists(), destination.exists()) {
+        (_, false) => Recovery::Fail,
+        (false, true) => Recovery::Finish,
+        (true, true) if utils::is_hard_link_of(source, destination) => Recovery::Finish,
+        (true, true) if !journaled.copying => {
+            let claimed = if destination.is_dir() {
+                fs::read_dir(destination).is_ok_and(|mut entries| entries.next().is_none())
+            } else {
+                fs::metadata(destination).is_ok_and(|metadata| metadata.len() == 0)
+            };
+            if claimed { Recovery::Undo } else { Recovery::Finish }
+        }
+        (true, true) if destination.is_dir() => {
+            if is_partial_folder_copy(source, destination) { Recovery::Undo } else { Recovery::Finish }
+        }
+        (true, true) => {
+            if utils::is_prefix_of(destination, source).unwrap_or(false) { Recovery::Undo } else { Recovery::Finish }
+        }
+    }
+}
+
+// Whether every file in a folder's copy matches the start of its original,
+// i.e. the original hasn't started being removed
+fn is_partial_folder_copy(source: &Path, destination: &Path) -> bool {
+    let Ok(copied) = utils::collect_files(destination, None, true) else { return false };
+    copied.iter().all(|path| {
+        path.strip_prefix(destination)
+            .is_ok_and(|relative| utils::is_prefix_of(path, &source.join(relative)).unwrap_or(false))
+    })
+}
+
+
+// Setting with the number of hours between scheduled scans of the watched
+// folders; 0 turns them off
+pub const SCAN_INTERVAL_SETTING: &str = "scan_interval_hours";
+
+// When the last scheduled scan ran, so the schedule carries over restarts
+const LAST_SCAN_SETTING: &str = "last_scheduled_scan";
+
+// Scans move whatever the rules match, so they only run once turned on
+const DEFAULT_SCAN_INTERVAL_HOURS: i64 = 0;
+
+// How often the scheduler checks whether a scan is due
+const SCAN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// Files organized and indexed together during a scan
+const SCAN_BATCH_SIZE: usize = 200;
+
+// Set while a scan runs, so scheduled and manual scans never overlap
+static SCAN_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// How long a folder whose watcher lost events is left to settle before it's
+// rescanned, and how often the rescan checks whether another scan finished
+const OVERFLOW_RESCAN_DELAY: Duration = Duration::from_secs(30);
+
+// Folders with a rescan queued after their watcher lost events
+static OVERFLOW_RESCANS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+// Times a watcher lost events since launch, reported by get_health
+static WATCH_OVERFLOWS: AtomicU64 = AtomicU64::new(0);
+
+// Why scan_folder didn't start
+#[derive(Debug, thiserror::Error)]
+#[error("A scan is already running")]
+pub struct ScanAlreadyRunning;
+
+// Result of scan_folder
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ScanSummary {
+    pub folder: String,
+    pub scanned: usize,
+    pub organized: usize,
+    // Files already in the folder their rule puts them in
+    pub already_organized: usize,
+    // Files left where they were, counted by reason
+    pub skipped: HashMap<SkipReason, usize>,
+    // Files that couldn't be organized, with the reason
+    pub errors: Vec<String>,
+    // Space moved onto each drive from other drives
+    pub drives: Vec<DriveDelta>,
+}
+
+// Run the rules over every file already in a folder, catching what the watcher
+// missed (e.g. files added while the app was closed). max_depth defaults to
+// MAX_DEPTH_SETTING
+pub async fn scan_folder<R: Runtime>(app: &AppHandle<R>, path: String, max_depth: Option<u32>) -> Result<ScanSummary> {
+    if SCAN_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err(ScanAlreadyRunning.into());
+    }
+    events::task_updated(app, events::TASK_SCAN, events::TASK_STARTED, Some(&path), None);
+    let result = scan_folder_inner(app, path.clone(), max_depth).await;
+    SCAN_RUNNING.store(false, Ordering::SeqCst);
+    match &result {
+        Ok(_) => events::task_updated(app, events::TASK_SCAN, events::TASK_FINISHED, Some(&path), None),
+        Err(e) => events::task_updated(app, events::TASK_SCAN, events::TASK_FAILED, Some(&path), Some(format!("{:#}", e))),
+    }
+    result
+}
+
+async fn scan_folder_inner<R: Runtime>(app: &AppHandle<R>, path: String, max_depth: Option<u32>) -> Result<ScanSummary> {
+    let folder = PathBuf::from(&path);
+    if !folder.is_dir() {
+        return Err(anyhow::anyhow!("Invalid folder path"));
+    }
+    
+    let max_depth = match max_depth {
+        Some(max_depth) => Some(max_depth),
+        None => max_depth_setting(app)?,
+    };
+    let include_hidden = database::is_setting_enabled(app, INCLUDE_HIDDEN_SETTING)?;
+    let rule_limit = database::get_folder_rule_limit(app, &path)?;
+    let files = tokio::task::spawn_blocking(move || {
+        utils::collect_files(&folder, max_depth, include_hidden)
+    })
+    .await??;
+    
+    let mut summary = ScanSummary {
+        folder: path,
+        scanned: 0,
+        organized: 0,
+        already_organized: 0,
+        skipped: HashMap::new(),
+        errors: vec![],
+        drives: vec![],
+    };
+    let mut drives = DriveTally::default();
+    
+    summary.scanned = files.len();
+    
+    for chunk in files.chunks(SCAN_BATCH_SIZE) {
+        let paths = chunk.to_vec();
+        let app_handle = app.clone();
+        let rule_limit = rule_limit.clone();
+        let outcomes = tokio::task::spawn_blocking(move || {
+            organize_files_by_rules(&app_handle, &paths, rule_limit.as_deref())
+        }).await?;
+        for (file_path, outcome) in chunk.iter().zip(outcomes) {
+            drives.add_outcome(&outcome);
+            match outcome {
+                OrganizeOutcome::Moved { .. } => summary.organized += 1,
+                OrganizeOutcome::AlreadyOrganized { .. } => summary.already_organized += 1,
+                OrganizeOutcome::Skipped { reason } => *summary.skipped.entry(reason).or_default() += 1,
+                OrganizeOutcome::Failed { error } => {
+                    events::file_error(app, events::FileError {
+                        path: file_path.to_string_lossy().to_string(),
+                        message: error.clone(),
+                    });
+                    summary.errors.push(format!("{}: {}", file_path.display(), error));
+                }
+            }
+        }
+    }
+    
+    summary.drives = drives.into_deltas(false);
+    Ok(summary)
+}
+
+// One file of an organize_folder_once run
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PlannedFile {
+    pub path: String,
+    #[serde(flatten)]
+    pub outcome: OrganizeOutcome,
+}
+
+// Result of organize_folder_once. With dry_run the counts and outcomes are
+// what would happen, and nothing was moved
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OrganizePlan {
+    pub folder: String,
+    pub dry_run: bool,
+    pub scanned: usize,
+    pub organized: usize,
+    pub already_organized: usize,
+    pub skipped: HashMap<SkipReason, usize>,
+    pub errors: Vec<String>,
+    // Space moved, or with dry_run to be moved, onto each drive from other drives
+    pub drives: Vec<DriveDelta>,
+    pub files: Vec<PlannedFile>,
+}
+
+// Apply the rules to a folder one time, e.g. to tidy Downloads now and then,
+// without watching it: no watcher is started and it isn't added to the watched
+// folders. Every active rule applies; only the top level is organized unless
+// recursive is set. With dry_run nothing is moved, and the plan says where
+// each file would go
+pub async fn organize_folder_once(app: &AppHandle, path: String, recursive: bool, dry_run: bool) -> Result<OrganizePlan> {
+    let folder = PathBuf::from(&path);
+    if !folder.is_dir() {
+        return Err(anyhow::anyhow!("Invalid folder path"));
+    }
+    let include_hidden = database::is_setting_enabled(app, INCLUDE_HIDDEN_SETTING)?;
+    let max_depth = if recursive { None } else { Some(0) };
+    let files = tokio::task::spawn_blocking(move || utils::collect_files(&folder, max_depth, include_hidden)).await??;
+    
+    let mut plan = OrganizePlan {
+        folder: path,
+        dry_run,
+        scanned: files.len(),
+        organized: 0,
+        already_organized: 0,
+        skipped: HashMap::new(),
+        errors: vec![],
+        drives: vec![],
+        files: Vec::with_capacity(files.len()),
+    };
+    let mut drives = DriveTally::default();
+    
+    for chunk in files.chunks(SCAN_BATCH_SIZE) {
+        let app_handle = app.clone();
+        let paths = chunk.to_vec();
+        let outcomes = tokio::task::spawn_blocking(move || match dry_run {
+            true => plan_files_by_rules(&app_handle, &paths),
+            false => organize_files_by_rules(&app_handle, &paths, None),
+        })
+        .await?;
+        
+        for (file_path, outcome) in chunk.iter().zip(outcomes) {
+            drives.add_outcome(&outcome);
+            match &outcome {
+                OrganizeOutcome::Moved { .. } => plan.organized += 1,
+                OrganizeOutcome::AlreadyOrganized { .. } => plan.already_organized += 1,
+                OrganizeOutcome::Skipped { reason } => *plan.skipped.entry(*reason).or_default() += 1,
+                OrganizeOutcome::Failed { error } => {
+                    if !dry_run {
+                        events::file_error(app, events::FileError {
+                            path: file_path.to_string_lossy().to_string(),
+                            message: error.clone(),
+                        });
+                    }
+                    plan.errors.push(format!("{}: {}", file_path.display(), error));
+                }
+            }
+            plan.files.push(PlannedFile { path: file_path.to_string_lossy().to_string(), outcome });
+        }
+    }
+    
+    plan.drives = drives.into_deltas(dry_run);
+    Ok(plan)
+}
+
+// What organize_files_by_rules would do with each file, without moving any.
+// A Moved outcome's destination has the file's current name; renamed says
+// whether it would have to be numbered
+fn plan_files_by_rules(app: &AppHandle, paths: &[PathBuf]) -> Vec<OrganizeOutcome> {
+    let loaded = RuleEngine::load(app)
+        .and_then(|engine| Ok((engine, IgnoreList::load(app)?, extra_screenshot_patterns(app)?)));
+    let (engine, ignore_list, screenshot_patterns) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            let e = e.context("Failed to load rules");
+            return paths.iter().map(|_| OrganizeOutcome::failed(&e)).collect();
+        }
+    };
+    
+    paths
+        .iter()
+        .map(|path| {
+            plan_file_by_rules(app, &engine, &ignore_list, &screenshot_patterns, path)
+                .unwrap_or_else(|e| OrganizeOutcome::failed(&e))
+        })
+        .collect()
+}
+
+// What move_file_by_rules would do with a file, without moving it
+fn plan_file_by_rules(
+    app: &AppHandle,
+    engine: &RuleEngine,
+    ignore_list: &IgnoreList,
+    screenshot_patterns: &[String],
+    file_path: &Path,
+) -> Result<OrganizeOutcome> {
+    let RuleTarget { file_path, rule, dest_path, .. } = match decide_by_rules(app, engine, ignore_list, screenshot_patterns, file_path)? {
+        RuleDecision::Unmoved(outcome) => return Ok(outcome),
+        RuleDecision::Move(target) => *target,
+    };
+    
+    let file_name = file_path.file_name().ok_or_else(|| anyhow::anyhow!("Invalid file path"))?;
+    let destination = dest_path.join(utils::safe_file_name(file_name));
+    Ok(OrganizeOutcome::Moved {
+        from: file_path.to_string_lossy().to_string(),
+        to: destination.to_string_lossy().to_string(),
+        rule: Some(rule.name),
+        renamed: destination.exists(),
+        // A file's own override isn't looked up for a plan
+        auto_tag_suppressed: rule.exclude_auto_tags,
+    })
+}
+
+// Setting that removes orphaned index rows after each scheduled scan
+pub const CLEANUP_ORPHANS_SETTING: &str = "cleanup_orphans_on_scan";
+
+// Remove indexed files that no longer exist on disk, along with their tag
+// links, and any tag links pointing at deleted files or tags
+pub async fn cleanup_orphans(app: &AppHandle) -> Result<database::OrphanCleanup> {
+    events::task_updated(app, events::TASK_ORPHAN_CLEANUP, events::TASK_STARTED, None, None);
+    let result = cleanup_orphans_inner(app).await;
+    match &result {
+        Ok(_) => events::task_updated(app, events::TASK_ORPHAN_CLEANUP, events::TASK_FINISHED, None, None),
+        Err(e) => events::task_updated(app, events::TASK_ORPHAN_CLEANUP, events::TASK_FAILED, None, Some(format!("{:#}", e))),
+    }
+    result
+}
+
+async fn cleanup_orphans_inner(app: &AppHandle) -> Result<database::OrphanCleanup> {
+    let files = database::get_all_file_paths(app)?;
+    let (missing, unavailable) = tokio::task::spawn_blocking(move || {
+        // A file whose drive or share isn't there right now is kept; so is
+        // one whose existence can't be told (no permission, a stale share)
+        let mut unavailable = 0;
+        let missing: Vec<(i64, String)> = files
+            .into_iter()
+            .filter(|(_, path)| match Path::new(path).try_exists() {
+                Ok(false) if utils::is_volume_available(Path::new(path)) => true,
+                Ok(true) => false,
+                _ => {
+                    unavailable += 1;
+                    false
+                }
+            })
+            .collect();
+        (missing, unavailable)
+    })
+    .await?;
+    
+    let cleanup = database::remove_orphans(app, &missing)?;
+    tracing::info!(
+        files_removed = cleanup.files_removed,
+        files_unavailable = unavailable,
+        tag_links_removed = cleanup.tag_links_removed,
+        "Cleaned up orphaned index rows"
+    );
+    
+    Ok(cleanup)
+}
+
+// Start the background task that periodically scans the watched folders
+pub fn start_scan_scheduler(app: &AppHandle) {
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SCAN_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_scheduled_scan(&app_handle).await {
+                tracing::error!(error = format!("{:#}", e), "Scheduled scan failed");
+            }
+        }
+    });
+}
+
+// Scan every active watched folder if the interval has passed since the last run
+async fn run_scheduled_scan(app: &AppHandle) -> Result<()> {
+    let interval_hours = database::get_setting(app, SCAN_INTERVAL_SETTING)?
+        .and_then(|value| value.trim().parse::<i64>().ok())
+        .unwrap_or(DEFAULT_SCAN_INTERVAL_HOURS);
+    // A scan moves files too, so it waits for the active hours
+    if interval_hours <= 0 || !schedule::is_active_now(app) {
+        return Ok(());
+    }
+    
+    let now = Utc::now();
+    let last_scan = database::get_setting(app, LAST_SCAN_SETTING)?
+        .and_then(|value| DateTime::parse_from_rfc3339(&value).ok())
+        .map(|time| time.with_timezone(&Utc));
+    if let Some(last_scan) = last_scan {
+        if now - last_scan < chrono::Duration::hours(interval_hours) {
+            return Ok(());
+        }
+    }
+    
+    // Watched files are kept up to date as they change, there's nothing to scan
+    let folders: Vec<String> = get_watched_folders(app)
+        .into_iter()
+        .filter(|folder| !folder.paused && !folder.is_file && folder.error.is_none())
+        .map(|folder| folder.path)
+        .collect();
+    if folders.is_empty() {
+        return Ok(());
+    }
+    
+    database::set_setting(app, LAST_SCAN_SETTING, &now.to_rfc3339())?;
+    for folder in folders {
+        match scan_folder(app, folder.clone(), None).await {
+            Ok(summary) => tracing::info!(
+                folder = %summary.folder,
+                scanned = summary.scanned,
+                organized = summary.organized,
+                errors = summary.errors.len(),
+                "Scheduled scan finished"
+            ),
+            Err(e) => tracing::warn!(folder = %folder, error = format!("{:#}", e), "Scheduled scan skipped folder"),
+        }
+    }
+    
+    if database::is_setting_enabled(app, CLEANUP_ORPHANS_SETTING)? {
+        cleanup_orphans(app).await?;
+    }
+    
+    Ok(())
 }
 
-// Event struct for frontend
-#[derive(Clone, serde::Serialize)]
-pub struct FileEvent {
-    pub path: String,
-    pub file_name: String,
-    pub event_type: String,
-    pub extension: String,
-    pub size: u64,
+// How often the scheduler checks for queued files it may organize
+const PENDING_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// Set while queued files are organized, so the scheduler and flush_pending_now
+// never overlap
+static FLUSH_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// What organizing the files queued outside the active hours did
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct PendingFlush {
+    pub total: usize,
+    pub organized: usize,
+    pub already_organized: usize,
+    // Left where they were, e.g. removed or no longer matching a rule
+    pub skipped: usize,
+    pub failed: usize,
 }
 
-// Start watching a folder
-pub async fn start_watching(app: &AppHandle, path: String) -> Result<()> {
-    // Create state if it doesn't exist
-    let state = app.state::<Arc<Mutex<WatcherState>>>();
+// Organize every file queued outside the active hours, whatever the time, and
+// send deferred-batch-executed when there were any
+pub async fn flush_pending(app: &AppHandle) -> Result<PendingFlush> {
+    if FLUSH_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err(anyhow::anyhow!("Queued files are already being organized"));
+    }
+    let result = flush_pending_inner(app).await;
+    FLUSH_RUNNING.store(false, Ordering::SeqCst);
     
-    // Create channel for events
-    let (tx, mut rx) = mpsc::channel::<FileEvent>(100);
+    if let Ok(flush) = &result {
+        if flush.total > 0 {
+            tracing::info!(
+                total = flush.total,
+                organized = flush.organized,
+                skipped = flush.skipped,
+                failed = flush.failed,
+                "Organized queued files"
+            );
+            let _ = app.emit(events::DEFERRED_BATCH_EXECUTED, flush.clone());
+        }
+    }
+    result
+}
+
+async fn flush_pending_inner(app: &AppHandle) -> Result<PendingFlush> {
+    let mut by_root: BTreeMap<String, Vec<database::PendingAction>> = BTreeMap::new();
+    for action in database::get_pending_actions(app)? {
+        by_root.entry(action.watch_root.clone()).or_default().push(action);
+    }
     
-    // Create debouncer - properly implemented for notify-debouncer-mini 0.4
-    let tx_clone = tx.clone();
-    let event_handler = move |res: notify::Result<Vec<DebouncedEvent>>| {
-        if let Ok(events) = res {
-            for e in events {
-                let path = e.path.clone();
-                
-                // Skip directories, hidden files
-                if path.is_dir() || path.file_name()
-                    .and_then(|s| s.to_str())
-                    .map(|s| s.starts_with("."))
-                    .unwrap_or(false) 
-                {
-                    continue;
+    let mut flush = PendingFlush::default();
+    for (root, actions) in by_root {
+        // Folders queued for a folder rule move whole
+        let (folders, actions): (Vec<_>, Vec<_>) = actions
+            .into_iter()
+            .partition(|action| Path::new(&action.path).is_dir());
+        for action in folders {
+            flush.total += 1;
+            // Already being moved since the active hours started
+            let folder = PathBuf::from(&action.path);
+            if !IN_FLIGHT_PATHS.lock().unwrap().insert(folder.clone()) {
+                flush.skipped += 1;
+                database::remove_pending_actions(app, &[action.id])?;
+                continue;
+            }
+            let claim = InFlightPaths(vec![folder]);
+            let app_handle = app.clone();
+            let watch_root = root.clone();
+            match tokio::task::spawn_blocking(move || move_folder_by_rules(&app_handle, &watch_root, &claim.0[0])).await? {
+                Ok(Some(_)) => flush.organized += 1,
+                Ok(None) => flush.skipped += 1,
+                Err(e) => {
+                    flush.failed += 1;
+                    events::file_error(app, events::FileError { path: action.path.clone(), message: format!("{:#}", e) });
                 }
-                
-                // Get file extension and name
-                let extension = path
-                    .extension()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("")
-                    .to_lowercase();
-                
-                let file_name = path
-                    .file_name()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("")
-                    .to_string();
-                
-                // Get file size
-                let size = fs::metadata(&path)
-                    .map(|m| m.len())
-                    .unwrap_or(0);
-                
-                // Create event
-                let file_event = FileEvent {
-                    path: path.to_string_lossy().to_string(),
-                    file_name,
-                    extension,
-                    size,
-                    event_type: "created".into(),
-                };
-                
-                // Send to channel
-                let _ = tx_clone.try_send(file_event);
             }
+            database::remove_pending_actions(app, &[action.id])?;
         }
-    };
-    
-    let mut debouncer = new_debouncer(Duration::from_secs(2), event_handler)?;
-    
-    // Start watcher
-    match debouncer.watcher().watch(Path::new(&path), notify::RecursiveMode::Recursive) {
-        Ok(_) => {
-            let mut state_guard = state.lock().unwrap();
-            state_guard.watchers.insert(path.clone(), debouncer);
-        }
-        Err(e) => {
-            return Err(anyhow::anyhow!("Failed to watch path: {}", e));
+        
+        // The folder's rule limit as it is now, not when the file arrived
+        let rule_limit = database::get_folder_rule_limit(app, &root)?;
+        for chunk in actions.chunks(SCAN_BATCH_SIZE) {
+            let paths: Vec<PathBuf> = chunk.iter().map(|action| PathBuf::from(&action.path)).collect();
+            let app_handle = app.clone();
+            let rule_limit = rule_limit.clone();
+            let outcomes = tokio::task::spawn_blocking(move || {
+                organize_files_by_rules(&app_handle, &paths, rule_limit.as_deref())
+            }).await?;
+            
+            for (action, outcome) in chunk.iter().zip(outcomes) {
+                flush.total += 1;
+                match outcome {
+                    OrganizeOutcome::Moved { .. } => flush.organized += 1,
+                    OrganizeOutcome::AlreadyOrganized { .. } => flush.already_organized += 1,
+                    OrganizeOutcome::Skipped { .. } => flush.skipped += 1,
+                    OrganizeOutcome::Failed { error } => {
+                        flush.failed += 1;
+                        events::file_error(app, events::FileError { path: action.path.clone(), message: error });
+                    }
+                }
+            }
+            
+            // Failed files aren't retried here; the next scan of the folder
+            // picks them up
+            let ids: Vec<i64> = chunk.iter().map(|action| action.id).collect();
+            database::remove_pending_actions(app, &ids)?;
         }
     }
     
-    // Create task to process file events
+    Ok(flush)
+}
+
+// Start the background task that organizes queued files once the active hours
+// start. Paused watching holds them back too
+pub fn start_pending_scheduler(app: &AppHandle) {
     let app_handle = app.clone();
-    tokio::spawn(async move {
-        while let Some(event) = rx.recv().await {
-            // Process the file - this will auto-organize based on rules
-            let _ = organize_file_by_rules(&app_handle, &PathBuf::from(&event.path)).await;
-            
-            // Emit the event to the frontend
-            let _ = app_handle.emit("file_event", event);
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(PENDING_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if pause_status(&app_handle).0 || !schedule::is_active_now(&app_handle) {
+                continue;
+            }
+            match database::count_pending_actions(&app_handle) {
+                Ok(0) => {}
+                Ok(_) => {
+                    if let Err(e) = flush_pending(&app_handle).await {
+                        tracing::error!(error = format!("{:#}", e), "Failed to organize queued files");
+                    }
+                }
+                Err(e) => tracing::error!(error = format!("{:#}", e), "Failed to read queued files"),
+            }
         }
     });
+}
+
+// Upper limit for preview_text_file, whatever the caller asks for
+const MAX_PREVIEW_BYTES: usize = 1024 * 1024;
+
+// Why a file couldn't be previewed; serialized as {"kind": ..., "message": ...}
+// so the UI can tell a binary file from a real failure
+#[derive(Debug, thiserror::Error, serde::Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum PreviewError {
+    #[error("Binary file")]
+    Binary,
+    #[error("{0}")]
+    Io(String),
+}
+
+impl From<std::io::Error> for PreviewError {
+    fn from(e: std::io::Error) -> Self {
+        PreviewError::Io(e.to_string())
+    }
+}
+
+// Read the start of a text file, at most max_bytes of it
+pub fn preview_text_file(path: &Path, max_bytes: usize) -> std::result::Result<String, PreviewError> {
+    let file = fs::File::open(path)?;
+    let mut buffer = vec![];
+    file.take(max_bytes.min(MAX_PREVIEW_BYTES) as u64).read_to_end(&mut buffer)?;
     
-    // Store the watched folder in the database
-    // This is async, so we don't await it to avoid blocking
-    let app_handle = app.clone();
-    let watch_path_clone = path.clone();
-    tokio::spawn(async move {
-        if let Ok(conn) = database::get_connection(&app_handle) {
-            let conn_guard = conn.lock().unwrap();
-            let _ = conn_guard.0.execute(
-                "INSERT OR REPLACE INTO watched_folders (path, is_active) VALUES (?, 1)",
-                rusqlite::params![watch_path_clone],
-            );
+    // Text files don't contain null bytes; nearly every binary format does
+    if buffer.contains(&0) {
+        return Err(PreviewError::Binary);
+    }
+    
+    match std::str::from_utf8(&buffer) {
+        Ok(text) => Ok(text.to_string()),
+        // Cut off in the middle of a character by max_bytes
+        Err(e) if e.error_len().is_none() => {
+            Ok(String::from_utf8_lossy(&buffer[..e.valid_up_to()]).into_owned())
         }
-    });
+        // Not UTF-8 (e.g. Latin-1); show it with replacement characters
+        Err(_) => Ok(String::from_utf8_lossy(&buffer).into_owned()),
+    }
+}
+
+// Error returned by get_file_details; NotFound lets the UI drop stale entries
+#[derive(Debug, thiserror::Error, serde::Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum FileDetailsError {
+    #[error("File {0} not found")]
+    NotFound(i64),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<anyhow::Error> for FileDetailsError {
+    fn from(e: anyhow::Error) -> Self {
+        FileDetailsError::Other(format!("{:#}", e))
+    }
+}
+
+// Everything known about one indexed file, for its details view
+#[derive(Debug, serde::Serialize)]
+pub struct FileDetails {
+    #[serde(flatten)]
+    pub file: FileInfo,
+    // Checked on disk when the details are requested
+    pub exists: bool,
+    // SHA-256 of the contents; None when the file is missing or unreadable
+    pub hash: Option<String>,
+    pub mime_type: String,
+    pub last_backup_at: Option<String>,
+    pub last_backup_bucket: Option<String>,
+    // How the file was last organized, e.g. "rule: Documents" or "manual"
+    pub organized_by: Option<String>,
+}
+
+// Get the full details of an indexed file
+pub async fn get_file_details(app: &AppHandle, file_id: i64) -> std::result::Result<FileDetails, FileDetailsError> {
+    let file = database::get_file_info(app, file_id)?
+        .ok_or(FileDetailsError::NotFound(file_id))?;
     
-    Ok(())
+    let path = PathBuf::from(&file.path);
+    let exists = path.is_file();
+    let hash = if exists {
+        tokio::task::spawn_blocking(move || utils::hash_file(&path).ok())
+            .await
+            .ok()
+            .flatten()
+    } else {
+        None
+    };
+    
+    let last_backup = database::get_last_file_event(app, file_id, database::EVENT_BACKED_UP)?;
+    let last_move = database::get_last_file_event(app, file_id, database::EVENT_MOVED)?;
+    
+    Ok(FileDetails {
+        exists,
+        hash,
+        mime_type: utils::get_mime_type(&file.extension),
+        last_backup_at: last_backup.as_ref().map(|event| event.created_at.clone()),
+        last_backup_bucket: last_backup.and_then(|event| event.detail),
+        organized_by: last_move.and_then(|event| event.detail),
+        file,
+    })
 }
 
-// Stop watching a folder
-pub async fn stop_watching(app: &AppHandle) -> Result<()> {
-    // Get the watcher state if it exists
-    if let Some(state) = app.try_state::<Arc<Mutex<WatcherState>>>() {
-        let mut state_guard = state.lock().unwrap();
-        
-        // Clear all watchers
-        state_guard.watchers.clear();
-        
-        // Update database
-        let app_handle = app.clone();
-        tokio::spawn(async move {
-            if let Ok(conn) = database::get_connection(&app_handle) {
-                let conn_guard = conn.lock().unwrap();
-                let _ = conn_guard.0.execute(
-                    "UPDATE watched_folders SET is_active = 0",
-                    [],
-                );
+// Compute the total size of a folder; can be cancelled with cancel_folder_size
+pub async fn get_folder_size(app: &AppHandle, path: String) -> Result<u64> {
+    let folder = PathBuf::from(&path);
+    if !folder.is_dir() {
+        return Err(anyhow::anyhow!("Invalid folder path"));
+    }
+    
+    let cancel = Arc::new(AtomicBool::new(false));
+    let state = app.state::<Arc<Mutex<FolderSizeState>>>();
+    state.lock().unwrap().scans.insert(path.clone(), cancel.clone());
+    
+    // Walking a large tree blocks, so keep it off the async runtime
+    let result = tokio::task::spawn_blocking(move || utils::get_folder_size(&folder, &cancel)).await;
+    
+    state.lock().unwrap().scans.remove(&path);
+    
+    result?
+}
+
+// Cancel a running folder size scan
+pub fn cancel_folder_size(app: &AppHandle, path: &str) {
+    let state = app.state::<Arc<Mutex<FolderSizeState>>>();
+    let state_guard = state.lock().unwrap();
+    if let Some(cancel) = state_guard.scans.get(path) {
+        cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+// Maximum Hamming distance between pHashes for two images to count as similar
+pub const DEFAULT_SIMILARITY_THRESHOLD: u32 = 10;
+
+// Group indexed images that look alike, even if resized or re-encoded
+pub async fn find_similar_images(app: &AppHandle, threshold: u32) -> Result<Vec<Vec<SimilarImage>>> {
+    let entries = database::get_image_hash_entries(app, &utils::HASHABLE_IMAGE_EXTENSIONS)?;
+    
+    // Hashing decodes every image, so keep it off the async runtime
+    let app_handle = app.clone();
+    let hashed = tokio::task::spawn_blocking(move || {
+        let mut hashed = vec![];
+        for entry in entries {
+            let path = PathBuf::from(&entry.path);
+            let modified_at = match fs::metadata(&path).and_then(|m| m.modified()) {
+                Ok(modified) => utils::format_timestamp(&modified.into()),
+                // The file is gone or unreadable
+                Err(_) => continue,
+            };
+            
+            // Reuse the cached hash unless the file changed since it was computed
+            let phash = match entry.phash {
+                Some(phash) if entry.phash_modified_at.as_deref() == Some(modified_at.as_str()) => phash,
+                _ => match utils::perceptual_hash(&path) {
+                    Ok(phash) => {
+                        if let Err(e) = database::set_file_phash(&app_handle, entry.file_id, phash, &modified_at) {
+                            tracing::warn!(file_id = entry.file_id, error = %e, "Failed to cache image hash");
+                        }
+                        phash
+                    }
+                    Err(_) => continue,
+                },
+            };
+            
+            hashed.push((entry, phash));
+        }
+        hashed
+    })
+    .await?;
+    
+    // Union-find so chains of similar images end up in one group
+    let mut parent: Vec<usize> = (0..hashed.len()).collect();
+    fn find(parent: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        parent[i] = root;
+        root
+    }
+    
+    for i in 0..hashed.len() {
+        for j in (i + 1)..hashed.len() {
+            if utils::hamming_distance(hashed[i].1, hashed[j].1) <= threshold {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                if a != b {
+                    parent[b] = a;
+                }
             }
+        }
+    }
+    
+    let mut groups: HashMap<usize, Vec<SimilarImage>> = HashMap::new();
+    for (i, (entry, phash)) in hashed.iter().enumerate() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(SimilarImage {
+            file_id: entry.file_id,
+            path: entry.path.clone(),
+            name: entry.name.clone(),
+            size: entry.size,
+            hash: format!("{:016x}", phash),
         });
     }
     
-    Ok(())
+    let groups: Vec<Vec<SimilarImage>> = groups.into_values().filter(|group| group.len() > 1).collect();
+    if !groups.is_empty() {
+        notifications::notify(
+            app,
+            notifications::KIND_DUPLICATE_DETECTED,
+            format!("Found {} groups of similar images", groups.len()),
+        );
+    }
+    
+    Ok(groups)
 }
 
-// Organize a file based on rules
-pub async fn organize_file_by_rules(app: &AppHandle, file_path: &Path) -> Result<()> {
-    // Check if file exists and is a file
-    if !file_path.exists() || !file_path.is_file() {
-        return Ok(());
+// Cancel flag of the running duplicate scan; None when none is running
+static DUPLICATE_SCAN: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+// Least time between two duplicate_scan_progress events
+const DUPLICATE_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+// Result of find_duplicates
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DuplicateScanResult {
+    pub groups: Vec<DuplicateGroup>,
+    pub hashed: usize,
+    pub total: usize,
+    // Stopped by cancel_duplicate_scan; groups holds what was found until then
+    pub cancelled: bool,
+}
+
+// Find indexed files with identical content. Only files sharing their size
+// with another one are hashed, a size at a time, and each group is sent to the
+// frontend as soon as its size is done. Hashes are cached per file and reused
+// while its modification time is unchanged, so a second run is quick
+pub async fn find_duplicates(app: &AppHandle) -> Result<DuplicateScanResult> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    {
+        let mut running = DUPLICATE_SCAN.lock().unwrap();
+        if running.is_some() {
+            return Err(anyhow::anyhow!("A duplicate scan is already running"));
+        }
+        *running = Some(cancel.clone());
     }
     
-    // Get file extension
-    let extension = file_path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("")
-        .to_lowercase();
+    let result = find_duplicates_inner(app, &cancel).await;
+    *DUPLICATE_SCAN.lock().unwrap() = None;
+    result
+}
+
+async fn find_duplicates_inner(app: &AppHandle, cancel: &AtomicBool) -> Result<DuplicateScanResult> {
+    let entries = database::get_content_hash_entries(app)?;
+    let concurrency = hash_concurrency(app);
+    let mut result = DuplicateScanResult {
+        groups: vec![],
+        hashed: 0,
+        total: entries.len(),
+        cancelled: false,
+    };
+    let mut last_progress: Option<std::time::Instant> = None;
     
-    // Skip if no extension
-    if extension.is_empty() {
-        return Ok(());
+    let mut remaining = entries.as_slice();
+    while let Some(first) = remaining.first() {
+        if cancel.load(Ordering::Relaxed) {
+            result.cancelled = true;
+            break;
+        }
+        let same_size = remaining.iter().take_while(|entry| entry.size == first.size).count();
+        let (bucket, rest) = remaining.split_at(same_size);
+        remaining = rest;
+        
+        // Reuse cached hashes of files unchanged since, hash the others
+        let mut hashes: HashMap<String, Vec<&database::ContentHashEntry>> = HashMap::new();
+        let mut to_hash = vec![];
+        for entry in bucket {
+            let modified_at = match fs::metadata(&entry.path).and_then(|m| m.modified()) {
+                Ok(modified) => utils::format_timestamp(&modified.into()),
+                // The file is gone or unreadable
+                Err(_) => continue,
+            };
+            match &entry.content_hash {
+                Some(hash) if entry.content_hash_modified_at.as_deref() == Some(modified_at.as_str()) => {
+                    hashes.entry(hash.clone()).or_default().push(entry);
+                }
+                _ => to_hash.push((entry, modified_at)),
+            }
+        }
+        if !to_hash.is_empty() {
+            let paths = to_hash.iter().map(|(entry, _)| PathBuf::from(&entry.path)).collect();
+            let hashed = hash_files(paths, concurrency).await;
+            for (entry, modified_at) in to_hash {
+                let Some(hash) = hashed.hashes.get(&entry.path) else { continue };
+                if let Err(e) = database::set_file_content_hash(app, entry.file_id, hash, &modified_at) {
+                    tracing::warn!(file_id = entry.file_id, error = %e, "Failed to cache content hash");
+                }
+                hashes.entry(hash.clone()).or_default().push(entry);
+            }
+        }
+        result.hashed += bucket.len();
+        
+        for (hash, entries) in hashes.into_iter().filter(|(_, entries)| entries.len() > 1) {
+            let group = DuplicateGroup {
+                hash,
+                size: first.size,
+                files: entries
+                    .into_iter()
+                    .map(|entry| DuplicateFile {
+                        file_id: entry.file_id,
+                        path: entry.path.clone(),
+                        name: entry.name.clone(),
+                    })
+                    .collect(),
+            };
+            let _ = app.emit(events::DUPLICATE_GROUP_FOUND, &group);
+            result.groups.push(group);
+        }
+        
+        if remaining.is_empty() || last_progress.is_none_or(|at| at.elapsed() >= DUPLICATE_PROGRESS_INTERVAL) {
+            last_progress = Some(std::time::Instant::now());
+            let _ = app.emit(events::DUPLICATE_SCAN_PROGRESS, events::DuplicateScanProgress {
+                hashed: result.hashed,
+                total: result.total,
+            });
+        }
     }
     
-    // Get file metadata
-    let metadata = fs::metadata(file_path)?;
-    let size = metadata.len() as i64;
+    if !result.groups.is_empty() {
+        notifications::notify(
+            app,
+            notifications::KIND_DUPLICATE_DETECTED,
+            format!("Found {} groups of duplicate files", result.groups.len()),
+        );
+    }
     
-    // Get creation and modification times
-    let created = metadata.created().unwrap_or_else(|_| std::time::SystemTime::now());
-    let modified = metadata.modified().unwrap_or_else(|_| std::time::SystemTime::now());
+    Ok(result)
+}
+
+// Stop the running duplicate scan, if any
+pub fn cancel_duplicate_scan() {
+    if let Some(cancel) = DUPLICATE_SCAN.lock().unwrap().as_ref() {
+        cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+// Setting for how many files are hashed at once
+pub const HASH_CONCURRENCY_SETTING: &str = "hash_concurrency";
+
+// Default number of files hashed at once; more mostly thrashes the disk
+const DEFAULT_HASH_CONCURRENCY: usize = 4;
+
+// Result of hashing a batch of files
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct HashBatchResult {
+    pub hashes: HashMap<String, String>,
+    pub errors: HashMap<String, String>,
+}
+
+// Get the configured hashing concurrency
+pub fn hash_concurrency(app: &AppHandle) -> usize {
+    database::get_setting(app, HASH_CONCURRENCY_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(DEFAULT_HASH_CONCURRENCY)
+}
+
+// Hash a batch of files in parallel, at most `concurrency` at a time. A file
+// that can't be read is reported in `errors` without aborting the rest.
+pub async fn hash_files(paths: Vec<PathBuf>, concurrency: usize) -> HashBatchResult {
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
+    let mut tasks = tokio::task::JoinSet::new();
     
-    let created_dt: DateTime<Utc> = created.into();
-    let modified_dt: DateTime<Utc> = modified.into();
+    for path in paths {
+        let semaphore = semaphore.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let hash_path = path.clone();
+            let result = tokio::task::spawn_blocking(move || utils::hash_file(&hash_path))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result);
+            (path, result)
+        });
+    }
     
-    let created_str = created_dt.format("%Y-%m-%d %H:%M:%S").to_string();
-    let modified_str = modified_dt.format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut batch = HashBatchResult::default();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((path, result)) = joined {
+            let path = path.to_string_lossy().to_string();
+            match result {
+                Ok(hash) => {
+                    batch.hashes.insert(path, hash);
+                }
+                Err(e) => {
+                    batch.errors.insert(path, e.to_string());
+                }
+            }
+        }
+    }
     
-    // Get file name
-    let file_name = file_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("")
-        .to_string();
+    batch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::scratch_dir;
+
+    fn journaled(source: &Path, destination: &Path, copying: bool) -> database::JournaledMove {
+        database::JournaledMove {
+            id: 1,
+            source: source.to_path_buf(),
+            destination: destination.to_path_buf(),
+            detail: None,
+            copying,
+        }
+    }
+
+    #[test]
+    fn folders_with_future_dates_settle() {
+        let dir = scratch_dir("settle");
+        let file = dir.join("IMG_0001.jpg");
+        fs::write(&file, b"photo").unwrap();
+        let tomorrow = std::time::SystemTime::now() + Duration::from_secs(24 * 60 * 60);
+        fs::File::options().write(true).open(&file).unwrap().set_modified(tomorrow).unwrap();
+
+        let first = FolderSnapshot::of(&dir).unwrap();
+        assert!(!first.is_settled(None));
+        let second = FolderSnapshot::of(&dir).unwrap();
+        assert!(second.is_settled(Some(&first)));
+
+        fs::write(dir.join("IMG_0002.jpg"), b"another").unwrap();
+        let third = FolderSnapshot::of(&dir).unwrap();
+        assert!(!third.is_settled(Some(&second)));
+    }
+
+    #[test]
+    fn old_folders_settle_at_once() {
+        let dir = scratch_dir("settle-old");
+        let file = dir.join("notes.txt");
+        fs::write(&file, b"notes").unwrap();
+        let last_week = std::time::SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+        fs::File::options().write(true).open(&file).unwrap().set_modified(last_week).unwrap();
+        fs::File::open(&dir).unwrap().set_modified(last_week).unwrap();
+        assert!(FolderSnapshot::of(&dir).unwrap().is_settled(None));
+    }
+
+    #[test]
+    fn interrupted_move_keeps_a_renamed_file_when_the_source_is_reused() {
+        let dir = scratch_dir("journal-rename");
+        let (source, destination) = (dir.join("report.pdf"), dir.join("Documents").join("report.pdf"));
+        fs::create_dir_all(destination.parent().unwrap()).unwrap();
+
+        // Crashed right after claiming the destination
+        fs::write(&source, b"original").unwrap();
+        fs::write(&destination, b"").unwrap();
+        assert_eq!(recovery_of(&journaled(&source, &destination, false)), Recovery::Undo);
+
+        // Renamed, then a new file of the same size was saved at the old path
+        fs::rename(&source, &destination).unwrap();
+        fs::write(&source, b"new file").unwrap();
+        assert_eq!(recovery_of(&journaled(&source, &destination, false)), Recovery::Finish);
+        assert_eq!(recovery_of(&journaled(&source, &destination, true)), Recovery::Finish);
+
+        fs::remove_file(&source).unwrap();
+        assert_eq!(recovery_of(&journaled(&source, &destination, false)), Recovery::Finish);
+        fs::remove_file(&destination).unwrap();
+        assert_eq!(recovery_of(&journaled(&source, &destination, false)), Recovery::Fail);
+    }
+
+    #[test]
+    fn interrupted_copy_is_undone_only_when_it_matches_the_original() {
+        let dir = scratch_dir("journal-copy");
+        let (source, destination) = (dir.join("video.mp4"), dir.join("video_1.mp4"));
+        fs::write(&source, b"0123456789").unwrap();
+
+        fs::write(&destination, b"01234").unwrap();
+        assert_eq!(recovery_of(&journaled(&source, &destination, true)), Recovery::Undo);
+        fs::write(&destination, b"0123456789").unwrap();
+        assert_eq!(recovery_of(&journaled(&source, &destination, true)), Recovery::Undo);
+        fs::write(&destination, b"abcde").unwrap();
+        assert_eq!(recovery_of(&journaled(&source, &destination, true)), Recovery::Finish);
+    }
+
+    #[test]
+    fn interrupted_folder_move_keeps_a_renamed_folder() {
+        let dir = scratch_dir("journal-folder");
+        let (source, destination) = (dir.join("Trip"), dir.join("Photos").join("Trip"));
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.jpg"), b"photo").unwrap();
+        fs::create_dir_all(&destination).unwrap();
+        assert_eq!(recovery_of(&journaled(&source, &destination, false)), Recovery::Undo);
+
+        fs::remove_dir(&destination).unwrap();
+        fs::rename(&source, &destination).unwrap();
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.jpg"), b"other").unwrap();
+        assert_eq!(recovery_of(&journaled(&source, &destination, false)), Recovery::Finish);
+        assert_eq!(recovery_of(&journaled(&source, &destination, true)), Recovery::Finish);
+
+        fs::write(source.join("a.jpg"), b"photo and more").unwrap();
+        assert_eq!(recovery_of(&journaled(&source, &destination, true)), Recovery::Undo);
+    }
     
-    // Get the rules for this extension
-    let conn = database::get_connection(&app)?;
-    let conn_guard = conn.lock().unwrap();
+    #[test]
+    fn lost_events_are_told_from_watcher_failures() {
+        let overflow = notify::Error::io(std::io::Error::other("inotify queue overflow"));
+        assert!(is_event_overflow(&overflow));
+        assert!(is_event_overflow(&notify::Error::new(notify::ErrorKind::MaxFilesWatch)));
+        assert!(!is_event_overflow(&notify::Error::path_not_found()));
+        assert!(!is_event_overflow(&notify::Error::generic("watch removed")));
+    }
     
-    let mut stmt = conn_guard.0.prepare(
-        "SELECT destination_folder FROM rules 
-         WHERE is_active = 1 AND is_extension = 1 
-         AND ? IN (SELECT value FROM json_each(REPLACE(pattern, ',', '\",\"')))"
-    )?;
+    #[test]
+    fn overflow_rescans_wait_for_their_turn() {
+        assert_eq!(overflow_rescan_step(true, false, true, false), RescanStep::Run);
+        // Paused, outside the active hours, or behind another scan
+        assert_eq!(overflow_rescan_step(true, true, true, false), RescanStep::Wait);
+        assert_eq!(overflow_rescan_step(true, false, false, false), RescanStep::Wait);
+        assert_eq!(overflow_rescan_step(true, false, true, true), RescanStep::Wait);
+        // No longer watched at all
+        assert_eq!(overflow_rescan_step(false, false, true, false), RescanStep::Drop);
+        assert_eq!(overflow_rescan_step(false, false, false, true), RescanStep::Drop);
+    }
     
-    let mut destination = None;
-    let rows = stmt.query_map([extension.clone()], |row| {
-        Ok(row.get::<_, String>(0)?)
-    })?;
+    #[test]
+    fn moves_are_checked_against_the_space_left() {
+        let low = health::LOW_DISK_SPACE_BYTES;
+        assert_eq!(space_after_move(low * 2, low / 2), SpaceAfterMove::Enough);
+        assert_eq!(space_after_move(low * 2, low), SpaceAfterMove::Enough);
+        assert_eq!(space_after_move(low, 1), SpaceAfterMove::Low(low - 1));
+        assert_eq!(space_after_move(10, 10), SpaceAfterMove::Low(0));
+        assert_eq!(space_after_move(10, 11), SpaceAfterMove::NoRoom);
+    }
     
-    for row in rows {
-        destination = Some(row?);
-        break;
+    // Claim every path at once from its own thread, keeping the claims held
+    fn claim_concurrently(paths: &[PathBuf]) -> Vec<Result<Option<StartingWatch>>> {
+        let state = Arc::new(Mutex::new(WatcherState::default()));
+        let barrier = Arc::new(std::sync::Barrier::new(paths.len()));
+        let threads: Vec<_> = paths
+            .iter()
+            .map(|path| {
+                let (state, barrier) = (state.clone(), barrier.clone());
+                let path = path.to_string_lossy().to_string();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    claim_watch_in(&state, &path, false, None)
+                })
+            })
+            .collect();
+        threads.into_iter().map(|thread| thread.join().unwrap()).collect()
     }
     
-    // If we have a destination, move the file
-    if let Some(dest_folder) = destination {
-        // Get home directory
-        let home_dir = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Failed to get home directory"))?;
+    #[test]
+    fn concurrent_starts_watch_a_folder_once() {
+        let dir = scratch_dir("claim-same");
+        let claims = claim_concurrently(&vec![dir.clone(); 8]);
         
-        // Create destination path
-        let dest_path = home_dir.join(&dest_folder);
-        if !dest_path.exists() {
-            fs::create_dir_all(&dest_path)?;
-        }
+        assert!(claims.iter().all(|claim| claim.is_ok()));
+        assert_eq!(claims.iter().filter(|claim| matches!(claim, Ok(Some(_)))).count(), 1);
+        let _ = fs::remove_dir_all(dir);
+    }
+    
+    #[test]
+    fn nested_folders_are_refused_whichever_starts_first() {
+        let dir = scratch_dir("claim-nested");
+        let child = dir.join("child");
+        fs::create_dir_all(&child).unwrap();
         
-        // Create new file path
-        let new_path = dest_path.join(&file_name);
+        for _ in 0..20 {
+            let claims = claim_concurrently(&[dir.clone(), child.clone()]);
+            assert_eq!(claims.iter().filter(|claim| matches!(claim, Ok(Some(_)))).count(), 1);
+            assert_eq!(claims.iter().filter(|claim| claim.is_err()).count(), 1);
+        }
         
-        // Check if destination file already exists
-        if new_path.exists() {
-            // Create a unique filename by adding timestamp
-            let now = Utc::now();
-            let timestamp = now.format("%Y%m%d%H%M%S").to_string();
-            
-            let file_stem = file_path
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("")
-                .to_string();
-            
-            let new_filename = format!("{}_{}.{}", file_stem, timestamp, extension);
-            let new_path = dest_path.join(new_filename);
-            
-            // Move the file
-            fs::copy(file_path, &new_path)?;
-            fs::remove_file(file_path)?;
-        } else {
-            // Move the file
-            fs::copy(file_path, &new_path)?;
-            fs::remove_file(file_path)?;
+        // A parent only watched shallowly doesn't reach the child's files
+        let state = Arc::new(Mutex::new(WatcherState::default()));
+        let _child = claim_watch_in(&state, &child.to_string_lossy(), false, Some(0)).unwrap();
+        assert!(claim_watch_in(&state, &dir.to_string_lossy(), false, Some(0)).unwrap().is_some());
+        let _ = fs::remove_dir_all(dir);
+    }
+    
+    fn file_event(path: &Path) -> FileEvent {
+        FileEvent {
+            path: path.to_string_lossy().to_string(),
+            file_name: utils::get_file_name(path).unwrap_or_default(),
+            event_type: "created".to_string(),
+            extension: utils::get_file_extension(path).unwrap_or_default(),
+            size: 0,
+            outcome: None,
         }
+    }
+    
+    fn multi_thread_runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_multi_thread().worker_threads(4).enable_all().build().unwrap()
+    }
+    
+    #[test]
+    fn bursts_larger_than_the_queue_are_not_dropped() {
+        let total = WATCHER_CHANNEL_CAPACITY * 3;
+        let (tx, rx) = mpsc::channel::<FileEvent>(WATCHER_CHANNEL_CAPACITY);
         
-        // Add file to database
-        database::add_file(
-            &app,
-            &new_path,
-            &file_name,
-            &extension,
-            size,
-            &created_str,
-            &modified_str,
-        )?;
+        // Sent from a thread of its own, as the debouncer does
+        let sender = std::thread::spawn(move || {
+            for i in 0..total {
+                tx.blocking_send(file_event(Path::new(&format!("/organizer-test/burst/{}.jpg", i)))).unwrap();
+            }
+        });
         
-        // Auto-tag by extension
-        let file_id = conn_guard.0.query_row(
-            "SELECT id FROM files WHERE path = ?",
-            [new_path.to_string_lossy().to_string()],
-            |row| row.get::<_, i64>(0),
-        )?;
+        let received = multi_thread_runtime().block_on(async move {
+            let rx = Arc::new(tokio::sync::Mutex::new(rx));
+            let workers: Vec<_> = (0..4)
+                .map(|_| {
+                    let rx = rx.clone();
+                    tokio::spawn(async move {
+                        let mut received = vec![];
+                        while let Some(events) = next_watcher_batch(&rx, 4).await {
+                            assert!(events.len() <= WATCHER_BATCH_SIZE);
+                            received.extend(events.into_iter().map(|event| event.path));
+                            // Slower than the sender, so the queue fills up
+                            tokio::time::sleep(Duration::from_millis(1)).await;
+                        }
+                        received
+                    })
+                })
+                .collect();
+            let mut received = vec![];
+            for worker in workers {
+                received.extend(worker.await.unwrap());
+            }
+            received
+        });
+        sender.join().unwrap();
+        
+        assert_eq!(received.len(), total);
+        assert_eq!(received.into_iter().collect::<HashSet<_>>().len(), total);
+    }
+    
+    // Organize a burst of same-named files, each event sent twice, into one
+    // folder the way the watcher's workers do. Returns the most groups of
+    // files that were being moved at the same time
+    fn organize_burst(dir: &Path, workers: usize) -> usize {
+        let sorted = dir.join("Sorted");
+        fs::create_dir_all(&sorted).unwrap();
+        let (tx, rx) = mpsc::channel::<FileEvent>(WATCHER_CHANNEL_CAPACITY);
+        for i in 0..200 {
+            let folder = dir.join("Camera").join(i.to_string());
+            fs::create_dir_all(&folder).unwrap();
+            let file = folder.join("IMG_0001.jpg");
+            fs::write(&file, i.to_string()).unwrap();
+            tx.try_send(file_event(&file)).unwrap();
+            tx.try_send(file_event(&file)).unwrap();
+        }
+        drop(tx);
         
-        // Get tag ID based on extension type
-        let tag_name = match extension.as_str() {
-            "pdf" | "doc" | "docx" | "txt" | "rtf" | "odt" => "Documents",
-            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "svg" => "Images",
-            "mp4" | "avi" | "mov" | "wmv" | "mkv" | "webm" => "Videos",
-            "mp3" | "wav" | "flac" | "ogg" | "aac" => "Music",
-            "zip" | "rar" | "7z" | "tar" | "gz" => "Archives",
-            _ => "",
+        let busy = Arc::new(Mutex::new(HashSet::new()));
+        let active = Arc::new(AtomicU64::new(0));
+        let peak = Arc::new(AtomicU64::new(0));
+        let organize = {
+            let (busy, active, peak, sorted) = (busy.clone(), active.clone(), peak.clone(), sorted.clone());
+            move |paths: &[PathBuf]| {
+                let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now_active, Ordering::SeqCst);
+                let outcomes = paths
+                    .iter()
+                    .map(|path| {
+                        assert!(busy.lock().unwrap().insert(path.clone()), "{} organized twice at once", path.display());
+                        // Long enough for the other workers' groups to overlap
+                        std::thread::sleep(Duration::from_millis(2));
+                        let outcome = match path.exists() {
+                            true => {
+                                let moved = utils::move_to_dir(path, &sorted, false).unwrap();
+                                OrganizeOutcome::moved(path, &moved, None, false)
+                            }
+                            false => OrganizeOutcome::Skipped { reason: SkipReason::NotFound },
+                        };
+                        busy.lock().unwrap().remove(path);
+                        outcome
+                    })
+                    .collect();
+                active.fetch_sub(1, Ordering::SeqCst);
+                outcomes
+            }
         };
         
-        if !tag_name.is_empty() {
-            let tag_id = conn_guard.0.query_row(
-                "SELECT id FROM tags WHERE name = ?",
-                [tag_name],
-                |row| row.get::<_, i64>(0),
-            )?;
-            
-            database::add_tag_to_file(&app, file_id, tag_id)?;
-        }
+        let reported = multi_thread_runtime().block_on(async {
+            let rx = Arc::new(tokio::sync::Mutex::new(rx));
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    let (rx, organize) = (rx.clone(), organize.clone());
+                    tokio::spawn(async move {
+                        let mut reported = vec![];
+                        while let Some(events) = next_watcher_batch(&rx, workers).await {
+                            organize_when_free(events, organize.clone(), |_, outcome| reported.push(outcome)).await;
+                        }
+                        reported
+                    })
+                })
+                .collect();
+            let mut reported = vec![];
+            for handle in handles {
+                reported.extend(handle.await.unwrap());
+            }
+            reported
+        });
+        
+        // Every event is reported, and each file moved once, under a name of its own
+        assert_eq!(reported.len(), 400);
+        assert_eq!(reported.iter().filter(|outcome| matches!(outcome, OrganizeOutcome::Moved { .. })).count(), 200);
+        assert_eq!(fs::read_dir(&sorted).unwrap().count(), 200);
+        peak.load(Ordering::SeqCst) as usize
     }
     
-    Ok(())
-}
+    #[test]
+    fn worker_pools_organize_bursts_in_parallel() {
+        let dir = scratch_dir("worker-pool");
+        assert_eq!(organize_burst(&dir.join("one"), 1), 1);
+        assert!(organize_burst(&dir.join("four"), 4) > 1);
+        let _ = fs::remove_dir_all(dir);
+    }
 
-// Manually organize a file
-pub async fn organize_file(
-    app: &AppHandle, 
-    file_path: PathBuf,
-    destination_folder: Option<String>,
-) -> Result<()> {
-    if let Some(dest) = destination_folder {
-        // User specified a destination folder
-        let dest_path = PathBuf::from(dest);
+    // Needs a destination on another drive, so the move is a long copy rather
+    // than a rename; /dev/shm is a separate tmpfs on most Linux systems
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn searches_run_while_a_large_file_is_being_organized() {
+        use std::os::unix::fs::MetadataExt;
         
-        // Ensure destination folder exists
-        if !dest_path.exists() {
-            fs::create_dir_all(&dest_path)?;
+        let dir = scratch_dir("organize-large");
+        let shm = PathBuf::from("/dev/shm").join(dir.file_name().unwrap());
+        let Ok(shm_metadata) = fs::create_dir_all(&shm).and_then(|_| fs::metadata(&shm)) else { return };
+        if shm_metadata.dev() == fs::metadata(&dir).unwrap().dev() {
+            let _ = fs::remove_dir_all(&shm);
+            return;
         }
         
-        // Get file name
-        let file_name = file_path
-            .file_name()
-            .ok_or_else(|| anyhow::anyhow!("Invalid file path"))?;
+        let conn = database::open_database(&dir.join("organizer.db")).unwrap();
+        conn.execute(
+            "INSERT INTO rules (name, pattern, destination_folder, is_extension, profile_id)
+             VALUES ('Disk images', 'iso', ?, 1, (SELECT MIN(id) FROM profiles))",
+            [shm.to_string_lossy()],
+        )
+        .unwrap();
+        let app = tauri::test::mock_app();
+        app.manage(Arc::new(Mutex::new(database::DatabaseConnection(conn))));
+        app.manage(database::CategoryCache::default());
+        let app = app.handle().clone();
         
-        // Create new path
-        let new_path = dest_path.join(file_name);
+        // Sparse, so it takes no room on disk but a while to copy
+        let large = dir.join("installer.iso");
+        fs::File::create(&large).unwrap().set_len(512 * 1024 * 1024).unwrap();
+        let copy = shm.join("installer.iso");
         
-        // Move the file
-        fs::copy(&file_path, &new_path)?;
-        fs::remove_file(&file_path)?;
-        
-        // Add to database
-        let extension = file_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("")
-            .to_lowercase();
+        let runtime = multi_thread_runtime();
+        let organizing = runtime.spawn({
+            let app = app.clone();
+            async move { organize_file_by_rules(&app, &large).await }
+        });
+        while !fs::metadata(&copy).is_ok_and(|metadata| metadata.len() > 0) {
+            assert!(!organizing.is_finished(), "Organized before the copy was seen");
+            std::thread::sleep(Duration::from_millis(1));
+        }
         
-        let metadata = fs::metadata(&new_path)?;
-        let size = metadata.len() as i64;
+        let filter = crate::commands::SearchFilter { query: Some("installer".to_string()), ..Default::default() };
+        database::search_files(&app, &filter).unwrap();
+        assert!(!organizing.is_finished(), "The search waited for the move to finish");
         
-        let created = metadata.created().unwrap_or_else(|_| std::time::SystemTime::now());
-        let modified = metadata.modified().unwrap_or_else(|_| std::time::SystemTime::now());
+        let outcome = runtime.block_on(organizing).unwrap().unwrap();
+        assert!(matches!(outcome, OrganizeOutcome::Moved { .. }), "{:?}", outcome);
+        let found = database::search_files(&app, &filter).unwrap();
+        assert_eq!(found.iter().map(|file| PathBuf::from(&file.path)).collect::<Vec<_>>(), [copy]);
+        fs::remove_dir_all(&shm).unwrap();
+    }
+
+    #[test]
+    fn lost_watcher_events_are_reported_and_rescanned() {
+        use tauri::Listener;
         
-        let created_dt: DateTime<Utc> = created.into();
-        let modified_dt: DateTime<Utc> = modified.into();
+        let dir = scratch_dir("watch-overflow");
+        let root = dir.to_string_lossy().to_string();
+        let app = tauri::test::mock_app();
+        let app = app.handle().clone();
+        let reported = Arc::new(Mutex::new(vec![]));
+        app.listen_any(events::WATCH_OVERFLOW, {
+            let reported = reported.clone();
+            move |event| reported.lock().unwrap().push(serde_json::from_str::<serde_json::Value>(event.payload()).unwrap())
+        });
+        let (tx, mut rx) = mpsc::channel(WATCHER_CHANNEL_CAPACITY);
+        let mut handle_events = watcher_event_handler(app, root.clone(), utils::normalize_path(&dir), None, false, tx);
         
-        let created_str = created_dt.format("%Y-%m-%d %H:%M:%S").to_string();
-        let modified_str = modified_dt.format("%Y-%m-%d %H:%M:%S").to_string();
+        handle_events(Err(notify::Error::io(std::io::Error::other("inotify queue overflow"))));
+        assert!(OVERFLOW_RESCANS.lock().unwrap().contains(&root));
+        // The rescan already queued covers a second overflow too
+        handle_events(Err(notify::Error::new(notify::ErrorKind::MaxFilesWatch)));
         
-        database::add_file(
-            &app,
-            &new_path,
-            file_name.to_str().unwrap_or(""),
-            &extension,
-            size,
-            &created_str,
-            &modified_str,
-        )?;
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 2);
+        assert_eq!(reported[0]["path"], root.as_str());
+        assert_eq!(reported[0]["rescan_queued"], true);
+        assert_eq!(reported[1]["rescan_queued"], false);
         
-        Ok(())
-    } else {
-        // Use rule-based organization
-        organize_file_by_rules(app, &file_path).await
+        // Events that do arrive still reach the workers
+        let file = dir.join("report.pdf");
+        fs::write(&file, b"report").unwrap();
+        handle_events(Ok(vec![DebouncedEvent { path: file.clone(), kind: notify_debouncer_mini::DebouncedEventKind::Any }]));
+        let queued = rx.try_recv().unwrap();
+        assert_eq!(Path::new(&queued.path), utils::normalize_path(&file));
+        OVERFLOW_RESCANS.lock().unwrap().remove(&root);
     }
-} 
\ No newline at end of file
+}
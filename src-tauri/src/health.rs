@@ -0,0 +1,190 @@
+use anyhow::Result;
+use chrono::{Local, Utc};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use crate::database;
+use crate::events;
+use crate::file_ops;
+use crate::logging;
+use crate::schedule;
+use crate::utils;
+
+// get_health is meant to be polled, so the database check only runs this often
+const INTEGRITY_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Less free space than this on the app data volume is reported as a warning
+pub const LOW_DISK_SPACE_BYTES: u64 = 1024 * 1024 * 1024;
+
+// How far back logged errors are counted
+const RECENT_ERRORS_WINDOW_MINUTES: i64 = 60;
+
+// How long after launch the startup check runs, so the window is listening
+const STARTUP_CHECK_DELAY: Duration = Duration::from_secs(5);
+
+// Last quick check result, and when it ran
+static INTEGRITY: Mutex<Option<(Instant, IntegrityResult)>> = Mutex::new(None);
+
+// Result of the periodic database check
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityResult {
+    // "ok", or what the check found
+    pub result: String,
+    pub checked_at: String,
+}
+
+// When a bucket last received a backed up file
+#[derive(Debug, Clone, Serialize)]
+pub struct LastBackup {
+    pub bucket: String,
+    pub backed_up_at: String,
+}
+
+// Diagnostics returned by get_health
+#[derive(Debug, Clone, Serialize)]
+pub struct Health {
+    pub database_path: String,
+    pub schema_version: i32,
+    pub integrity: IntegrityResult,
+    // Watchers running or paused right now
+    pub active_watchers: usize,
+    // Folders and files marked as watched in the database. More of these than
+    // active_watchers means some weren't restarted
+    pub active_watched_folders: usize,
+    pub last_backups: Vec<LastBackup>,
+    // None where the platform can't tell
+    pub free_disk_bytes: Option<u64>,
+    // Files the watcher queued outside the active hours
+    pub pending_actions: usize,
+    // When the queued files will be organized; None when nothing waits for
+    // the active hours to start
+    pub next_scheduled_flush: Option<String>,
+    // Errors logged in the last RECENT_ERRORS_WINDOW_MINUTES
+    pub recent_errors: usize,
+    // Times a watcher lost events since launch; each one rescans its folder
+    pub watch_overflows: u64,
+    // Everything above that looks wrong, in words
+    pub warnings: Vec<String>,
+}
+
+// Payload of health_warning
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthWarning {
+    pub message: String,
+}
+
+// Collect diagnostics for support requests. Cheap enough to poll every minute
+pub fn get_health(app: &AppHandle) -> Result<Health> {
+    let database_path = database::get_database_path(app)?;
+    let integrity = integrity(app)?;
+    let watched = database::get_active_watched_paths(app)?;
+    let active_watchers = file_ops::get_watched_folders(app)
+        .iter()
+        .filter(|folder| folder.error.is_none())
+        .count();
+    let free_disk_bytes = database_path.parent().and_then(utils::free_disk_space);
+    let since = Local::now() - chrono::Duration::minutes(RECENT_ERRORS_WINDOW_MINUTES);
+    let last_backups = database::get_last_backups(app)?
+        .into_iter()
+        .map(|(bucket, backed_up_at)| LastBackup { bucket, backed_up_at })
+        .collect();
+
+    let pending_actions = database::count_pending_actions(app)?;
+    let next_scheduled_flush = match pending_actions {
+        0 => None,
+        _ => schedule::next_active_start(app)?.map(|start| start.to_rfc3339()),
+    };
+
+    let mut warnings = startup_warnings(app, &watched, free_disk_bytes);
+    if integrity.result != "ok" {
+        warnings.push(format!("Database check failed: {}", integrity.result));
+    }
+    if active_watchers < watched.len() {
+        warnings.push(format!(
+            "{} of {} watched folders aren't being watched",
+            watched.len() - active_watchers,
+            watched.len()
+        ));
+    }
+
+    Ok(Health {
+        database_path: database_path.to_string_lossy().to_string(),
+        schema_version: database::get_schema_version(app)?,
+        integrity,
+        active_watchers,
+        active_watched_folders: watched.len(),
+        last_backups,
+        free_disk_bytes,
+        pending_actions,
+        next_scheduled_flush,
+        recent_errors: logging::count_errors_since(since),
+        watch_overflows: file_ops::watch_overflow_count(),
+        warnings,
+    })
+}
+
+// Check the basics shortly after launch and send a health_warning event for
+// each problem found
+pub fn start_startup_check(app: &AppHandle) {
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_CHECK_DELAY).await;
+
+        let watched = match database::get_active_watched_paths(&app_handle) {
+            Ok(watched) => watched,
+            Err(e) => {
+                tracing::warn!(error = format!("{:#}", e), "Failed to run startup health check");
+                return;
+            }
+        };
+        let free_disk_bytes = database::get_database_path(&app_handle)
+            .ok()
+            .and_then(|path| path.parent().and_then(utils::free_disk_space));
+
+        for message in startup_warnings(&app_handle, &watched, free_disk_bytes) {
+            tracing::warn!(warning = %message, "Health check");
+            let _ = app_handle.emit(events::HEALTH_WARNING, HealthWarning { message });
+        }
+    });
+}
+
+// Problems cheap enough to look for at every launch
+fn startup_warnings(app: &AppHandle, watched: &[String], free_disk_bytes: Option<u64>) -> Vec<String> {
+    let mut warnings = vec![];
+
+    let status = database::get_database_status(app);
+    if !status.healthy {
+        warnings.push(format!("Database unavailable: {}", status.error.unwrap_or_default()));
+    } else if let Some(corrupt_path) = status.corrupt_backup_path {
+        warnings.push(format!("Database was recovered; the damaged copy is at {}", corrupt_path));
+    }
+
+    for path in watched.iter().filter(|path| !Path::new(path).exists()) {
+        warnings.push(format!("Watched folder is missing: {}", path));
+    }
+
+    if let Some(free) = free_disk_bytes.filter(|free| *free < LOW_DISK_SPACE_BYTES) {
+        warnings.push(format!("Low disk space: {} left", utils::format_file_size(free)));
+    }
+
+    warnings
+}
+
+// The cached quick check result, rerun once it's INTEGRITY_CHECK_INTERVAL old
+fn integrity(app: &AppHandle) -> Result<IntegrityResult> {
+    let mut cached = INTEGRITY.lock().unwrap();
+    if let Some((ran_at, result)) = cached.as_ref() {
+        if ran_at.elapsed() < INTEGRITY_CHECK_INTERVAL {
+            return Ok(result.clone());
+        }
+    }
+
+    let result = IntegrityResult {
+        result: database::quick_check(app)?,
+        checked_at: utils::format_timestamp(&Utc::now()),
+    };
+    *cached = Some((Instant::now(), result.clone()));
+    Ok(result)
+}
@@ -0,0 +1,474 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_shell::process::CommandEvent;
+use tauri_plugin_shell::ShellExt;
+use tokio::sync::Semaphore;
+use crate::database;
+use crate::events;
+use crate::utils;
+use crate::webhook;
+
+// Hook actions: POST the event to a URL, or run a command
+pub const ACTION_WEBHOOK: &str = "webhook";
+pub const ACTION_COMMAND: &str = "command";
+
+// Events hooks can be triggered by
+pub const HOOK_EVENTS: [&str; 2] = [events::FILE_ORGANIZED, events::FOLDER_ORGANIZED];
+
+// A hook still running after this is cut off: the request is dropped, the
+// command killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Hooks running at once; the rest wait their turn, so a large batch of moves
+// doesn't start hundreds of processes
+const MAX_RUNNING_HOOKS: usize = 4;
+
+static HOOK_SLOTS: Semaphore = Semaphore::const_new(MAX_RUNNING_HOOKS);
+
+// How much of a failed command's error output goes in the activity log
+const MAX_ERROR_OUTPUT: usize = 500;
+
+// Programs whose -c argument is a script, where substituted values are quoted
+const POSIX_SHELLS: [&str; 5] = ["sh", "bash", "zsh", "dash", "ksh"];
+
+// Shells whose quoting can't be relied on; placeholders can't be used with them
+const OTHER_SHELLS: [&str; 3] = ["cmd", "powershell", "pwsh"];
+
+// An action run after the organizer moves a file or folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hook {
+    pub id: i64,
+    pub name: String,
+    // One of HOOK_EVENTS
+    pub event: String,
+    // ACTION_WEBHOOK or ACTION_COMMAND
+    pub action: String,
+    // The URL of a webhook, or a command template such as
+    // `ocrmypdf {path} {path}`: {path}, {name} and {tags} are replaced in each
+    // argument on its own, so the values are never parsed by a shell
+    pub target: String,
+    pub enabled: bool,
+}
+
+// What a hook is told about the moved file or folder
+#[derive(Debug, Clone, Serialize)]
+pub struct HookContext {
+    // Where it is now
+    pub path: String,
+    pub name: String,
+    pub from: String,
+    pub rule: Option<String>,
+    pub category: Option<String>,
+    // Tag names of an indexed file
+    pub tags: Vec<String>,
+}
+
+// Body POSTed by a webhook hook
+#[derive(Debug, Clone, Serialize)]
+struct HookPayload<'a> {
+    event: &'a str,
+    hook: &'a str,
+    timestamp: String,
+    #[serde(flatten)]
+    context: &'a HookContext,
+}
+
+// Check a hook before saving it
+pub fn validate(hook: &Hook) -> Result<()> {
+    if hook.name.trim().is_empty() {
+        return Err(anyhow::anyhow!("Hook name is empty"));
+    }
+    if !HOOK_EVENTS.contains(&hook.event.as_str()) {
+        return Err(anyhow::anyhow!(
+            "Unknown hook event \"{}\" (expected one of {})",
+            hook.event,
+            HOOK_EVENTS.join(", ")
+        ));
+    }
+    match hook.action.as_str() {
+        ACTION_WEBHOOK if hook.target.trim().is_empty() => Err(anyhow::anyhow!("Webhook URL is empty")),
+        ACTION_WEBHOOK => webhook::validate_url(&hook.target),
+        ACTION_COMMAND => command_line(&hook.target, &sample_context()).map(|_| ()),
+        other => Err(anyhow::anyhow!("Unknown hook action \"{}\"", other)),
+    }
+}
+
+// Run the enabled hooks of an event in the background. Failures are logged
+// and recorded in the activity log; they never affect the organize result
+pub fn dispatch<R: Runtime>(app: &AppHandle<R>, event: &str, context: HookContext) {
+    let hooks = match database::get_enabled_hooks(app, event) {
+        Ok(hooks) => hooks,
+        Err(e) => {
+            tracing::warn!(event, error = format!("{:#}", e), "Failed to read hooks");
+            return;
+        }
+    };
+    if hooks.is_empty() {
+        return;
+    }
+
+    let app_handle = app.clone();
+    let event = event.to_string();
+    tauri::async_runtime::spawn(async move {
+        let mut context = context;
+        let file_id = database::get_file_id_by_path(&app_handle, Path::new(&context.path)).ok().flatten();
+        if let Some(file_id) = file_id {
+            context.tags = database::get_file_tags(&app_handle, file_id)
+                .map(|tags| tags.into_iter().map(|tag| tag.name).collect())
+                .unwrap_or_default();
+        }
+
+        for hook in hooks {
+            let app_handle = app_handle.clone();
+            let event = event.clone();
+            let context = context.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = run(&app_handle, &hook, &event, &context).await {
+                    tracing::warn!(hook = %hook.name, path = %context.path, error = format!("{:#}", e), "Hook failed");
+                    let recorded = database::record_file_event(
+                        &app_handle,
+                        file_id,
+                        database::EVENT_HOOK_FAILED,
+                        None,
+                        Some(&context.path),
+                        Some(&format!("hook {}: {:#}", hook.name, e)),
+                    );
+                    if let Err(e) = recorded {
+                        tracing::warn!(hook = %hook.name, error = %e, "Failed to record hook failure");
+                    }
+                }
+            });
+        }
+    });
+}
+
+// Fire a hook, enabled or not, with a made-up file, waiting for the result
+pub async fn test_hook(app: &AppHandle, hook_id: i64) -> Result<()> {
+    let hook = database::get_hook(app, hook_id)?
+        .ok_or_else(|| anyhow::anyhow!("Hook {} not found", hook_id))?;
+    run(app, &hook, &hook.event, &sample_context()).await
+}
+
+// Run one hook, waiting for a free slot first
+async fn run<R: Runtime>(app: &AppHandle<R>, hook: &Hook, event: &str, context: &HookContext) -> Result<()> {
+    let _slot = HOOK_SLOTS.acquire().await?;
+    match hook.action.as_str() {
+        ACTION_WEBHOOK => call_webhook(hook, event, context).await,
+        ACTION_COMMAND => run_command(app, &hook.target, context).await,
+        other => Err(anyhow::anyhow!("Unknown hook action \"{}\"", other)),
+    }
+}
+
+async fn call_webhook(hook: &Hook, event: &str, context: &HookContext) -> Result<()> {
+    let payload = HookPayload {
+        event,
+        hook: &hook.name,
+        timestamp: utils::format_timestamp(&Utc::now()),
+        context,
+    };
+    webhook::post(&hook.target, &payload, HOOK_TIMEOUT).await
+}
+
+async fn run_command<R: Runtime>(app: &AppHandle<R>, template: &str, context: &HookContext) -> Result<()> {
+    let words = command_line(template, context)?;
+    let (program, args) = words.split_first().ok_or_else(|| anyhow::anyhow!("Hook command is empty"))?;
+    let (mut events, child) = app.shell().command(program).args(args).spawn()?;
+
+    let finished = async {
+        let mut error_output = String::new();
+        while let Some(event) = events.recv().await {
+            match event {
+                CommandEvent::Stderr(line) => {
+                    error_output.push_str(&String::from_utf8_lossy(&line));
+                    error_output.push('\n');
+                    keep_tail(&mut error_output, MAX_ERROR_OUTPUT);
+                }
+                CommandEvent::Error(e) => return Err(anyhow::anyhow!(e)),
+                CommandEvent::Terminated(status) => return Ok((status.code, error_output)),
+                _ => {}
+            }
+        }
+        Err(anyhow::anyhow!("Lost track of the hook command"))
+    };
+
+    match tokio::time::timeout(HOOK_TIMEOUT, finished).await {
+        Ok(Ok((Some(0), _))) => Ok(()),
+        Ok(Ok((code, error_output))) => {
+            let status = code.map_or("was killed".to_string(), |code| format!("exited with code {}", code));
+            Err(anyhow::anyhow!("{} {}: {}", program, status, error_output.trim()))
+        }
+        Ok(Err(e)) => Err(e),
+        Err(_) => {
+            let _ = child.kill();
+            Err(anyhow::anyhow!("{} timed out after {} seconds", program, HOOK_TIMEOUT.as_secs()))
+        }
+    }
+}
+
+// Drop the start of a text longer than max bytes, so a chatty command's
+// output never piles up in memory
+fn keep_tail(text: &mut String, max: usize) {
+    if text.len() > max {
+        let start = (text.len() - max..=text.len()).find(|i| text.is_char_boundary(*i)).unwrap_or(0);
+        text.drain(..start);
+    }
+}
+
+// The program and arguments of a command template with the placeholders
+// filled in. Substituting after splitting keeps every value one argument,
+// whatever it contains. The exception is the script of `sh -c '...'` and
+// similar, which a shell parses: there a placeholder becomes a reference to a
+// positional parameter ("$1" for the path, "$2" for the name, "$3" for the
+// tags) and the values are passed after the script, so the shell never sees
+// them as code
+fn command_line(template: &str, context: &HookContext) -> Result<Vec<String>> {
+    let words = split_words(template)?;
+    if words.is_empty() {
+        return Err(anyhow::anyhow!("Hook command is empty"));
+    }
+    let shell_names: Vec<String> = words
+        .iter()
+        .map(|word| {
+            Path::new(word)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_lowercase())
+                .unwrap_or_default()
+        })
+        .collect();
+
+    if let Some(shell) = shell_names.iter().find(|name| OTHER_SHELLS.contains(&name.as_str())) {
+        if words.iter().any(|word| has_placeholder(word)) {
+            return Err(anyhow::anyhow!(
+                "{} can't be given {{path}}, {{name}} or {{tags}} safely; run the program directly with them as its arguments",
+                shell
+            ));
+        }
+    }
+
+    // A shell anywhere in the command counts, so `env sh -c '...'` is covered
+    let script = shell_names
+        .iter()
+        .position(|name| POSIX_SHELLS.contains(&name.as_str()))
+        .and_then(|shell| script_position(&words, shell).map(|script| (shell, script)));
+
+    let tags = context.tags.join(",");
+    let value = |placeholder: &str| match placeholder {
+        "path" => Some(context.path.as_str()),
+        "name" => Some(context.name.as_str()),
+        "tags" => Some(tags.as_str()),
+        _ => None,
+    };
+    let mut line: Vec<String> = words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| match script {
+            Some((_, script)) if i == script => substitute(word, |placeholder| match placeholder {
+                "path" => Some("\"$1\""),
+                "name" => Some("\"$2\""),
+                "tags" => Some("\"$3\""),
+                _ => None,
+            }),
+            _ => substitute(word, value),
+        })
+        .collect();
+
+    if let Some((shell, script)) = script {
+        if has_placeholder(&words[script]) {
+            if script + 1 < words.len() {
+                return Err(anyhow::anyhow!(
+                    "A {} script with {{path}}, {{name}} or {{tags}} can't be followed by more arguments",
+                    shell_names[shell]
+                ));
+            }
+            line.extend([shell_names[shell].clone(), context.path.clone(), context.name.clone(), tags]);
+        }
+    }
+    Ok(line)
+}
+
+// Where the script of a POSIX shell at `shell` is: the word after its first
+// short option cluster holding c, such as -c or -ec
+fn script_position(words: &[String], shell: usize) -> Option<usize> {
+    words[shell + 1..]
+        .iter()
+        .take_while(|word| word.starts_with('-'))
+        .position(|word| !word.starts_with("--") && word.contains('c'))
+        .map(|i| shell + 1 + i + 1)
+        .filter(|script| *script < words.len())
+}
+
+// Replace every {placeholder} of a word in one pass, so text coming from a
+// value is never looked at again. Unknown placeholders are left as they are
+fn substitute<'a>(word: &str, value: impl Fn(&str) -> Option<&'a str>) -> String {
+    let mut result = String::with_capacity(word.len());
+    let mut rest = word;
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}').and_then(|close| value(&after[..close]).map(|value| (close, value))) {
+            Some((close, value)) => {
+                result.push_str(value);
+                rest = &after[close + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn has_placeholder(word: &str) -> bool {
+    ["{path}", "{name}", "{tags}"].iter().any(|placeholder| word.contains(placeholder))
+}
+
+// Split a command template into words the way a shell would: on whitespace,
+// keeping quoted text together. Double quotes take \" and \\; backslashes
+// elsewhere are kept, so Windows paths need no escaping
+fn split_words(template: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('"'), '\\') => match chars.next() {
+                Some(escaped @ ('"' | '\\')) => word.push(escaped),
+                Some(other) => {
+                    word.push('\\');
+                    word.push(other);
+                }
+                None => word.push('\\'),
+            },
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow::anyhow!("Unterminated quote in hook command"));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+// A file for test_hook and for checking command templates
+fn sample_context() -> HookContext {
+    let path = std::env::temp_dir().join("Organizer hook test.pdf");
+    HookContext {
+        name: "Organizer hook test.pdf".to_string(),
+        from: std::env::temp_dir().join("Downloads").join("Organizer hook test.pdf").to_string_lossy().to_string(),
+        path: path.to_string_lossy().to_string(),
+        rule: Some("Hook test".to_string()),
+        category: Some("Documents".to_string()),
+        tags: vec!["Example".to_string()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(path: &str) -> HookContext {
+        HookContext {
+            path: path.to_string(),
+            name: Path::new(path).file_name().unwrap().to_string_lossy().to_string(),
+            from: String::new(),
+            rule: None,
+            category: None,
+            tags: vec!["a".to_string(), "b".to_string()],
+        }
+    }
+
+    fn words(list: &[&str]) -> Vec<String> {
+        list.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn split_words_handles_quotes() {
+        assert_eq!(split_words("  ocrmypdf  a b ").unwrap(), words(&["ocrmypdf", "a", "b"]));
+        assert_eq!(split_words("cp 'my file' \"other file\"").unwrap(), words(&["cp", "my file", "other file"]));
+        assert_eq!(split_words("echo \"say \\\"hi\\\" \\\\ \\n\"").unwrap(), words(&["echo", "say \"hi\" \\ \\n"]));
+        assert_eq!(split_words("copy C:\\Temp\\x.txt").unwrap(), words(&["copy", "C:\\Temp\\x.txt"]));
+        assert_eq!(split_words("echo '' a''b").unwrap(), words(&["echo", "", "ab"]));
+        assert!(split_words("echo 'open").is_err());
+        assert!(split_words("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn command_line_keeps_values_one_argument() {
+        let line = command_line("ocrmypdf {path} out/{name}", &context("/tmp/a b; rm -rf ~.pdf")).unwrap();
+        assert_eq!(line, words(&["ocrmypdf", "/tmp/a b; rm -rf ~.pdf", "out/a b; rm -rf ~.pdf"]));
+    }
+
+    #[test]
+    fn command_line_substitutes_once() {
+        let line = command_line("echo {path} {tags}", &context("/tmp/{name}{tags}.txt")).unwrap();
+        assert_eq!(line, words(&["echo", "/tmp/{name}{tags}.txt", "a,b"]));
+        let line = command_line("echo {unknown} {path", &context("/tmp/x")).unwrap();
+        assert_eq!(line, words(&["echo", "{unknown}", "{path"]));
+    }
+
+    #[test]
+    fn command_line_passes_script_values_as_parameters() {
+        let hostile = context("/tmp/$(touch pwned)'\"`id`{name}.txt");
+        let line = command_line("sh -c 'lpr {path} && echo {name} {tags}'", &hostile).unwrap();
+        assert_eq!(line[..3], words(&["sh", "-c", "lpr \"$1\" && echo \"$2\" \"$3\""]));
+        assert_eq!(line[3..], [
+            "sh".to_string(),
+            hostile.path.clone(),
+            hostile.name.clone(),
+            "a,b".to_string(),
+        ]);
+
+        let line = command_line("/usr/bin/env bash -ec 'cat {path}'", &hostile).unwrap();
+        assert_eq!(line[3], "cat \"$1\"");
+        assert_eq!(line[4..], ["bash".to_string(), hostile.path.clone(), hostile.name.clone(), "a,b".to_string()]);
+
+        // Without placeholders nothing is added
+        let line = command_line("sh -c 'echo done' name", &hostile).unwrap();
+        assert_eq!(line, words(&["sh", "-c", "echo done", "name"]));
+
+        assert!(command_line("sh -c 'echo {path}' name", &hostile).is_err());
+    }
+
+    #[test]
+    fn command_line_rejects_placeholders_for_other_shells() {
+        assert!(command_line("cmd /c type {path}", &context("/tmp/x")).is_err());
+        assert!(command_line("powershell -Command Get-Item {path}", &context("/tmp/x")).is_err());
+        assert!(command_line("cmd /c echo done", &context("/tmp/x")).is_ok());
+        assert!(command_line("   ", &context("/tmp/x")).is_err());
+    }
+
+    #[test]
+    fn keep_tail_respects_char_boundaries() {
+        let mut text = "ééé".to_string();
+        keep_tail(&mut text, 3);
+        assert_eq!(text, "é");
+        let mut text = "short".to_string();
+        keep_tail(&mut text, 10);
+        assert_eq!(text, "short");
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Runtime};
+use crate::database;
+use crate::utils;
+
+// Setting that still indexes ignored files the watcher sees, so they show up
+// in search; they are never moved either way
+pub const INDEX_IGNORED_SETTING: &str = "index_ignored_files";
+
+// How an ignore entry is matched against a path
+enum IgnoreMatcher {
+    // An absolute path without wildcards: that file, or everything in that folder
+    Path(PathBuf),
+    // A glob without a path separator, e.g. "*.part" or ".stfolder": matched
+    // against the file's name and the name of every folder above it
+    Name(Regex),
+    // A glob with a path separator, e.g. "*/torrents/incomplete/*": matched
+    // against the whole path
+    FullPath(Regex),
+}
+
+impl IgnoreMatcher {
+    fn parse(pattern: &str) -> Result<IgnoreMatcher> {
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            return Err(anyhow::anyhow!("Ignore pattern is empty"));
+        }
+        let is_glob = pattern.contains(['*', '?']);
+        let has_separator = pattern.contains(['/', '\\']);
+
+        if !is_glob && Path::new(pattern).is_absolute() {
+            return Ok(IgnoreMatcher::Path(utils::normalize_path(Path::new(pattern))));
+        }
+        if !has_separator {
+            return Ok(IgnoreMatcher::Name(utils::rule_pattern_regex(pattern, false)?));
+        }
+        if !is_glob {
+            return Err(anyhow::anyhow!(
+                "{} is a relative path; use a full path, or a pattern such as */{}",
+                pattern,
+                pattern.trim_start_matches(['.', '/', '\\'])
+            ));
+        }
+        Ok(IgnoreMatcher::FullPath(utils::rule_pattern_regex(
+            &utils::normalize_path_query(pattern),
+            false,
+        )?))
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        match self {
+            IgnoreMatcher::Path(ignored) => path.starts_with(ignored),
+            IgnoreMatcher::Name(regex) => path
+                .components()
+                .any(|component| regex.is_match(&component.as_os_str().to_string_lossy())),
+            IgnoreMatcher::FullPath(regex) => regex.is_match(&path.to_string_lossy()),
+        }
+    }
+}
+
+// Check that a pattern can be added to the ignore list
+pub fn validate_pattern(pattern: &str) -> Result<()> {
+    IgnoreMatcher::parse(pattern).map(|_| ())
+}
+
+// Files and folders the organizer never moves
+pub struct IgnoreList {
+    matchers: Vec<IgnoreMatcher>,
+}
+
+impl IgnoreList {
+    pub fn load<R: Runtime>(app: &AppHandle<R>) -> Result<IgnoreList> {
+        let mut matchers = vec![];
+        for entry in database::get_ignores(app)? {
+            match IgnoreMatcher::parse(&entry.pattern) {
+                Ok(matcher) => matchers.push(matcher),
+                Err(e) => tracing::warn!(pattern = %entry.pattern, error = %e, "Skipping invalid ignore entry"),
+            }
+        }
+        Ok(IgnoreList { matchers })
+    }
+
+    // Whether a path, as normalized by utils::normalize_path, is ignored
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.matchers.iter().any(|matcher| matcher.matches(path))
+    }
+}
@@ -0,0 +1,278 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tokio::sync::oneshot;
+use crate::file_ops::{self, OrganizeOutcome};
+use crate::tray;
+
+// Custom URL scheme, e.g. smartorganizer://organize?path=/Users/me/Downloads/report.pdf
+pub const URL_SCHEME: &str = "smartorganizer";
+
+// Event with the outcome of each command-line or deep-link request
+const LAUNCH_RESULT_EVENT: &str = "launch_result";
+
+const ACTION_ORGANIZE: &str = "organize";
+const ACTION_WATCH: &str = "watch";
+
+// Something the app was asked to do from outside, by `--organize <path>`,
+// `--watch <path>` or a smartorganizer:// link
+#[derive(Debug, Clone, PartialEq)]
+enum LaunchAction {
+    Organize(PathBuf),
+    Watch(PathBuf),
+}
+
+impl LaunchAction {
+    fn name(&self) -> &'static str {
+        match self {
+            LaunchAction::Organize(_) => ACTION_ORGANIZE,
+            LaunchAction::Watch(_) => ACTION_WATCH,
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            LaunchAction::Organize(path) | LaunchAction::Watch(path) => path,
+        }
+    }
+}
+
+// Outcome of a launch request, shown to the user as a toast
+#[derive(Debug, Clone, Serialize)]
+pub struct LaunchResult {
+    // "organize" or "watch", or the argument that couldn't be understood
+    pub action: String,
+    pub path: Option<String>,
+    pub success: bool,
+    pub message: String,
+}
+
+impl LaunchResult {
+    fn failed(action: &str, path: Option<&Path>, message: String) -> Self {
+        LaunchResult {
+            action: action.to_string(),
+            path: path.map(|path| path.to_string_lossy().to_string()),
+            success: false,
+            message,
+        }
+    }
+}
+
+// Handle the arguments and link of the launch that started the app and listen
+// for deep links; called once from setup
+pub fn init(app: &AppHandle) {
+    // Links are only handled here, never as arguments: the deep-link plugin
+    // reads them from the first launch's arguments, and the single instance
+    // plugin hands it those of later launches, which arrive as events
+    let app_handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        let requests = event.urls().iter().map(parse_url).collect();
+        run_requests(&app_handle, requests, true);
+    });
+    match app.deep_link().get_current() {
+        Ok(Some(urls)) => run_requests(app, urls.iter().map(parse_url).collect(), true),
+        Ok(None) => {}
+        Err(e) => tracing::warn!(error = %e, "Failed to read the link the app was opened with"),
+    }
+
+    // Installers register the scheme; this covers development builds and AppImages
+    #[cfg(any(windows, target_os = "linux"))]
+    if let Err(e) = app.deep_link().register_all() {
+        tracing::warn!(error = %e, "Failed to register URL scheme");
+    }
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let requests = parse_args(&args, &cwd);
+    if !requests.is_empty() {
+        run_requests(app, requests, false);
+    }
+}
+
+// Handle the arguments of a second launch, forwarded by the single instance
+// plugin once the deep-link plugin has taken its link; the window is brought
+// forward even when there's nothing to do
+pub fn handle_second_launch(app: &AppHandle, args: Vec<String>, cwd: String) {
+    let requests = parse_args(&args, Path::new(&cwd));
+    if requests.is_empty() {
+        tray::show_main_window(app);
+    } else {
+        run_requests(app, requests, false);
+    }
+}
+
+// Read --organize/--watch options. Relative paths are taken from the folder
+// the command was run in; other arguments, links included, are ignored
+fn parse_args(args: &[String], cwd: &Path) -> Vec<Result<LaunchAction, LaunchResult>> {
+    let mut requests = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let action = match arg.as_str() {
+            "--organize" => ACTION_ORGANIZE,
+            "--watch" => ACTION_WATCH,
+            _ => continue,
+        };
+
+        let request = match args.next() {
+            Some(path) => Ok(make_action(action, cwd.join(path))),
+            None => Err(LaunchResult::failed(arg, None, format!("{} needs a path", arg))),
+        };
+        requests.push(request);
+    }
+    requests
+}
+
+// Read a link such as smartorganizer://watch?path=/Users/me/Downloads
+fn parse_url(url: &Url) -> Result<LaunchAction, LaunchResult> {
+    let link = url.as_str();
+    if url.scheme() != URL_SCHEME {
+        return Err(LaunchResult::failed(link, None, format!("Not a {}:// link", URL_SCHEME)));
+    }
+
+    // smartorganizer://organize puts the action in the host, smartorganizer:organize in the path
+    let action = url
+        .host_str()
+        .unwrap_or_else(|| url.path())
+        .trim_matches('/');
+    if action != ACTION_ORGANIZE && action != ACTION_WATCH {
+        return Err(LaunchResult::failed(link, None, format!("Unknown action \"{}\"", action)));
+    }
+
+    let Some(path) = url.query_pairs().find(|(key, _)| key == "path").map(|(_, path)| path) else {
+        return Err(LaunchResult::failed(action, None, "The link has no path".to_string()));
+    };
+    let path = PathBuf::from(path.as_ref());
+    if !path.is_absolute() {
+        return Err(LaunchResult::failed(action, Some(&path), "Links need an absolute path".to_string()));
+    }
+
+    Ok(make_action(action, path))
+}
+
+fn make_action(action: &str, path: PathBuf) -> LaunchAction {
+    if action == ACTION_WATCH {
+        LaunchAction::Watch(path)
+    } else {
+        LaunchAction::Organize(path)
+    }
+}
+
+// Carry out each request in order, report every outcome to the frontend and
+// bring the window forward so the user sees them. Any web page or program can
+// open a link, so the user confirms each one's action first
+fn run_requests(app: &AppHandle, requests: Vec<Result<LaunchAction, LaunchResult>>, from_links: bool) {
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tray::show_main_window(&app_handle);
+        for request in requests {
+            let result = match request {
+                Ok(action) if from_links && !confirm_link(&app_handle, &action).await => {
+                    LaunchResult::failed(action.name(), Some(action.path()), "Cancelled".to_string())
+                }
+                Ok(action) => run_action(&app_handle, action).await,
+                Err(result) => result,
+            };
+            if result.success {
+                tracing::info!(action = %result.action, message = %result.message, "Handled launch request");
+            } else {
+                tracing::warn!(action = %result.action, message = %result.message, "Launch request failed");
+            }
+            let _ = app_handle.emit(LAUNCH_RESULT_EVENT, result);
+        }
+    });
+}
+
+// Ask whether to carry out a link's action
+async fn confirm_link(app: &AppHandle, action: &LaunchAction) -> bool {
+    let (message, button) = match action {
+        LaunchAction::Organize(path) => (
+            format!("A link asks to organize {} by your rules, which may move it.", path.display()),
+            "Organize",
+        ),
+        LaunchAction::Watch(path) => (
+            format!("A link asks to watch {} and organize the files in it by your rules.", path.display()),
+            "Watch",
+        ),
+    };
+    let (tx, rx) = oneshot::channel();
+    app.dialog()
+        .message(message)
+        .title("Open link?")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(button.to_string(), "Cancel".to_string()))
+        .show(move |confirmed| {
+            let _ = tx.send(confirmed);
+        });
+    rx.await.unwrap_or(false)
+}
+
+async fn run_action(app: &AppHandle, action: LaunchAction) -> LaunchResult {
+    let (name, path, outcome) = match action {
+        LaunchAction::Organize(path) => {
+            let outcome = if path.is_file() {
+                file_ops::organize_file_by_rules(app, &path).await.and_then(|outcome| match outcome {
+                    OrganizeOutcome::Moved { to, .. } => Ok(format!("Moved to {}", to)),
+                    OrganizeOutcome::Skipped { reason } => Ok(format!("Left where it is: {}", reason)),
+                    OrganizeOutcome::AlreadyOrganized { rule } => {
+                        Ok(format!("Already in the folder of rule \"{}\"", rule))
+                    }
+                    OrganizeOutcome::Failed { error } => Err(anyhow::anyhow!(error)),
+                })
+            } else {
+                Err(anyhow::anyhow!("{} is not a file", path.display()))
+            };
+            (ACTION_ORGANIZE, path, outcome)
+        }
+        LaunchAction::Watch(path) => {
+            let outcome = if path.exists() {
+                file_ops::start_watching(app, path.to_string_lossy().to_string())
+                    .await
+                    .map(|_| format!("Watching {}", path.display()))
+            } else {
+                Err(anyhow::anyhow!("{} does not exist", path.display()))
+            };
+            (ACTION_WATCH, path, outcome)
+        }
+    };
+
+    match outcome {
+        Ok(message) => LaunchResult {
+            action: name.to_string(),
+            path: Some(path.to_string_lossy().to_string()),
+            success: true,
+            message,
+        },
+        Err(e) => LaunchResult::failed(name, Some(&path), format!("{:#}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn arguments_leave_links_to_the_deep_link_plugin() {
+        let args: Vec<String> = ["smartorganizer://watch?path=/", "--organize", "report.pdf", "--watch"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let requests = parse_args(&args, Path::new("/home/me"));
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].as_ref().unwrap(), &LaunchAction::Organize(PathBuf::from("/home/me/report.pdf")));
+        assert!(requests[1].is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_need_a_known_action_and_an_absolute_path() {
+        let parse = |link: &str| parse_url(&Url::parse(link).unwrap());
+        assert_eq!(parse("smartorganizer://watch?path=/data").unwrap(), LaunchAction::Watch(PathBuf::from("/data")));
+        assert_eq!(parse("smartorganizer:organize?path=/a.pdf").unwrap(), LaunchAction::Organize(PathBuf::from("/a.pdf")));
+        assert!(parse("smartorganizer://delete?path=/data").is_err());
+        assert!(parse("smartorganizer://watch?path=data").is_err());
+        assert!(parse("smartorganizer://watch").is_err());
+    }
+}
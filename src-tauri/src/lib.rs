@@ -4,37 +4,239 @@ mod database;
 mod cloud_sync;
 mod utils;
 mod commands;
+mod cleanup;
+mod logging;
+mod notifications;
+mod webhook;
+mod tray;
+mod rule_config;
+mod launch;
+mod rule_engine;
+mod events;
+mod health;
+mod schedule;
+mod ignore_list;
+mod external_rules;
+mod hooks;
+mod batch;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Manager;
 
 // Re-exports for public API
 pub use commands::*;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
+// How long shutdown waits for queued file moves before exiting anyway
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Set once the first exit request starts the shutdown sequence
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+// Stop watchers, let in-flight moves finish and close the database
+async fn shutdown(app: &tauri::AppHandle) {
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, file_ops::shutdown_watchers(app)).await.is_err() {
+        tracing::warn!("Timed out waiting for pending file moves during shutdown");
+    }
+    
+    if let Err(e) = database::close_database(app) {
+        tracing::error!(error = format!("{:#}", e), "Failed to close database");
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        // Must come first: a second launch forwards its arguments here and exits
+        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+            launch::handle_second_launch(app, argv.into_iter().skip(1).collect(), cwd);
+        }))
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init())
+        .manage(database::CategoryCache::default())
+        .manage(Arc::new(Mutex::new(file_ops::WatcherState::default())))
+        .manage(Arc::new(Mutex::new(file_ops::FolderSizeState::default())))
+        .manage(cloud_sync::BandwidthLimits::default())
+        .manage(notifications::NotificationState::default())
+        .manage(webhook::WebhookState::default())
         .setup(|app| {
-            // Initialize database
+            // Initialize database; failures are reported through get_database_status
+            // so the window still opens and can tell the user what happened
             let app_handle = app.handle();
-            database::init_database(&app_handle).expect("Failed to initialize database");
+            if let Err(e) = logging::init_logging(&app_handle) {
+                eprintln!("Failed to set up logging: {:#}", e);
+            }
+            database::init_database_with_recovery(&app_handle);
+            // Before anything else moves files, settle moves a crash cut short
+            if let Err(e) = file_ops::reconcile_move_journal(&app_handle) {
+                tracing::warn!(error = format!("{:#}", e), "Failed to reconcile the move journal");
+            }
+            if let Err(e) = logging::load_log_level(&app_handle) {
+                tracing::warn!(error = format!("{:#}", e), "Failed to load log level");
+            }
+            if let Err(e) = cloud_sync::load_bandwidth_limits(&app_handle) {
+                tracing::warn!(error = format!("{:#}", e), "Failed to load bandwidth limits");
+            }
+            
+            // Periodically apply stale-file cleanup policies
+            cleanup::start_cleanup_sweeper(&app_handle);
+            
+            // Periodically re-scan the watched folders for files the watcher missed
+            file_ops::start_scan_scheduler(&app_handle);
+            
+            // Organize files queued outside the active hours once they start
+            file_ops::start_pending_scheduler(&app_handle);
+            
+            if let Err(e) = tray::create_tray(&app_handle) {
+                tracing::warn!(error = format!("{:#}", e), "Failed to create tray icon");
+            }
+            
+            // --organize/--watch arguments and smartorganizer:// links
+            launch::init(&app_handle);
+            
+            health::start_startup_check(&app_handle);
             Ok(())
         })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::Focused(true) => {
+                notifications::on_window_focused(window.app_handle());
+            }
+            tauri::WindowEvent::CloseRequested { api, .. } if tray::close_to_tray(window.app_handle()) => {
+                api.prevent_close();
+                let _ = window.hide();
+            }
+            _ => {}
+        })
         .invoke_handler(tauri::generate_handler![
             commands::greet,
             commands::select_folder,
             commands::start_watching_folder,
+            commands::watch_folder_with_rules,
             commands::stop_watching_folder,
+            commands::stop_watching_path,
+            commands::pause_watching,
+            commands::resume_watching,
+            commands::get_watched_folders,
             commands::organize_file,
+            commands::analyze_folder,
+            commands::organize_everything,
+            commands::scan_folder,
+            commands::organize_folder_once,
+            commands::rename_file,
+            commands::preview_text_file,
+            commands::get_folder_size,
+            commands::cancel_folder_size,
+            commands::hash_files,
+            commands::format_file_size,
             commands::get_tags,
             commands::add_tag,
+            commands::rename_tag,
             commands::remove_tag,
+            commands::merge_tags,
+            commands::suggest_tags,
+            commands::get_rules,
+            commands::add_rule,
+            commands::update_rule,
+            commands::remove_rule,
+            commands::load_rules_from_file,
+            commands::import_external_rules,
+            commands::export_rules_to_file,
+            commands::export_data,
+            commands::import_data,
+            commands::create_profile,
+            commands::list_profiles,
+            commands::switch_profile,
+            commands::delete_profile,
+            commands::test_rule,
+            commands::test_rule_pattern,
+            commands::test_rule_against_index,
+            commands::explain_file,
+            commands::get_categories,
+            commands::recategorize_file,
+            commands::set_auto_tag_override,
+            commands::update_category,
             commands::search_files,
-            commands::backup_to_cloud
+            commands::get_untagged_files,
+            commands::get_unorganized_report,
+            commands::cleanup_orphans,
+            commands::get_large_files,
+            commands::get_file_details,
+            commands::get_extensions,
+            commands::get_trends,
+            commands::get_file_history,
+            commands::find_similar_images,
+            commands::find_duplicates,
+            commands::cancel_duplicate_scan,
+            commands::set_file_note,
+            commands::set_file_rating,
+            commands::set_file_metadata,
+            commands::get_file_metadata,
+            commands::delete_file_metadata,
+            commands::backup_to_cloud,
+            commands::backup_selection,
+            commands::get_bucket_layout,
+            commands::set_bucket_layout,
+            commands::delete_backup,
+            commands::list_cloud_backups,
+            commands::restore_from_cloud,
+            commands::restore_selection,
+            commands::list_backup_contents,
+            commands::get_backup_contents,
+            commands::restore_files,
+            commands::request_archive_restore,
+            commands::compare_with_backup,
+            commands::get_cleanup_policies,
+            commands::set_cleanup_policy,
+            commands::remove_cleanup_policy,
+            commands::get_hooks,
+            commands::add_hook,
+            commands::update_hook,
+            commands::remove_hook,
+            commands::test_hook,
+            commands::run_cleanup_now,
+            commands::cleanup_empty_dirs,
+            commands::find_collision_duplicates,
+            commands::clean_collision_duplicates,
+            commands::get_database_status,
+            commands::get_health,
+            commands::flush_pending_now,
+            commands::add_ignore,
+            commands::remove_ignore,
+            commands::list_ignores,
+            commands::backup_database,
+            commands::restore_database,
+            commands::compact_database,
+            commands::get_recent_logs,
+            commands::set_log_level,
+            commands::open_log_folder,
+            commands::get_setting,
+            commands::set_setting,
+            commands::get_base_directory,
+            commands::set_base_directory,
+            commands::get_platform_capabilities
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            // Hold the exit back until shutdown is done; the exit call at the
+            // end raises ExitRequested again, which is then let through
+            if let tauri::RunEvent::ExitRequested { api, .. } = event {
+                if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+                    return;
+                }
+                api.prevent_exit();
+                let app_handle = app_handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    shutdown(&app_handle).await;
+                    app_handle.exit(0);
+                });
+            }
+        });
 }
@@ -0,0 +1,290 @@
+use anyhow::Result;
+use chrono::Local;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Manager};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, Layer, Registry};
+use crate::database;
+
+// Setting holding the minimum level that gets logged
+pub const LOG_LEVEL_SETTING: &str = "log_level";
+
+// Level used until the setting is loaded, or when it is missing
+const DEFAULT_LOG_LEVEL: Level = Level::INFO;
+
+// Number of entries kept in memory for get_recent_logs
+const RECENT_LOG_CAPACITY: usize = 2000;
+
+// Daily log files kept; older ones are deleted as the log rolls over
+const LOG_RETENTION_DAYS: usize = 7;
+
+const LOG_FILE_PREFIX: &str = "smart-file-organizer";
+
+// Our own events are logged at the configured level. Dependencies (the AWS SDK
+// in particular, whose debug output includes request details) only get
+// through with warnings and errors, so credentials never end up in the log.
+const OWN_TARGET_PREFIX: &str = "smart_file_organizer";
+
+// Timestamp format of log lines and LogEntry, which sorts as text
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+// Swaps the level filter when the log level changes, once init_logging has run
+static LEVEL_FILTER: OnceLock<reload::Handle<Targets, Registry>> = OnceLock::new();
+
+// The most recent entries, for get_recent_logs and count_errors_since
+static RECENT: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+
+// A log line as returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+// Verbosity of a level, ERROR being 0
+fn level_index(level: &Level) -> usize {
+    match *level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+fn parse_level(level: &str) -> Result<Level> {
+    level
+        .trim()
+        .parse::<Level>()
+        .map_err(|_| anyhow::anyhow!("Unknown log level: {}", level))
+}
+
+// Our own events at `level`, everything else at warnings and errors
+fn level_filter(level: Level) -> Targets {
+    Targets::new()
+        .with_default(LevelFilter::WARN)
+        .with_target(OWN_TARGET_PREFIX, level)
+}
+
+// Local time in TIMESTAMP_FORMAT, for the log file
+struct LocalTime;
+
+impl FormatTime for LocalTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        write!(w, "{}", Local::now().format(TIMESTAMP_FORMAT))
+    }
+}
+
+// Collects an event's or span's fields as "message field=value ..."
+#[derive(Default)]
+struct FieldWriter {
+    message: String,
+    fields: String,
+}
+
+impl Visit for FieldWriter {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+// A span's fields, kept in its extensions for RecentLogs
+struct SpanFields(String);
+
+// Keeps the most recent entries in memory, in the file's format
+struct RecentLogs;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for RecentLogs {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = FieldWriter::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(fields.fields));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldWriter::default();
+        values.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            if let Some(span_fields) = span.extensions_mut().get_mut::<SpanFields>() {
+                span_fields.0.push_str(&fields.fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldWriter::default();
+        event.record(&mut fields);
+
+        // Prefix the spans the event happened in, outermost first
+        let mut context = String::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                let extensions = span.extensions();
+                let span_fields = extensions.get::<SpanFields>().map(|f| f.0.trim_start()).unwrap_or_default();
+                let _ = write!(context, "{}{{{}}}: ", span.name(), span_fields);
+            }
+        }
+
+        let metadata = event.metadata();
+        let entry = LogEntry {
+            timestamp: Local::now().format(TIMESTAMP_FORMAT).to_string(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: format!("{}{}{}", context, fields.message, fields.fields),
+        };
+
+        let mut recent = RECENT.lock().unwrap();
+        if recent.len() == RECENT_LOG_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+}
+
+// Get the folder log files are written to
+pub fn get_log_dir(app: &AppHandle) -> Result<PathBuf> {
+    let app_dir = app.path().app_data_dir()?;
+    Ok(app_dir.join("logs"))
+}
+
+// Install the logger; called once at startup, before anything logs
+pub fn init_logging(app: &AppHandle) -> Result<()> {
+    let dir = get_log_dir(app)?;
+    fs::create_dir_all(&dir)?;
+
+    let file = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix("log")
+        .max_log_files(LOG_RETENTION_DAYS)
+        .build(&dir)?;
+    let (filter, handle) = reload::Layer::new(level_filter(DEFAULT_LOG_LEVEL));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(file)
+                .with_ansi(false)
+                .with_timer(LocalTime),
+        )
+        .with(RecentLogs)
+        .try_init()?;
+    let _ = LEVEL_FILTER.set(handle);
+
+    Ok(())
+}
+
+// Apply the saved log level, once the database is available
+pub fn load_log_level(app: &AppHandle) -> Result<()> {
+    if let Some(level) = database::get_setting(app, LOG_LEVEL_SETTING)? {
+        apply_log_level(&level)?;
+    }
+    Ok(())
+}
+
+fn apply_log_level(level: &str) -> Result<()> {
+    let level = parse_level(level)?;
+    if let Some(handle) = LEVEL_FILTER.get() {
+        handle.reload(level_filter(level))?;
+    }
+    Ok(())
+}
+
+// Change the log level now and for future runs
+pub fn set_log_level(app: &AppHandle, level: &str) -> Result<()> {
+    apply_log_level(level)?;
+    database::set_setting(app, LOG_LEVEL_SETTING, &level.trim().to_uppercase())?;
+    tracing::info!(level, "Log level changed");
+    Ok(())
+}
+
+// Most recent entries at `level` or more severe, newest first
+pub fn get_recent_logs(level: Option<&str>, limit: usize) -> Result<Vec<LogEntry>> {
+    let min_level = level.map(parse_level).transpose()?.unwrap_or(Level::TRACE);
+
+    let recent = RECENT.lock().unwrap();
+    Ok(recent
+        .iter()
+        .rev()
+        .filter(|entry| {
+            entry
+                .level
+                .parse::<Level>()
+                .map(|level| level_index(&level) <= level_index(&min_level))
+                .unwrap_or(true)
+        })
+        .take(limit)
+        .cloned()
+        .collect())
+}
+
+// Number of errors logged since a moment, among the entries kept in memory
+pub fn count_errors_since(since: chrono::DateTime<Local>) -> usize {
+    // Timestamps are all in the same format, so they compare as strings
+    let since = since.format(TIMESTAMP_FORMAT).to_string();
+
+    let recent = RECENT.lock().unwrap();
+    recent
+        .iter()
+        .rev()
+        .take_while(|entry| entry.timestamp >= since)
+        .filter(|entry| entry.level == Level::ERROR.as_str())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_entries_carry_their_spans_and_respect_the_level() {
+        let (filter, handle) = reload::Layer::new(level_filter(Level::INFO));
+        let subscriber = tracing_subscriber::registry().with(filter).with(RecentLogs);
+        
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("scan", folder = "Downloads");
+            let _entered = span.enter();
+            tracing::debug!("Not logged at info");
+            tracing::info!(moved = 3, "Scan finished");
+            
+            handle.reload(level_filter(Level::DEBUG)).unwrap();
+            tracing::debug!("Logged at debug");
+        });
+        
+        let recent = get_recent_logs(None, 2).unwrap();
+        assert_eq!(recent[1].message, r#"scan{folder="Downloads"}: Scan finished moved=3"#);
+        assert_eq!(recent[1].level, "INFO");
+        assert_eq!(recent[0].message, r#"scan{folder="Downloads"}: Logged at debug"#);
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tauri_plugin_notification::NotificationExt;
+use crate::batch::Batches;
+use crate::database;
+
+// Setting that silences every notification
+pub const DO_NOT_DISTURB_SETTING: &str = "do_not_disturb";
+
+// What a notification is about. Each kind has its own "notify_<kind>" setting,
+// on unless set to "false"
+pub const KIND_ORGANIZED: &str = "organized";
+pub const KIND_BACKUP_COMPLETE: &str = "backup_complete";
+pub const KIND_BACKUP_FAILED: &str = "backup_failed";
+pub const KIND_DUPLICATE_DETECTED: &str = "duplicate_detected";
+
+// Focusing the window this soon after a notification is taken as clicking it
+const CLICK_THROUGH_WINDOW: Duration = Duration::from_secs(60);
+
+// Payload of the "notification_navigate" event
+#[derive(Debug, Clone, Serialize)]
+pub struct NavigateEvent {
+    pub kind: String,
+    pub view: String,
+}
+
+#[derive(Default)]
+pub struct NotificationState {
+    // Messages waiting to be shown, by kind
+    pending: Batches<&'static str, String>,
+    // Last notification shown while the window was in the background
+    last_shown: Mutex<Option<(NavigateEvent, Instant)>>,
+}
+
+// Setting key for turning one kind of notification on or off
+pub fn kind_setting(kind: &str) -> String {
+    format!("notify_{}", kind)
+}
+
+fn is_enabled<R: Runtime>(app: &AppHandle<R>, kind: &str) -> Result<bool> {
+    if database::is_setting_enabled(app, DO_NOT_DISTURB_SETTING)? {
+        return Ok(false);
+    }
+    Ok(database::get_setting(app, &kind_setting(kind))?.as_deref() != Some("false"))
+}
+
+// Queue a notification; it is shown once no more of the same kind arrive
+// for a moment, merged into one summary with any others queued meanwhile
+pub fn notify<R: Runtime>(app: &AppHandle<R>, kind: &'static str, message: String) {
+    match is_enabled(app, kind) {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            tracing::warn!(kind, error = %e, "Failed to read notification settings");
+            return;
+        }
+    }
+
+    let Some(state) = app.try_state::<NotificationState>() else { return };
+    if !state.pending.push(kind, message) {
+        return;
+    }
+
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<NotificationState>();
+        if let Some(messages) = state.pending.take_when_quiet(&kind).await {
+            show(&app_handle, kind, &messages);
+        }
+    });
+}
+
+// Title, summary for several messages, and the view to open, for each kind
+fn describe(kind: &str, count: usize) -> (&'static str, String, &'static str) {
+    match kind {
+        KIND_ORGANIZED => ("Files organized", format!("Moved {} files", format_count(count)), "files"),
+        KIND_BACKUP_COMPLETE => ("Backup finished", format!("{} backups finished", format_count(count)), "backups"),
+        KIND_BACKUP_FAILED => ("Backup failed", format!("{} backups failed", format_count(count)), "backups"),
+        KIND_DUPLICATE_DETECTED => ("Duplicates found", format!("{} duplicate scans found matches", format_count(count)), "duplicates"),
+        _ => ("Smart File Organizer", format!("{} updates", format_count(count)), "files"),
+    }
+}
+
+fn show<R: Runtime>(app: &AppHandle<R>, kind: &str, messages: &[String]) {
+    let (title, summary, view) = describe(kind, messages.len());
+    let body = match messages {
+        [message] => message.clone(),
+        _ => summary,
+    };
+
+    let result = app
+        .notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .extra("view", view)
+        .show();
+    if let Err(e) = result {
+        tracing::warn!(kind, error = %e, "Failed to show notification");
+        return;
+    }
+
+    // Desktop notifications can't report clicks, but clicking one brings the
+    // window to the front; remember where it should lead
+    let focused = app
+        .get_webview_window("main")
+        .and_then(|window| window.is_focused().ok())
+        .unwrap_or(false);
+    if !focused {
+        let event = NavigateEvent {
+            kind: kind.to_string(),
+            view: view.to_string(),
+        };
+        *app.state::<NotificationState>().last_shown.lock().unwrap() = Some((event, Instant::now()));
+    }
+}
+
+// Called when the main window gains focus; sends the frontend to the view of
+// a notification that was just shown
+pub fn on_window_focused(app: &AppHandle) {
+    let Some(state) = app.try_state::<NotificationState>() else { return };
+    let last_shown = state.last_shown.lock().unwrap().take();
+
+    if let Some((event, shown_at)) = last_shown {
+        if shown_at.elapsed() <= CLICK_THROUGH_WINDOW {
+            let _ = app.emit("notification_navigate", event);
+        }
+    }
+}
+
+// 1204 -> "1,204"
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+// Notify about a finished backup, successful or not
+pub fn notify_backup_result(app: &AppHandle, result: Result<usize, &anyhow::Error>) {
+    match result {
+        Ok(file_count) => notify(
+            app,
+            KIND_BACKUP_COMPLETE,
+            format!("Backup finished ({} files)", format_count(file_count)),
+        ),
+        Err(e) => notify(app, KIND_BACKUP_FAILED, format!("Backup failed: {}", e)),
+    }
+}
@@ -0,0 +1,527 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use tauri::AppHandle;
+use crate::commands::{Rule, SearchFilter};
+use crate::database;
+use crate::file_ops;
+use crate::utils;
+
+// Rules file layout, the same in TOML and JSON:
+//
+//   profile = "Work"
+//
+//   [[rules]]
+//   name = "Invoices"
+//   extensions = ["pdf"]
+//   destination = "Documents/Invoices/{year}"
+//   tag = "Finance"
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    // Name of the profile the rules were exported from, for reference; an
+    // import goes into whichever profile it's asked to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    #[serde(default)]
+    rules: Vec<RuleEntry>,
+}
+
+// One rule in a rules file. Tags are referred to by name so the file can be
+// shared between machines
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extensions: Option<Vec<String>>,
+    // Filename pattern, or the domains of a source_domain rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pattern: Option<String>,
+    destination: String,
+    #[serde(default = "default_active")]
+    active: bool,
+    #[serde(default)]
+    preserve_structure: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    condition: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    // Days without modification before an older_than rule applies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_age_days: Option<i64>,
+    // The pattern matches folder names, and matching folders move whole
+    #[serde(default)]
+    applies_to_folders: bool,
+    // Files the rule moves don't get their category's tag
+    #[serde(default)]
+    exclude_auto_tags: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+// Result of load_rules_from_file
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleImportSummary {
+    pub added: usize,
+    pub updated: usize,
+}
+
+enum Format {
+    Toml,
+    Json,
+}
+
+fn format_for(path: &Path) -> Result<Format> {
+    match utils::get_file_extension(path).as_deref() {
+        Some("toml") => Ok(Format::Toml),
+        Some("json") => Ok(Format::Json),
+        _ => Err(anyhow::anyhow!("Rules file must be a .toml or .json file")),
+    }
+}
+
+// Read rules from a TOML or JSON file and add them to a profile (the active
+// one by default), replacing its rules with the same name. Nothing is saved
+// unless every rule in the file is valid
+pub fn load_rules_from_file(app: &AppHandle, path: &Path, profile_id: Option<i64>) -> Result<RuleImportSummary> {
+    let profile_id = profile_for(app, profile_id)?.id;
+    let format = format_for(path)?;
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    // Both parsers report the line and column of syntax and schema errors
+    let parsed: Result<RulesFile, String> = match format {
+        Format::Toml => toml::from_str(&contents).map_err(|e| e.to_string()),
+        Format::Json => serde_json::from_str(&contents).map_err(|e| e.to_string()),
+    };
+    let file = parsed
+        .map_err(|e| anyhow::anyhow!("Invalid rules file {}: {}", path.display(), e))?;
+
+    let errors: Vec<String> = file
+        .rules
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entry)| {
+            validate_entry(entry)
+                .err()
+                .map(|e| format!("rules[{}] \"{}\": {}", i, entry.name, e))
+        })
+        .collect();
+    if !errors.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Invalid rules file {}:\n{}",
+            path.display(),
+            errors.join("\n")
+        ));
+    }
+
+    let mut rules = vec![];
+    for entry in file.rules {
+        rules.push(rule_from_entry(app, entry)?);
+    }
+
+    let (added, updated) = database::upsert_rules_by_name(app, profile_id, &rules)?;
+    tracing::info!(file = %path.display(), added, updated, "Loaded rules from file");
+
+    Ok(RuleImportSummary { added, updated })
+}
+
+// A validated rules file entry as a rule to save, creating its tag if needed
+fn rule_from_entry(app: &AppHandle, entry: RuleEntry) -> Result<Rule> {
+    let tag_id = match &entry.tag {
+        Some(tag) => Some(database::get_or_create_tag(app, tag.trim(), utils::DEFAULT_TAG_COLOR)?),
+        None => None,
+    };
+    let (pattern, is_extension) = match entry.extensions {
+        Some(extensions) => (extensions.join(","), true),
+        None => (entry.pattern.unwrap_or_default(), false),
+    };
+    Ok(Rule {
+        id: 0,
+        name: entry.name.trim().to_string(),
+        pattern,
+        destination_folder: entry.destination.trim().to_string(),
+        is_extension,
+        is_active: entry.active,
+        preserve_structure: entry.preserve_structure,
+        condition: entry.condition,
+        tag_id,
+        max_age_days: entry.max_age_days,
+        applies_to_folders: entry.applies_to_folders,
+        exclude_auto_tags: entry.exclude_auto_tags,
+    })
+}
+
+fn validate_entry(entry: &RuleEntry) -> Result<()> {
+    if entry.name.trim().is_empty() {
+        return Err(anyhow::anyhow!("name is empty"));
+    }
+    if entry.destination.trim().is_empty() {
+        return Err(anyhow::anyhow!("destination is empty"));
+    }
+    if entry.tag.as_deref().is_some_and(|tag| tag.trim().is_empty()) {
+        return Err(anyhow::anyhow!("tag is empty"));
+    }
+    if entry.max_age_days.is_some() && entry.condition.as_deref() != Some(file_ops::CONDITION_OLDER_THAN) {
+        return Err(anyhow::anyhow!(
+            "max_age_days is only used with condition \"{}\"",
+            file_ops::CONDITION_OLDER_THAN
+        ));
+    }
+
+    if entry.applies_to_folders {
+        return match (&entry.condition, &entry.extensions, entry.pattern.as_deref()) {
+            (Some(_), _, _) => Err(anyhow::anyhow!("a folder rule can't have a condition")),
+            (_, Some(_), _) => Err(anyhow::anyhow!("a folder rule takes a folder pattern, not extensions")),
+            (_, _, Some(pattern)) if !pattern.trim().is_empty() => Ok(()),
+            _ => Err(anyhow::anyhow!("a folder rule needs a folder pattern in pattern")),
+        };
+    }
+
+    match entry.condition.as_deref() {
+        None => match (&entry.extensions, &entry.pattern) {
+            (Some(_), Some(_)) => Err(anyhow::anyhow!("set either extensions or pattern, not both")),
+            (None, None) => Err(anyhow::anyhow!("extensions or pattern is required")),
+            (Some(extensions), None) if extensions.iter().all(|e| e.trim().trim_start_matches('.').is_empty()) => {
+                Err(anyhow::anyhow!("extensions is empty"))
+            }
+            _ => Ok(()),
+        },
+        Some(file_ops::CONDITION_IS_SCREENSHOT) if entry.extensions.is_some() => {
+            Err(anyhow::anyhow!("extensions can't be combined with a condition"))
+        }
+        Some(file_ops::CONDITION_IS_SCREENSHOT) => Ok(()),
+        Some(file_ops::CONDITION_SOURCE_DOMAIN) if entry.extensions.is_some() => {
+            Err(anyhow::anyhow!("extensions can't be combined with a condition"))
+        }
+        Some(file_ops::CONDITION_SOURCE_DOMAIN) => match entry.pattern.as_deref() {
+            Some(domains) if !domains.trim().is_empty() => Ok(()),
+            _ => Err(anyhow::anyhow!("a source_domain rule needs its domains in pattern")),
+        },
+        Some(file_ops::CONDITION_FOLDER_NAME) if entry.extensions.is_some() => {
+            Err(anyhow::anyhow!("extensions can't be combined with a condition"))
+        }
+        Some(file_ops::CONDITION_FOLDER_NAME) => match entry.pattern.as_deref() {
+            Some(pattern) if !pattern.trim().is_empty() => Ok(()),
+            _ => Err(anyhow::anyhow!("a folder_name rule needs a folder pattern in pattern")),
+        },
+        Some(file_ops::CONDITION_NAME_MATCHES) if entry.extensions.is_some() => {
+            Err(anyhow::anyhow!("extensions can't be combined with a condition"))
+        }
+        Some(file_ops::CONDITION_NAME_MATCHES) => match entry.pattern.as_deref() {
+            Some(pattern) if !pattern.trim().is_empty() => Ok(()),
+            _ => Err(anyhow::anyhow!("a name_matches rule needs a file name pattern in pattern")),
+        },
+        // Extensions are optional here and narrow the rule to those types
+        Some(file_ops::CONDITION_OLDER_THAN) if entry.pattern.is_some() => {
+            Err(anyhow::anyhow!("an older_than rule takes extensions, not a pattern"))
+        }
+        Some(file_ops::CONDITION_OLDER_THAN)
+            if entry.extensions.as_ref().is_some_and(|extensions| {
+                extensions.iter().all(|e| e.trim().trim_start_matches('.').is_empty())
+            }) =>
+        {
+            Err(anyhow::anyhow!("extensions is empty"))
+        }
+        Some(file_ops::CONDITION_OLDER_THAN) => match entry.max_age_days {
+            Some(days) if days > 0 => Ok(()),
+            Some(_) => Err(anyhow::anyhow!("max_age_days must be at least 1")),
+            None => Err(anyhow::anyhow!("an older_than rule needs max_age_days")),
+        },
+        Some(other) => Err(anyhow::anyhow!(
+            "unknown condition \"{}\" (expected \"{}\", \"{}\", \"{}\", \"{}\" or \"{}\")",
+            other,
+            file_ops::CONDITION_IS_SCREENSHOT,
+            file_ops::CONDITION_SOURCE_DOMAIN,
+            file_ops::CONDITION_OLDER_THAN,
+            file_ops::CONDITION_FOLDER_NAME,
+            file_ops::CONDITION_NAME_MATCHES
+        )),
+    }
+}
+
+// The profile with this ID, or the active one
+fn profile_for(app: &AppHandle, profile_id: Option<i64>) -> Result<database::Profile> {
+    database::get_profiles(app)?
+        .into_iter()
+        .find(|profile| profile_id.map_or(profile.is_active, |id| profile.id == id))
+        .ok_or_else(|| anyhow::anyhow!("Profile {} not found", profile_id.unwrap_or_default()))
+}
+
+// Write every rule of a profile (the active one by default) to a TOML or JSON
+// file, returning how many were written
+pub fn export_rules_to_file(app: &AppHandle, path: &Path, profile_id: Option<i64>) -> Result<usize> {
+    let format = format_for(path)?;
+    let profile = profile_for(app, profile_id)?;
+
+    let tag_names: HashMap<i64, String> = database::get_all_tags(app)?
+        .into_iter()
+        .map(|tag| (tag.id, tag.name))
+        .collect();
+
+    let rules: Vec<RuleEntry> = database::get_profile_rules(app, profile.id)?
+        .into_iter()
+        .map(|rule| rule_entry(rule, &tag_names))
+        .collect();
+    let count = rules.len();
+
+    let file = RulesFile { profile: Some(profile.name), rules };
+    let contents = match format {
+        Format::Toml => toml::to_string_pretty(&file)?,
+        Format::Json => serde_json::to_string_pretty(&file)?,
+    };
+    fs::write(path, contents)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(count)
+}
+
+// A saved rule as a rules file entry, its tag referred to by name
+fn rule_entry(rule: Rule, tag_names: &HashMap<i64, String>) -> RuleEntry {
+    let (extensions, pattern) = if rule.is_extension {
+        let extensions = rule
+            .pattern
+            .split(',')
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .collect();
+        (Some(extensions), None)
+    } else {
+        // Age rules without extensions have no pattern at all
+        (None, Some(rule.pattern).filter(|pattern| !pattern.is_empty()))
+    };
+    RuleEntry {
+        name: rule.name,
+        extensions,
+        pattern,
+        destination: rule.destination_folder,
+        active: rule.is_active,
+        preserve_structure: rule.preserve_structure,
+        condition: rule.condition,
+        tag: rule.tag_id.and_then(|id| tag_names.get(&id).cloned()),
+        max_age_days: rule.max_age_days,
+        applies_to_folders: rule.applies_to_folders,
+        exclude_auto_tags: rule.exclude_auto_tags,
+    }
+}
+
+// Version of the export_data bundle layout
+const BUNDLE_VERSION: u32 = 1;
+
+// Everything export_data writes, as JSON: tags and the active profile's rules,
+// and optionally the file index. Files are identified by path, so only the
+// ones found at the same path are restored by an import
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DataBundle {
+    version: u32,
+    exported_at: String,
+    #[serde(default)]
+    tags: Vec<TagEntry>,
+    #[serde(default)]
+    rules: Vec<RuleEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    files: Option<Vec<FileEntry>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TagEntry {
+    name: String,
+    color: String,
+}
+
+// An indexed file and what the user added to it
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileEntry {
+    path: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rating: Option<i64>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, String>,
+}
+
+// Result of export_data
+#[derive(Debug, Clone, Serialize)]
+pub struct DataExportSummary {
+    pub tags: usize,
+    pub rules: usize,
+    pub files: usize,
+}
+
+// Result of import_data. What was already here wins: tags and rules with a
+// name that's taken are skipped, not overwritten
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DataImportSummary {
+    pub tags_imported: usize,
+    pub tags_skipped: usize,
+    pub rules_imported: usize,
+    pub rules_skipped: usize,
+    pub files_imported: usize,
+    pub files_skipped: usize,
+    // Why each rule or file was skipped, other than already being here
+    pub errors: Vec<String>,
+}
+
+// Write tags, the active profile's rules and, with include_files, the file
+// index with tags, notes, ratings and metadata to a JSON bundle for import_data
+pub fn export_data(app: &AppHandle, path: &Path, include_files: bool) -> Result<DataExportSummary> {
+    let tags = database::get_all_tags(app)?;
+    let tag_names: HashMap<i64, String> = tags.iter().map(|tag| (tag.id, tag.name.clone())).collect();
+    let rules = database::get_rules(app)?
+        .into_iter()
+        .map(|rule| rule_entry(rule, &tag_names))
+        .collect::<Vec<_>>();
+
+    let files = if include_files {
+        let filter = SearchFilter { include_metadata: Some(true), ..Default::default() };
+        let files = database::search_files(app, &filter)?
+            .into_iter()
+            .map(|file| FileEntry {
+                path: file.path,
+                tags: file.tags.into_iter().map(|tag| tag.name).collect(),
+                note: file.notes,
+                rating: file.rating,
+                metadata: file.metadata.unwrap_or_default(),
+            })
+            .collect();
+        Some(files)
+    } else {
+        None
+    };
+
+    let summary = DataExportSummary {
+        tags: tags.len(),
+        rules: rules.len(),
+        files: files.as_ref().map_or(0, Vec::len),
+    };
+    let bundle = DataBundle {
+        version: BUNDLE_VERSION,
+        exported_at: utils::format_timestamp(&Utc::now()),
+        tags: tags.into_iter().map(|tag| TagEntry { name: tag.name, color: tag.color }).collect(),
+        rules,
+        files,
+    };
+    fs::write(path, serde_json::to_string_pretty(&bundle)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(summary)
+}
+
+// Merge a bundle written by export_data into this database: missing tags and
+// rules (into the active profile) are added, matching by name, and files that
+// exist at the same path here get their tags, note, rating and metadata
+pub fn import_data(app: &AppHandle, path: &Path) -> Result<DataImportSummary> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let bundle: DataBundle = serde_json::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("Invalid data bundle {}: {}", path.display(), e))?;
+    if bundle.version > BUNDLE_VERSION {
+        return Err(anyhow::anyhow!(
+            "{} was exported by a newer version of the app (bundle version {})",
+            path.display(),
+            bundle.version
+        ));
+    }
+
+    let mut summary = DataImportSummary::default();
+
+    let existing_tags: Vec<String> = database::get_all_tags(app)?.into_iter().map(|tag| tag.name).collect();
+    for tag in &bundle.tags {
+        if existing_tags.contains(&tag.name) {
+            summary.tags_skipped += 1;
+        } else {
+            database::get_or_create_tag(app, &tag.name, &tag.color)?;
+            summary.tags_imported += 1;
+        }
+    }
+
+    let existing_rules: Vec<String> = database::get_rules(app)?.into_iter().map(|rule| rule.name).collect();
+    let mut rules = vec![];
+    for entry in bundle.rules {
+        if existing_rules.contains(&entry.name.trim().to_string()) {
+            summary.rules_skipped += 1;
+            continue;
+        }
+        if let Err(e) = validate_entry(&entry) {
+            summary.rules_skipped += 1;
+            summary.errors.push(format!("rule \"{}\": {}", entry.name, e));
+            continue;
+        }
+        rules.push(rule_from_entry(app, entry)?);
+    }
+    let profile_id = database::get_active_profile_id(app)?;
+    summary.rules_imported = database::upsert_rules_by_name(app, profile_id, &rules)?.0;
+
+    for file in bundle.files.unwrap_or_default() {
+        match import_file(app, &file) {
+            Ok(true) => summary.files_imported += 1,
+            Ok(false) => summary.files_skipped += 1,
+            Err(e) => {
+                summary.files_skipped += 1;
+                summary.errors.push(format!("{}: {:#}", file.path, e));
+            }
+        }
+    }
+
+    tracing::info!(
+        file = %path.display(),
+        tags = summary.tags_imported,
+        rules = summary.rules_imported,
+        files = summary.files_imported,
+        "Imported data bundle"
+    );
+    Ok(summary)
+}
+
+// Restore a file's tags, note, rating and metadata, indexing it first if
+// needed. False when there's no such file on this machine
+fn import_file(app: &AppHandle, file: &FileEntry) -> Result<bool> {
+    let path = Path::new(&file.path);
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return Ok(false),
+    };
+
+    let file_id = match database::get_file_id_by_path(app, path)? {
+        Some(file_id) => file_id,
+        None => {
+            let (created, created_approximate) = utils::file_created(&metadata);
+            let modified: DateTime<Utc> = metadata.modified().unwrap_or_else(|_| std::time::SystemTime::now()).into();
+            database::add_file(app, &database::NewFile {
+                path: path.to_path_buf(),
+                name: utils::get_file_name(path).unwrap_or_default(),
+                extension: utils::get_file_extension(path).unwrap_or_default(),
+                size: metadata.len() as i64,
+                created_at: utils::format_timestamp(&created),
+                created_at_approximate: created_approximate,
+                modified_at: utils::format_timestamp(&modified),
+            })?
+        }
+    };
+
+    for tag in &file.tags {
+        let tag_id = database::get_or_create_tag(app, tag, utils::DEFAULT_TAG_COLOR)?;
+        database::add_tag_to_file(app, file_id, tag_id)?;
+    }
+    if file.note.is_some() {
+        database::set_file_note(app, file_id, file.note.clone())?;
+    }
+    if file.rating.is_some() {
+        database::set_file_rating(app, file_id, file.rating)?;
+    }
+    for (key, value) in &file.metadata {
+        database::set_file_metadata(app, file_id, key, value)?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,465 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use tauri::{AppHandle, Runtime};
+use crate::commands::{FileInfo, Rule};
+use crate::database;
+use crate::file_ops;
+use crate::utils;
+
+// What rules are matched against, read from the file on disk or from the index
+pub struct FileFacts {
+    pub file_name: String,
+    pub extension: String,
+    pub source_domain: Option<String>,
+    pub is_screenshot: bool,
+    // Only when the file system recorded it; see utils::file_created
+    pub created: Option<DateTime<Utc>>,
+    pub modified: DateTime<Utc>,
+    // Names of the folders between the file and its watched root, nearest
+    // first; see folder_names
+    pub folders: Vec<String>,
+}
+
+// Names of the folders a file is in, nearest first, stopping below the watched
+// root, e.g. ["2024", "Invoices"] for Downloads/Invoices/2024/a.pdf when
+// Downloads is watched. A file outside any watched folder only has its own
+pub fn folder_names(path: &Path, watched_root: Option<&Path>) -> Vec<String> {
+    let Some(parent) = path.parent() else { return vec![] };
+    match watched_root.and_then(|root| parent.strip_prefix(root).ok()) {
+        Some(relative) => relative
+            .components()
+            .rev()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect(),
+        None => utils::get_file_name(parent).into_iter().collect(),
+    }
+}
+
+impl FileFacts {
+    // Read a file's facts from disk. A path that doesn't exist is judged by
+    // its name alone, as if it had just been created
+    pub fn read(path: &Path, screenshot_patterns: &[String], watched_root: Option<&Path>) -> FileFacts {
+        let metadata = fs::metadata(path).ok();
+        let created = metadata
+            .as_ref()
+            .map(utils::file_created)
+            .and_then(|(created, approximate)| (!approximate).then_some(created));
+        let modified = metadata
+            .and_then(|metadata| metadata.modified().ok())
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(Utc::now);
+        let source_domain = utils::read_source_url(path)
+            .ok()
+            .flatten()
+            .and_then(|url| utils::url_domain(&url));
+
+        FileFacts {
+            file_name: utils::get_file_name(path).unwrap_or_default(),
+            extension: utils::get_file_extension(path).unwrap_or_default(),
+            source_domain,
+            is_screenshot: utils::is_screenshot(path, screenshot_patterns),
+            created,
+            modified,
+            folders: folder_names(path, watched_root),
+        }
+    }
+
+    // Facts as the index recorded them
+    pub fn from_index(file: &FileInfo, screenshot_patterns: &[String], watched_root: Option<&Path>) -> FileFacts {
+        let created = DateTime::parse_from_rfc3339(&file.created_at)
+            .ok()
+            .filter(|_| !file.created_at_approximate)
+            .map(|created| created.with_timezone(&Utc));
+        let modified = DateTime::parse_from_rfc3339(&file.modified_at)
+            .map(|modified| modified.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+
+        FileFacts {
+            file_name: file.name.clone(),
+            extension: file.extension.to_lowercase(),
+            source_domain: file.source_domain.clone(),
+            is_screenshot: utils::is_screenshot(Path::new(&file.path), screenshot_patterns),
+            created,
+            modified,
+            folders: folder_names(Path::new(&file.path), watched_root),
+        }
+    }
+
+    // Date {year}/{month}/{day} destinations are expanded from: when the file
+    // was created, or when it was last modified if the file system doesn't
+    // record creation times. The approximate creation time isn't used, as
+    // moving or restoring a file resets it
+    pub fn organize_date(&self) -> DateTime<Utc> {
+        self.created.unwrap_or(self.modified)
+    }
+
+    fn age_days(&self) -> i64 {
+        (Utc::now() - self.modified).num_days()
+    }
+}
+
+// The parts of a matching rule needed to organize a file
+pub struct MatchedRule {
+    pub id: i64,
+    pub name: String,
+    pub destination_folder: String,
+    pub preserve_structure: bool,
+    pub tag_id: Option<i64>,
+    // Why the rule matched, e.g. "extension .pdf"
+    pub reason: String,
+    // The folder a folder name rule matched, which {parent} expands to
+    pub matched_folder: Option<String>,
+    pub exclude_auto_tags: bool,
+}
+
+impl MatchedRule {
+    fn new(rule: &Rule, reason: String, matched_folder: Option<String>) -> MatchedRule {
+        MatchedRule {
+            id: rule.id,
+            name: rule.name.clone(),
+            destination_folder: rule.destination_folder.clone(),
+            preserve_structure: rule.preserve_structure,
+            tag_id: rule.tag_id,
+            reason,
+            matched_folder,
+            exclude_auto_tags: rule.exclude_auto_tags,
+        }
+    }
+}
+
+// How a rule decides whether it applies. The variants are in order of
+// precedence: download source rules, then screenshot rules, then age rules,
+// then folder name rules, then file name rules, then extension rules
+enum Matcher {
+    SourceDomain(String),
+    Screenshot,
+    OlderThan { max_age_days: i64, extensions: Option<Vec<String>> },
+    FolderName { pattern: String, regex: Regex },
+    FileName { pattern: String, regex: Regex },
+    Extensions(Vec<String>),
+}
+
+impl Matcher {
+    // None for rules that can never match, such as an empty extension list or
+    // an unknown condition. Plain file name patterns have never been applied
+    // by the organizer and still aren't, only those of name_matches rules;
+    // test_rule tries them on their own
+    fn for_rule(rule: &Rule) -> Result<Option<Matcher>> {
+        let extensions = || Some(database::split_extensions(&rule.pattern)).filter(|e| !e.is_empty());
+
+        let matcher = match rule.condition.as_deref() {
+            Some(file_ops::CONDITION_SOURCE_DOMAIN) => Some(Matcher::SourceDomain(rule.pattern.clone())),
+            Some(file_ops::CONDITION_IS_SCREENSHOT) => Some(Matcher::Screenshot),
+            Some(file_ops::CONDITION_OLDER_THAN) => match (rule.max_age_days, rule.is_extension) {
+                (None, _) => None,
+                (Some(max_age_days), false) => Some(Matcher::OlderThan { max_age_days, extensions: None }),
+                (Some(max_age_days), true) => {
+                    extensions().map(|extensions| Matcher::OlderThan { max_age_days, extensions: Some(extensions) })
+                }
+            },
+            Some(file_ops::CONDITION_FOLDER_NAME) if rule.pattern.trim().is_empty() => None,
+            Some(file_ops::CONDITION_FOLDER_NAME) => Some(Matcher::FolderName {
+                pattern: rule.pattern.trim().to_string(),
+                regex: utils::rule_pattern_regex(rule.pattern.trim(), false)?,
+            }),
+            Some(file_ops::CONDITION_NAME_MATCHES) if rule.pattern.trim().is_empty() => None,
+            Some(file_ops::CONDITION_NAME_MATCHES) => Some(Matcher::FileName {
+                pattern: rule.pattern.trim().to_string(),
+                regex: utils::rule_pattern_regex(rule.pattern.trim(), false)?,
+            }),
+            Some(_) => None,
+            None if rule.is_extension => extensions().map(Matcher::Extensions),
+            None => None,
+        };
+        Ok(matcher)
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Matcher::SourceDomain(_) => 0,
+            Matcher::Screenshot => 1,
+            Matcher::OlderThan { .. } => 2,
+            Matcher::FolderName { .. } => 3,
+            Matcher::FileName { .. } => 4,
+            Matcher::Extensions(_) => 5,
+        }
+    }
+
+    // The nearest of the file's folders a folder name rule matches
+    fn matched_folder<'a>(&self, facts: &'a FileFacts) -> Option<&'a str> {
+        match self {
+            Matcher::FolderName { regex, .. } => facts
+                .folders
+                .iter()
+                .find(|folder| regex.is_match(folder))
+                .map(String::as_str),
+            _ => None,
+        }
+    }
+
+    // Why the rule applies to a file, or None when it doesn't
+    fn reason(&self, facts: &FileFacts) -> Option<String> {
+        match self {
+            Matcher::SourceDomain(domains) => facts
+                .source_domain
+                .as_deref()
+                .filter(|domain| utils::domain_matches(domain, domains))
+                .map(|domain| format!("downloaded from {} (rule domains: {})", domain, domains)),
+            Matcher::Screenshot => facts
+                .is_screenshot
+                .then(|| "detected as a screenshot".to_string()),
+            Matcher::OlderThan { max_age_days, extensions } => {
+                let age_days = facts.age_days();
+                let extension_matches = extensions
+                    .as_ref()
+                    .is_none_or(|extensions| extensions.contains(&facts.extension));
+                (age_days >= *max_age_days && extension_matches).then(|| {
+                    format!("not modified for {} days (rule threshold: {} days)", age_days, max_age_days)
+                })
+            }
+            Matcher::FolderName { pattern, .. } => self
+                .matched_folder(facts)
+                .map(|folder| format!("in folder \"{}\" (matches \"{}\")", folder, pattern)),
+            Matcher::FileName { pattern, regex } => regex
+                .is_match(&facts.file_name)
+                .then(|| format!("name matches \"{}\"", pattern)),
+            Matcher::Extensions(extensions) => extensions
+                .contains(&facts.extension)
+                .then(|| format!("extension .{}", facts.extension)),
+        }
+    }
+}
+
+// The folder name pattern of a folder rule; None for an empty pattern, which
+// never matches
+fn folder_pattern(rule: &Rule) -> Result<Option<Regex>> {
+    let pattern = rule.pattern.trim();
+    if pattern.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(utils::rule_pattern_regex(pattern, false)?))
+}
+
+// Active rules ready to be matched against files, in order of precedence;
+// rules of the same kind apply oldest first
+pub struct RuleEngine {
+    rules: Vec<(Rule, Matcher)>,
+    // Rules that move whole folders, with their folder name pattern; these
+    // never match single files
+    folder_rules: Vec<(Rule, Regex)>,
+}
+
+impl RuleEngine {
+    // The active saved rules
+    pub fn load<R: Runtime>(app: &AppHandle<R>) -> Result<RuleEngine> {
+        RuleEngine::load_limited(app, None)
+    }
+
+    // The active saved rules, or only those of them in rule_ids, e.g. for a
+    // watched folder limited to some rules
+    pub fn load_limited<R: Runtime>(app: &AppHandle<R>, rule_ids: Option<&[i64]>) -> Result<RuleEngine> {
+        let rules = database::get_rules(app)?
+            .into_iter()
+            .filter(|rule| rule.is_active && rule_ids.is_none_or(|ids| ids.contains(&rule.id)))
+            .collect();
+        Ok(RuleEngine::new(rules))
+    }
+
+    // Build an engine from rules that may not be saved. A rule with an invalid
+    // pattern is left out rather than stopping every other rule
+    pub fn new(rules: Vec<Rule>) -> RuleEngine {
+        let mut compiled = vec![];
+        let mut folder_rules = vec![];
+        for rule in rules {
+            if rule.applies_to_folders {
+                match folder_pattern(&rule) {
+                    Ok(Some(regex)) => folder_rules.push((rule, regex)),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!(rule = %rule.name, error = %e, "Skipping rule with an invalid pattern"),
+                }
+                continue;
+            }
+            match Matcher::for_rule(&rule) {
+                Ok(Some(matcher)) => compiled.push((rule, matcher)),
+                Ok(None) => {}
+                Err(e) => tracing::warn!(rule = %rule.name, error = %e, "Skipping rule with an invalid pattern"),
+            }
+        }
+        compiled.sort_by_key(|(rule, matcher)| (matcher.precedence(), rule.id));
+        folder_rules.sort_by_key(|(rule, _)| rule.id);
+        RuleEngine { rules: compiled, folder_rules }
+    }
+
+    // Check that a rule can be matched, e.g. that its pattern compiles
+    pub fn validate(rule: &Rule) -> Result<()> {
+        if rule.applies_to_folders {
+            return folder_pattern(rule).map(|_| ());
+        }
+        Matcher::for_rule(rule).map(|_| ())
+    }
+
+    pub fn has_folder_rules(&self) -> bool {
+        !self.folder_rules.is_empty()
+    }
+
+    // The folder rule that moves a folder of this name, the oldest one when
+    // several match
+    pub fn matching_folder(&self, folder_name: &str) -> Option<MatchedRule> {
+        let (rule, _) = self.folder_rules.iter().find(|(_, regex)| regex.is_match(folder_name))?;
+        let reason = format!("folder name matches \"{}\"", rule.pattern.trim());
+        Some(MatchedRule::new(rule, reason, Some(folder_name.to_string())))
+    }
+
+    // Every rule matching a file, in order of precedence. The first one is the
+    // rule the organizer applies
+    pub fn matching(&self, facts: &FileFacts) -> Vec<MatchedRule> {
+        self.rules
+            .iter()
+            .filter_map(|(rule, matcher)| {
+                matcher
+                    .reason(facts)
+                    .map(|reason| MatchedRule::new(rule, reason, matcher.matched_folder(facts).map(str::to_string)))
+            })
+            .collect()
+    }
+
+    // Whether a plain file name pattern rule matches a file, for trying one
+    // out: as a glob over the whole name, or as a regex with is_regex. The
+    // organizer never applies these rules
+    pub fn name_pattern_match(rule: &Rule, is_regex: bool, facts: &FileFacts) -> Result<Option<MatchedRule>> {
+        let pattern = rule.pattern.trim();
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+        let regex = utils::rule_pattern_regex(pattern, is_regex)?;
+        Ok(regex
+            .is_match(&facts.file_name)
+            .then(|| MatchedRule::new(rule, format!("name matches \"{}\"", pattern), None)))
+    }
+
+    // Whether a rule has a plain file name pattern rather than a condition,
+    // extensions or a folder pattern
+    pub fn is_name_pattern_rule(rule: &Rule) -> bool {
+        rule.condition.is_none() && !rule.is_extension && !rule.applies_to_folders
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: i64, condition: Option<&str>, pattern: &str, is_extension: bool) -> Rule {
+        Rule {
+            id,
+            name: format!("Rule {}", id),
+            pattern: pattern.to_string(),
+            destination_folder: "Sorted".to_string(),
+            is_extension,
+            is_active: true,
+            preserve_structure: false,
+            condition: condition.map(str::to_string),
+            tag_id: None,
+            max_age_days: None,
+            applies_to_folders: false,
+            exclude_auto_tags: false,
+        }
+    }
+
+    fn facts(path: &str) -> FileFacts {
+        let path = Path::new(path);
+        FileFacts {
+            file_name: utils::get_file_name(path).unwrap_or_default(),
+            extension: utils::get_file_extension(path).unwrap_or_default(),
+            source_domain: None,
+            is_screenshot: false,
+            created: None,
+            modified: Utc::now(),
+            folders: folder_names(path, Some(Path::new("/home/me/Downloads"))),
+        }
+    }
+
+    fn matching_ids(engine: &RuleEngine, facts: &FileFacts) -> Vec<i64> {
+        engine.matching(facts).iter().map(|rule| rule.id).collect()
+    }
+
+    #[test]
+    fn rules_apply_in_order_of_precedence() {
+        let old_pdfs = Rule {
+            max_age_days: Some(30),
+            ..rule(2, Some(file_ops::CONDITION_OLDER_THAN), "pdf", true)
+        };
+        let engine = RuleEngine::new(vec![
+            rule(1, None, "pdf", true),
+            old_pdfs,
+            rule(3, Some(file_ops::CONDITION_FOLDER_NAME), "Invoices", false),
+            rule(4, Some(file_ops::CONDITION_SOURCE_DOMAIN), "example.com", false),
+            rule(5, None, "pdf,doc", true),
+            rule(6, Some(file_ops::CONDITION_NAME_MATCHES), "MARCH*.pdf", false),
+        ]);
+
+        let mut file = facts("/home/me/Downloads/Invoices/march.pdf");
+        assert_eq!(matching_ids(&engine, &file), [3, 6, 1, 5]);
+        file.source_domain = Some("files.example.com".to_string());
+        file.modified = Utc::now() - chrono::Duration::days(45);
+        assert_eq!(matching_ids(&engine, &file), [4, 2, 3, 6, 1, 5]);
+        assert_eq!(engine.matching(&file)[2].matched_folder.as_deref(), Some("Invoices"));
+        assert_eq!(engine.matching(&file)[3].reason, "name matches \"MARCH*.pdf\"");
+    }
+
+    #[test]
+    fn name_patterns_are_not_applied_by_the_organizer() {
+        let name_rule = rule(1, None, "*.pdf", false);
+        let engine = RuleEngine::new(vec![name_rule.clone(), rule(2, None, "", true)]);
+        assert!(engine.matching(&facts("/home/me/Downloads/report.pdf")).is_empty());
+        assert!(RuleEngine::is_name_pattern_rule(&name_rule));
+    }
+
+    #[test]
+    fn name_patterns_are_globs_unless_marked_as_regexes() {
+        let draft = rule(1, None, "inv.*", false);
+        let invoice = facts("/home/me/Downloads/invoice.pdf");
+        assert!(RuleEngine::name_pattern_match(&draft, false, &invoice).unwrap().is_none());
+        assert!(RuleEngine::name_pattern_match(&draft, true, &invoice).unwrap().is_some());
+        assert!(RuleEngine::name_pattern_match(&draft, false, &facts("/home/me/Downloads/INV.pdf")).unwrap().is_some());
+        assert!(RuleEngine::name_pattern_match(&rule(2, None, "inv(", false), true, &invoice).is_err());
+    }
+
+    #[test]
+    fn extension_lists_ignore_spacing_case_and_dots() {
+        let engine = RuleEngine::new(vec![rule(1, None, " PDF, .Doc ,,docx ", true)]);
+        for name in ["a.pdf", "b.DOC", "c.Docx"] {
+            assert_eq!(matching_ids(&engine, &facts(&format!("/home/me/Downloads/{}", name))), [1], "{}", name);
+        }
+        assert!(matching_ids(&engine, &facts("/home/me/Downloads/d.pdfx")).is_empty());
+        assert!(matching_ids(&engine, &facts("/home/me/Downloads/pdf")).is_empty());
+    }
+
+    #[test]
+    fn age_rules_go_by_when_files_were_last_modified() {
+        let dir = utils::tests::scratch_dir("age-rules");
+        let stale_pdfs = Rule {
+            max_age_days: Some(90),
+            ..rule(1, Some(file_ops::CONDITION_OLDER_THAN), "pdf", true)
+        };
+        let engine = RuleEngine::new(vec![stale_pdfs]);
+
+        let modified_days_ago = |name: &str, days: u64| {
+            let path = dir.join(name);
+            let file = fs::File::create(&path).unwrap();
+            let modified = std::time::SystemTime::now() - std::time::Duration::from_secs(days * 24 * 60 * 60 + 60);
+            file.set_modified(modified).unwrap();
+            FileFacts::read(&path, &[], Some(&dir))
+        };
+        assert!(matching_ids(&engine, &modified_days_ago("fresh.pdf", 0)).is_empty());
+        assert!(matching_ids(&engine, &modified_days_ago("almost.pdf", 89)).is_empty());
+        assert_eq!(matching_ids(&engine, &modified_days_ago("due.pdf", 90)), [1]);
+        assert_eq!(matching_ids(&engine, &modified_days_ago("stale.pdf", 400)), [1]);
+        assert!(matching_ids(&engine, &modified_days_ago("stale.txt", 400)).is_empty());
+
+        let reason = &engine.matching(&modified_days_ago("old.pdf", 120))[0].reason;
+        assert_eq!(reason, "not modified for 120 days (rule threshold: 90 days)");
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveTime, TimeZone};
+use tauri::{AppHandle, Runtime};
+use crate::database;
+
+// Setting with the local time window the watcher may move files in, as
+// "HH:MM-HH:MM" (e.g. "22:00-06:00" runs overnight). Unset or empty means
+// files are organized as soon as they appear
+pub const ACTIVE_HOURS_SETTING: &str = "active_hours";
+
+// Daily window, in local wall-clock time, in which files are moved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl ActiveHours {
+    pub fn parse(value: &str) -> Result<ActiveHours> {
+        let (start, end) = value
+            .split_once('-')
+            .with_context(|| format!("Active hours must look like 22:00-06:00, not {:?}", value))?;
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .with_context(|| format!("Invalid time in active hours: {:?}", time.trim()))
+        };
+        let hours = ActiveHours { start: parse_time(start)?, end: parse_time(end)? };
+        if hours.start == hours.end {
+            return Err(anyhow::anyhow!("Active hours must start and end at different times"));
+        }
+        Ok(hours)
+    }
+
+    // Whether a wall-clock time falls in the window. The end is exclusive, and
+    // a window whose end comes before its start runs past midnight
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    // When the window next opens after now. A start time skipped by a DST
+    // change opens the window once the clocks have jumped; one that happens
+    // twice opens it the first time
+    pub fn next_start(&self, now: DateTime<Local>) -> DateTime<Local> {
+        (0..=2)
+            .filter_map(|days| now.date_naive().checked_add_days(chrono::Days::new(days)))
+            .filter_map(|date| {
+                let start = date.and_time(self.start);
+                Local
+                    .from_local_datetime(&start)
+                    .earliest()
+                    .or_else(|| Local.from_local_datetime(&(start + chrono::Duration::hours(1))).earliest())
+            })
+            .find(|start| *start > now)
+            .unwrap_or(now)
+    }
+}
+
+// The configured active hours; None when files may be moved at any time
+pub fn active_hours<R: Runtime>(app: &AppHandle<R>) -> Result<Option<ActiveHours>> {
+    match database::get_setting(app, ACTIVE_HOURS_SETTING)? {
+        Some(value) if !value.trim().is_empty() => Ok(Some(ActiveHours::parse(&value)?)),
+        _ => Ok(None),
+    }
+}
+
+// Whether files may be moved right now. An unreadable setting doesn't hold
+// files back
+pub fn is_active_now<R: Runtime>(app: &AppHandle<R>) -> bool {
+    match active_hours(app) {
+        Ok(hours) => hours.is_none_or(|hours| hours.contains(Local::now().time())),
+        Err(e) => {
+            tracing::warn!(error = format!("{:#}", e), "Failed to read active hours");
+            true
+        }
+    }
+}
+
+// When deferred moves will next run; None when the window is open now, or
+// there's no window at all
+pub fn next_active_start(app: &AppHandle) -> Result<Option<DateTime<Local>>> {
+    let now = Local::now();
+    Ok(active_hours(app)?
+        .filter(|hours| !hours.contains(now.time()))
+        .map(|hours| hours.next_start(now)))
+}
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use chrono::{Local, Utc};
+use std::time::Duration;
+use tauri::image::Image;
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager, Runtime};
+use crate::database;
+use crate::file_ops;
+use crate::utils;
+
+// Setting that hides the window to the tray on close instead of quitting
+pub const CLOSE_TO_TRAY_SETTING: &str = "close_to_tray";
+
+const TRAY_ID: &str = "main";
+
+// How long "Pause watching" pauses for
+const PAUSE_DURATION: Duration = Duration::from_secs(60 * 60);
+
+// Activity changes with every organized file, so the menu is also refreshed
+// on a timer rather than after each move
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+// Menu item IDs
+const MENU_PAUSE: &str = "pause";
+const MENU_RESUME: &str = "resume";
+const MENU_ORGANIZE_DOWNLOADS: &str = "organize_downloads";
+const MENU_OPEN: &str = "open";
+const MENU_ACTIVITY: &str = "activity";
+const MENU_QUIT: &str = "quit";
+
+// Create the tray icon; called once from setup
+pub fn create_tray(app: &AppHandle) -> Result<()> {
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Smart File Organizer")
+        .menu(&build_menu(app)?)
+        .show_menu_on_left_click(false)
+        .on_menu_event(handle_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                show_main_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    refresh(app);
+
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            refresh(&app_handle);
+        }
+    });
+
+    Ok(())
+}
+
+// Rebuild the menu, tooltip and icon from the current watcher state
+pub fn refresh<R: Runtime>(app: &AppHandle<R>) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else { return };
+
+    let (paused, paused_until) = file_ops::pause_status(app);
+    let watching = file_ops::watched_paths(app).len();
+    let tooltip = if paused {
+        match paused_until {
+            Some(until) => format!(
+                "Smart File Organizer: paused until {}",
+                until.with_timezone(&Local).format("%H:%M")
+            ),
+            None => "Smart File Organizer: paused".to_string(),
+        }
+    } else if watching > 0 {
+        format!("Smart File Organizer: watching {} folder(s)", watching)
+    } else {
+        "Smart File Organizer: not watching".to_string()
+    };
+
+    let result = build_menu(app).and_then(|menu| {
+        tray.set_menu(Some(menu))?;
+        tray.set_tooltip(Some(tooltip))?;
+        if let Some(icon) = app.default_window_icon() {
+            tray.set_icon(Some(if paused { grayscale(icon) } else { icon.clone() }))?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        tracing::warn!(error = format!("{:#}", e), "Failed to update tray");
+    }
+}
+
+fn build_menu<R: Runtime>(app: &AppHandle<R>) -> Result<Menu<R>> {
+    let (paused, _) = file_ops::pause_status(app);
+    let pause_item = if paused {
+        MenuItem::with_id(app, MENU_RESUME, "Resume watching", true, None::<&str>)?
+    } else {
+        let watching = !file_ops::watched_paths(app).is_empty();
+        MenuItem::with_id(app, MENU_PAUSE, "Pause watching for 1 hour", watching, None::<&str>)?
+    };
+
+    let since = utils::format_timestamp(&(Utc::now() - chrono::Duration::hours(24)));
+    let activity = match database::count_file_events_since(app, &since) {
+        Ok(count) => format!("{} actions in the last 24 hours", count),
+        Err(_) => "Recent activity unavailable".to_string(),
+    };
+
+    let menu = Menu::with_items(app, &[
+        &pause_item,
+        &MenuItem::with_id(app, MENU_ORGANIZE_DOWNLOADS, "Organize Downloads now", dirs::download_dir().is_some(), None::<&str>)?,
+        &PredefinedMenuItem::separator(app)?,
+        &MenuItem::with_id(app, MENU_ACTIVITY, activity, false, None::<&str>)?,
+        &PredefinedMenuItem::separator(app)?,
+        &MenuItem::with_id(app, MENU_OPEN, "Open Smart File Organizer", true, None::<&str>)?,
+        &MenuItem::with_id(app, MENU_QUIT, "Quit", true, None::<&str>)?,
+    ])?;
+    Ok(menu)
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id.as_ref() {
+        MENU_PAUSE => {
+            if let Err(e) = file_ops::pause_watching(app, Some(PAUSE_DURATION)) {
+                tracing::error!(error = format!("{:#}", e), "Failed to pause watching");
+            }
+        }
+        MENU_RESUME => {
+            let app_handle = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = file_ops::resume_watching(&app_handle).await {
+                    tracing::error!(error = format!("{:#}", e), "Failed to resume watching");
+                }
+            });
+        }
+        MENU_ORGANIZE_DOWNLOADS => {
+            let Some(downloads) = dirs::download_dir() else { return };
+            let downloads = downloads.to_string_lossy().to_string();
+            let app_handle = app.clone();
+            tauri::async_runtime::spawn(async move {
+                match file_ops::organize_everything(&app_handle, downloads.clone(), downloads).await {
+                    Ok(summary) => {
+                        for error in &summary.errors {
+                            tracing::warn!(error = %error, "Failed to organize file from Downloads");
+                        }
+                    }
+                    Err(e) => tracing::error!(error = format!("{:#}", e), "Failed to organize Downloads"),
+                }
+                refresh(&app_handle);
+            });
+        }
+        MENU_OPEN => show_main_window(app),
+        MENU_QUIT => app.exit(0),
+        _ => {}
+    }
+}
+
+// Bring the main window back from the tray
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+// Whether closing the main window should hide it to the tray
+pub fn close_to_tray(app: &AppHandle) -> bool {
+    database::is_setting_enabled(app, CLOSE_TO_TRAY_SETTING).unwrap_or(false)
+}
+
+// Greyed out copy of the app icon, shown while watching is paused
+fn grayscale(icon: &Image<'_>) -> Image<'static> {
+    let mut rgba = icon.rgba().to_vec();
+    for pixel in rgba.chunks_exact_mut(4) {
+        let luma = (pixel[0] as u32 * 30 + pixel[1] as u32 * 59 + pixel[2] as u32 * 11) / 100;
+        pixel[0] = luma as u8;
+        pixel[1] = luma as u8;
+        pixel[2] = luma as u8;
+        pixel[3] /= 2;
+    }
+    Image::new_owned(rgba, icon.width(), icon.height())
+}
@@ -1,35 +1,529 @@
 use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use regex::{Regex, RegexBuilder};
+use sha2::{Digest, Sha256};
+use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 use std::fs;
+use std::io::{Read, Write};
+use crate::commands::Category;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use unicode_normalization::UnicodeNormalization;
+
+// The name helpers below convert lossily, so a name that isn't valid UTF-8
+// still has a (display) name instead of none. Build paths for file system
+// operations from the Path itself, never from these strings
 
 // Get the file extension from a path
 pub fn get_file_extension(path: &Path) -> Option<String> {
     path.extension()
-        .and_then(|ext| ext.to_str())
-        .map(|s| s.to_lowercase())
+        .map(|ext| ext.to_string_lossy().to_lowercase())
 }
 
 // Get the file name without extension
 pub fn get_file_stem(path: &Path) -> Option<String> {
     path.file_stem()
-        .and_then(|s| s.to_str())
-        .map(|s| s.to_string())
+        .map(|s| s.to_string_lossy().to_string())
 }
 
 // Get the file name with extension
 pub fn get_file_name(path: &Path) -> Option<String> {
     path.file_name()
-        .and_then(|s| s.to_str())
-        .map(|s| s.to_string())
+        .map(|s| s.to_string_lossy().to_string())
+}
+
+// Whether a file or folder is hidden. Windows marks hidden files with an
+// attribute rather than a leading dot
+#[cfg(windows)]
+pub fn is_hidden(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    
+    fs::symlink_metadata(path)
+        .is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+}
+
+#[cfg(not(windows))]
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+// Whether a file, or any folder between root and it, is hidden
+pub fn is_hidden_under(root: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else { return is_hidden(path) };
+    let mut current = root.to_path_buf();
+    relative.components().any(|component| {
+        current.push(component);
+        is_hidden(&current)
+    })
+}
+
+// Name for the nth numbered copy of a file, e.g. "report_1.pdf" for
+// "report.pdf". Built from the OS string so undecodable names keep their bytes
+pub fn numbered_file_name(file_path: &Path, n: u32) -> OsString {
+    let mut name = file_path
+        .file_stem()
+        .map(|stem| stem.to_os_string())
+        .unwrap_or_default();
+    name.push(format!("_{}", n));
+    if let Some(extension) = file_path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    name
+}
+
+// Names Windows reserves for devices, whatever the extension: "nul.txt"
+// opens the null device instead of creating a file. The superscript digits
+// count as port numbers too
+const RESERVED_WINDOWS_NAMES: [&str; 32] = [
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$",
+    "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "COM¹", "COM²", "COM³",
+    "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+// Whether Windows treats a file name as a device; only the part before the
+// first dot counts, and trailing spaces are ignored
+pub fn is_reserved_windows_name(file_name: &str) -> bool {
+    let base = file_name.split('.').next().unwrap_or_default().trim_end_matches(' ');
+    RESERVED_WINDOWS_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(base))
+}
+
+// A file name that can be created on this platform: on Windows a reserved
+// device name gets an underscore, e.g. "CON.txt" becomes "CON_.txt"
+pub fn safe_file_name(file_name: &OsStr) -> OsString {
+    if !cfg!(windows) {
+        return file_name.to_os_string();
+    }
+    let name = file_name.to_string_lossy();
+    if !is_reserved_windows_name(&name) {
+        return file_name.to_os_string();
+    }
+    let mut parts = name.splitn(2, '.');
+    let base = parts.next().unwrap_or_default().trim_end_matches(' ');
+    match parts.next() {
+        Some(rest) => OsString::from(format!("{}_.{}", base, rest)),
+        None => OsString::from(format!("{}_", base)),
+    }
+}
+
+// Longest path the classic Windows APIs accept, including the terminating NUL
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+// The path to hand to file system calls. On Windows an absolute path longer
+// than MAX_PATH gets the \\?\ prefix, which lifts the limit; it's stripped
+// again by normalize_path, so it never reaches the database. So does a path
+// to a file with a reserved name, which only opens the device without it
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> PathBuf {
+    let s = path.to_string_lossy();
+    let reserved = path
+        .file_name()
+        .is_some_and(|name| is_reserved_windows_name(&name.to_string_lossy()));
+    if (s.encode_utf16().count() < MAX_PATH && !reserved) || !path.is_absolute() || s.starts_with(r"\\?\") {
+        return path.to_path_buf();
+    }
+    // The prefix turns off parsing of "/", "." and "..", so rebuild the path
+    // from its components first
+    let path: PathBuf = path.components().collect();
+    let s = path.to_string_lossy();
+    match s.strip_prefix(r"\\") {
+        Some(unc) => PathBuf::from(format!(r"\\?\UNC\{}", unc)),
+        None => PathBuf::from(format!(r"\\?\{}", s)),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
 }
 
 // Get the file size in bytes
 pub fn get_file_size(path: &Path) -> Result<u64> {
-    let metadata = fs::metadata(path)?;
+    let metadata = fs::metadata(long_path(path))?;
     Ok(metadata.len())
 }
 
+// Recursively collect all files in a directory, without following symlinks.
+// max_depth limits how many folder levels below dir are entered (0 is just
+// dir itself); None means no limit
+pub fn collect_files(dir: &Path, max_depth: Option<u32>, include_hidden: bool) -> Result<Vec<PathBuf>> {
+    collect_files_cancellable(dir, max_depth, include_hidden, &AtomicBool::new(false))
+}
+
+// Same as collect_files, but gives up as soon as `cancel` is set
+pub fn collect_files_cancellable(
+    dir: &Path,
+    max_depth: Option<u32>,
+    include_hidden: bool,
+    cancel: &AtomicBool,
+) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    
+    if dir.is_dir() {
+        for entry in fs::read_dir(dir)? {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(anyhow::anyhow!("Operation cancelled"));
+            }
+            
+            let entry = entry?;
+            let path = entry.path();
+            
+            // file_type() doesn't follow symlinks, so linked trees aren't counted twice
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                continue;
+            }
+            
+            // Hidden folders are skipped whole, along with everything in them
+            if !include_hidden && is_hidden(&path) {
+                continue;
+            }
+            
+            if file_type.is_dir() {
+                if max_depth != Some(0) {
+                    let max_depth = max_depth.map(|depth| depth - 1);
+                    files.extend(collect_files_cancellable(&path, max_depth, include_hidden, cancel)?);
+                }
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    
+    Ok(files)
+}
+
+// Number of folder levels between root and a file under it, e.g. 1 for
+// root/sub/file.txt; None if the file isn't under root
+pub fn folder_depth(root: &Path, file_path: &Path) -> Option<usize> {
+    let relative = file_path.parent()?.strip_prefix(root).ok()?;
+    Some(relative.components().count())
+}
+
+// Whether a file is deeper below root than max_depth folder levels, the files
+// collect_files leaves out
+pub fn is_beyond_depth(root: &Path, file_path: &Path, max_depth: Option<u32>) -> bool {
+    max_depth.is_some_and(|max_depth| folder_depth(root, file_path).is_some_and(|depth| depth > max_depth as usize))
+}
+
+// Call `visit` for every file under a directory as it is found, without
+// collecting the whole tree first; symlinks are not followed
+pub fn visit_files(dir: &Path, visit: &mut dyn FnMut(&Path) -> Result<()>) -> Result<()> {
+    if dir.is_dir() {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                continue;
+            }
+            
+            if file_type.is_dir() {
+                visit_files(&path, visit)?;
+            } else {
+                visit(&path)?;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+// Get the total size in bytes of all files under a directory
+pub fn get_folder_size(dir: &Path, cancel: &AtomicBool) -> Result<u64> {
+    let mut total = 0;
+    
+    for file in collect_files_cancellable(dir, None, true, cancel)? {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("Operation cancelled"));
+        }
+        
+        // Files can vanish mid-scan; skip them rather than failing the total
+        total += get_file_size(&file).unwrap_or(0);
+    }
+    
+    Ok(total)
+}
+
+// Compute the SHA-256 hash of a file's contents as a hex string
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(long_path(path))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    
+    Ok(hex_digest(hasher))
+}
+
+fn hex_digest(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Formats that are already compressed and gain nothing from another pass
+const COMPRESSED_EXTENSIONS: [&str; 30] = [
+    "jpg", "jpeg", "png", "gif", "webp", "heic", "avif",
+    "mp4", "mov", "mkv", "avi", "webm", "wmv",
+    "mp3", "aac", "ogg", "flac", "m4a", "opus",
+    "zip", "rar", "7z", "gz", "bz2", "xz", "zst",
+    "docx", "xlsx", "pptx", "pdf",
+];
+
+// Smaller files aren't worth compressing
+const MIN_COMPRESS_SIZE: u64 = 1024;
+
+// Check if a file is worth compressing before upload
+pub fn is_compressible(path: &Path) -> bool {
+    let extension = get_file_extension(path).unwrap_or_default();
+    !COMPRESSED_EXTENSIONS.contains(&extension.as_str())
+        && get_file_size(path).map(|size| size >= MIN_COMPRESS_SIZE).unwrap_or(false)
+}
+
+// Gzip a file into destination, returning the SHA-256 of the original
+pub fn gzip_file(source: &Path, destination: &Path) -> Result<String> {
+    let mut input = fs::File::open(long_path(source))?;
+    let output = std::io::BufWriter::new(fs::File::create(destination)?);
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    let mut hasher = Sha256::new();
+    
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = input.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        encoder.write_all(&buffer[..read])?;
+    }
+    encoder.finish()?.flush()?;
+    
+    Ok(hex_digest(hasher))
+}
+
+// Unpack a gzipped file into destination, failing if the result doesn't match expected_sha256
+pub fn gunzip_file(source: &Path, destination: &Path, expected_sha256: Option<&str>) -> Result<()> {
+    let mut decoder = GzDecoder::new(std::io::BufReader::new(fs::File::open(source)?));
+    let mut output = std::io::BufWriter::new(fs::File::create(destination)?);
+    let mut hasher = Sha256::new();
+    
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = decoder.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        output.write_all(&buffer[..read])?;
+    }
+    output.flush()?;
+    
+    if let Some(expected) = expected_sha256 {
+        let actual = hex_digest(hasher);
+        if !actual.eq_ignore_ascii_case(expected) {
+            let _ = fs::remove_file(destination);
+            return Err(anyhow::anyhow!(
+                "Checksum mismatch after decompressing {}: expected {}, got {}",
+                destination.display(),
+                expected,
+                actual
+            ));
+        }
+    }
+    
+    Ok(())
+}
+
+// Check if two paths refer to the same file on disk
+#[cfg(unix)]
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+// Windows file systems are case-insensitive, so compare resolved paths that way
+#[cfg(not(unix))]
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase(),
+        _ => false,
+    }
+}
+
+// Check if two paths are hard links to one file: the same device and inode,
+// or on Windows the same volume serial number and file index
+pub fn is_hard_link_of(a: &Path, b: &Path) -> bool {
+    same_file::is_same_file(a, b).unwrap_or(false)
+}
+
+// Space left for unprivileged writes on the volume holding a path
+#[cfg(unix)]
+pub fn free_disk_space(path: &Path) -> Option<u64> {
+    let stat = rustix::fs::statvfs(path).ok()?;
+    Some(stat.f_bavail.saturating_mul(stat.f_frsize))
+}
+
+#[cfg(windows)]
+pub fn free_disk_space(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory_name: *const u16,
+            free_bytes_available_to_caller: *mut u64,
+            total_number_of_bytes: *mut u64,
+            total_number_of_free_bytes: *mut u64,
+        ) -> i32;
+    }
+    
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut free = 0u64;
+    // SAFETY: wide is NUL-terminated and free a writable u64; the totals may be null
+    let ok = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut free, std::ptr::null_mut(), std::ptr::null_mut()) };
+    (ok != 0).then_some(free)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn free_disk_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+// Where the drive holding a path is mounted, e.g. "D:\" or "/mnt/data". A path
+// that doesn't exist yet counts as being on the drive of its nearest existing
+// folder
+#[cfg(unix)]
+pub fn drive_root(path: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+    let mut ancestors = path.ancestors().filter_map(|ancestor| Some((ancestor, fs::metadata(ancestor).ok()?)));
+    let (mut root, metadata) = ancestors.next()?;
+    let device = metadata.dev();
+    for (ancestor, metadata) in ancestors {
+        if metadata.dev() != device {
+            break;
+        }
+        root = ancestor;
+    }
+    Some(root.to_path_buf())
+}
+
+// The drive letter or UNC share a path starts with
+#[cfg(not(unix))]
+pub fn drive_root(path: &Path) -> Option<PathBuf> {
+    match path.components().next()? {
+        std::path::Component::Prefix(prefix) => Some(PathBuf::from(prefix.as_os_str()).join("\\")),
+        _ => None,
+    }
+}
+
+// Whether the drive or share a path is on can be reached. A drive letter or
+// share that's gone is unavailable on Windows
+#[cfg(windows)]
+pub fn is_volume_available(path: &Path) -> bool {
+    drive_root(path).is_none_or(|root| root.try_exists().unwrap_or(false))
+}
+
+// Elsewhere a drive that isn't mounted leaves an empty mount point, or none
+// at all under a folder holding mount points (/Volumes, /media, /mnt): the
+// path's nearest folder that exists is one of those
+#[cfg(not(windows))]
+pub fn is_volume_available(path: &Path) -> bool {
+    const MOUNT_FOLDERS: [&str; 4] = ["/Volumes", "/media", "/mnt", "/run/media"];
+    let Some(parent) = path.parent() else { return true };
+    let Some(existing) = parent.ancestors().find(|ancestor| ancestor.try_exists().unwrap_or(false)) else {
+        return false;
+    };
+    if existing == parent {
+        return true;
+    }
+    let in_mount_folder = MOUNT_FOLDERS
+        .iter()
+        .any(|folder| existing.starts_with(folder) && existing.components().count() <= Path::new(folder).components().count() + 1);
+    let is_empty = fs::read_dir(existing).is_ok_and(|mut entries| entries.next().is_none());
+    !in_mount_folder && !is_empty
+}
+
+// Normalize a path before it's stored or looked up, so one file always maps
+// to one row: resolved to its real location when it exists (which also fixes
+// the case of each component on Windows), with consistent separators, and on
+// macOS in composed (NFC) unicode, as names there may arrive decomposed
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let resolved = match fs::canonicalize(path) {
+        Ok(canonical) => strip_verbatim_prefix(canonical),
+        // Rebuilding from components drops "." and doubled separators, and
+        // turns "/" into "\" on Windows
+        Err(_) => path.components().collect(),
+    };
+    normalize_unicode(resolved)
+}
+
+// Key for comparing normalized paths; Windows and macOS file systems ignore case
+pub fn path_key(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(any(windows, target_os = "macos")) {
+        path.to_lowercase()
+    } else {
+        path.into_owned()
+    }
+}
+
+// Normalize text searched for in stored paths the way the paths themselves were
+pub fn normalize_path_query(query: &str) -> String {
+    let query = if cfg!(windows) { query.replace('/', "\\") } else { query.to_string() };
+    if cfg!(target_os = "macos") {
+        query.nfc().collect()
+    } else {
+        query
+    }
+}
+
+// canonicalize returns \\?\C:\... on Windows, which nothing else produces
+#[cfg(windows)]
+fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+    let stripped = {
+        let s = path.to_string_lossy();
+        if let Some(rest) = s.strip_prefix(r"\\?\UNC\") {
+            Some(PathBuf::from(format!(r"\\{}", rest)))
+        } else {
+            s.strip_prefix(r"\\?\").map(PathBuf::from)
+        }
+    };
+    stripped.unwrap_or(path)
+}
+
+#[cfg(not(windows))]
+fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+    path
+}
+
+// Other file systems compare names byte for byte, so a composed name would
+// no longer find a decomposed file there
+#[cfg(target_os = "macos")]
+fn normalize_unicode(path: PathBuf) -> PathBuf {
+    match path.to_str() {
+        Some(s) if !unicode_normalization::is_nfc(s) => PathBuf::from(s.nfc().collect::<String>()),
+        _ => path,
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn normalize_unicode(path: PathBuf) -> PathBuf {
+    path
+}
+
 // Check if a path is a directory
 pub fn is_directory(path: &Path) -> bool {
     path.is_dir()
@@ -42,8 +536,9 @@ pub fn is_file(path: &Path) -> bool {
 
 // Create directory if it doesn't exist
 pub fn ensure_dir_exists(path: &Path) -> Result<()> {
+    let path = long_path(path);
     if !path.exists() {
-        fs::create_dir_all(path)?;
+        fs::create_dir_all(&path)?;
     }
     Ok(())
 }
@@ -112,43 +607,355 @@ pub fn group_files_by_extension(files: &[PathBuf]) -> HashMap<String, Vec<PathBu
     groups
 }
 
-// Categorize files by type
-pub fn categorize_files(files: &[PathBuf]) -> HashMap<String, Vec<PathBuf>> {
-    let mut categories: HashMap<String, Vec<PathBuf>> = HashMap::new();
+// Categorize files by type using the category mapping
+pub fn categorize_files(files: &[PathBuf], categories: &[Category]) -> HashMap<String, Vec<PathBuf>> {
+    let mut groups: HashMap<String, Vec<PathBuf>> = HashMap::new();
     
     for file in files {
-        if let Some(ext) = get_file_extension(file) {
-            let category = match ext.as_str() {
-                // Documents
-                "pdf" | "doc" | "docx" | "txt" | "rtf" | "odt" | "xls" | "xlsx" | "ppt" | "pptx" => "Documents",
-                
-                // Images
-                "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "svg" => "Images",
-                
-                // Videos
-                "mp4" | "avi" | "mov" | "wmv" | "mkv" | "webm" => "Videos",
-                
-                // Audio
-                "mp3" | "wav" | "flac" | "ogg" | "aac" => "Music",
-                
-                // Archives
-                "zip" | "rar" | "7z" | "tar" | "gz" => "Archives",
-                
-                // Code
-                "js" | "ts" | "html" | "css" | "rs" | "py" | "java" | "cpp" | "c" | "h" => "Code",
-                
-                // Other
-                _ => "Other",
-            };
-            
-            categories.entry(category.to_string()).or_default().push(file.clone());
+        let category = get_file_extension(file)
+            .and_then(|ext| categories.iter().find(|c| c.extensions.contains(&ext)))
+            .map(|c| c.name.as_str())
+            // Files without extension or with an unknown one
+            .unwrap_or("Other");
+        
+        groups.entry(category.to_string()).or_default().push(file.clone());
+    }
+    
+    groups
+}
+
+// Claim an unused path in dest_dir for the file, numbering the name when it's
+// taken. The path is claimed by creating an empty placeholder with create_new,
+// which is atomic, so two same-named files moved at once never get the same path.
+// A name Windows reserves for a device is renamed (see safe_file_name)
+pub fn reserve_path(dest_dir: &Path, file_path: &Path) -> Result<PathBuf> {
+    let file_name = safe_file_name(
+        file_path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid file path"))?,
+    );
+    
+    let mut candidate = dest_dir.join(&file_name);
+    let mut n = 1;
+    loop {
+        match fs::OpenOptions::new().write(true).create_new(true).open(long_path(&candidate)) {
+            Ok(_) => return Ok(candidate),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+        
+        candidate = dest_dir.join(numbered_file_name(Path::new(&file_name), n));
+        n += 1;
+    }
+}
+
+// Raw OS errors Windows reports for a file another program has open
+#[cfg(windows)]
+const ERROR_SHARING_VIOLATION: i32 = 32;
+#[cfg(windows)]
+const ERROR_LOCK_VIOLATION: i32 = 33;
+
+// How long to wait before each retry of a move blocked by another program
+const LOCKED_RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+];
+
+// What a move is about to do, told to its before_move callback: the
+// destination has just been claimed, or the original is about to be copied
+// there because it's on another drive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveStep {
+    Claimed,
+    Copying,
+}
+
+// A file left where it was because it can't be moved right now
+#[derive(Debug, thiserror::Error)]
+pub enum MoveError {
+    #[error("{} is in use by another program", .0.display())]
+    Locked(PathBuf),
+    #[error("{} is read-only", .0.display())]
+    ReadOnly(PathBuf),
+}
+
+// Move a file into dest_dir under a freshly reserved name, returning the new
+// path. A file another program has open is retried a few times before giving
+// up with MoveError::Locked. A read-only file that can't be removed after
+// being copied to another drive gets its read-only flag cleared when
+// clear_read_only is set, and fails with MoveError::ReadOnly otherwise. The
+// retries sleep, so async code calls this through spawn_blocking
+pub fn move_to_dir(file_path: &Path, dest_dir: &Path, clear_read_only: bool) -> Result<PathBuf> {
+    move_to_dir_with(file_path, dest_dir, clear_read_only, |_, _| Ok(()))
+}
+
+// move_to_dir, calling before_move with the reserved destination ahead of each
+// attempt and again before a copy to another drive, e.g. to record the move in
+// a journal. An error from it leaves the file where it is
+pub fn move_to_dir_with(
+    file_path: &Path,
+    dest_dir: &Path,
+    clear_read_only: bool,
+    mut before_move: impl FnMut(&Path, MoveStep) -> Result<()>,
+) -> Result<PathBuf> {
+    let mut delays = LOCKED_RETRY_DELAYS.iter();
+    loop {
+        let e = match try_move_to_dir(file_path, dest_dir, clear_read_only, &mut before_move) {
+            Ok(new_path) => return Ok(new_path),
+            Err(e) => e,
+        };
+        let Some(io_error) = e.downcast_ref::<std::io::Error>() else { return Err(e) };
+        
+        if is_locked(io_error) {
+            match delays.next() {
+                Some(delay) => std::thread::sleep(*delay),
+                None => return Err(MoveError::Locked(file_path.to_path_buf()).into()),
+            }
+        } else if io_error.kind() == std::io::ErrorKind::PermissionDenied && is_read_only(&long_path(file_path)) {
+            return Err(MoveError::ReadOnly(file_path.to_path_buf()).into());
         } else {
-            // Files without extension
-            categories.entry("Other".to_string()).or_default().push(file.clone());
+            return Err(e);
+        }
+    }
+}
+
+fn try_move_to_dir(
+    file_path: &Path,
+    dest_dir: &Path,
+    clear_read_only: bool,
+    before_move: &mut impl FnMut(&Path, MoveStep) -> Result<()>,
+) -> Result<PathBuf> {
+    let new_path = reserve_path(dest_dir, file_path)?;
+    // The returned path stays in its usual form; only the file system calls
+    // below need the long form
+    let (source, target) = (long_path(file_path), long_path(&new_path));
+    if let Err(e) = before_move(&new_path, MoveStep::Claimed) {
+        let _ = fs::remove_file(&target);
+        return Err(e);
+    }
+    
+    // Renaming replaces the placeholder; fall back to copy + delete only when
+    // the target is on another drive, so a locked or read-only file is
+    // reported as such rather than copied. The source is only deleted once
+    // the copy is verified
+    let result = match fs::rename(&source, &target) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() != std::io::ErrorKind::CrossesDevices => Err(e.into()),
+        Err(_) => before_move(&new_path, MoveStep::Copying).and_then(|_| {
+            copy_verified(&source, &target)?;
+            Ok(remove_source(&source, clear_read_only)?)
+        }),
+    };
+    
+    if let Err(e) = result {
+        // The source is still in place, so drop the placeholder or partial
+        // copy. A copy of a read-only file is read-only too, which Windows
+        // won't delete
+        if fs::remove_file(&target).is_err() {
+            let _ = set_writable(&target);
+            let _ = fs::remove_file(&target);
+        }
+        return Err(e);
+    }
+    
+    Ok(new_path)
+}
+
+// Whether another program has the file open. Only Windows locks open files;
+// elsewhere they can be moved anyway
+fn is_locked(e: &std::io::Error) -> bool {
+    #[cfg(windows)]
+    {
+        matches!(e.raw_os_error(), Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION))
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = e;
+        false
+    }
+}
+
+fn is_read_only(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().readonly())
+}
+
+// Delete the source of a copied file. Windows refuses to delete read-only
+// files, so the flag is cleared first when that's allowed
+fn remove_source(path: &Path, clear_read_only: bool) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied && clear_read_only && is_read_only(path) => {
+            set_writable(path)?;
+            fs::remove_file(path)
+        }
+        result => result,
+    }
+}
+
+// Clear a file's read-only flag; on unix only the owner gets write access back
+fn set_writable(path: &Path) -> std::io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(false);
+    fs::set_permissions(path, permissions)
+}
+
+// Move a folder with everything in it into dest_dir, returning its new path.
+// A folder of that name already there is never merged into: the moved folder
+// gets a numbered name instead ("Trip_1"). The name is claimed with an empty
+// folder, which create_dir makes atomically, and before_move is called with
+// it ahead of the move. Across drives the whole tree is copied and verified
+// before the original is removed, with before_move called again first
+pub fn move_dir_with(
+    dir: &Path,
+    dest_dir: &Path,
+    mut before_move: impl FnMut(&Path, MoveStep) -> Result<()>,
+) -> Result<PathBuf> {
+    let name = safe_file_name(dir.file_name().ok_or_else(|| anyhow::anyhow!("Invalid folder path"))?);
+    let mut candidate = dest_dir.join(&name);
+    let mut n = 1;
+    loop {
+        match fs::create_dir(long_path(&candidate)) {
+            Ok(()) => break,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+        let mut numbered = name.clone();
+        numbered.push(format!("_{}", n));
+        candidate = dest_dir.join(numbered);
+        n += 1;
+    }
+    
+    let (source, target) = (long_path(dir), long_path(&candidate));
+    if let Err(e) = before_move(&candidate, MoveStep::Claimed) {
+        let _ = fs::remove_dir(&target);
+        return Err(e);
+    }
+    
+    // Renaming onto the empty claim replaces it atomically. Windows won't
+    // rename onto an existing folder, so there the claim is given up just
+    // before the rename
+    #[cfg(windows)]
+    fs::remove_dir(&target)?;
+    match fs::rename(&source, &target) {
+        Ok(()) => return Ok(candidate),
+        Err(e) if e.kind() != std::io::ErrorKind::CrossesDevices => {
+            let _ = fs::remove_dir(&target);
+            return Err(e.into());
         }
+        Err(_) => {}
     }
     
-    categories
+    before_move(&candidate, MoveStep::Copying)?;
+    if let Err(e) = copy_dir_verified(&source, &target) {
+        let _ = fs::remove_dir_all(&target);
+        return Err(e.into());
+    }
+    // Everything is at the destination by now, so a file that can't be
+    // removed (e.g. one held open) leaves a duplicate rather than losing data.
+    // The folder rules leave such a leftover alone and retry removing it
+    if let Err(e) = fs::remove_dir_all(&source) {
+        tracing::warn!(folder = %dir.display(), error = %e, "Failed to remove the original of a copied folder");
+    }
+    Ok(candidate)
+}
+
+// Copy a folder tree, verifying each file. Symbolic links are copied as links
+// where the platform allows it
+fn copy_dir_verified(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let (source, target) = (entry.path(), to.join(entry.file_name()));
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_dir_verified(&source, &target)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&source, &target)?;
+        } else {
+            copy_verified(&source, &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, _to: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::other(format!("Can't copy the link {}", from.display())))
+}
+
+// Whether part holds the start of whole's contents, e.g. a copy of whole that
+// was cut short. A complete copy counts too
+pub fn is_prefix_of(part: &Path, whole: &Path) -> std::io::Result<bool> {
+    let (mut part, whole) = (fs::File::open(part)?, fs::File::open(whole)?);
+    let part_len = part.metadata()?.len();
+    if part_len > whole.metadata()?.len() {
+        return Ok(false);
+    }
+    let mut whole = whole.take(part_len);
+    let (mut a, mut b) = (vec![0u8; 64 * 1024], vec![0u8; 64 * 1024]);
+    loop {
+        let read = part.read(&mut a)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        whole.read_exact(&mut b[..read])?;
+        if a[..read] != b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+// Copy a file and check the copy is complete and on disk
+fn copy_verified(from: &Path, to: &Path) -> std::io::Result<()> {
+    let expected = fs::metadata(from)?.len();
+    let copied = fs::copy(from, to)?;
+    sync_file(to)?;
+    let written = fs::metadata(to)?.len();
+    
+    if copied != expected || written != expected {
+        return Err(std::io::Error::other(format!(
+            "Copy of {} is incomplete ({} of {} bytes)",
+            from.display(),
+            written,
+            expected
+        )));
+    }
+    Ok(())
+}
+
+// Flush a file to disk. Unix syncs through any handle
+#[cfg(not(windows))]
+fn sync_file(path: &Path) -> std::io::Result<()> {
+    fs::File::open(path)?.sync_all()
+}
+
+// Windows only flushes through a handle opened for writing, which a read-only
+// file refuses until its attribute is cleared for the moment
+#[cfg(windows)]
+fn sync_file(path: &Path) -> std::io::Result<()> {
+    let permissions = fs::metadata(path)?.permissions();
+    if permissions.readonly() {
+        let mut writable = permissions.clone();
+        #[allow(clippy::permissions_set_readonly_false)]
+        writable.set_readonly(false);
+        fs::set_permissions(path, writable)?;
+    }
+    let synced = fs::OpenOptions::new().write(true).open(path).and_then(|file| file.sync_all());
+    if permissions.readonly() {
+        fs::set_permissions(path, permissions)?;
+    }
+    synced
 }
 
 // Format file size to human-readable string
@@ -169,4 +976,853 @@ pub fn format_file_size(size: u64) -> String {
     } else {
         format!("{:.2} TB", size as f64 / TB as f64)
     }
-} 
\ No newline at end of file
+} 
+// Color used for tags that don't carry one of their own
+pub const DEFAULT_TAG_COLOR: &str = "#9e9e9e";
+
+// Check a "#rgb" or "#rrggbb" color and return it as lowercase "#rrggbb"
+pub fn normalize_hex_color(color: &str) -> Result<String> {
+    let invalid = || anyhow::anyhow!("Invalid color {:?}, expected #rgb or #rrggbb", color);
+    
+    let hex = color.trim().strip_prefix('#').ok_or_else(invalid)?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    
+    let hex = hex.to_lowercase();
+    match hex.len() {
+        6 => Ok(format!("#{}", hex)),
+        3 => Ok(hex.chars().fold(String::from("#"), |mut out, c| {
+            out.push(c);
+            out.push(c);
+            out
+        })),
+        _ => Err(invalid()),
+    }
+}
+
+// Extended attribute holding Finder tags on macOS
+#[cfg(target_os = "macos")]
+const FINDER_TAGS_XATTR: &str = "com.apple.metadata:_kMDItemUserTags";
+
+// Extended attribute holding freedesktop tags on Linux and other unixes
+#[cfg(all(unix, not(target_os = "macos")))]
+const XDG_TAGS_XATTR: &str = "user.xdg.tags";
+
+// A tag read from the operating system's file metadata
+#[derive(Debug, Clone)]
+pub struct OsTag {
+    pub name: String,
+    pub color: Option<String>,
+}
+
+// Check if this platform can read and write OS-level file tags
+pub fn supports_os_tags() -> bool {
+    cfg!(unix)
+}
+
+// Check if OS-level file tags carry a color on this platform
+pub fn supports_os_tag_colors() -> bool {
+    cfg!(target_os = "macos")
+}
+
+// Map a Finder label index to a hex color
+#[cfg(target_os = "macos")]
+fn finder_label_color(index: u8) -> Option<&'static str> {
+    match index {
+        1 => Some("#8e8e93"), // Gray
+        2 => Some("#34c759"), // Green
+        3 => Some("#af52de"), // Purple
+        4 => Some("#007aff"), // Blue
+        5 => Some("#ffcc00"), // Yellow
+        6 => Some("#ff3b30"), // Red
+        7 => Some("#ff9500"), // Orange
+        _ => None,
+    }
+}
+
+// Read the tags the operating system has stored on a file
+#[cfg(target_os = "macos")]
+pub fn read_os_tags(path: &Path) -> Result<Vec<OsTag>> {
+    let data = match xattr::get(path, FINDER_TAGS_XATTR)? {
+        Some(data) => data,
+        None => return Ok(vec![]),
+    };
+
+    // Finder stores a binary plist array of "Name\nLabelIndex" strings
+    let entries: Vec<String> = plist::from_bytes(&data)?;
+
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            let mut parts = entry.splitn(2, '\n');
+            let name = parts.next()?.trim();
+            if name.is_empty() {
+                return None;
+            }
+
+            let color = parts
+                .next()
+                .and_then(|index| index.trim().parse::<u8>().ok())
+                .and_then(finder_label_color)
+                .map(|c| c.to_string());
+
+            Some(OsTag { name: name.to_string(), color })
+        })
+        .collect())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn read_os_tags(path: &Path) -> Result<Vec<OsTag>> {
+    let data = match xattr::get(path, XDG_TAGS_XATTR)? {
+        Some(data) => data,
+        None => return Ok(vec![]),
+    };
+
+    // xdg tags are a comma separated UTF-8 list
+    Ok(String::from_utf8_lossy(&data)
+        .split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(|name| OsTag { name: name.to_string(), color: None })
+        .collect())
+}
+
+#[cfg(not(unix))]
+pub fn read_os_tags(_path: &Path) -> Result<Vec<OsTag>> {
+    Ok(vec![])
+}
+
+// Replace the tags the operating system has stored on a file
+#[cfg(target_os = "macos")]
+pub fn write_os_tags(path: &Path, names: &[String]) -> Result<()> {
+    // Keep any label colors Finder already knows about for these names
+    let existing = read_os_tags(path).unwrap_or_default();
+    let entries: Vec<String> = names
+        .iter()
+        .map(|name| {
+            let label = existing
+                .iter()
+                .find(|t| &t.name == name)
+                .and_then(|t| t.color.as_deref())
+                .and_then(|color| (1..=7).find(|i| finder_label_color(*i) == Some(color)))
+                .unwrap_or(0);
+            format!("{}\n{}", name, label)
+        })
+        .collect();
+
+    let mut data = vec![];
+    plist::to_writer_binary(&mut data, &entries)?;
+    xattr::set(path, FINDER_TAGS_XATTR, &data)?;
+
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn write_os_tags(path: &Path, names: &[String]) -> Result<()> {
+    xattr::set(path, XDG_TAGS_XATTR, names.join(",").as_bytes())?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn write_os_tags(_path: &Path, _names: &[String]) -> Result<()> {
+    Ok(())
+}
+
+// Image formats that can be decoded for perceptual hashing
+pub const HASHABLE_IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "gif", "bmp", "webp"];
+
+// Compute a 64-bit perceptual hash (pHash) of an image
+pub fn perceptual_hash(path: &Path) -> Result<u64> {
+    // Work on a small grayscale copy; only the low frequencies of its DCT are kept
+    const SIZE: usize = 32;
+    const HASH_SIZE: usize = 8;
+    
+    let pixels: Vec<f64> = image::open(path)?
+        .resize_exact(SIZE as u32, SIZE as u32, image::imageops::FilterType::Triangle)
+        .to_luma8()
+        .pixels()
+        .map(|p| p.0[0] as f64)
+        .collect();
+    
+    // cos((2x + 1) * u * PI / 2N) for every frequency u and position x
+    let mut cosines = [[0f64; SIZE]; HASH_SIZE];
+    for (u, row) in cosines.iter_mut().enumerate() {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = (((2 * x + 1) * u) as f64 * std::f64::consts::PI / (2 * SIZE) as f64).cos();
+        }
+    }
+    
+    // 2D DCT-II, low frequency block only
+    let mut coefficients = [0f64; HASH_SIZE * HASH_SIZE];
+    for v in 0..HASH_SIZE {
+        for u in 0..HASH_SIZE {
+            let mut sum = 0.0;
+            for y in 0..SIZE {
+                for x in 0..SIZE {
+                    sum += pixels[y * SIZE + x] * cosines[u][x] * cosines[v][y];
+                }
+            }
+            coefficients[v * HASH_SIZE + u] = sum;
+        }
+    }
+    
+    // The DC term only reflects overall brightness, so leave it out of the median
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+    
+    let mut hash = 0u64;
+    for (i, coefficient) in coefficients.iter().enumerate() {
+        if *coefficient > median {
+            hash |= 1 << i;
+        }
+    }
+    
+    Ok(hash)
+}
+
+// Number of differing bits between two perceptual hashes
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// Filename prefixes used by screenshot tools, lowercased
+const SCREENSHOT_PREFIXES: [&str; 14] = [
+    "screenshot",           // macOS 10.14+, Windows, GNOME, KDE, Android
+    "screen shot",          // macOS before 10.14
+    "capture d'écran",      // French
+    "capture d’écran",      // French, as written by macOS
+    "bildschirmfoto",       // German
+    "captura de pantalla",  // Spanish
+    "schermata",            // Italian
+    "captura de tela",      // Portuguese
+    "schermafbeelding",     // Dutch
+    "zrzut ekranu",         // Polish
+    "снимок экрана",        // Russian
+    "スクリーンショット",     // Japanese
+    "屏幕截图",              // Chinese
+    "스크린샷",              // Korean
+];
+
+// Resolutions of common displays; a PNG of exactly this size is very likely a screenshot
+const DISPLAY_RESOLUTIONS: [(u32, u32); 14] = [
+    (1280, 800),
+    (1366, 768),
+    (1440, 900),
+    (1536, 864),
+    (1680, 1050),
+    (1920, 1080),
+    (1920, 1200),
+    (2560, 1440),
+    (2560, 1600),
+    (2880, 1800),
+    (3024, 1964),
+    (3456, 2234),
+    (3840, 2160),
+    (5120, 2880),
+];
+
+// Check if a file looks like a screenshot, by filename or by PNG dimensions
+pub fn is_screenshot(path: &Path, extra_prefixes: &[String]) -> bool {
+    let name = match get_file_name(path) {
+        Some(name) => name.to_lowercase(),
+        None => return false,
+    };
+    
+    let matches_prefix = SCREENSHOT_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+        || extra_prefixes
+            .iter()
+            .filter(|prefix| !prefix.is_empty())
+            .any(|prefix| name.starts_with(&prefix.to_lowercase()));
+    if matches_prefix {
+        return true;
+    }
+    
+    // Only the image header is read, not the whole file
+    get_file_extension(path).as_deref() == Some("png")
+        && image::image_dimensions(path)
+            .map(|dimensions| DISPLAY_RESOLUTIONS.contains(&dimensions))
+            .unwrap_or(false)
+}
+
+// Format a time the way the index stores it: RFC 3339 in UTC with whole
+// seconds, e.g. "2024-03-09T14:05:00Z", which also sorts correctly as text
+pub fn format_timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Turn a date filter bound into a stored timestamp to compare against. Takes
+// RFC 3339 with any offset, "YYYY-MM-DD HH:MM:SS" in UTC, or "YYYY-MM-DD" for
+// the start of that day (its last second when end_of_day is set)
+pub fn parse_timestamp_bound(value: &str, end_of_day: bool) -> Result<String> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(format_timestamp(&time.with_timezone(&Utc)));
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Ok(format_timestamp(&time.and_utc()));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let time = if end_of_day {
+            date.and_hms_opt(23, 59, 59)
+        } else {
+            date.and_hms_opt(0, 0, 0)
+        };
+        if let Some(time) = time {
+            return Ok(format_timestamp(&time.and_utc()));
+        }
+    }
+    Err(anyhow::anyhow!("Invalid date: {}", value))
+}
+
+// When a file was created, and whether that's an estimate. Many Linux file
+// systems don't record creation times; then the earliest of the inode change
+// and modification times is used instead of the time it was indexed
+pub fn file_created(metadata: &fs::Metadata) -> (DateTime<Utc>, bool) {
+    if let Ok(created) = metadata.created() {
+        return (created.into(), false);
+    }
+    
+    let modified: DateTime<Utc> = metadata
+        .modified()
+        .unwrap_or_else(|_| std::time::SystemTime::now())
+        .into();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Some(changed) = DateTime::from_timestamp(metadata.ctime(), metadata.ctime_nsec() as u32) {
+            return (changed.min(modified), true);
+        }
+    }
+    (modified, true)
+}
+
+// Symbolic folders default rules use as destinations, e.g. "$DOCUMENTS/{year}".
+// They resolve to the platform's own folders (xdg-user-dirs on Linux, and
+// localized names on Windows and macOS); the English name under the home
+// folder is only used when the platform reports none
+type PlatformDir = fn() -> Option<PathBuf>;
+const KNOWN_FOLDERS: [(&str, PlatformDir, &str); 5] = [
+    ("$DOCUMENTS", dirs::document_dir, "Documents"),
+    ("$PICTURES", dirs::picture_dir, "Pictures"),
+    ("$MUSIC", dirs::audio_dir, "Music"),
+    ("$VIDEOS", dirs::video_dir, "Videos"),
+    ("$DOWNLOADS", dirs::download_dir, "Downloads"),
+];
+
+// Resolve a destination starting with a symbolic folder, or with "~" for the
+// home folder, keeping the rest of it below that folder. None when the
+// destination doesn't start with one
+pub fn resolve_known_folder(destination: &str) -> Option<Result<PathBuf>> {
+    let (first, rest) = destination
+        .split_once(['/', '\\'])
+        .unwrap_or((destination, ""));
+    let folder = if first == "~" {
+        dirs::home_dir()
+    } else {
+        let (_, platform_dir, fallback) = KNOWN_FOLDERS.iter().find(|(name, _, _)| *name == first)?;
+        platform_dir().or_else(|| dirs::home_dir().map(|home| home.join(fallback)))
+    };
+
+    let folder = folder.ok_or_else(|| anyhow::anyhow!("Failed to get home directory"));
+    Some(folder.map(|folder| folder.join(rest.trim_start_matches(['/', '\\']))))
+}
+
+// Expand {year}, {month}, {day} and {parent} placeholders in a destination folder
+pub fn expand_destination_template(template: &str, date: &DateTime<Utc>, parent: &str) -> String {
+    template
+        .replace("{year}", &date.format("%Y").to_string())
+        .replace("{month}", &date.format("%m").to_string())
+        .replace("{day}", &date.format("%d").to_string())
+        .replace("{parent}", parent)
+}
+
+// Build a case-insensitive regex from a rule pattern. Plain patterns are
+// globs over the whole file name, where * matches any run of characters and
+// ? matches a single character.
+pub fn rule_pattern_regex(pattern: &str, is_regex: bool) -> Result<Regex> {
+    let source = if is_regex {
+        pattern.to_string()
+    } else {
+        let mut source = String::from("^");
+        for c in pattern.chars() {
+            match c {
+                '*' => source.push_str(".*"),
+                '?' => source.push('.'),
+                _ => source.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        source.push('$');
+        source
+    };
+    
+    RegexBuilder::new(&source)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| anyhow::anyhow!("Invalid pattern: {}", e))
+}
+
+// Check if a file name matches a rule pattern
+pub fn rule_matches(pattern: &str, is_extension: bool, is_regex: bool, file_name: &str) -> Result<bool> {
+    if is_extension {
+        // Extension rules hold a comma separated list such as "jpg,jpeg,png"
+        let extension = match get_file_extension(Path::new(file_name)) {
+            Some(extension) => extension,
+            None => return Ok(false),
+        };
+        return Ok(pattern
+            .split(',')
+            .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+            .any(|e| e == extension));
+    }
+    
+    Ok(rule_pattern_regex(pattern, is_regex)?.is_match(file_name))
+}
+
+// Extended attribute browsers use for the download source on macOS
+#[cfg(target_os = "macos")]
+const WHERE_FROMS_XATTR: &str = "com.apple.metadata:kMDItemWhereFroms";
+
+// Extended attribute browsers use for the download source on Linux
+#[cfg(all(unix, not(target_os = "macos")))]
+const ORIGIN_URL_XATTR: &str = "user.xdg.origin.url";
+
+// Read the URL a file was downloaded from, if the browser recorded it
+#[cfg(target_os = "macos")]
+pub fn read_source_url(path: &Path) -> Result<Option<String>> {
+    let data = match xattr::get(path, WHERE_FROMS_XATTR)? {
+        Some(data) => data,
+        None => return Ok(None),
+    };
+
+    // A binary plist array: the download URL, then the referring page
+    let urls: Vec<String> = plist::from_bytes(&data)?;
+    Ok(urls.into_iter().find(|url| !url.is_empty()))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn read_source_url(path: &Path) -> Result<Option<String>> {
+    Ok(xattr::get(path, ORIGIN_URL_XATTR)?
+        .map(|data| String::from_utf8_lossy(&data).trim().to_string())
+        .filter(|url| !url.is_empty()))
+}
+
+#[cfg(windows)]
+pub fn read_source_url(path: &Path) -> Result<Option<String>> {
+    // Browsers write a Zone.Identifier alternate data stream in INI format
+    let stream = format!("{}:Zone.Identifier", path.to_string_lossy());
+    let contents = match fs::read_to_string(stream) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(None),
+    };
+
+    let value = |key: &str| {
+        contents
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(key))
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty() && url != "about:internet")
+    };
+    Ok(value("HostUrl=").or_else(|| value("ReferrerUrl=")))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn read_source_url(_path: &Path) -> Result<Option<String>> {
+    Ok(None)
+}
+
+// Get the lowercased host name of a URL
+pub fn url_domain(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map(|(_, rest)| rest)?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    // Drop any user:password@ prefix and :port suffix
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?.trim_end_matches('.');
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
+
+// Check if a domain is one of a comma separated list of domains or a subdomain of one
+pub fn domain_matches(domain: &str, patterns: &str) -> bool {
+    let domain = domain.to_lowercase();
+    patterns
+        .split(',')
+        .map(|p| p.trim().trim_start_matches("*.").to_lowercase())
+        .filter(|p| !p.is_empty())
+        .any(|p| domain == p || domain.ends_with(&format!(".{}", p)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    // An empty directory of its own for a test, under the system temp folder
+    pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "organizer-test-{}-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Japanese, emoji, and accents written as combining characters (NFD)
+    pub(crate) const UNICODE_NAMES: [&str; 3] = ["議事録 2024.pdf", "🎉 party.jpg", "Re\u{301}sume\u{301}.docx"];
+
+    #[test]
+    fn unicode_names_survive_moves() {
+        let dir = scratch_dir("unicode-moves");
+        let dest_dir = dir.join("Sorted");
+        fs::create_dir(&dest_dir).unwrap();
+        for name in UNICODE_NAMES {
+            // A file of the same name is already there, so the move is numbered
+            fs::write(dest_dir.join(name), b"older").unwrap();
+            let file = dir.join(name);
+            fs::write(&file, b"newer").unwrap();
+
+            let moved = move_to_dir(&file, &dest_dir, false).unwrap();
+            let stem = get_file_stem(&file).unwrap();
+            let extension = get_file_extension(&file).unwrap();
+            assert_eq!(get_file_name(&moved).unwrap(), format!("{}_1.{}", stem, extension));
+            assert_eq!(fs::read(&moved).unwrap(), b"newer");
+            assert!(!file.exists());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn undecodable_names_keep_their_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = scratch_dir("undecodable-names");
+        let dest_dir = dir.join("Sorted");
+        fs::create_dir(&dest_dir).unwrap();
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.pdf");
+        fs::write(dest_dir.join(name), b"older").unwrap();
+        let file = dir.join(name);
+        fs::write(&file, b"newer").unwrap();
+
+        // Displayed lossily, but never nameless
+        assert_eq!(get_file_name(&file).unwrap(), "caf\u{FFFD}.pdf");
+        assert_eq!(get_file_extension(&file).unwrap(), "pdf");
+        assert!(!is_hidden(&file));
+
+        let moved = move_to_dir(&file, &dest_dir, false).unwrap();
+        assert_eq!(moved.file_name().unwrap().as_bytes(), b"caf\xe9_1.pdf");
+        assert_eq!(fs::read(&moved).unwrap(), b"newer");
+    }
+
+    #[test]
+    fn screenshots_are_told_by_name_or_display_size() {
+        let dir = scratch_dir("screenshots");
+        let screenshots = [
+            "Screenshot 2024-05-01 at 10.12.03.png",             // macOS
+            "Screen Shot 2018-11-02 at 9.41.15 AM.png",          // macOS before 10.14
+            "Capture d\u{2019}écran 2024-05-01 à 10.12.03.png", // macOS, French
+            "Bildschirmfoto 2024-05-01 um 10.12.03.png",         // macOS, German
+            "Screenshot (12).png",                               // Windows
+            "Screenshot 2024-05-01 101203.png",                  // Windows Snipping Tool
+            "Screenshot from 2024-05-01 10-12-03.png",           // GNOME
+            "Screenshot_20240501_101203.png",                    // KDE Spectacle
+            "Captura de pantalla 2024-05-01 101203.png",         // Windows, Spanish
+            "スクリーンショット 2024-05-01 10.12.03.png",           // macOS, Japanese
+        ];
+        for name in screenshots {
+            assert!(is_screenshot(&dir.join(name), &[]), "{}", name);
+        }
+        for name in ["IMG_0001.png", "holiday screenshots.zip", "report.pdf"] {
+            assert!(!is_screenshot(&dir.join(name), &[]), "{}", name);
+        }
+
+        // Other locales are added from settings
+        let extra = ["Skärmbild".to_string(), String::new()];
+        assert!(is_screenshot(&dir.join("Skärmbild 2024-05-01 101203.png"), &extra));
+        assert!(!is_screenshot(&dir.join("IMG_0001.png"), &extra));
+
+        // A PNG the size of a display, whatever its name
+        let capture = dir.join("untitled.png");
+        image::RgbImage::new(1366, 768).save(&capture).unwrap();
+        assert!(is_screenshot(&capture, &[]));
+        let photo = dir.join("photo.png");
+        image::RgbImage::new(1365, 768).save(&photo).unwrap();
+        assert!(!is_screenshot(&photo, &[]));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    // One key for every way a path can be written, whether or not it exists
+    fn keys_of(paths: &[&str]) -> std::collections::HashSet<String> {
+        paths.iter().map(|path| path_key(&normalize_path(Path::new(path)))).collect()
+    }
+
+    #[test]
+    fn paths_written_differently_share_a_key() {
+        let dir = scratch_dir("path-keys");
+        fs::write(dir.join("a.pdf"), b"a").unwrap();
+        let dir = dir.to_string_lossy();
+        assert_eq!(keys_of(&[&format!("{}/a.pdf", dir), &format!("{}//a.pdf", dir), &format!("{}/./a.pdf", dir)]).len(), 1);
+        assert_eq!(keys_of(&["/organizer-test/missing/a.pdf", "/organizer-test//missing/./a.pdf"]).len(), 1);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_paths_share_a_key_whatever_their_separators_and_case() {
+        let keys = keys_of(&[
+            r"C:\Users\me\Downloads\a.pdf",
+            "c:/users/me/downloads/a.pdf",
+            r"C:\Users\me/Downloads\\A.PDF",
+        ]);
+        assert_eq!(keys.len(), 1);
+        assert_eq!(normalize_path(Path::new("C:/organizer-test/a.pdf")), Path::new(r"C:\organizer-test\a.pdf"));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn decomposed_names_are_stored_composed() {
+        let normalized = normalize_path(Path::new("/organizer-test/Re\u{301}sume\u{301}.pdf"));
+        assert_eq!(normalized, Path::new("/organizer-test/R\u{e9}sum\u{e9}.pdf"));
+        assert_eq!(keys_of(&["/organizer-test/RÉSUMÉ.pdf", "/organizer-test/Re\u{301}sume\u{301}.pdf"]).len(), 1);
+    }
+
+    #[test]
+    fn nothing_below_the_depth_limit_is_collected_or_watched() {
+        let dir = scratch_dir("max-depth");
+        let mut folder = dir.clone();
+        let mut all = vec![];
+        for level in 0..5 {
+            let file = folder.join(format!("level{}.txt", level));
+            fs::write(&file, b"nested").unwrap();
+            all.push(file);
+            folder = folder.join(format!("sub{}", level));
+            fs::create_dir(&folder).unwrap();
+        }
+
+        for max_depth in [Some(0), Some(2), None] {
+            let mut collected = collect_files(&dir, max_depth, false).unwrap();
+            collected.sort();
+            let expected: Vec<PathBuf> = all
+                .iter()
+                .filter(|file| !is_beyond_depth(&dir, file, max_depth))
+                .cloned()
+                .collect();
+            assert_eq!(collected, expected, "max_depth {:?}", max_depth);
+            assert_eq!(expected.len(), max_depth.map_or(all.len(), |depth| depth as usize + 1));
+        }
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn destinations_in_the_home_folder_resolve_there() {
+        let date = DateTime::parse_from_rfc3339("2024-03-09T14:05:00Z").unwrap().with_timezone(&Utc);
+        let destination = expand_destination_template("~/Invoices/{year}", &date, "Downloads");
+        assert_eq!(destination, "~/Invoices/2024");
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(resolve_known_folder(&destination).unwrap().unwrap(), home.join("Invoices").join("2024"));
+        assert_eq!(resolve_known_folder("~").unwrap().unwrap(), home);
+        // Only a leading "~" folder is the home folder
+        assert!(resolve_known_folder("~backup/{year}").is_none());
+        assert!(resolve_known_folder("Invoices/~").is_none());
+    }
+
+    #[test]
+    fn copy_verified_copies_read_only_files() {
+        let dir = scratch_dir("copy");
+        let from = dir.join("report.pdf");
+        fs::write(&from, b"contents").unwrap();
+        let mut permissions = fs::metadata(&from).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&from, permissions).unwrap();
+
+        let to = dir.join("copy.pdf");
+        copy_verified(&from, &to).unwrap();
+        assert_eq!(fs::read(&to).unwrap(), b"contents");
+        assert!(fs::metadata(&to).unwrap().permissions().readonly());
+    }
+
+    #[test]
+    fn hard_links_are_told_from_copies() {
+        let dir = scratch_dir("hard-link");
+        let (file, link, copy) = (dir.join("a.pdf"), dir.join("link.pdf"), dir.join("copy.pdf"));
+        fs::write(&file, b"same contents").unwrap();
+        fs::hard_link(&file, &link).unwrap();
+        fs::copy(&file, &copy).unwrap();
+        // A copy with the same size and time is still a different file
+        let modified = fs::metadata(&file).unwrap().modified().unwrap();
+        fs::File::options().write(true).open(&copy).unwrap().set_modified(modified).unwrap();
+
+        assert!(is_hard_link_of(&file, &link));
+        assert!(!is_hard_link_of(&file, &copy));
+        assert!(!is_hard_link_of(&file, &dir.join("missing.pdf")));
+    }
+
+    #[test]
+    fn folders_are_moved_onto_their_claim() {
+        let dir = scratch_dir("move-dir");
+        let (folder, dest_dir) = (dir.join("Trip"), dir.join("Photos"));
+        fs::create_dir_all(folder.join("Day 1")).unwrap();
+        fs::write(folder.join("Day 1").join("a.jpg"), b"photo").unwrap();
+        fs::create_dir_all(dest_dir.join("Trip")).unwrap();
+
+        let mut claims = vec![];
+        let moved = move_dir_with(&folder, &dest_dir, |claim, step| {
+            assert!(claim.is_dir());
+            claims.push((claim.to_path_buf(), step));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(moved, dest_dir.join("Trip_1"));
+        assert_eq!(claims, [(moved.clone(), MoveStep::Claimed)]);
+        assert_eq!(fs::read(moved.join("Day 1").join("a.jpg")).unwrap(), b"photo");
+        assert!(!folder.exists());
+        // The folder already there was left as it was
+        assert_eq!(fs::read_dir(dest_dir.join("Trip")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn same_named_files_moved_at_once_all_keep_their_own_name() {
+        let dir = scratch_dir("same-names");
+        let dest_dir = dir.join("Photos");
+        fs::create_dir_all(&dest_dir).unwrap();
+        let sources: Vec<_> = (0..50)
+            .map(|i| {
+                let camera = dir.join(format!("Camera {}", i));
+                fs::create_dir_all(&camera).unwrap();
+                fs::write(camera.join("photo.jpg"), format!("photo {}", i)).unwrap();
+                camera.join("photo.jpg")
+            })
+            .collect();
+
+        let moved: Vec<PathBuf> = std::thread::scope(|scope| {
+            let moves: Vec<_> = sources
+                .iter()
+                .map(|source| scope.spawn(|| move_to_dir(source, &dest_dir, false).unwrap()))
+                .collect();
+            moves.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        let names: std::collections::HashSet<_> = moved.iter().collect();
+        assert_eq!(names.len(), 50);
+        let contents: std::collections::HashSet<_> = fs::read_dir(&dest_dir)
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect();
+        assert_eq!(contents, (0..50).map(|i| format!("photo {}", i)).collect());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn unmounted_drives_are_unavailable() {
+        let dir = scratch_dir("volume");
+        fs::write(dir.join("kept.txt"), b"").unwrap();
+        assert!(is_volume_available(&dir.join("gone.pdf")));
+        assert!(is_volume_available(&dir.join("Deleted folder").join("gone.pdf")));
+
+        // An empty mount point
+        let mount_point = dir.join("USB");
+        fs::create_dir(&mount_point).unwrap();
+        assert!(!is_volume_available(&mount_point.join("Photos").join("a.jpg")));
+    }
+
+    // Moves a file within the scratch folder, returning the steps it went through
+    fn move_in(dir: &Path, file: &Path) -> (Result<PathBuf>, Vec<MoveStep>) {
+        let dest_dir = dir.join("Sorted");
+        fs::create_dir_all(&dest_dir).unwrap();
+        let mut steps = vec![];
+        let result = move_to_dir_with(file, &dest_dir, false, |_, step| {
+            steps.push(step);
+            Ok(())
+        });
+        (result, steps)
+    }
+
+    #[test]
+    fn read_only_files_are_renamed_on_one_drive() {
+        let dir = scratch_dir("move-read-only");
+        let file = dir.join("locked.txt");
+        fs::write(&file, b"keep").unwrap();
+        let mut permissions = fs::metadata(&file).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&file, permissions).unwrap();
+
+        let (result, steps) = move_in(&dir, &file);
+        let moved = result.unwrap();
+        assert_eq!(steps, [MoveStep::Claimed]);
+        assert!(!file.exists());
+        assert_eq!(fs::read(&moved).unwrap(), b"keep");
+        assert!(fs::metadata(&moved).unwrap().permissions().readonly());
+    }
+
+    #[test]
+    fn failed_renames_are_not_copied() {
+        let dir = scratch_dir("move-missing");
+        let (result, steps) = move_in(&dir, &dir.join("gone.txt"));
+        let error = result.unwrap_err();
+        assert_eq!(error.downcast_ref::<std::io::Error>().map(|e| e.kind()), Some(std::io::ErrorKind::NotFound));
+        assert_eq!(steps, [MoveStep::Claimed]);
+        // The claimed name was given up
+        assert_eq!(fs::read_dir(dir.join("Sorted")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn device_names_are_reserved_whatever_the_extension() {
+        for name in ["CON", "nul.txt", "aux.tar.gz", "CONIN$", "conout$.log", "COM0", "com9.bin", "LPT0.txt", "lpt¹", "PRN .txt"] {
+            assert!(is_reserved_windows_name(name), "{}", name);
+        }
+        for name in ["CONSOLE", "COM10", "LPT", "my nul.txt", "nul_.txt", "CONIN"] {
+            assert!(!is_reserved_windows_name(name), "{}", name);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn reserved_names_are_opened_as_files() {
+        let dir = scratch_dir("reserved");
+        let short = dir.join("notes.txt");
+        assert_eq!(long_path(&short), short);
+        
+        let reserved = dir.join("CON.txt");
+        assert!(long_path(&reserved).to_string_lossy().starts_with(r"\\?\"));
+        fs::write(long_path(&reserved), b"not a console").unwrap();
+        assert_eq!(get_file_size(&reserved).unwrap(), 13);
+        assert_eq!(hash_file(&reserved).unwrap().len(), 64);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn files_open_elsewhere_are_reported_locked() {
+        use std::os::windows::fs::OpenOptionsExt;
+        let dir = scratch_dir("move-locked");
+        let file = dir.join("open.docx");
+        fs::write(&file, b"draft").unwrap();
+        let _open = fs::OpenOptions::new().read(true).share_mode(0).open(&file).unwrap();
+
+        let (result, steps) = move_in(&dir, &file);
+        assert!(matches!(result.unwrap_err().downcast_ref::<MoveError>(), Some(MoveError::Locked(_))));
+        assert!(steps.iter().all(|step| *step == MoveStep::Claimed));
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn rule_patterns_are_tried_on_sample_names() {
+        assert!(rule_matches("jpg, .PNG", true, false, "Holiday.png").unwrap());
+        assert!(!rule_matches("jpg,png", true, false, "notes.txt").unwrap());
+        assert!(!rule_matches("jpg", true, false, "jpg").unwrap());
+        assert!(rule_matches("invoice_*.pdf", false, false, "Invoice_2024.PDF").unwrap());
+        assert!(!rule_matches("invoice_*.pdf", false, false, "my invoice_2024.pdf").unwrap());
+        assert!(rule_matches(r"^IMG_\d{4}\.", false, true, "IMG_0042.heic").unwrap());
+
+        let error = rule_matches("IMG_(", false, true, "IMG_0042.heic").unwrap_err().to_string();
+        assert!(error.starts_with("Invalid pattern:"), "{}", error);
+        // Parentheses are plain characters in a glob
+        assert!(rule_matches("IMG_(", false, false, "img_(").unwrap());
+    }
+}
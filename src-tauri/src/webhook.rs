@@ -0,0 +1,121 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime};
+use crate::batch::Batches;
+use crate::database;
+
+// URL organization events are POSTed to; empty or missing means off
+pub const WEBHOOK_URL_SETTING: &str = "webhook_url";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// One file moved by the organizer
+#[derive(Debug, Clone, Serialize)]
+pub struct OrganizedFile {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    pub category: Option<String>,
+}
+
+// Body of the webhook request
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    pub event: String,
+    pub timestamp: String,
+    pub files: Vec<OrganizedFile>,
+    // Number of files moved per category; files without one are not counted
+    pub categories: HashMap<String, usize>,
+}
+
+#[derive(Default)]
+pub struct WebhookState {
+    // Moved files waiting to be sent, all under one key
+    pending: Batches<(), OrganizedFile>,
+}
+
+// Check a webhook URL before saving it; an empty value turns the webhook off
+pub fn validate_url(url: &str) -> Result<()> {
+    if url.trim().is_empty() {
+        return Ok(());
+    }
+
+    let parsed = reqwest::Url::parse(url.trim())?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(()),
+        other => Err(anyhow::anyhow!("Webhook URL must use http or https, not {}", other)),
+    }
+}
+
+fn webhook_url<R: Runtime>(app: &AppHandle<R>) -> Result<Option<String>> {
+    Ok(database::get_setting(app, WEBHOOK_URL_SETTING)?
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty()))
+}
+
+// Queue a moved file for the webhook; the request is sent once the current
+// batch of moves settles
+pub fn file_organized<R: Runtime>(app: &AppHandle<R>, file: OrganizedFile) {
+    match webhook_url(app) {
+        Ok(Some(_)) => {}
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to read webhook settings");
+            return;
+        }
+    }
+
+    let Some(state) = app.try_state::<WebhookState>() else { return };
+    if !state.pending.push((), file) {
+        return;
+    }
+
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<WebhookState>();
+        let Some(files) = state.pending.take_when_quiet(&()).await else { return };
+        let file_count = files.len();
+        if let Err(e) = send(&app_handle, files).await {
+            tracing::warn!(files = file_count, error = format!("{:#}", e), "Failed to call webhook");
+        }
+    });
+}
+
+async fn send<R: Runtime>(app: &AppHandle<R>, files: Vec<OrganizedFile>) -> Result<()> {
+    // The URL may have been cleared while the batch was building up
+    let Some(url) = webhook_url(app)? else { return Ok(()) };
+
+    let mut categories = HashMap::new();
+    for category in files.iter().filter_map(|file| file.category.as_ref()) {
+        *categories.entry(category.clone()).or_insert(0) += 1;
+    }
+
+    let payload = WebhookPayload {
+        event: "files_organized".into(),
+        timestamp: Utc::now().to_rfc3339(),
+        files,
+        categories,
+    };
+
+    post(&url, &payload, REQUEST_TIMEOUT).await?;
+
+    tracing::info!(files = payload.files.len(), "Webhook called");
+    Ok(())
+}
+
+// POST a JSON body to a webhook URL, failing on an error status. Every
+// webhook the app calls goes through here, hooks included
+pub async fn post(url: &str, body: &impl Serialize, timeout: Duration) -> Result<()> {
+    validate_url(url)?;
+    let client = reqwest::Client::builder().timeout(timeout).build()?;
+    client
+        .post(url.trim())
+        .json(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}